@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use penumbra_crypto::asset;
 use penumbra_proto::{chain as pb, crypto as pbc, Protobuf};
 use serde::{Deserialize, Serialize};
@@ -27,11 +29,15 @@ impl TryFrom<pb::AssetInfo> for AssetInfo {
 
 impl From<AssetInfo> for pb::AssetInfo {
     fn from(ai: AssetInfo) -> Self {
+        let display_unit = ai.denom.default_unit();
         pb::AssetInfo {
             asset_id: Some(pbc::AssetId::from(ai.asset_id)),
             denom: Some(pbc::Denom::from(ai.denom)),
             as_of_block_height: ai.as_of_block_height,
             total_supply: ai.total_supply,
+            display_denom: display_unit.to_string(),
+            display_exponent: display_unit.exponent() as u32,
+            display_total_supply: display_unit.format_value(ai.total_supply),
         }
     }
 }
@@ -41,8 +47,37 @@ impl From<AssetInfo> for pb::AssetInfo {
 pub struct ChainParams {
     pub chain_id: String,
     pub epoch_duration: u64,
+    /// The number of most recent note commitment tree roots, one per block,
+    /// that transactions may use as their anchor.
+    pub num_recent_anchors: u64,
+    /// The maximum number of transactions in a block.
+    pub max_block_transactions: u64,
+    /// The maximum total size, in bytes, of the transactions in a block.
+    pub max_block_bytes: u64,
+    /// The share of each epoch's fees, in basis points, distributed to the
+    /// active validators; the rest is burned.
+    pub fee_distribution_bps: u64,
+    /// If nonzero, epochs last this many seconds of block time rather than
+    /// `epoch_duration` blocks, so that they don't drift as block times vary.
+    pub epoch_duration_secs: u64,
+    /// If nonzero, the largest share of the total delegated stake, in basis
+    /// points, that a single validator may hold.
+    pub max_validator_stake_bps: u64,
+    /// The smallest amount of unbonded stake that a single delegation,
+    /// undelegation, or redelegation may move.
+    pub min_delegation_amount: u64,
+    /// The height at which each listed kind of action becomes valid, by the
+    /// name of the kind.  Kinds that aren't listed are always valid.
+    pub action_activations: BTreeMap<String, u64>,
 }
 
+/// The anchor window used when none is specified.
+pub const DEFAULT_NUM_RECENT_ANCHORS: u64 = 256;
+/// The block transaction limit used when none is specified.
+pub const DEFAULT_MAX_BLOCK_TRANSACTIONS: u64 = 1000;
+/// The block size limit used when none is specified.
+pub const DEFAULT_MAX_BLOCK_BYTES: u64 = 4 * 1024 * 1024;
+
 impl Protobuf<pb::ChainParams> for ChainParams {}
 
 impl From<pb::ChainParams> for ChainParams {
@@ -50,6 +85,32 @@ impl From<pb::ChainParams> for ChainParams {
         ChainParams {
             chain_id: msg.chain_id,
             epoch_duration: msg.epoch_duration,
+            // Chain params from before the anchor window was configurable don't set it.
+            num_recent_anchors: if msg.num_recent_anchors == 0 {
+                DEFAULT_NUM_RECENT_ANCHORS
+            } else {
+                msg.num_recent_anchors
+            },
+            // Likewise for the block limits.
+            max_block_transactions: if msg.max_block_transactions == 0 {
+                DEFAULT_MAX_BLOCK_TRANSACTIONS
+            } else {
+                msg.max_block_transactions
+            },
+            max_block_bytes: if msg.max_block_bytes == 0 {
+                DEFAULT_MAX_BLOCK_BYTES
+            } else {
+                msg.max_block_bytes
+            },
+            fee_distribution_bps: msg.fee_distribution_bps,
+            epoch_duration_secs: msg.epoch_duration_secs,
+            max_validator_stake_bps: msg.max_validator_stake_bps,
+            min_delegation_amount: msg.min_delegation_amount,
+            action_activations: msg
+                .action_activations
+                .into_iter()
+                .map(|activation| (activation.action, activation.height))
+                .collect(),
         }
     }
 }
@@ -59,6 +120,18 @@ impl From<ChainParams> for pb::ChainParams {
         pb::ChainParams {
             chain_id: params.chain_id,
             epoch_duration: params.epoch_duration,
+            num_recent_anchors: params.num_recent_anchors,
+            max_block_transactions: params.max_block_transactions,
+            max_block_bytes: params.max_block_bytes,
+            fee_distribution_bps: params.fee_distribution_bps,
+            epoch_duration_secs: params.epoch_duration_secs,
+            max_validator_stake_bps: params.max_validator_stake_bps,
+            min_delegation_amount: params.min_delegation_amount,
+            action_activations: params
+                .action_activations
+                .into_iter()
+                .map(|(action, height)| pb::ActionActivation { action, height })
+                .collect(),
         }
     }
 }
@@ -68,6 +141,14 @@ impl Default for ChainParams {
         Self {
             chain_id: String::new(),
             epoch_duration: 8640,
+            num_recent_anchors: DEFAULT_NUM_RECENT_ANCHORS,
+            max_block_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS,
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
+            fee_distribution_bps: 0,
+            epoch_duration_secs: 0,
+            max_validator_stake_bps: 0,
+            min_delegation_amount: 0,
+            action_activations: BTreeMap::new(),
         }
     }
 }
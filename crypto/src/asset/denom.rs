@@ -268,7 +268,8 @@ impl Unit {
         }
     }
 
-    fn exponent(&self) -> u8 {
+    /// Returns the power of ten relating this unit to the base unit.
+    pub fn exponent(&self) -> u8 {
         self.inner
             .units
             .get(self.unit_index as usize)
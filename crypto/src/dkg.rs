@@ -0,0 +1,493 @@
+//! Feldman verifiable secret sharing over decaf377, the building block of the
+//! distributed key generation (DKG) run by validators to establish a threshold
+//! decryption key.
+//!
+//! Each of the `n` participants deals a random polynomial of degree
+//! `threshold - 1`: it publishes commitments to the polynomial's coefficients,
+//! and gives every participant the evaluation of the polynomial at that
+//! participant's index, encrypted to the participant's key.  The group key is
+//! the sum of the dealers' commitments to their constant terms, and each
+//! participant's key share is the sum of the shares it was dealt, so any
+//! `threshold` participants can jointly decrypt messages encrypted to the
+//! group key, while fewer learn nothing about it.
+//!
+//! Participants are indexed from 1, since the polynomials' values at 0 are
+//! the dealers' secrets.
+//!
+//! Each dealing proves knowledge of its constant term, so that no dealer can
+//! choose its contribution to the group key as a function of the others'.
+//! A participant dealt a share that doesn't match the dealer's commitments can
+//! publish a [`Complaint`], which anyone can check, to have the dealer left out.
+
+use ark_ff::{Field, UniformRand, Zero};
+use decaf377::FieldExt;
+use penumbra_proto::{crypto as pb, Protobuf};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{flow::DleqProof, prf, Fr};
+
+/// A dealer's secret polynomial.
+pub struct Polynomial {
+    coefficients: Vec<Fr>,
+}
+
+impl Polynomial {
+    /// Chooses a random polynomial whose shares can be combined by any
+    /// `threshold` participants.
+    pub fn random<R: RngCore + CryptoRng>(threshold: usize, rng: &mut R) -> Self {
+        assert!(threshold > 0, "the threshold must be positive");
+        Self {
+            coefficients: (0..threshold).map(|_| Fr::rand(rng)).collect(),
+        }
+    }
+
+    /// Evaluates the polynomial at the given participant index.
+    pub fn evaluate(&self, index: u32) -> Fr {
+        let x = Fr::from(index as u64);
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Fr::zero(), |acc, coefficient| acc * x + *coefficient)
+    }
+
+    /// Deals a share to each of the participants with the given public keys,
+    /// which are given in participant order.
+    ///
+    /// The proof of knowledge of the constant term is bound to `context`, which
+    /// should identify the dealer and the round, so it can't be replayed.
+    pub fn deal<R: RngCore + CryptoRng>(
+        &self,
+        participant_keys: &[decaf377::Element],
+        context: &[u8],
+        rng: &mut R,
+    ) -> Dealing {
+        let esk = Fr::rand(rng);
+        let encrypted_shares = participant_keys
+            .iter()
+            .zip(1u32..)
+            .map(|(key, index)| self.evaluate(index) + share_mask(&(esk * *key), index))
+            .collect();
+        let commitments = self
+            .coefficients
+            .iter()
+            .map(|coefficient| *coefficient * decaf377::basepoint())
+            .collect::<Vec<_>>();
+
+        Dealing {
+            proof: ConstantTermProof::prove(&self.coefficients[0], &commitments[0], context, rng),
+            commitments,
+            ephemeral_key: esk * decaf377::basepoint(),
+            encrypted_shares,
+        }
+    }
+}
+
+/// A Schnorr proof of knowledge of the constant term of a dealer's
+/// polynomial, given its commitment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstantTermProof {
+    pub challenge: Fr,
+    pub response: Fr,
+}
+
+impl ConstantTermProof {
+    fn challenge(commitment: &decaf377::Element, context: &[u8], r: &decaf377::Element) -> Fr {
+        let mut input = context.to_vec();
+        input.extend_from_slice(&r.compress().0);
+        prf::expand_ff(b"Penumbra_DKG_PoK", &commitment.compress().0, &input)
+    }
+
+    fn prove<R: RngCore + CryptoRng>(
+        constant_term: &Fr,
+        commitment: &decaf377::Element,
+        context: &[u8],
+        rng: &mut R,
+    ) -> Self {
+        let k = Fr::rand(rng);
+        let challenge = Self::challenge(commitment, context, &(k * decaf377::basepoint()));
+        Self {
+            challenge,
+            response: k + challenge * *constant_term,
+        }
+    }
+
+    fn verify(&self, commitment: &decaf377::Element, context: &[u8]) -> bool {
+        let r = self.response * decaf377::basepoint() - self.challenge * *commitment;
+        Self::challenge(commitment, context, &r) == self.challenge
+    }
+}
+
+/// The public part of a dealer's contribution to a DKG.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dealing {
+    /// Commitments to the coefficients of the dealer's polynomial, starting
+    /// with the constant term.
+    pub commitments: Vec<decaf377::Element>,
+    /// A proof that the dealer knows the constant term.
+    pub proof: ConstantTermProof,
+    /// The ephemeral public key the shares were encrypted with.
+    pub ephemeral_key: decaf377::Element,
+    /// The share dealt to each participant, in participant order, encrypted
+    /// to the participant's key.
+    pub encrypted_shares: Vec<Fr>,
+}
+
+impl Dealing {
+    /// The number of participants needed to combine the shares of this dealing.
+    pub fn threshold(&self) -> usize {
+        self.commitments.len()
+    }
+
+    /// The dealer's contribution to the group key.
+    pub fn group_key_contribution(&self) -> decaf377::Element {
+        self.commitments
+            .first()
+            .copied()
+            .unwrap_or_else(decaf377::Element::default)
+    }
+
+    /// Checks the dealer's proof of knowledge of the constant term, which must
+    /// have been made with the same `context`.
+    pub fn verify_proof(&self, context: &[u8]) -> anyhow::Result<()> {
+        match self.commitments.first() {
+            Some(commitment) if self.proof.verify(commitment, context) => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "the dealing doesn't prove knowledge of its constant term"
+            )),
+        }
+    }
+
+    /// Computes the public key corresponding to the share dealt to the
+    /// participant with the given index, from the commitments alone.
+    pub fn public_share(&self, index: u32) -> decaf377::Element {
+        let x = Fr::from(index as u64);
+        self.commitments
+            .iter()
+            .rev()
+            .fold(decaf377::Element::default(), |acc, commitment| {
+                x * acc + *commitment
+            })
+    }
+
+    /// Decrypts the share dealt to the participant with the given index and
+    /// secret key, checking it against the dealer's commitments.
+    pub fn decrypt_share(&self, index: u32, secret_key: &Fr) -> anyhow::Result<Fr> {
+        let share = self.unmask_share(index, &(*secret_key * self.ephemeral_key))?;
+        if share * decaf377::basepoint() != self.public_share(index) {
+            return Err(anyhow::anyhow!(
+                "the share dealt to participant {} doesn't match the dealer's commitments",
+                index
+            ));
+        }
+        Ok(share)
+    }
+
+    /// Unmasks the share dealt to the participant with the given index, given
+    /// the secret shared between its key and the ephemeral key.
+    fn unmask_share(&self, index: u32, shared_secret: &decaf377::Element) -> anyhow::Result<Fr> {
+        let encrypted_share = index
+            .checked_sub(1)
+            .and_then(|position| self.encrypted_shares.get(position as usize))
+            .ok_or_else(|| anyhow::anyhow!("no share was dealt to participant {}", index))?;
+        Ok(*encrypted_share - share_mask(shared_secret, index))
+    }
+}
+
+/// A participant's complaint that the share a dealing dealt it doesn't match
+/// the dealer's commitments.
+///
+/// The complaint reveals the secret shared between the participant's key and
+/// the dealing's ephemeral key, with a proof that it was computed with the
+/// participant's key, so that anyone can unmask the share and check it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Complaint {
+    pub shared_secret: decaf377::Element,
+    pub proof: DleqProof,
+}
+
+impl Complaint {
+    /// Complains about the share of `dealing` dealt to the participant with
+    /// the given secret key.
+    pub fn new<R: RngCore + CryptoRng>(dealing: &Dealing, secret_key: &Fr, rng: &mut R) -> Self {
+        let shared_secret = *secret_key * dealing.ephemeral_key;
+        Self {
+            shared_secret,
+            proof: DleqProof::prove(
+                secret_key,
+                &(*secret_key * decaf377::basepoint()),
+                &dealing.ephemeral_key,
+                &shared_secret,
+                rng,
+            ),
+        }
+    }
+
+    /// Checks that the complaint is justified: that it was made by the
+    /// participant with the given index and public key, and that the share
+    /// `dealing` dealt it doesn't match the dealer's commitments.
+    pub fn verify(
+        &self,
+        dealing: &Dealing,
+        index: u32,
+        participant_key: &decaf377::Element,
+    ) -> anyhow::Result<()> {
+        if !self
+            .proof
+            .verify(participant_key, &dealing.ephemeral_key, &self.shared_secret)
+        {
+            return Err(anyhow::anyhow!(
+                "the complaint's shared secret wasn't computed with participant {}'s key",
+                index
+            ));
+        }
+        let share = dealing.unmask_share(index, &self.shared_secret)?;
+        if share * decaf377::basepoint() == dealing.public_share(index) {
+            return Err(anyhow::anyhow!(
+                "the share dealt to participant {} matches the dealer's commitments",
+                index
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Derives the mask hiding the share dealt to a participant from the shared
+/// secret between the dealer's ephemeral key and the participant's key.
+fn share_mask(shared_secret: &decaf377::Element, index: u32) -> Fr {
+    prf::expand_ff(
+        b"Penumbra_DKGShar",
+        &shared_secret.compress().0,
+        &index.to_le_bytes(),
+    )
+}
+
+/// Computes the group key from the dealings of the qualified dealers.
+pub fn group_key<'a>(dealings: impl IntoIterator<Item = &'a Dealing>) -> decaf377::Element {
+    dealings
+        .into_iter()
+        .fold(decaf377::Element::default(), |acc, dealing| {
+            acc + dealing.group_key_contribution()
+        })
+}
+
+/// Computes the public key corresponding to the key share of the participant
+/// with the given index, from the dealings of the qualified dealers.
+pub fn public_key_share<'a>(
+    dealings: impl IntoIterator<Item = &'a Dealing>,
+    index: u32,
+) -> decaf377::Element {
+    dealings
+        .into_iter()
+        .fold(decaf377::Element::default(), |acc, dealing| {
+            acc + dealing.public_share(index)
+        })
+}
+
+/// Computes the Lagrange coefficient of the participant with index `index`
+/// for interpolating at 0 from the shares of the participants in `indices`,
+/// which must include `index` and have no duplicates.
+pub fn lagrange_coefficient(index: u32, indices: &[u32]) -> Fr {
+    let x = Fr::from(index as u64);
+    let (numerator, denominator) = indices
+        .iter()
+        .filter(|other| **other != index)
+        .map(|other| Fr::from(*other as u64))
+        .fold((Fr::from(1u64), Fr::from(1u64)), |(num, den), other| {
+            (num * other, den * (other - x))
+        });
+    numerator
+        * denominator
+            .inverse()
+            .expect("participant indices are distinct")
+}
+
+impl Protobuf<pb::Dealing> for Dealing {}
+
+impl From<Dealing> for pb::Dealing {
+    fn from(dealing: Dealing) -> Self {
+        pb::Dealing {
+            commitments: dealing
+                .commitments
+                .iter()
+                .map(|commitment| commitment.compress().0.to_vec())
+                .collect(),
+            ephemeral_key: dealing.ephemeral_key.compress().0.to_vec(),
+            proof: [
+                dealing.proof.challenge.to_bytes(),
+                dealing.proof.response.to_bytes(),
+            ]
+            .concat(),
+            encrypted_shares: dealing
+                .encrypted_shares
+                .iter()
+                .map(|share| share.to_bytes().to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::Dealing> for Dealing {
+    type Error = anyhow::Error;
+
+    fn try_from(dealing: pb::Dealing) -> Result<Self, Self::Error> {
+        if dealing.proof.len() != 64 {
+            return Err(anyhow::anyhow!("constant term proofs must be 64 bytes"));
+        }
+
+        Ok(Dealing {
+            commitments: dealing
+                .commitments
+                .iter()
+                .map(|commitment| element(commitment))
+                .collect::<anyhow::Result<_>>()?,
+            proof: ConstantTermProof {
+                challenge: scalar(&dealing.proof[..32])?,
+                response: scalar(&dealing.proof[32..])?,
+            },
+            ephemeral_key: element(&dealing.ephemeral_key)?,
+            encrypted_shares: dealing
+                .encrypted_shares
+                .iter()
+                .map(|share| scalar(share))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+impl Protobuf<pb::DealingComplaint> for Complaint {}
+
+impl From<Complaint> for pb::DealingComplaint {
+    fn from(complaint: Complaint) -> Self {
+        pb::DealingComplaint {
+            shared_secret: complaint.shared_secret.compress().0.to_vec(),
+            proof: [
+                complaint.proof.challenge.to_bytes(),
+                complaint.proof.response.to_bytes(),
+            ]
+            .concat(),
+        }
+    }
+}
+
+impl TryFrom<pb::DealingComplaint> for Complaint {
+    type Error = anyhow::Error;
+
+    fn try_from(complaint: pb::DealingComplaint) -> Result<Self, Self::Error> {
+        if complaint.proof.len() != 64 {
+            return Err(anyhow::anyhow!("complaint proofs must be 64 bytes"));
+        }
+
+        Ok(Complaint {
+            shared_secret: element(&complaint.shared_secret)?,
+            proof: DleqProof {
+                challenge: scalar(&complaint.proof[..32])?,
+                response: scalar(&complaint.proof[32..])?,
+            },
+        })
+    }
+}
+
+fn element(bytes: &[u8]) -> anyhow::Result<decaf377::Element> {
+    decaf377::Encoding(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("group elements must be 32 bytes"))?,
+    )
+    .decompress()
+    .map_err(|_| anyhow::anyhow!("invalid group element"))
+}
+
+fn scalar(bytes: &[u8]) -> anyhow::Result<Fr> {
+    Fr::from_bytes(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("scalars must be 32 bytes"))?,
+    )
+    .map_err(|_| anyhow::anyhow!("invalid scalar"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn threshold_of_participants_recovers_group_secret() {
+        let secret_keys = (0..4).map(|_| Fr::rand(&mut OsRng)).collect::<Vec<_>>();
+        let public_keys = secret_keys
+            .iter()
+            .map(|sk| *sk * decaf377::basepoint())
+            .collect::<Vec<_>>();
+
+        let polynomials = (0..4)
+            .map(|_| Polynomial::random(3, &mut OsRng))
+            .collect::<Vec<_>>();
+        let dealings = polynomials
+            .iter()
+            .map(|polynomial| polynomial.deal(&public_keys, b"round", &mut OsRng))
+            .collect::<Vec<_>>();
+        for dealing in &dealings {
+            dealing.verify_proof(b"round").unwrap();
+        }
+
+        // Each participant's key share is the sum of the shares dealt to it.
+        let key_shares = secret_keys
+            .iter()
+            .zip(1u32..)
+            .map(|(sk, index)| {
+                dealings
+                    .iter()
+                    .map(|dealing| dealing.decrypt_share(index, sk).unwrap())
+                    .fold(Fr::zero(), |acc, share| acc + share)
+            })
+            .collect::<Vec<_>>();
+        for (key_share, index) in key_shares.iter().zip(1u32..) {
+            assert_eq!(
+                *key_share * decaf377::basepoint(),
+                public_key_share(&dealings, index)
+            );
+        }
+
+        // Any three participants can interpolate the group secret.
+        let indices = [1, 3, 4];
+        let secret = indices
+            .iter()
+            .map(|index| key_shares[*index as usize - 1] * lagrange_coefficient(*index, &indices))
+            .fold(Fr::zero(), |acc, term| acc + term);
+        assert_eq!(secret * decaf377::basepoint(), group_key(&dealings));
+    }
+
+    #[test]
+    fn tampered_share_is_rejected() {
+        let sk = Fr::rand(&mut OsRng);
+        let pk = sk * decaf377::basepoint();
+        let mut dealing = Polynomial::random(1, &mut OsRng).deal(&[pk], b"round", &mut OsRng);
+
+        // A complaint about a valid share is rejected.
+        let complaint = Complaint::new(&dealing, &sk, &mut OsRng);
+        assert!(complaint.verify(&dealing, 1, &pk).is_err());
+
+        dealing.encrypted_shares[0] += Fr::from(1u64);
+        assert!(dealing.decrypt_share(1, &sk).is_err());
+
+        let complaint = Complaint::new(&dealing, &sk, &mut OsRng);
+        complaint.verify(&dealing, 1, &pk).unwrap();
+        // Only the participant the share was dealt to can complain about it.
+        let other = Fr::rand(&mut OsRng) * decaf377::basepoint();
+        assert!(complaint.verify(&dealing, 1, &other).is_err());
+    }
+
+    #[test]
+    fn rogue_constant_term_is_rejected() {
+        let pk = Fr::rand(&mut OsRng) * decaf377::basepoint();
+        let honest = Polynomial::random(2, &mut OsRng).deal(&[pk], b"honest", &mut OsRng);
+
+        // A dealer can't reuse another's proof, or prove a commitment it
+        // chose to cancel out the others'.
+        assert!(honest.verify_proof(b"rogue").is_err());
+        let mut rogue = Polynomial::random(2, &mut OsRng).deal(&[pk], b"rogue", &mut OsRng);
+        rogue.commitments[0] = rogue.commitments[0] - honest.commitments[0];
+        assert!(rogue.verify_proof(b"rogue").is_err());
+    }
+}
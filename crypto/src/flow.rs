@@ -0,0 +1,407 @@
+//! Additively homomorphic encryption of flows, such as the delegations to a
+//! validator during an epoch, to a threshold key established by a
+//! [DKG](crate::dkg).
+//!
+//! An amount is split into [`NUM_LIMBS`] limbs of [`LIMB_BITS`] bits, and each
+//! limb `m` is encrypted with ElGamal as `(r B, m B + r K)`, where `K` is the
+//! threshold key.  Ciphertexts are added componentwise, so a sum of
+//! ciphertexts encrypts the limbwise sum of their amounts.  To decrypt a sum,
+//! the holders of the key shares each publish a [`DecryptionShare`], with a
+//! proof that it was computed with their share; any `threshold` of these can
+//! be combined to recover each limb sum `M B`, and `M` is then recovered by a
+//! discrete log search bounded by the number of summands.
+
+use std::{collections::HashMap, ops::Add};
+
+use ark_ff::{UniformRand, Zero};
+use decaf377::FieldExt;
+use penumbra_proto::{crypto as pb, Protobuf};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{dkg, prf, Fr};
+
+/// The number of bits in each limb of an encrypted amount.
+pub const LIMB_BITS: u32 = 16;
+
+/// The number of limbs an amount is split into.
+pub const NUM_LIMBS: usize = 4;
+
+/// The largest value of a single limb.
+const LIMB_MAX: u64 = (1 << LIMB_BITS) - 1;
+
+/// An encryption of an amount, or of the sum of several amounts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Ciphertext {
+    /// The ElGamal ciphertext `(r B, m B + r K)` of each limb, least
+    /// significant first.
+    pub limbs: [(decaf377::Element, decaf377::Element); NUM_LIMBS],
+}
+
+impl Ciphertext {
+    /// Chooses random blinding factors for [`Ciphertext::encrypt`].
+    pub fn random_blindings<R: RngCore + CryptoRng>(rng: &mut R) -> [Fr; NUM_LIMBS] {
+        [(); NUM_LIMBS].map(|_| Fr::rand(rng))
+    }
+
+    /// Encrypts `amount` to `key`, with one blinding factor per limb.
+    pub fn encrypt(amount: u64, key: &decaf377::Element, blindings: &[Fr; NUM_LIMBS]) -> Self {
+        let mut limbs = [(decaf377::Element::default(), decaf377::Element::default()); NUM_LIMBS];
+        for (i, (limb, blinding)) in limbs.iter_mut().zip(blindings.iter()).enumerate() {
+            let m = (amount >> (i as u32 * LIMB_BITS)) & LIMB_MAX;
+            *limb = (
+                *blinding * decaf377::basepoint(),
+                Fr::from(m) * decaf377::basepoint() + *blinding * *key,
+            );
+        }
+        Self { limbs }
+    }
+}
+
+impl Add for Ciphertext {
+    type Output = Ciphertext;
+
+    fn add(self, other: Ciphertext) -> Ciphertext {
+        let mut limbs = self.limbs;
+        for (limb, other) in limbs.iter_mut().zip(other.limbs.iter()) {
+            *limb = (limb.0 + other.0, limb.1 + other.1);
+        }
+        Ciphertext { limbs }
+    }
+}
+
+/// One key share holder's contribution to decrypting a [`Ciphertext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecryptionShare {
+    /// The first component of each limb's ciphertext, multiplied by the key
+    /// share.
+    pub limbs: [decaf377::Element; NUM_LIMBS],
+    /// For each limb, a proof that it was multiplied by the same key share
+    /// as the public key share.
+    pub proofs: [DleqProof; NUM_LIMBS],
+}
+
+/// A Chaum-Pedersen proof that `log_B(P) = log_C(D)` for the basepoint `B`,
+/// a public key share `P`, and ciphertext and decryption share components
+/// `C` and `D`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DleqProof {
+    pub challenge: Fr,
+    pub response: Fr,
+}
+
+impl DleqProof {
+    fn challenge(
+        public_key_share: &decaf377::Element,
+        c: &decaf377::Element,
+        d: &decaf377::Element,
+        r1: &decaf377::Element,
+        r2: &decaf377::Element,
+    ) -> Fr {
+        let mut input = Vec::with_capacity(4 * 32);
+        for element in [c, d, r1, r2] {
+            input.extend_from_slice(&element.compress().0);
+        }
+        prf::expand_ff(b"Penumbra_FlowDLE", &public_key_share.compress().0, &input)
+    }
+
+    pub(crate) fn prove<R: RngCore + CryptoRng>(
+        key_share: &Fr,
+        public_key_share: &decaf377::Element,
+        c: &decaf377::Element,
+        d: &decaf377::Element,
+        rng: &mut R,
+    ) -> Self {
+        let k = Fr::rand(rng);
+        let challenge = Self::challenge(
+            public_key_share,
+            c,
+            d,
+            &(k * decaf377::basepoint()),
+            &(k * *c),
+        );
+        Self {
+            challenge,
+            response: k + challenge * *key_share,
+        }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        public_key_share: &decaf377::Element,
+        c: &decaf377::Element,
+        d: &decaf377::Element,
+    ) -> bool {
+        let r1 = self.response * decaf377::basepoint() - self.challenge * *public_key_share;
+        let r2 = self.response * *c - self.challenge * *d;
+        Self::challenge(public_key_share, c, d, &r1, &r2) == self.challenge
+    }
+}
+
+impl DecryptionShare {
+    /// Computes the decryption share of `ciphertext` for the given key share.
+    pub fn new<R: RngCore + CryptoRng>(
+        ciphertext: &Ciphertext,
+        key_share: &Fr,
+        rng: &mut R,
+    ) -> Self {
+        let public_key_share = *key_share * decaf377::basepoint();
+        let limbs = ciphertext.limbs.map(|(c, _)| *key_share * c);
+        let mut proofs = [DleqProof {
+            challenge: Fr::zero(),
+            response: Fr::zero(),
+        }; NUM_LIMBS];
+        for ((proof, (c, _)), d) in proofs
+            .iter_mut()
+            .zip(ciphertext.limbs.iter())
+            .zip(limbs.iter())
+        {
+            *proof = DleqProof::prove(key_share, &public_key_share, c, d, rng);
+        }
+        Self { limbs, proofs }
+    }
+
+    /// Checks that this is a decryption share of `ciphertext` computed with
+    /// the key share corresponding to `public_key_share`.
+    pub fn verify(
+        &self,
+        ciphertext: &Ciphertext,
+        public_key_share: &decaf377::Element,
+    ) -> anyhow::Result<()> {
+        for ((proof, (c, _)), d) in self
+            .proofs
+            .iter()
+            .zip(ciphertext.limbs.iter())
+            .zip(self.limbs.iter())
+        {
+            if !proof.verify(public_key_share, c, d) {
+                return Err(anyhow::anyhow!(
+                    "decryption share doesn't match the public key share"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decrypts `ciphertext`, the sum of the encryptions of at most
+/// `max_summands` amounts, from the verified decryption shares of at least
+/// `threshold` key share holders, given with their participant indices.
+pub fn decrypt(
+    ciphertext: &Ciphertext,
+    shares: &[(u32, &DecryptionShare)],
+    max_summands: u64,
+) -> anyhow::Result<u64> {
+    let indices = shares.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+    let coefficients = indices
+        .iter()
+        .map(|index| dkg::lagrange_coefficient(*index, &indices))
+        .collect::<Vec<_>>();
+    let bound = max_summands
+        .checked_mul(LIMB_MAX)
+        .ok_or_else(|| anyhow::anyhow!("too many summands to decrypt"))?;
+
+    let mut amount = 0u128;
+    for (i, (_, c2)) in ciphertext.limbs.iter().enumerate() {
+        let mask = shares.iter().zip(coefficients.iter()).fold(
+            decaf377::Element::default(),
+            |acc, ((_, share), coefficient)| acc + *coefficient * share.limbs[i],
+        );
+        let limb = discrete_log(&(*c2 - mask), bound)
+            .ok_or_else(|| anyhow::anyhow!("limb {} is out of range or undecryptable", i))?;
+        amount += (limb as u128) << (i as u32 * LIMB_BITS);
+    }
+
+    amount
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("decrypted amount {} doesn't fit in 64 bits", amount))
+}
+
+/// Finds `m <= bound` with `m B = target` by baby-step giant-step.
+fn discrete_log(target: &decaf377::Element, bound: u64) -> Option<u64> {
+    let step = ((bound as f64).sqrt() as u64).max(1) + 1;
+
+    let mut baby_steps = HashMap::with_capacity(step as usize);
+    let mut point = decaf377::Element::default();
+    for j in 0..step {
+        baby_steps.insert(point.compress().0, j);
+        point = point + decaf377::basepoint();
+    }
+
+    let giant_step = Fr::from(step) * decaf377::basepoint();
+    let mut point = *target;
+    for i in 0..=step {
+        if let Some(j) = baby_steps.get(&point.compress().0) {
+            let m = i * step + j;
+            return if m <= bound { Some(m) } else { None };
+        }
+        point = point - giant_step;
+    }
+    None
+}
+
+fn element_from_bytes(bytes: &[u8]) -> anyhow::Result<decaf377::Element> {
+    decaf377::Encoding(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("group elements must be 32 bytes"))?,
+    )
+    .decompress()
+    .map_err(|_| anyhow::anyhow!("invalid group element"))
+}
+
+fn scalar_from_bytes(bytes: &[u8]) -> anyhow::Result<Fr> {
+    Fr::from_bytes(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("scalars must be 32 bytes"))?,
+    )
+    .map_err(|_| anyhow::anyhow!("invalid scalar"))
+}
+
+impl Protobuf<pb::FlowCiphertext> for Ciphertext {}
+
+impl From<Ciphertext> for pb::FlowCiphertext {
+    fn from(ciphertext: Ciphertext) -> Self {
+        pb::FlowCiphertext {
+            limbs: ciphertext
+                .limbs
+                .iter()
+                .map(|(c1, c2)| [c1.compress().0, c2.compress().0].concat())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::FlowCiphertext> for Ciphertext {
+    type Error = anyhow::Error;
+
+    fn try_from(ciphertext: pb::FlowCiphertext) -> Result<Self, Self::Error> {
+        if ciphertext.limbs.len() != NUM_LIMBS {
+            return Err(anyhow::anyhow!(
+                "flow ciphertexts must have {} limbs",
+                NUM_LIMBS
+            ));
+        }
+        let mut limbs = [(decaf377::Element::default(), decaf377::Element::default()); NUM_LIMBS];
+        for (limb, bytes) in limbs.iter_mut().zip(ciphertext.limbs.iter()) {
+            if bytes.len() != 64 {
+                return Err(anyhow::anyhow!("flow ciphertext limbs must be 64 bytes"));
+            }
+            *limb = (
+                element_from_bytes(&bytes[..32])?,
+                element_from_bytes(&bytes[32..])?,
+            );
+        }
+        Ok(Ciphertext { limbs })
+    }
+}
+
+impl Protobuf<pb::FlowDecryptionShare> for DecryptionShare {}
+
+impl From<DecryptionShare> for pb::FlowDecryptionShare {
+    fn from(share: DecryptionShare) -> Self {
+        pb::FlowDecryptionShare {
+            limbs: share
+                .limbs
+                .iter()
+                .map(|d| d.compress().0.to_vec())
+                .collect(),
+            proofs: share
+                .proofs
+                .iter()
+                .map(|proof| [proof.challenge.to_bytes(), proof.response.to_bytes()].concat())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::FlowDecryptionShare> for DecryptionShare {
+    type Error = anyhow::Error;
+
+    fn try_from(share: pb::FlowDecryptionShare) -> Result<Self, Self::Error> {
+        if share.limbs.len() != NUM_LIMBS || share.proofs.len() != NUM_LIMBS {
+            return Err(anyhow::anyhow!(
+                "flow decryption shares must have {} limbs",
+                NUM_LIMBS
+            ));
+        }
+        let mut limbs = [decaf377::Element::default(); NUM_LIMBS];
+        for (limb, bytes) in limbs.iter_mut().zip(share.limbs.iter()) {
+            *limb = element_from_bytes(bytes)?;
+        }
+        let mut proofs = [DleqProof {
+            challenge: Fr::zero(),
+            response: Fr::zero(),
+        }; NUM_LIMBS];
+        for (proof, bytes) in proofs.iter_mut().zip(share.proofs.iter()) {
+            if bytes.len() != 64 {
+                return Err(anyhow::anyhow!("decryption share proofs must be 64 bytes"));
+            }
+            *proof = DleqProof {
+                challenge: scalar_from_bytes(&bytes[..32])?,
+                response: scalar_from_bytes(&bytes[32..])?,
+            };
+        }
+        Ok(DecryptionShare { limbs, proofs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn threshold_decryption_of_aggregate() {
+        // A 2-of-3 sharing of a random key.
+        let polynomial = dkg::Polynomial::random(2, &mut OsRng);
+        let key = polynomial.evaluate(0) * decaf377::basepoint();
+        let key_shares = (1..=3u32)
+            .map(|index| polynomial.evaluate(index))
+            .collect::<Vec<_>>();
+
+        let amounts = [1u64, 70_000, u32::MAX as u64 + 5];
+        let aggregate = amounts
+            .iter()
+            .map(|amount| {
+                Ciphertext::encrypt(*amount, &key, &Ciphertext::random_blindings(&mut OsRng))
+            })
+            .fold(Ciphertext::default(), |acc, ciphertext| acc + ciphertext);
+
+        let shares = [1u32, 3]
+            .iter()
+            .map(|index| {
+                let key_share = key_shares[*index as usize - 1];
+                let share = DecryptionShare::new(&aggregate, &key_share, &mut OsRng);
+                share
+                    .verify(&aggregate, &(key_share * decaf377::basepoint()))
+                    .unwrap();
+                (*index, share)
+            })
+            .collect::<Vec<_>>();
+        let shares = shares
+            .iter()
+            .map(|(index, share)| (*index, share))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            decrypt(&aggregate, &shares, amounts.len() as u64).unwrap(),
+            amounts.iter().sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn share_from_wrong_key_is_rejected() {
+        let key_share = Fr::rand(&mut OsRng);
+        let ciphertext = Ciphertext::encrypt(
+            42,
+            &(key_share * decaf377::basepoint()),
+            &Ciphertext::random_blindings(&mut OsRng),
+        );
+        let share = DecryptionShare::new(&ciphertext, &Fr::rand(&mut OsRng), &mut OsRng);
+        assert!(share
+            .verify(&ciphertext, &(key_share * decaf377::basepoint()))
+            .is_err());
+    }
+}
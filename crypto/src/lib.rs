@@ -7,6 +7,8 @@ pub use decaf377_rdsa as rdsa;
 
 mod address;
 pub mod asset;
+pub mod dkg;
+pub mod flow;
 pub mod keys;
 pub mod memo;
 pub mod merkle;
@@ -2,12 +2,15 @@
 
 use std::convert::{TryFrom, TryInto};
 
+use ark_ff::Zero;
 use decaf377::FieldExt;
 use decaf377_rdsa::{SpendAuth, VerificationKey};
 use penumbra_proto::{transparent_proofs, Message, Protobuf};
 use thiserror;
 
-use crate::{asset, ka, keys, merkle, merkle::Hashable, note, value, Fq, Fr, Nullifier, Value};
+use crate::{
+    asset, flow, ka, keys, merkle, merkle::Hashable, note, value, Fq, Fr, Nullifier, Value,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -31,6 +34,10 @@ pub enum Error {
     InvalidDiversifiedAddress,
     #[error("Bad nullifier")]
     BadNullifier,
+    #[error("Flow ciphertext mismatch")]
+    FlowCiphertextMismatch,
+    #[error("Exchange rate mismatch")]
+    ExchangeRateMismatch,
     #[error("Transparent proof proto malformed")]
     ProtoMalformed,
 }
@@ -228,6 +235,89 @@ impl OutputProof {
     }
 }
 
+/// Transparent proof for encrypted delegation flows.
+///
+/// This structure keeps track of the auxiliary (private) inputs, which are
+/// sent in the clear: anyone who sees the transaction learns the flow's
+/// amounts, so encrypted flows are not private until this is replaced with a
+/// zero-knowledge proof.
+#[derive(Clone, Debug)]
+pub struct FlowProof {
+    // The amount of unbonded stake delegated or undelegated.
+    pub unbonded_amount: u64,
+    // The amount of delegation tokens produced or consumed.
+    pub delegation_amount: u64,
+    // The blinding factor used for generating the value commitment.
+    pub v_blinding: Fr,
+    // The blinding factors used for encrypting the delegation amount.
+    pub flow_blindings: [Fr; flow::NUM_LIMBS],
+}
+
+impl FlowProof {
+    /// Called to verify the proof using the provided public inputs.
+    ///
+    /// The public inputs are:
+    /// * value commitment of the flow,
+    /// * the encrypted delegation amount,
+    /// * the key the delegation amount is encrypted to,
+    /// * the staking token and delegation token asset IDs,
+    /// * the validator's exchange rate, in basis points squared,
+    /// * whether the flow is an undelegation rather than a delegation.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify(
+        &self,
+        value_commitment: value::Commitment,
+        ciphertext: &flow::Ciphertext,
+        flow_key: &decaf377::Element,
+        staking_token: asset::Id,
+        delegation_token: asset::Id,
+        validator_exchange_rate: u64,
+        undelegation: bool,
+    ) -> anyhow::Result<(), Error> {
+        // Exchange rate integrity, computed in the same directions as for
+        // transparent delegations and undelegations.
+        let rate = validator_exchange_rate as u128;
+        if self.delegation_amount == 0 || rate == 0 {
+            return Err(Error::ExchangeRateMismatch);
+        }
+        let exchange_ok = if undelegation {
+            (self.delegation_amount as u128 * rate) / 1_0000_0000 == self.unbonded_amount as u128
+        } else {
+            (self.unbonded_amount as u128 * 1_0000_0000) / rate == self.delegation_amount as u128
+        };
+        if !exchange_ok {
+            return Err(Error::ExchangeRateMismatch);
+        }
+
+        // Value commitment integrity.
+        let stake = Value {
+            amount: self.unbonded_amount,
+            asset_id: staking_token,
+        };
+        let delegation = Value {
+            amount: self.delegation_amount,
+            asset_id: delegation_token,
+        };
+        let value_commitment_test = if undelegation {
+            stake.commit(self.v_blinding) - delegation.commit(Fr::zero())
+        } else {
+            delegation.commit(self.v_blinding) - stake.commit(Fr::zero())
+        };
+        if value_commitment != value_commitment_test {
+            return Err(Error::ValueCommitmentMismatch);
+        }
+
+        // Flow ciphertext integrity.
+        if *ciphertext
+            != flow::Ciphertext::encrypt(self.delegation_amount, flow_key, &self.flow_blindings)
+        {
+            return Err(Error::FlowCiphertextMismatch);
+        }
+
+        Ok(())
+    }
+}
+
 // Conversions
 
 impl Protobuf<transparent_proofs::SpendProof> for SpendProof {}
@@ -391,6 +481,50 @@ impl TryFrom<transparent_proofs::OutputProof> for OutputProof {
     }
 }
 
+impl Protobuf<transparent_proofs::FlowProof> for FlowProof {}
+
+impl From<FlowProof> for transparent_proofs::FlowProof {
+    fn from(msg: FlowProof) -> Self {
+        transparent_proofs::FlowProof {
+            unbonded_amount: msg.unbonded_amount,
+            delegation_amount: msg.delegation_amount,
+            v_blinding: msg.v_blinding.to_bytes().to_vec(),
+            flow_blindings: msg
+                .flow_blindings
+                .iter()
+                .map(|blinding| blinding.to_bytes().to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<transparent_proofs::FlowProof> for FlowProof {
+    type Error = Error;
+
+    fn try_from(proto: transparent_proofs::FlowProof) -> anyhow::Result<Self, Self::Error> {
+        if proto.flow_blindings.len() != flow::NUM_LIMBS {
+            return Err(Error::ProtoMalformed);
+        }
+        let mut flow_blindings = [Fr::zero(); flow::NUM_LIMBS];
+        for (blinding, bytes) in flow_blindings.iter_mut().zip(proto.flow_blindings.iter()) {
+            *blinding = Fr::from_bytes(bytes[..].try_into().map_err(|_| Error::ProtoMalformed)?)
+                .map_err(|_| Error::ProtoMalformed)?;
+        }
+
+        Ok(FlowProof {
+            unbonded_amount: proto.unbonded_amount,
+            delegation_amount: proto.delegation_amount,
+            v_blinding: Fr::from_bytes(
+                proto.v_blinding[..]
+                    .try_into()
+                    .map_err(|_| Error::ProtoMalformed)?,
+            )
+            .map_err(|_| Error::ProtoMalformed)?,
+            flow_blindings,
+        })
+    }
+}
+
 impl From<SpendProof> for Vec<u8> {
     fn from(spend_proof: SpendProof) -> Vec<u8> {
         let protobuf_serialized_proof: transparent_proofs::SpendProof = spend_proof.into();
@@ -429,6 +563,25 @@ impl TryFrom<&[u8]> for OutputProof {
     }
 }
 
+impl From<FlowProof> for Vec<u8> {
+    fn from(flow_proof: FlowProof) -> Vec<u8> {
+        let protobuf_serialized_proof: transparent_proofs::FlowProof = flow_proof.into();
+        protobuf_serialized_proof.encode_to_vec()
+    }
+}
+
+impl TryFrom<&[u8]> for FlowProof {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<FlowProof, Self::Error> {
+        let protobuf_serialized_proof =
+            transparent_proofs::FlowProof::decode(bytes).map_err(|_| Error::ProtoMalformed)?;
+        protobuf_serialized_proof
+            .try_into()
+            .map_err(|_| Error::ProtoMalformed)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ark_ff::UniformRand;
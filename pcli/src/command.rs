@@ -2,6 +2,7 @@ use structopt::StructOpt;
 
 mod addr;
 mod balance;
+mod loadtest;
 mod stake;
 mod tx;
 mod validator;
@@ -9,6 +10,7 @@ mod wallet;
 
 pub use addr::AddrCmd;
 pub use balance::BalanceCmd;
+pub use loadtest::LoadtestCmd;
 pub use stake::StakeCmd;
 pub use tx::TxCmd;
 pub use validator::ValidatorCmd;
@@ -33,6 +35,11 @@ pub enum Command {
     Validator(ValidatorCmd),
     /// Manages delegations and undelegations.
     Stake(StakeCmd),
+    /// Submits synthetic transactions at a fixed rate and reports their latencies.
+    ///
+    /// Intended for benchmarking devnets; the transactions send value from the
+    /// wallet back to itself.
+    Loadtest(LoadtestCmd),
 }
 
 impl Command {
@@ -46,6 +53,7 @@ impl Command {
             Command::Balance(cmd) => cmd.needs_sync(),
             Command::Validator(cmd) => cmd.needs_sync(),
             Command::Stake(cmd) => cmd.needs_sync(),
+            Command::Loadtest(cmd) => cmd.needs_sync(),
         }
     }
 }
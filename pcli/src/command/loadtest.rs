@@ -0,0 +1,215 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use comfy_table::{presets, Table};
+use penumbra_crypto::Value;
+use penumbra_proto::Protobuf;
+use penumbra_stake::STAKING_TOKEN_ASSET_ID;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use structopt::StructOpt;
+
+use crate::{ClientStateFile, Opt};
+
+/// How often to check whether a submitted transaction has been committed.
+const COMMIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, StructOpt)]
+pub struct LoadtestCmd {
+    /// The number of transactions to submit per second.
+    #[structopt(long, default_value = "1")]
+    rate: f64,
+    /// The total number of transactions to submit.
+    #[structopt(long, default_value = "100")]
+    count: u64,
+    /// The amount of upenumbra each transaction sends back to the wallet.
+    #[structopt(long, default_value = "1")]
+    amount: u64,
+    /// The transaction fee (paid in upenumbra).
+    #[structopt(long, default_value = "0")]
+    fee: u64,
+    /// How long to wait for each transaction to be committed, in seconds.
+    #[structopt(long, default_value = "60")]
+    commit_timeout: u64,
+}
+
+/// How long one transaction took to be accepted into the mempool and committed.
+#[derive(Debug)]
+struct Latency {
+    check_tx: Duration,
+    /// `None` if the transaction wasn't committed before the timeout.
+    commit: Option<Duration>,
+}
+
+impl LoadtestCmd {
+    /// Determine if this command requires a network sync before it executes.
+    pub fn needs_sync(&self) -> bool {
+        true
+    }
+
+    /// Submits self-addressed transactions at a fixed rate, then reports how
+    /// long they took to pass `CheckTx` and to be committed.
+    ///
+    /// Each transaction spends at least one note, and change only becomes
+    /// spendable once it's committed, so the sustainable rate is bounded by the
+    /// number of notes in the wallet.  When the wallet runs out, the load test
+    /// waits for the transactions in flight to be committed and resyncs before
+    /// continuing.  Commits are detected with Tendermint's `/tx` endpoint, so the
+    /// node must have transaction indexing enabled.
+    pub async fn exec(&self, opt: &Opt, state: &mut ClientStateFile) -> Result<()> {
+        if self.rate <= 0.0 {
+            return Err(anyhow!("the submission rate must be positive"));
+        }
+        let (_, self_address) = state.wallet().address_by_index(0)?;
+        let value = Value {
+            amount: self.amount,
+            asset_id: *STAKING_TOKEN_ASSET_ID,
+        };
+        let rpc = format!("http://{}:{}", opt.node, opt.rpc_port);
+        let commit_timeout = Duration::from_secs(self.commit_timeout);
+
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / self.rate));
+        let mut in_flight = Vec::new();
+        let mut results = Vec::new();
+        for i in 0..self.count {
+            interval.tick().await;
+
+            let transaction = match state.build_send(
+                &mut OsRng,
+                &[value],
+                self.fee,
+                self_address,
+                None,
+                None,
+            ) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    tracing::info!(
+                        ?e,
+                        in_flight = in_flight.len(),
+                        "no spendable notes left, waiting for submitted transactions to be committed"
+                    );
+                    for submission in in_flight.drain(..) {
+                        results.push(submission.await?);
+                    }
+                    crate::sync::sync(opt, state).await?;
+                    state.build_send(&mut OsRng, &[value], self.fee, self_address, None, None)?
+                }
+            };
+
+            tracing::debug!(i, "submitting transaction");
+            in_flight.push(tokio::spawn(submit(
+                rpc.clone(),
+                transaction.encode_to_vec(),
+                commit_timeout,
+            )));
+        }
+        for submission in in_flight {
+            results.push(submission.await?);
+        }
+        // Record the notes spent by the load test, so they aren't selected again
+        // before they're seen on chain.
+        state.commit()?;
+
+        let mut check_tx = Vec::new();
+        let mut commit = Vec::new();
+        let (mut rejected, mut timed_out) = (0, 0);
+        for result in results {
+            match result {
+                Ok(latency) => {
+                    check_tx.push(latency.check_tx);
+                    match latency.commit {
+                        Some(latency) => commit.push(latency),
+                        None => timed_out += 1,
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(?e, "transaction was rejected");
+                    rejected += 1;
+                }
+            }
+        }
+
+        let mut table = Table::new();
+        table.load_preset(presets::NOTHING);
+        table.set_header(vec!["Latency", "Count", "Min", "p50", "p90", "p99", "Max"]);
+        table.add_row(latency_row("CheckTx", check_tx));
+        table.add_row(latency_row("Commit", commit));
+        println!("{}", table);
+        println!(
+            "{} submitted, {} rejected, {} not committed within {}s",
+            self.count, rejected, timed_out, self.commit_timeout
+        );
+
+        Ok(())
+    }
+}
+
+/// Submits a transaction with `broadcast_tx_sync`, then polls until it's
+/// committed or the timeout passes.
+async fn submit(rpc: String, tx: Vec<u8>, commit_timeout: Duration) -> Result<Latency> {
+    let start = Instant::now();
+    let rsp: serde_json::Value = reqwest::get(format!(
+        "{}/broadcast_tx_sync?tx=0x{}",
+        rpc,
+        hex::encode(&tx)
+    ))
+    .await?
+    .json()
+    .await?;
+    let check_tx = start.elapsed();
+
+    let result = rsp.get("result").unwrap_or(&rsp);
+    let code = result
+        .get("code")
+        .and_then(|c| c.as_i64())
+        .ok_or_else(|| anyhow!("could not parse JSON response"))?;
+    if code != 0 {
+        return Err(anyhow!(
+            "CheckTx failed with code {}: {}",
+            code,
+            result.get("log").and_then(|l| l.as_str()).unwrap_or("")
+        ));
+    }
+
+    let hash = hex::encode(Sha256::digest(&tx));
+    while start.elapsed() < commit_timeout {
+        tokio::time::sleep(COMMIT_POLL_INTERVAL).await;
+        let rsp: serde_json::Value = reqwest::get(format!("{}/tx?hash=0x{}", rpc, hash))
+            .await?
+            .json()
+            .await?;
+        // The transaction isn't indexed until its block has been committed.
+        if rsp.get("error").is_none() {
+            return Ok(Latency {
+                check_tx,
+                commit: Some(start.elapsed()),
+            });
+        }
+    }
+
+    Ok(Latency {
+        check_tx,
+        commit: None,
+    })
+}
+
+fn latency_row(name: &str, mut latencies: Vec<Duration>) -> Vec<String> {
+    latencies.sort();
+    let percentile = |p: usize| {
+        if latencies.is_empty() {
+            "-".to_string()
+        } else {
+            format!("{:?}", latencies[(latencies.len() - 1) * p / 100])
+        }
+    };
+    vec![
+        name.to_string(),
+        latencies.len().to_string(),
+        percentile(0),
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        percentile(100),
+    ]
+}
@@ -4,9 +4,12 @@ use anyhow::{anyhow, Context, Result};
 use comfy_table::{presets, Table};
 use futures::stream::TryStreamExt;
 use penumbra_crypto::Value;
-use penumbra_proto::{light_wallet::ValidatorInfoRequest, thin_wallet::ValidatorRateRequest};
+use penumbra_proto::{
+    light_wallet::ValidatorInfoRequest,
+    thin_wallet::{CurrentEpochRequest, ValidatorRateRequest},
+};
 use penumbra_stake::{
-    DelegationToken, Epoch, IdentityKey, RateData, ValidatorInfo, STAKING_TOKEN_ASSET_ID,
+    DelegationToken, IdentityKey, RateData, ValidatorInfo, STAKING_TOKEN_ASSET_ID,
     STAKING_TOKEN_DENOM,
 };
 use rand_core::OsRng;
@@ -17,6 +20,9 @@ use crate::{ClientStateFile, Opt};
 #[derive(Debug, StructOpt)]
 pub enum StakeCmd {
     /// Deposit stake into a validator's delegation pool.
+    ///
+    /// The amount delegated is public.  Delegations aren't private yet: the
+    /// proofs of encrypted delegations still reveal their amounts.
     Delegate {
         /// The identity key of the validator to delegate to.
         #[structopt(long)]
@@ -31,6 +37,9 @@ pub enum StakeCmd {
         source: Option<u64>,
     },
     /// Withdraw stake from a validator's delegation pool.
+    ///
+    /// The amount undelegated is public.  Undelegations aren't private yet:
+    /// the proofs of encrypted undelegations still reveal their amounts.
     Undelegate {
         /// The amount of delegation tokens to undelegate.
         amount: String,
@@ -49,8 +58,8 @@ pub enum StakeCmd {
         /// The identity key of the validator to delegate to.
         #[structopt(long)]
         to: String,
-        /// The amount of stake to delegate.
-        amount: String,
+        /// The amount of delegation tokens of the `from` validator to redelegate.
+        amount: u64,
         /// The transaction fee (paid in upenumbra).
         #[structopt(long, default_value = "0")]
         fee: u64,
@@ -94,18 +103,19 @@ impl StakeCmd {
 
                 let to = to.parse::<IdentityKey>()?;
 
-                let current_epoch = Epoch::from_height(
-                    state.last_block_height().unwrap() as u64,
-                    state.chain_params().unwrap().epoch_duration,
-                );
-                let next_epoch = current_epoch.next();
-
                 let mut client = opt.thin_wallet_client().await?;
 
+                // Epochs may be time-based, so the current one is found by pd.
+                let current_epoch = client
+                    .current_epoch(tonic::Request::new(CurrentEpochRequest {}))
+                    .await?
+                    .into_inner();
+                let next_epoch_index = current_epoch.index + 1;
+
                 let rate_data: RateData = client
                     .validator_rate(tonic::Request::new(ValidatorRateRequest {
                         identity_key: Some(to.into()),
-                        epoch_index: next_epoch.index,
+                        epoch_index: next_epoch_index,
                     }))
                     .await?
                     .into_inner()
@@ -139,18 +149,19 @@ impl StakeCmd {
 
                 let from = delegation_token.validator();
 
-                let current_epoch = Epoch::from_height(
-                    state.last_block_height().unwrap() as u64,
-                    state.chain_params().unwrap().epoch_duration,
-                );
-                let next_epoch = current_epoch.next();
-
                 let mut client = opt.thin_wallet_client().await?;
 
+                // Epochs may be time-based, so the current one is found by pd.
+                let current_epoch = client
+                    .current_epoch(tonic::Request::new(CurrentEpochRequest {}))
+                    .await?
+                    .into_inner();
+                let next_epoch_index = current_epoch.index + 1;
+
                 let rate_data: RateData = client
                     .validator_rate(tonic::Request::new(ValidatorRateRequest {
                         identity_key: Some(from.into()),
-                        epoch_index: next_epoch.index,
+                        epoch_index: next_epoch_index,
                     }))
                     .await?
                     .into_inner()
@@ -169,8 +180,53 @@ impl StakeCmd {
                 // so that we don't store pending notes that will never appear on-chain.
                 state.commit()?;
             }
-            StakeCmd::Redelegate { .. } => {
-                todo!()
+            StakeCmd::Redelegate {
+                from,
+                to,
+                amount,
+                fee,
+                source,
+            } => {
+                let from = from.parse::<IdentityKey>()?;
+                let to = to.parse::<IdentityKey>()?;
+
+                let mut client = opt.thin_wallet_client().await?;
+
+                // Epochs may be time-based, so the current one is found by pd.
+                let current_epoch = client
+                    .current_epoch(tonic::Request::new(CurrentEpochRequest {}))
+                    .await?
+                    .into_inner();
+                let next_epoch_index = current_epoch.index + 1;
+
+                let mut rates = Vec::new();
+                for identity_key in [from, to] {
+                    let rate_data: RateData = client
+                        .validator_rate(tonic::Request::new(ValidatorRateRequest {
+                            identity_key: Some(identity_key.into()),
+                            epoch_index: next_epoch_index,
+                        }))
+                        .await?
+                        .into_inner()
+                        .try_into()?;
+                    rates.push(rate_data);
+                }
+                let to_rate_data = rates.pop().unwrap();
+                let from_rate_data = rates.pop().unwrap();
+
+                let transaction = state.build_redelegate(
+                    &mut OsRng,
+                    from_rate_data,
+                    to_rate_data,
+                    *amount,
+                    *fee,
+                    *source,
+                )?;
+
+                opt.submit_transaction(&transaction).await?;
+                // Only commit the state if the transaction was submitted successfully,
+                // so that we don't store pending notes that will never appear on-chain.
+                state.commit()?;
             }
             StakeCmd::Show => {
                 let mut client = opt.light_wallet_client().await?;
@@ -1,5 +1,7 @@
-use anyhow::Result;
-use penumbra_stake::IdentityKey;
+use anyhow::{anyhow, Result};
+use comfy_table::{presets, Table};
+use penumbra_proto::thin_wallet::ValidatorOverviewRequest;
+use penumbra_stake::{IdentityKey, RateData, Validator, ValidatorStatus, STAKING_TOKEN_DENOM};
 use penumbra_wallet::ClientState;
 use structopt::StructOpt;
 
@@ -9,27 +11,128 @@ use crate::Opt;
 pub enum ValidatorCmd {
     /// Display the validator identity key derived from this wallet's spend seed.
     Identity,
+    /// Display a validator's status, delegation pool, rates, commission, and
+    /// uptime, as seen by the node.
+    Status {
+        /// The identity key of the validator, if not the one derived from
+        /// this wallet's spend seed.
+        #[structopt(long)]
+        identity_key: Option<String>,
+        /// The number of most recent blocks to measure uptime over, or zero
+        /// for the node's default.
+        #[structopt(long, default_value = "0")]
+        uptime_window: u64,
+    },
 }
 
 impl ValidatorCmd {
     pub fn needs_sync(&self) -> bool {
         match self {
             ValidatorCmd::Identity => false,
+            ValidatorCmd::Status { .. } => false,
         }
     }
 
-    pub async fn exec(&self, _opt: &Opt, state: &ClientState) -> Result<()> {
+    pub async fn exec(&self, opt: &Opt, state: &ClientState) -> Result<()> {
+        let wallet_identity_key = IdentityKey(
+            state
+                .wallet()
+                .full_viewing_key()
+                .spend_verification_key()
+                .clone(),
+        );
+
         match self {
             ValidatorCmd::Identity => {
-                let ik = IdentityKey(
-                    state
-                        .wallet()
-                        .full_viewing_key()
-                        .spend_verification_key()
-                        .clone(),
-                );
-
-                println!("{}", ik);
+                println!("{}", wallet_identity_key);
+            }
+            ValidatorCmd::Status {
+                identity_key,
+                uptime_window,
+            } => {
+                let identity_key = match identity_key {
+                    Some(identity_key) => identity_key.parse::<IdentityKey>()?,
+                    None => wallet_identity_key,
+                };
+
+                let mut client = opt.thin_wallet_client().await?;
+                let overview = client
+                    .validator_overview(tonic::Request::new(ValidatorOverviewRequest {
+                        identity_key: Some(identity_key.into()),
+                        uptime_window: *uptime_window,
+                    }))
+                    .await?
+                    .into_inner();
+
+                let validator: Validator = overview
+                    .validator
+                    .ok_or_else(|| anyhow!("missing validator definition"))?
+                    .try_into()?;
+                let status: ValidatorStatus = overview
+                    .status
+                    .ok_or_else(|| anyhow!("missing validator status"))?
+                    .try_into()?;
+                let next_rate: RateData = overview
+                    .next_rate
+                    .ok_or_else(|| anyhow!("missing next rate data"))?
+                    .try_into()?;
+                let projected_rate: RateData = overview
+                    .projected_rate
+                    .ok_or_else(|| anyhow!("missing projected rate data"))?
+                    .try_into()?;
+
+                let mut table = Table::new();
+                table.load_preset(presets::NOTHING);
+                table.add_row(vec!["Name".to_string(), validator.name]);
+                table.add_row(vec![
+                    "Identity Key".to_string(),
+                    validator.identity_key.to_string(),
+                ]);
+                table.add_row(vec!["State".to_string(), format!("{:?}", status.state)]);
+                table.add_row(vec![
+                    "Voting Power".to_string(),
+                    status.voting_power.to_string(),
+                ]);
+                table.add_row(vec![
+                    "Delegation Pool".to_string(),
+                    format!(
+                        "{}{} ({} delegation tokens)",
+                        overview.delegation_pool,
+                        *STAKING_TOKEN_DENOM,
+                        overview.delegation_token_supply
+                    ),
+                ]);
+                for (label, rate) in [("Next Rate", next_rate), ("Projected Rate", projected_rate)]
+                {
+                    table.add_row(vec![
+                        label.to_string(),
+                        format!(
+                            "epoch {}: exchange rate {}, reward rate {}",
+                            rate.epoch_index,
+                            rate.validator_exchange_rate,
+                            rate.validator_reward_rate
+                        ),
+                    ]);
+                }
+                for stream in validator.funding_streams.as_ref() {
+                    table.add_row(vec![
+                        "Funding Stream".to_string(),
+                        format!("{}bps to {}", stream.rate_bps, stream.address),
+                    ]);
+                }
+                table.add_row(vec![
+                    "Commission Paid".to_string(),
+                    format!("{}{}", overview.commission_paid, *STAKING_TOKEN_DENOM),
+                ]);
+                table.add_row(vec![
+                    "Uptime".to_string(),
+                    format!(
+                        "signed {} of {} commits in the last {} blocks",
+                        overview.uptime_signed, overview.uptime_expected, overview.uptime_window
+                    ),
+                ]);
+
+                println!("{}", table);
             }
         }
 
@@ -95,6 +95,7 @@ async fn main() -> Result<()> {
         Command::Balance(balance_cmd) => balance_cmd.exec(&state)?,
         Command::Validator(cmd) => cmd.exec(&opt, &state).await?,
         Command::Stake(cmd) => cmd.exec(&opt, &mut state).await?,
+        Command::Loadtest(cmd) => cmd.exec(&opt, &mut state).await?,
     }
 
     Ok(())
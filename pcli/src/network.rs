@@ -54,12 +54,14 @@ impl Opt {
     pub async fn thin_wallet_client(&self) -> Result<ThinWalletClient<Channel>, anyhow::Error> {
         ThinWalletClient::connect(format!("http://{}:{}", self.node, self.thin_wallet_port))
             .await
+            .map(|client| client.accept_gzip())
             .map_err(Into::into)
     }
 
     pub async fn light_wallet_client(&self) -> Result<LightWalletClient<Channel>, anyhow::Error> {
         LightWalletClient::connect(format!("http://{}:{}", self.node, self.light_wallet_port))
             .await
+            .map(|client| client.accept_gzip())
             .map_err(Into::into)
     }
 }
@@ -0,0 +1,24 @@
+-- The raw data pd processed at each height, kept so that derived tables can be
+-- audited and rebuilt later.  The genesis block has no BeginBlock request, so
+-- it has no rows here; its data is the genesis configuration in `blobs`.
+--
+-- These tables have no foreign keys to `blocks`, since the raw data must
+-- outlive the derived tables, so that `pd reindex` can clear them and rebuild
+-- them from it.
+CREATE TABLE IF NOT EXISTS raw_blocks (
+    height bigint PRIMARY KEY,
+    block_hash bytea NOT NULL,
+    -- The protobuf-encoded BeginBlock request, including the block header.
+    begin_block bytea NOT NULL
+);
+
+-- Every transaction delivered in a block, in delivery order, whether or not it
+-- passed verification.
+CREATE TABLE IF NOT EXISTS raw_transactions (
+    height bigint NOT NULL,
+    position integer NOT NULL,
+    tx_hash bytea NOT NULL,
+    data bytea NOT NULL,
+    PRIMARY KEY (height, position)
+);
+CREATE INDEX ON raw_transactions (tx_hash);
@@ -0,0 +1,7 @@
+-- The protobuf-encoded CompactBlock for each height, precomputed when the block
+-- is committed so that wallet sync is a range scan.  Blocks committed before
+-- this table existed can be filled in with `pd reindex`.
+CREATE TABLE IF NOT EXISTS compact_blocks (
+    height bigint PRIMARY KEY REFERENCES blocks (height),
+    data bytea NOT NULL
+);
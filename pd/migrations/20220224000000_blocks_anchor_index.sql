@@ -0,0 +1,2 @@
+-- Supports looking up the height at which a given anchor was the note commitment tree root.
+CREATE INDEX IF NOT EXISTS blocks_nct_anchor_idx ON blocks (nct_anchor);
@@ -0,0 +1,2 @@
+-- Supports looking up the notes created by a given transaction.
+CREATE INDEX IF NOT EXISTS notes_transaction_id_idx ON notes (transaction_id);
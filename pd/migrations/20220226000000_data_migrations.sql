@@ -0,0 +1,5 @@
+-- Records the data migrations (see `state::data_migrations`) that have been applied.
+CREATE TABLE IF NOT EXISTS data_migrations (
+    id varchar(64) PRIMARY KEY,
+    applied_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,5 @@
+-- The chain this database belongs to, recorded at genesis.  Holds at most one row.
+CREATE TABLE IF NOT EXISTS chain_identity (
+    chain_id varchar PRIMARY KEY,
+    genesis_hash bytea NOT NULL
+);
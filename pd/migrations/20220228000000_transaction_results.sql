@@ -0,0 +1,11 @@
+-- The result of delivering each transaction, so that transactions can be looked
+-- up by hash without relying on Tendermint's indexer.  The hash and raw data
+-- are in raw_transactions, at the same (height, position).
+CREATE TABLE IF NOT EXISTS transaction_results (
+    height bigint NOT NULL REFERENCES blocks (height),
+    position integer NOT NULL,
+    -- The DeliverTx response code; zero means the transaction was applied.
+    code integer NOT NULL,
+    log text NOT NULL,
+    PRIMARY KEY (height, position)
+);
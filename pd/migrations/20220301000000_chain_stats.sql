@@ -0,0 +1,30 @@
+-- Aggregate statistics, updated as each block is committed, so that they don't
+-- need to be computed from the raw tables.  Blocks committed before these
+-- tables existed are only included after a `pd reindex`.
+CREATE TABLE IF NOT EXISTS block_stats (
+    height bigint PRIMARY KEY REFERENCES blocks (height),
+    epoch bigint NOT NULL,
+    -- Transactions that were applied, and that were rejected.
+    transactions integer NOT NULL,
+    failed_transactions integer NOT NULL,
+    notes_created integer NOT NULL,
+    nullifiers_spent integer NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS epoch_stats (
+    epoch bigint PRIMARY KEY,
+    blocks bigint NOT NULL,
+    transactions bigint NOT NULL,
+    failed_transactions bigint NOT NULL,
+    notes_created bigint NOT NULL,
+    nullifiers_spent bigint NOT NULL
+);
+
+-- The delegation tokens minted and burned for each validator in each epoch.
+CREATE TABLE IF NOT EXISTS validator_epoch_stats (
+    validator_identity_key bytea NOT NULL REFERENCES validators (identity_key),
+    epoch bigint NOT NULL,
+    delegated bigint NOT NULL,
+    undelegated bigint NOT NULL,
+    PRIMARY KEY (epoch, validator_identity_key)
+);
@@ -0,0 +1,12 @@
+-- The full validator set as it was when each epoch began.  Rows are only ever
+-- inserted, so this is a record of every epoch's consensus participants.
+-- Epochs that began before this table existed only have snapshots after a
+-- `pd reindex`.
+CREATE TABLE IF NOT EXISTS validator_set_snapshots (
+    epoch bigint NOT NULL,
+    validator_identity_key bytea NOT NULL,
+    consensus_key bytea NOT NULL,
+    voting_power bigint NOT NULL,
+    validator_state varchar NOT NULL,
+    PRIMARY KEY (epoch, validator_identity_key)
+);
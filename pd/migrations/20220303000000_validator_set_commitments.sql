@@ -0,0 +1,6 @@
+-- The commitment to each epoch's validator set that is stored in the JMT, so
+-- that light clients can verify validator set snapshots against the app hash.
+CREATE TABLE IF NOT EXISTS validator_set_commitments (
+    epoch bigint PRIMARY KEY,
+    commitment bytea NOT NULL
+);
@@ -0,0 +1,6 @@
+-- The transaction fees collected in each epoch, which are burned or
+-- distributed to the active validators at the end of the epoch.
+CREATE TABLE IF NOT EXISTS epoch_fees (
+    epoch bigint PRIMARY KEY,
+    collected bigint NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Display metadata registered for assets by DenomMetadata actions.
+CREATE TABLE IF NOT EXISTS denom_metadata (
+    asset_id bytea PRIMARY KEY,
+    denom varchar NOT NULL,
+    description varchar NOT NULL,
+    display_exponent integer NOT NULL,
+    symbol varchar NOT NULL UNIQUE,
+    height bigint NOT NULL REFERENCES blocks (height)
+);
@@ -0,0 +1,6 @@
+-- The unit amounts of each asset are usually displayed in, and the power of
+-- ten relating it to the base unit.  Filled in for existing assets by the
+-- `backfill_asset_display_units` data migration.
+ALTER TABLE assets
+    ADD COLUMN display_denom varchar,
+    ADD COLUMN display_exponent integer;
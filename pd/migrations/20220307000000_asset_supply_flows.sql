@@ -0,0 +1,7 @@
+-- The total amounts of each asset ever minted and burned, accumulated from
+-- the changes to its supply as they're recorded.  Assets recorded before
+-- these columns existed are treated as having minted their whole supply.
+ALTER TABLE assets
+    ADD COLUMN minted bigint NOT NULL DEFAULT 0,
+    ADD COLUMN burned bigint NOT NULL DEFAULT 0;
+UPDATE assets SET minted = total_supply;
@@ -0,0 +1,6 @@
+-- The signed headers of recent blocks, as fetched from Tendermint, which
+-- counterparty chains need to create or update a light client of this chain.
+CREATE TABLE IF NOT EXISTS tendermint_headers (
+    height bigint PRIMARY KEY,
+    signed_header bytea NOT NULL
+);
@@ -0,0 +1,10 @@
+-- The start of each epoch.  Time-based epochs end with the first block whose
+-- timestamp is at least `epoch_duration_secs` past the start time of their
+-- epoch, so the start of the current epoch is needed to process each block.
+-- Start times are in seconds since the Unix epoch.  Epochs that started
+-- before this table existed aren't recorded.
+CREATE TABLE IF NOT EXISTS epochs (
+    epoch bigint PRIMARY KEY,
+    start_height bigint NOT NULL,
+    start_time bigint NOT NULL
+);
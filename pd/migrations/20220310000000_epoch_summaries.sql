@@ -0,0 +1,14 @@
+-- A summary of what was applied at the end of each epoch, written along with
+-- the epoch's last block.  Amounts are in the staking token.
+CREATE TABLE IF NOT EXISTS epoch_summaries (
+    epoch bigint PRIMARY KEY,
+    end_height bigint NOT NULL REFERENCES blocks (height),
+    rewards_distributed bigint NOT NULL,
+    fees_collected bigint NOT NULL,
+    fees_distributed bigint NOT NULL,
+    delegated bigint NOT NULL,
+    undelegated bigint NOT NULL,
+    validators integer NOT NULL,
+    validators_changed integer NOT NULL,
+    staking_token_supply bigint NOT NULL
+);
@@ -0,0 +1,11 @@
+-- The stake redelegated away from and to each validator in each epoch, which
+-- is limited per epoch.  Outflows are in the validator's delegation tokens
+-- consumed, and inflows in its delegation tokens produced.  The epoch is the
+-- one the redelegations were prepared for.
+CREATE TABLE IF NOT EXISTS validator_epoch_redelegations (
+    validator_identity_key bytea NOT NULL REFERENCES validators (identity_key),
+    epoch bigint NOT NULL,
+    redelegated_out bigint NOT NULL,
+    redelegated_in bigint NOT NULL,
+    PRIMARY KEY (epoch, validator_identity_key)
+);
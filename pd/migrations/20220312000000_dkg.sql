@@ -0,0 +1,30 @@
+-- The distributed key generation run by the active validators of each epoch
+-- to establish the threshold decryption key for the next epoch.  A round is
+-- opened when its epoch begins, and finished when it ends; its commitment is
+-- NULL while it's open, and its group key is NULL if it failed.
+CREATE TABLE IF NOT EXISTS dkg_rounds (
+    epoch bigint PRIMARY KEY,
+    threshold bigint NOT NULL,
+    group_key bytea,
+    commitment bytea
+);
+
+-- The participants in each round, numbered from 1 in identity key order.
+-- Each participant's public key share is set when the round finishes.
+CREATE TABLE IF NOT EXISTS dkg_participants (
+    epoch bigint NOT NULL REFERENCES dkg_rounds (epoch),
+    participant_index bigint NOT NULL,
+    identity_key bytea NOT NULL,
+    public_key_share bytea,
+    PRIMARY KEY (epoch, participant_index),
+    UNIQUE (epoch, identity_key)
+);
+
+-- The signed dealings submitted to each round, at most one per participant.
+CREATE TABLE IF NOT EXISTS dkg_dealings (
+    epoch bigint NOT NULL REFERENCES dkg_rounds (epoch),
+    identity_key bytea NOT NULL,
+    height bigint NOT NULL,
+    dealing bytea NOT NULL,
+    PRIMARY KEY (epoch, identity_key)
+);
@@ -0,0 +1,22 @@
+-- The delegations and undelegations whose amounts are encrypted to the
+-- threshold key of the epoch in which they were performed.  Only the sums of
+-- each validator's flows in each epoch are ever decrypted.
+CREATE TABLE IF NOT EXISTS encrypted_flows (
+    epoch bigint NOT NULL,
+    validator_identity_key bytea NOT NULL,
+    height bigint NOT NULL,
+    undelegation boolean NOT NULL,
+    ciphertext bytea NOT NULL
+);
+
+CREATE INDEX ON encrypted_flows (epoch);
+
+-- The decryption shares of each epoch's summed flows, at most one set per
+-- key share holder.
+CREATE TABLE IF NOT EXISTS flow_decryptions (
+    epoch bigint NOT NULL,
+    identity_key bytea NOT NULL,
+    height bigint NOT NULL,
+    decryption bytea NOT NULL,
+    PRIMARY KEY (epoch, identity_key)
+);
@@ -0,0 +1,7 @@
+-- How much of each block's capacity was used, and what was paid for it, so
+-- that fees can be estimated from recent blocks.  Blocks committed before
+-- these columns existed are only included after a `pd reindex`.
+ALTER TABLE block_stats
+    ADD COLUMN bytes bigint NOT NULL DEFAULT 0,
+    ADD COLUMN weight bigint NOT NULL DEFAULT 0,
+    ADD COLUMN fees bigint NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- The commission paid to each validator's funding streams at the end of each
+-- epoch, so that validators can see what they've been paid.  Commission paid
+-- before this column existed is only included after a `pd reindex`.
+ALTER TABLE validator_epoch_stats
+    ADD COLUMN commission bigint NOT NULL DEFAULT 0;
@@ -0,0 +1,22 @@
+-- Note ciphertexts are most of the size of each note, so they're kept apart
+-- from the rest of it, and scans of the notes table by height, position, or
+-- transaction don't have to read them.
+CREATE TABLE IF NOT EXISTS note_ciphertexts (
+    note_commitment bytea PRIMARY KEY REFERENCES notes (note_commitment),
+    ephemeral_key bytea NOT NULL,
+    encrypted_note bytea NOT NULL
+);
+
+INSERT INTO note_ciphertexts (note_commitment, ephemeral_key, encrypted_note)
+    SELECT note_commitment, ephemeral_key, encrypted_note FROM notes;
+ALTER TABLE notes
+    DROP COLUMN ephemeral_key,
+    DROP COLUMN encrypted_note;
+
+-- Compact blocks no longer include the ciphertexts of their notes, which are
+-- filled in from note_ciphertexts when they're served, and what's left may be
+-- compressed (see `state::compact_block`).  The ciphertexts are removed from
+-- the compact blocks already stored by the `strip_compact_block_ciphertexts`
+-- data migration.
+ALTER TABLE compact_blocks
+    ADD COLUMN compression smallint NOT NULL DEFAULT 0;
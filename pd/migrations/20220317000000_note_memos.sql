@@ -0,0 +1,6 @@
+-- The encrypted memo attached to each note by the output that created it, or
+-- NULL for notes the chain creates itself, like funding stream rewards, which
+-- have no memo.  Memos are ciphertexts too, so they're kept with the note
+-- ciphertexts rather than in notes.
+ALTER TABLE note_ciphertexts
+    ADD COLUMN encrypted_memo bytea;
@@ -0,0 +1,18 @@
+-- Each validator's current vote for an upgrade plan.  Voting again replaces
+-- the validator's previous vote.
+CREATE TABLE IF NOT EXISTS upgrade_votes (
+    validator_identity_key bytea PRIMARY KEY,
+    name varchar NOT NULL,
+    upgrade_height bigint NOT NULL,
+    -- The height of the block the vote was cast in.
+    height bigint NOT NULL
+);
+
+-- The upgrade plans scheduled by the validators' votes, keyed by the height of
+-- the block whose votes scheduled them.  The most recently scheduled plan
+-- replaces any earlier one.
+CREATE TABLE IF NOT EXISTS upgrade_plans (
+    height bigint PRIMARY KEY,
+    name varchar NOT NULL,
+    upgrade_height bigint NOT NULL
+);
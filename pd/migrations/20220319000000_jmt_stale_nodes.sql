@@ -0,0 +1,9 @@
+-- The JMT nodes superseded by a newer version of the tree, by the version,
+-- which is the height, from which on they're no longer part of it.  A node
+-- stale since a version is only part of the states before that version, so it
+-- can be deleted once none of those states are retained.
+CREATE TABLE IF NOT EXISTS jmt_stale_nodes (
+    stale_since_version bigint NOT NULL,
+    node_key bytea NOT NULL,
+    PRIMARY KEY (stale_since_version, node_key)
+);
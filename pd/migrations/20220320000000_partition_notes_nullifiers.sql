@@ -0,0 +1,68 @@
+-- Notes and nullifiers are partitioned by ranges of 100000 heights, so that an
+-- old range can be detached or dropped without rewriting the rest of the
+-- table, and so that the indexes written to at the head of the chain stay
+-- small.  This creates the partitions for the heights already stored and the
+-- range after them; `state::partitions` creates the later ones ahead of the
+-- chain, and must use the same range size.
+--
+-- A partitioned table can only enforce the uniqueness of keys that include
+-- its partition key, so the keys now include the height.  Nullifiers are
+-- still only spent once, since stateful verification rejects double spends
+-- before a block is committed, but the tables referencing notes and
+-- nullifiers by their commitment or nullifier alone can no longer do so with
+-- a foreign key.
+ALTER TABLE note_ciphertexts DROP CONSTRAINT note_ciphertexts_note_commitment_fkey;
+ALTER TABLE unbonding_nullifiers DROP CONSTRAINT unbonding_nullifiers_nullifier_fkey;
+
+ALTER TABLE notes RENAME TO unpartitioned_notes;
+ALTER TABLE nullifiers RENAME TO unpartitioned_nullifiers;
+
+CREATE TABLE notes (
+    note_commitment bytea NOT NULL,
+    transaction_id bytea NOT NULL,
+    position bigint NOT NULL,
+    height bigint NOT NULL
+) PARTITION BY RANGE (height);
+
+CREATE TABLE nullifiers (
+    nullifier bytea NOT NULL,
+    height bigint NOT NULL
+) PARTITION BY RANGE (height);
+
+DO $$
+DECLARE
+    first_height bigint;
+BEGIN
+    FOR first_height IN
+        SELECT generate_series(0, COALESCE(MAX(height), 0) + 100000, 100000) FROM blocks
+    LOOP
+        EXECUTE format(
+            'CREATE TABLE notes_p%s PARTITION OF notes FOR VALUES FROM (%s) TO (%s)',
+            first_height / 100000, first_height, first_height + 100000
+        );
+        EXECUTE format(
+            'CREATE TABLE nullifiers_p%s PARTITION OF nullifiers FOR VALUES FROM (%s) TO (%s)',
+            first_height / 100000, first_height, first_height + 100000
+        );
+    END LOOP;
+END $$;
+
+INSERT INTO notes (note_commitment, transaction_id, position, height)
+    SELECT note_commitment, transaction_id, position, height FROM unpartitioned_notes;
+INSERT INTO nullifiers (nullifier, height)
+    SELECT nullifier, height FROM unpartitioned_nullifiers;
+
+DROP TABLE unpartitioned_notes;
+DROP TABLE unpartitioned_nullifiers;
+
+-- The keys and indexes are created once the old tables, and the names of
+-- their constraints and indexes, are gone.
+ALTER TABLE notes ADD PRIMARY KEY (note_commitment, height);
+ALTER TABLE notes ADD FOREIGN KEY (height) REFERENCES blocks (height);
+CREATE INDEX notes_position_idx ON notes (position);
+CREATE INDEX notes_height_idx ON notes (height);
+CREATE INDEX notes_transaction_id_idx ON notes (transaction_id);
+
+ALTER TABLE nullifiers ADD PRIMARY KEY (nullifier, height);
+ALTER TABLE nullifiers ADD FOREIGN KEY (height) REFERENCES blocks (height);
+CREATE INDEX nullifiers_height_idx ON nullifiers (height);
@@ -0,0 +1,32 @@
+-- Covering and partial indexes for the queries the Reader runs with every
+-- block or wallet sync, so that they're answered from the index alone.  Each
+-- covering index replaces the plain index on the same key.
+--
+-- Nullifier lookups, for double-spend checks, are already answered from the
+-- nullifiers primary key, which includes the height they return.
+
+-- Listing a block's nullifiers.
+CREATE INDEX IF NOT EXISTS nullifiers_height_covering_idx
+    ON nullifiers (height) INCLUDE (nullifier);
+DROP INDEX IF EXISTS nullifiers_height_idx;
+
+-- Scanning the notes in a range of heights, for wallet sync, compact blocks,
+-- and rebuilding the note commitment tree.
+CREATE INDEX IF NOT EXISTS notes_height_covering_idx
+    ON notes (height) INCLUDE (position, note_commitment, transaction_id);
+DROP INDEX IF EXISTS notes_height_idx;
+
+-- Looking up every validator's rates for an epoch.
+CREATE INDEX IF NOT EXISTS validator_rates_epoch_covering_idx
+    ON validator_rates (epoch) INCLUDE (identity_key, validator_reward_rate, validator_exchange_rate);
+
+-- Summing each validator's delegation changes in an epoch.
+CREATE INDEX IF NOT EXISTS delegation_changes_epoch_covering_idx
+    ON delegation_changes (epoch) INCLUDE (validator_identity_key, delegation_change);
+DROP INDEX IF EXISTS delegation_changes_epoch_idx;
+
+-- Listing the validators with voting power, for tallying upgrade votes.  Most
+-- validators ever defined have none, so they're left out of the index.
+CREATE INDEX IF NOT EXISTS validators_powered_idx
+    ON validators (validator_state) INCLUDE (identity_key, voting_power)
+    WHERE voting_power > 0;
@@ -0,0 +1,15 @@
+-- State changes scheduled for future blocks, carried out at the start of the
+-- first block at or after the height, or in the epoch, they're due.  Each is
+-- keyed by the height of the block that scheduled it and its position among
+-- the actions that block scheduled, which is the order they're carried out in.
+CREATE TABLE IF NOT EXISTS scheduled_actions (
+    height bigint NOT NULL,
+    position integer NOT NULL,
+    due_height bigint,
+    due_epoch bigint,
+    -- The kind of action, and its encoding (see `scheduler::ScheduledAction`).
+    kind varchar NOT NULL,
+    data bytea NOT NULL,
+    PRIMARY KEY (height, position),
+    CHECK ((due_height IS NULL) <> (due_epoch IS NULL))
+);
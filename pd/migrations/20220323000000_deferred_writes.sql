@@ -0,0 +1,9 @@
+-- The deferred writes of committed blocks that haven't been finished yet.
+-- Each is recorded in the same database transaction as the rest of its block,
+-- and removed in the one that finishes it, so that if pd stops in between,
+-- the writes are finished when it starts again.
+CREATE TABLE IF NOT EXISTS deferred_writes (
+    height bigint PRIMARY KEY REFERENCES blocks (height),
+    -- The encoded writes (see `state::writer::DeferredWrites`).
+    data bytea NOT NULL
+);
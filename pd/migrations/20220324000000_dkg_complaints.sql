@@ -0,0 +1,10 @@
+-- Justified complaints about DKG dealings, each of which leaves its dealer out
+-- of the round.
+CREATE TABLE IF NOT EXISTS dkg_complaints (
+    epoch bigint NOT NULL REFERENCES dkg_rounds (epoch),
+    dealer_identity_key bytea NOT NULL,
+    identity_key bytea NOT NULL,
+    height bigint NOT NULL,
+    complaint bytea NOT NULL,
+    PRIMARY KEY (epoch, dealer_identity_key, identity_key)
+);
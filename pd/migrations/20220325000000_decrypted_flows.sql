@@ -0,0 +1,17 @@
+-- The epochs whose encrypted flows have been decrypted and applied.  The flows
+-- of an epoch that isn't here yet are tried again at the end of each epoch,
+-- until enough key share holders have decrypted them.
+CREATE TABLE IF NOT EXISTS decrypted_flows (
+    epoch bigint PRIMARY KEY,
+    height bigint NOT NULL
+);
+
+-- The flows of every epoch that has already been followed by a whole epoch
+-- were tried once, and are recorded as decrypted so that they aren't applied
+-- again.  Any of them that couldn't be decrypted stay dropped.
+INSERT INTO decrypted_flows (epoch, height)
+SELECT DISTINCT encrypted_flows.epoch, latest.start_height - 1
+FROM encrypted_flows,
+    (SELECT epoch, start_height FROM epochs ORDER BY epoch DESC LIMIT 1) AS latest
+WHERE encrypted_flows.epoch + 1 < latest.epoch
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,3 @@
+-- The sequence number of each validator's current vote, which its next vote
+-- must exceed.  Votes cast before sequence numbers were signed count as 0.
+ALTER TABLE upgrade_votes ADD COLUMN IF NOT EXISTS sequence_number bigint NOT NULL DEFAULT 0;
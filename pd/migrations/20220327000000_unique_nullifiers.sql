@@ -0,0 +1,25 @@
+-- The partitioned `notes` and `nullifiers` tables can only enforce uniqueness
+-- per height, so each note commitment and nullifier is also recorded in an
+-- unpartitioned table keyed by it alone.  These enforce that a nullifier is
+-- only ever spent once and a note only ever created once, across every
+-- height, and give the tables referring to notes and nullifiers by their
+-- commitment or nullifier alone something to refer to.
+--
+-- They must be kept even if old partitions of `notes` and `nullifiers` are
+-- dropped, since a dropped nullifier must still never be spent again.
+CREATE TABLE IF NOT EXISTS unique_note_commitments (
+    note_commitment bytea PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS unique_nullifiers (
+    nullifier bytea PRIMARY KEY
+);
+
+INSERT INTO unique_note_commitments (note_commitment)
+    SELECT note_commitment FROM notes;
+INSERT INTO unique_nullifiers (nullifier)
+    SELECT nullifier FROM nullifiers;
+
+ALTER TABLE note_ciphertexts
+    ADD FOREIGN KEY (note_commitment) REFERENCES unique_note_commitments (note_commitment);
+ALTER TABLE unbonding_nullifiers
+    ADD FOREIGN KEY (nullifier) REFERENCES unique_nullifiers (nullifier);
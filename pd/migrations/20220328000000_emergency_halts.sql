@@ -0,0 +1,4 @@
+-- Whether each vote and scheduled plan is an emergency halt, which stops every
+-- release at its height.  Plans recorded before emergency halts existed aren't.
+ALTER TABLE upgrade_votes ADD COLUMN IF NOT EXISTS emergency_halt boolean NOT NULL DEFAULT false;
+ALTER TABLE upgrade_plans ADD COLUMN IF NOT EXISTS emergency_halt boolean NOT NULL DEFAULT false;
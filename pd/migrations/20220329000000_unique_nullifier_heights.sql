@@ -0,0 +1,11 @@
+-- The height each nullifier was spent at is recorded with it in
+-- `unique_nullifiers`, so that lookups of spent nullifiers don't have to
+-- search every partition of `nullifiers`.
+ALTER TABLE unique_nullifiers ADD COLUMN IF NOT EXISTS height bigint;
+
+UPDATE unique_nullifiers SET height = (
+    SELECT MIN(nullifiers.height) FROM nullifiers
+        WHERE nullifiers.nullifier = unique_nullifiers.nullifier
+);
+
+ALTER TABLE unique_nullifiers ALTER COLUMN height SET NOT NULL;
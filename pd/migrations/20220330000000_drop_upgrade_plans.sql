@@ -0,0 +1,4 @@
+-- Scheduled upgrade plans are only kept as the halts queued in
+-- `scheduled_actions`, where a replaced plan's halt is removed, so the plan
+-- in effect is the one whose halt is queued.
+DROP TABLE IF EXISTS upgrade_plans;
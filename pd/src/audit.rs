@@ -0,0 +1,98 @@
+use anyhow::{anyhow, Result};
+use penumbra_crypto::asset;
+
+use crate::state;
+
+/// Recomputes each validator's delegation token supply from the history of
+/// delegation changes, and compares it with the supply recorded in the asset
+/// registry and with the validator's voting power.
+///
+/// Supply changes are only applied at the end of each epoch, so the
+/// recomputed supply is the genesis allocation plus the net delegation
+/// changes of every epoch that has ended.  Any drift is logged, and makes the
+/// audit fail.  pd should be stopped while the audit runs, since an epoch
+/// boundary committed partway through would show up as drift.
+pub async fn audit_supply(uri: &str) -> Result<()> {
+    let (reader, _writer) = state::new(uri).await?;
+
+    let genesis = reader.genesis_configuration().await?;
+    let height = reader.height().await?.value();
+    // The index of the epoch the next block belongs to.
+    let ended_epochs = reader
+        .current_epoch()
+        .await?
+        .ok_or_else(|| anyhow!("the start of the current epoch wasn't recorded"))?
+        .index;
+    let delegation_changes = reader.total_delegation_changes(ended_epochs).await?;
+    tracing::info!(?height, ?ended_epochs, "auditing delegation token supply");
+
+    let mut drifted = 0;
+    for info in reader.validator_info(true).await? {
+        let identity_key = info.validator.identity_key;
+        let token = identity_key.delegation_token();
+
+        let genesis_supply = genesis
+            .allocations
+            .iter()
+            .filter(|allocation| {
+                asset::REGISTRY
+                    .parse_denom(&allocation.denom)
+                    .map(|denom| denom.id())
+                    == Some(token.id())
+            })
+            .map(|allocation| allocation.amount as i64)
+            .sum::<i64>();
+        let computed_supply =
+            genesis_supply + delegation_changes.get(&identity_key).copied().unwrap_or(0);
+        let recorded_supply = reader
+            .asset_lookup(token.id())
+            .await?
+            .map(|asset| asset.total_supply as i64)
+            .unwrap_or(0);
+
+        let mut ok = true;
+        if computed_supply != recorded_supply {
+            tracing::warn!(
+                %identity_key,
+                computed_supply,
+                recorded_supply,
+                "delegation token supply has drifted"
+            );
+            ok = false;
+        }
+
+        // Until the first epoch boundary, voting power is the one set in the
+        // genesis file rather than one derived from the supply.
+        if info.rate_data.epoch_index > 0 {
+            let base_rate_data = reader.base_rate_data(info.rate_data.epoch_index).await?;
+            let computed_power = info
+                .rate_data
+                .voting_power(computed_supply.max(0) as u64, &base_rate_data);
+            if computed_power != info.status.voting_power {
+                tracing::warn!(
+                    %identity_key,
+                    computed_power,
+                    recorded_power = info.status.voting_power,
+                    "voting power has drifted"
+                );
+                ok = false;
+            }
+        }
+
+        if ok {
+            tracing::info!(%identity_key, supply = recorded_supply, "supply is consistent");
+        } else {
+            drifted += 1;
+        }
+    }
+
+    if drifted > 0 {
+        return Err(anyhow!(
+            "delegation token supply has drifted for {} validators",
+            drifted
+        ));
+    }
+    tracing::info!("all delegation token supplies are consistent");
+
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::{postgres::PgPoolOptions, query};
+
+use crate::state;
+
+mod upload;
+
+pub use upload::{upload_backups, RawBlock, UploadConfig};
+
+/// Every table holding application state, ordered so that tables are restored
+/// after the tables their foreign keys refer to.
+pub(crate) const TABLES: &[&str] = &[
+    "blobs",
+    "jmt",
+    "jmt_stale_nodes",
+    "assets",
+    "blocks",
+    "nullifiers",
+    "unique_nullifiers",
+    "notes",
+    "unique_note_commitments",
+    "note_ciphertexts",
+    "validators",
+    "validator_fundingstreams",
+    "base_rates",
+    "validator_rates",
+    "delegation_changes",
+    "epoch_fees",
+    "epochs",
+    "denom_metadata",
+    "upgrade_votes",
+    "scheduled_actions",
+    "unbonding_notes",
+    "unbonding_nullifiers",
+    "raw_blocks",
+    "raw_transactions",
+    "tendermint_headers",
+    "transaction_results",
+    "block_stats",
+    "epoch_stats",
+    "epoch_summaries",
+    "validator_epoch_stats",
+    "validator_epoch_redelegations",
+    "validator_set_snapshots",
+    "validator_set_commitments",
+    "dkg_rounds",
+    "dkg_participants",
+    "dkg_dealings",
+    "dkg_complaints",
+    "encrypted_flows",
+    "flow_decryptions",
+    "decrypted_flows",
+    "compact_blocks",
+    "deferred_writes",
+    "data_migrations",
+    "chain_identity",
+];
+
+const MANIFEST: &str = "manifest.json";
+
+/// Describes the snapshot contained in a backup archive.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub chain_id: String,
+    pub height: u64,
+    #[serde_as(as = "serde_with::hex::Hex")]
+    pub app_hash: Vec<u8>,
+    /// The schema migrations applied to the database the snapshot was taken from.
+    pub migrations: Vec<i64>,
+}
+
+/// Writes a snapshot of the application state to a gzipped tar archive at `path`.
+///
+/// The snapshot is read in a single repeatable-read transaction, so it's
+/// consistent even while pd is running.  The archive contains a manifest
+/// followed by the binary `COPY` output of each table.
+pub async fn backup(uri: &str, path: &Path) -> Result<Manifest> {
+    let pool = PgPoolOptions::new().max_connections(1).connect(uri).await?;
+    let migrator = sqlx::migrate!("./migrations");
+    migrator.run(&pool).await?;
+
+    // Make sure a table added by a later migration isn't silently left out.
+    // Partitions are backed up as part of their parent tables.
+    for row in query!(
+        r#"SELECT relname::text AS "tablename!" FROM pg_class
+            WHERE relnamespace = 'public'::regnamespace
+            AND relkind IN ('r', 'p')
+            AND NOT relispartition"#
+    )
+    .fetch_all(&pool)
+    .await?
+    {
+        if row.tablename != "_sqlx_migrations" && !TABLES.contains(&row.tablename.as_str()) {
+            return Err(anyhow!(
+                "table {} is not included in backups",
+                row.tablename
+            ));
+        }
+    }
+
+    let mut dbtx = pool.begin().await?;
+    query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut dbtx)
+        .await?;
+
+    let chain_id = query!("SELECT chain_id FROM chain_identity")
+        .fetch_optional(&mut dbtx)
+        .await?
+        .ok_or_else(|| anyhow!("the database has no chain to back up"))?
+        .chain_id;
+    let latest = query!("SELECT height, app_hash FROM blocks ORDER BY height DESC LIMIT 1")
+        .fetch_one(&mut dbtx)
+        .await?;
+    // Compact blocks are written along with the rest of a block's deferred
+    // writes, so if the latest one is missing, the snapshot caught pd in the
+    // middle of committing a block.
+    let written = query!("SELECT MAX(height) AS height FROM compact_blocks")
+        .fetch_one(&mut dbtx)
+        .await?
+        .height;
+    if written != Some(latest.height) {
+        return Err(anyhow!(
+            "block {} was still being written when the snapshot was taken; try again",
+            latest.height
+        ));
+    }
+
+    let manifest = Manifest {
+        chain_id,
+        height: latest.height as u64,
+        app_hash: latest.app_hash,
+        migrations: migrator.iter().map(|m| m.version).collect(),
+    };
+    tracing::info!(?manifest, ?path, "writing backup");
+
+    let mut archive =
+        tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::default()));
+    append(
+        &mut archive,
+        MANIFEST,
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+
+    for table in TABLES {
+        // Spool the table to a temporary file, since tar needs each entry's size up front.
+        let mut spool = tempfile::tempfile()?;
+        // Partitioned tables can only be copied out through a query.
+        let mut rows = dbtx
+            .copy_out_raw(&format!(
+                "COPY (SELECT * FROM {}) TO STDOUT (FORMAT binary)",
+                table
+            ))
+            .await?;
+        while let Some(chunk) = rows.try_next().await? {
+            spool.write_all(&chunk)?;
+        }
+        drop(rows);
+
+        let size = spool.seek(SeekFrom::End(0))?;
+        spool.seek(SeekFrom::Start(0))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, table_entry(table), spool)?;
+
+        tracing::debug!(?table, size, "wrote table");
+    }
+
+    archive.into_inner()?.finish()?;
+    dbtx.rollback().await?;
+
+    Ok(manifest)
+}
+
+/// Restores a snapshot written by [`backup`] into an empty database.
+pub async fn import(uri: &str, path: &Path) -> Result<Manifest> {
+    let pool = PgPoolOptions::new().max_connections(1).connect(uri).await?;
+    let migrator = sqlx::migrate!("./migrations");
+    migrator.run(&pool).await?;
+
+    let has_blocks = query!("SELECT height FROM blocks LIMIT 1")
+        .fetch_optional(&pool)
+        .await?
+        .is_some();
+    if has_blocks {
+        return Err(anyhow!(
+            "backups can only be imported into an empty database"
+        ));
+    }
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let mut entries = archive.entries()?;
+
+    let manifest: Manifest = match entries.next() {
+        Some(entry) => {
+            serde_json::from_reader(entry?).context("could not parse backup manifest")?
+        }
+        None => return Err(anyhow!("backup archive is empty")),
+    };
+    let migrations = migrator.iter().map(|m| m.version).collect::<Vec<_>>();
+    if manifest.migrations != migrations {
+        return Err(anyhow!(
+            "the backup was taken with a different database schema than this version of pd uses"
+        ));
+    }
+    tracing::info!(?manifest, ?path, "importing backup");
+
+    // Rows copied into a partitioned table need a partition for their height.
+    state::create_partitions(&pool, manifest.height).await?;
+
+    let mut dbtx = pool.begin().await?;
+    for table in TABLES {
+        let mut entry = entries
+            .next()
+            .ok_or_else(|| anyhow!("backup archive is missing table {}", table))??;
+        if entry.path()?.as_ref() != Path::new(&table_entry(table)) {
+            return Err(anyhow!(
+                "backup archive has unexpected entry {:?}",
+                entry.path()?
+            ));
+        }
+
+        let mut copy = dbtx
+            .copy_in_raw(&format!("COPY {} FROM STDIN (FORMAT binary)", table))
+            .await?;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = entry.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            copy.send(&buf[..n]).await?;
+        }
+        let rows = copy.finish().await?;
+
+        tracing::debug!(?table, rows, "restored table");
+    }
+    dbtx.commit().await?;
+
+    Ok(manifest)
+}
+
+fn append<W: Write>(archive: &mut tar::Builder<W>, name: &str, data: Vec<u8>) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, name, &data[..])?;
+    Ok(())
+}
+
+fn table_entry(table: &str) -> String {
+    format!("{}.copy", table)
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use s3::{bucket::Bucket, creds::Credentials, region::Region};
+use serde::{Deserialize, Serialize};
+use tendermint_proto::Protobuf;
+
+use crate::state;
+
+/// Where and how often to upload backups.
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// The endpoint of the S3-compatible object storage service.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// The number of blocks between full snapshots.
+    pub snapshot_interval: u64,
+}
+
+/// The raw data of a committed block, as uploaded to `blocks/<height>`.
+///
+/// Replaying these on top of the latest snapshot reproduces the state at the
+/// height of the last uploaded block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawBlock {
+    /// The protobuf-encoded `BeginBlock` request.
+    pub begin_block: Vec<u8>,
+    /// The transactions delivered in the block, in delivery order.
+    pub transactions: Vec<Vec<u8>>,
+}
+
+/// Continuously uploads backups of the node's state to object storage.
+///
+/// A full snapshot, in the format written by `pd backup`, is uploaded to
+/// `snapshots/<height>.tar.gz` at startup and every `snapshot_interval` blocks
+/// after that.  In between, the raw data of each committed block is uploaded to
+/// `blocks/<height>`, so a node can be restored close to the chain tip by
+/// importing the latest snapshot and replaying the blocks after it.
+///
+/// Upload failures are logged and retried after the next block, rather than
+/// interrupting consensus.  Credentials are read from the usual
+/// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` environment variables.
+pub async fn upload_backups(
+    database_uri: String,
+    state: state::Reader,
+    config: UploadConfig,
+) -> Result<()> {
+    let bucket = Bucket::new_with_path_style(
+        &config.bucket,
+        Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.clone(),
+        },
+        Credentials::default()?,
+    )?;
+
+    let mut height_rx = state.height_rx().clone();
+    let mut uploaded_height = None;
+    let mut snapshot_height = None;
+
+    loop {
+        let height = height_rx.borrow().value();
+
+        let snapshot_due = match snapshot_height {
+            Some(snapshot_height) => height >= snapshot_height + config.snapshot_interval,
+            None => true,
+        };
+        if snapshot_due {
+            match upload_snapshot(&bucket, &database_uri).await {
+                Ok(snapshot) => {
+                    snapshot_height = Some(snapshot);
+                    // Blocks up to the snapshot don't need to be uploaded separately.
+                    uploaded_height = Some(
+                        uploaded_height.map_or(snapshot, |uploaded: u64| uploaded.max(snapshot)),
+                    );
+                }
+                Err(e) => tracing::warn!(?e, "failed to upload snapshot"),
+            }
+        }
+
+        if let Some(mut last) = uploaded_height {
+            while last < height {
+                if let Err(e) = upload_block(&bucket, &state, last + 1).await {
+                    tracing::warn!(?e, height = last + 1, "failed to upload block");
+                    break;
+                }
+                last += 1;
+            }
+            uploaded_height = Some(last);
+        }
+
+        height_rx.changed().await?;
+    }
+}
+
+async fn upload_snapshot(bucket: &Bucket, database_uri: &str) -> Result<u64> {
+    let file = tempfile::NamedTempFile::new()?;
+    let manifest = super::backup(database_uri, file.path()).await?;
+
+    let mut reader = tokio::fs::File::open(file.path()).await?;
+    bucket
+        .put_object_stream(&mut reader, format!("snapshots/{}.tar.gz", manifest.height))
+        .await?;
+
+    tracing::info!(height = manifest.height, "uploaded snapshot");
+    Ok(manifest.height)
+}
+
+async fn upload_block(bucket: &Bucket, state: &state::Reader, height: u64) -> Result<()> {
+    let (begin_block, transactions) = state
+        .raw_block(height)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no raw data is stored for height {}", height))?;
+
+    let raw_block = RawBlock {
+        begin_block: Protobuf::<tendermint_proto::abci::RequestBeginBlock>::encode_vec(
+            &begin_block,
+        )?,
+        transactions: transactions.into_iter().map(|tx| tx.to_vec()).collect(),
+    };
+    bucket
+        .put_object(
+            format!("blocks/{}", height),
+            &bincode::serialize(&raw_block)?,
+        )
+        .await?;
+
+    tracing::debug!(height, "uploaded block");
+    Ok(())
+}
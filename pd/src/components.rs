@@ -0,0 +1,99 @@
+//! The components of the application state machine.
+//!
+//! Each component handles one area of the chain's state, such as the shielded
+//! pool or staking, and hooks into each phase of a block.  The consensus
+//! worker drives the components in order, and the [`PendingBlock`] committed
+//! at the end of the block is assembled from their changes.
+//!
+//! Delivering a transaction is split into two steps, so that a transaction is
+//! applied to every component or to none of them: first every component checks
+//! the transaction against the changes already in the pending block, and only
+//! if all of them accept it is it applied.  The effects of its actions are
+//! added by their [handlers](crate::verify::REGISTRY), and then each
+//! component may make its own changes.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tendermint::abci;
+
+use crate::{
+    genesis, scheduler::ScheduledAction, state, verify::VerifiedTransaction, PendingBlock,
+};
+
+mod shielded_pool;
+mod staking;
+mod upgrades;
+
+pub use shielded_pool::{AlreadySpent, DoubleSpend, ShieldedPool};
+pub use staking::{Staking, BASE_REWARD_RATE};
+pub use upgrades::{Upgrades, RESOLVED_HALTS, SUPPORTED_UPGRADES};
+
+#[async_trait]
+pub trait Component: Send + Sync {
+    /// Adds the component's genesis state to the genesis block.
+    fn init_chain(&mut self, _app_state: &genesis::AppState, _pending_block: &mut PendingBlock) {}
+
+    /// Carries out a scheduled action that has come due, at the start of a
+    /// block, before any component's [`begin_block`](Component::begin_block).
+    /// Each component carries out the kinds of action it schedules.
+    async fn run_scheduled(
+        &mut self,
+        _reader: &state::Reader,
+        _pending_block: &mut PendingBlock,
+        _action: &ScheduledAction,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Prepares the pending block for a new block.
+    async fn begin_block(
+        &mut self,
+        _reader: &state::Reader,
+        _pending_block: &mut PendingBlock,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks a transaction that passed stateful verification against the
+    /// changes already in the pending block.
+    async fn check_tx(
+        &self,
+        _reader: &state::Reader,
+        _pending_block: &PendingBlock,
+        _transaction: &VerifiedTransaction,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Updates the pending block for a transaction accepted by every
+    /// component's [`check_tx`](Component::check_tx), after the effects of its
+    /// actions have been added by their handlers.
+    fn deliver_tx(
+        &mut self,
+        _pending_block: &mut PendingBlock,
+        _transaction: &VerifiedTransaction,
+    ) {
+    }
+
+    /// Adds the changes made at the end of the block, once its height is
+    /// known, to the pending block, returning the events describing them.
+    async fn end_block(
+        &mut self,
+        _reader: &state::Reader,
+        _pending_block: &mut PendingBlock,
+    ) -> Result<Vec<abci::Event>> {
+        Ok(Vec::new())
+    }
+
+    /// Updates the component's own state from the block about to be committed.
+    fn commit(&mut self, _pending_block: &PendingBlock) {}
+}
+
+/// The components of the application, in the order they're driven.
+pub async fn all(reader: &state::Reader) -> Result<Vec<Box<dyn Component>>> {
+    Ok(vec![
+        Box::new(ShieldedPool::new(reader).await?),
+        Box::new(Staking::default()),
+        Box::new(Upgrades::default()),
+    ])
+}
@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use penumbra_crypto::{asset, merkle::NoteCommitmentTree, Nullifier};
+
+use super::Component;
+use crate::{genesis, state, verify::VerifiedTransaction, PendingBlock};
+
+/// The shielded pool: the note commitment tree, spent nullifiers, and asset
+/// registrations.
+pub struct ShieldedPool {
+    /// The note commitment tree as of the last committed block.
+    note_commitment_tree: NoteCommitmentTree,
+}
+
+impl ShieldedPool {
+    pub async fn new(reader: &state::Reader) -> Result<Self> {
+        Ok(Self {
+            note_commitment_tree: reader.note_commitment_tree().await?,
+        })
+    }
+}
+
+/// The error rejecting a transaction that spends a nullifier already spent by
+/// an earlier transaction in the same block.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "nullifier {} was already spent by transaction {} earlier in this block",
+    hex::encode(.nullifier.to_bytes()),
+    hex::encode(.spent_by)
+)]
+pub struct DoubleSpend {
+    pub nullifier: Nullifier,
+    pub spent_by: [u8; 32],
+}
+
+/// The error rejecting a transaction that spends a nullifier already spent in
+/// a committed block.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "nullifier {} was already spent in state",
+    hex::encode(.nullifier.to_bytes())
+)]
+pub struct AlreadySpent {
+    pub nullifier: Nullifier,
+}
+
+#[async_trait]
+impl Component for ShieldedPool {
+    fn init_chain(&mut self, app_state: &genesis::AppState, pending_block: &mut PendingBlock) {
+        self.note_commitment_tree = NoteCommitmentTree::new(0);
+        pending_block.note_commitment_tree = self.note_commitment_tree.clone();
+
+        // The genesis notes themselves are added by the genesis transaction,
+        // but their supply is recorded here.
+        for allocation in &app_state.allocations {
+            let denom = asset::REGISTRY
+                .parse_denom(&allocation.denom)
+                .expect("genesis allocations must have valid denominations");
+            pending_block
+                .supply_updates
+                .entry(denom.id())
+                .or_insert((denom, 0))
+                .1 += allocation.amount;
+        }
+    }
+
+    async fn begin_block(
+        &mut self,
+        _reader: &state::Reader,
+        pending_block: &mut PendingBlock,
+    ) -> Result<()> {
+        pending_block.note_commitment_tree = self.note_commitment_tree.clone();
+        Ok(())
+    }
+
+    async fn check_tx(
+        &self,
+        _reader: &state::Reader,
+        pending_block: &PendingBlock,
+        transaction: &VerifiedTransaction,
+    ) -> Result<()> {
+        for nullifier in &transaction.spent_nullifiers {
+            if let Some(spent_by) = pending_block.spent_nullifiers.get(nullifier) {
+                return Err(DoubleSpend {
+                    nullifier: nullifier.clone(),
+                    spent_by: *spent_by,
+                }
+                .into());
+            }
+        }
+
+        for note_commitment in transaction.new_notes.keys() {
+            if pending_block.notes.contains_key(note_commitment) {
+                return Err(anyhow!(
+                    "note commitment {:?} is already created in the pending block",
+                    note_commitment
+                ));
+            }
+        }
+
+        let pending_metadata = &pending_block.denom_metadata;
+        for metadata in &transaction.denom_metadata {
+            if pending_metadata.contains_key(&metadata.denom.id()) {
+                return Err(anyhow!(
+                    "metadata for {} is already registered in the pending block",
+                    metadata.denom
+                ));
+            }
+            if pending_metadata
+                .values()
+                .any(|pending| pending.symbol == metadata.symbol)
+            {
+                return Err(anyhow!(
+                    "symbol {} is already registered in the pending block",
+                    metadata.symbol
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn commit(&mut self, pending_block: &PendingBlock) {
+        // Pull the updated note commitment tree, for use in the next block.
+        self.note_commitment_tree = pending_block.note_commitment_tree.clone();
+    }
+}
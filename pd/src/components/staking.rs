@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use penumbra_proto::thin_wallet::EpochSummary;
+use penumbra_stake::{
+    ValidatorState, ValidatorStatus, STAKING_TOKEN_ASSET_ID, STAKING_TOKEN_DENOM,
+};
+use tendermint::abci;
+
+use super::Component;
+use crate::{
+    consensus::events::{dkg_round_event, epoch_summary_event, flow_decryption_event},
+    flow, genesis, state,
+    verify::VerifiedTransaction,
+    PendingBlock,
+};
+
+/// The base reward rate for each epoch.
+///
+/// FIXME: set this less arbitrarily, and allow this to be set per-epoch
+/// 3bps -> 11% return over 365 epochs, why not
+pub const BASE_REWARD_RATE: u64 = 3_0000;
+
+/// Staking: delegations, validator rates and voting power, fee distribution,
+/// and the per-epoch DKG and encrypted flows.
+#[derive(Debug, Default)]
+pub struct Staking {}
+
+#[async_trait]
+impl Component for Staking {
+    fn init_chain(&mut self, app_state: &genesis::AppState, pending_block: &mut PendingBlock) {
+        // We might not have any allocations of delegation tokens, but we should record the denoms.
+        for genesis::ValidatorPower { validator, .. } in app_state.validators.iter() {
+            let denom = validator.identity_key.delegation_token().denom();
+            pending_block
+                .supply_updates
+                .entry(denom.id())
+                .or_insert((denom, 0));
+        }
+    }
+
+    async fn check_tx(
+        &self,
+        reader: &state::Reader,
+        pending_block: &PendingBlock,
+        transaction: &VerifiedTransaction,
+    ) -> Result<()> {
+        // The per-epoch redelegation limits were checked against the committed
+        // redelegations, but the pending block's count too.
+        let pending_redelegations = &pending_block.redelegations;
+        if !transaction.redelegations.is_empty() && !pending_redelegations.is_empty() {
+            reader
+                .check_redelegation_limits(&transaction.redelegations, pending_redelegations)
+                .await?;
+        }
+
+        // Likewise for the cap on each validator's share of the stake.
+        let pending_delegation_changes = &pending_block.delegation_changes;
+        if !pending_delegation_changes.is_empty() {
+            reader
+                .check_delegation_cap(&transaction.delegation_changes, pending_delegation_changes)
+                .await?;
+        }
+
+        // Each validator may only deal once per DKG round.
+        let pending_dkg_dealings = &pending_block.dkg_dealings;
+        if !transaction.dkg_dealings.is_empty() && !pending_dkg_dealings.is_empty() {
+            reader
+                .check_dkg_dealings(&transaction.dkg_dealings, pending_dkg_dealings)
+                .await?;
+        }
+
+        // Likewise, each participant may only complain about a dealer once.
+        let pending_dkg_complaints = &pending_block.dkg_complaints;
+        if !transaction.dkg_complaints.is_empty() && !pending_dkg_complaints.is_empty() {
+            reader
+                .check_dkg_complaints(&transaction.dkg_complaints, pending_dkg_complaints)
+                .await?;
+        }
+
+        // Likewise, each participant may only decrypt an epoch's flows once.
+        let pending_flow_decryptions = &pending_block.flow_decryptions;
+        if !transaction.flow_decryptions.is_empty() && !pending_flow_decryptions.is_empty() {
+            reader
+                .check_flow_decryptions(&transaction.flow_decryptions, pending_flow_decryptions)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// At the end of an epoch, applies the epoch's delegation changes and
+    /// computes the next epoch's rates and validator statuses, distributes
+    /// the epoch's fees, and finishes its DKG round.
+    async fn end_block(
+        &mut self,
+        reader: &state::Reader,
+        pending_block: &mut PendingBlock,
+    ) -> Result<Vec<abci::Event>> {
+        let mut events = Vec::new();
+        if !pending_block.ends_epoch {
+            return Ok(events);
+        }
+        let height = pending_block
+            .height
+            .expect("height must be set in EndBlock");
+        let epoch = pending_block
+            .epoch
+            .clone()
+            .expect("epoch must be set in EndBlock");
+
+        // We've finished processing the last block of `epoch`, so we've
+        // crossed the epoch boundary, and (prev | current | next) are:
+        let prev_epoch = epoch;
+        let current_epoch = prev_epoch.next();
+        let next_epoch = current_epoch.next();
+
+        tracing::info!(
+            ?height,
+            ?prev_epoch,
+            ?current_epoch,
+            ?next_epoch,
+            "crossed epoch boundary, processing rate updates"
+        );
+        metrics::increment_counter!("epoch");
+
+        // TODO (optimization): batch these queries
+        let current_base_rate = reader.base_rate_data(current_epoch.index).await?;
+        let current_rates = reader.rate_data(current_epoch.index).await?;
+
+        let mut staking_token_supply = reader
+            .asset_lookup(*STAKING_TOKEN_ASSET_ID)
+            .await?
+            .map(|info| info.total_supply)
+            .unwrap();
+
+        // steps (foreach validator):
+        // - get the total token supply for the validator's delegation tokens
+        // - process the updates to the token supply:
+        //   - collect all delegations occurring in previous epoch and apply them (adds to supply);
+        //   - collect all undelegations started in previous epoch and apply them (reduces supply);
+        // - feed the updated (current) token supply into current_rates.voting_power()
+        // - persist both the current voting power and the current supply
+        //
+
+        let next_base_rate = current_base_rate.next(BASE_REWARD_RATE);
+
+        // rename to curr_rate so it lines up with next_rate (same # chars)
+        tracing::debug!(curr_base_rate = ?current_base_rate);
+        tracing::debug!(?next_base_rate);
+
+        let mut next_rates = Vec::new();
+        let mut next_validator_statuses = Vec::new();
+        let mut fee_recipients = Vec::new();
+
+        // For the epoch summary.
+        let current_voting_power = reader
+            .validator_info(true)
+            .await?
+            .into_iter()
+            .map(|info| (info.validator.identity_key, info.status.voting_power))
+            .collect::<BTreeMap<_, _>>();
+        let mut rewards_distributed = 0u64;
+        let (mut delegated, mut undelegated) = (0u64, 0u64);
+
+        // this is a bit complicated: because we're in the EndBlock phase, and the
+        // delegations in this block have not yet been committed, we have to combine
+        // the delegations in pending_block with the ones already committed to the
+        // state. otherwise the delegations committed in the epoch threshold block
+        // would be lost.
+        // The encrypted flows of the epoch before last were decrypted
+        // during the epoch that just ended, so their sums are applied now,
+        // along with its transparent delegation changes.  The flows of
+        // earlier epochs that couldn't be decrypted yet are carried forward
+        // and tried again, since the tokens they moved are already in
+        // circulation.
+        for flow_epoch in reader.undecrypted_flow_epochs(prev_epoch.index).await? {
+            let aggregates = reader.flow_aggregates(flow_epoch).await?;
+            // Flows are only accepted when there's a key to encrypt them to,
+            // so the round that established it exists.
+            let round = match flow_epoch.checked_sub(1) {
+                Some(round_epoch) => reader.dkg_round(round_epoch).await?,
+                None => None,
+            }
+            .ok_or_else(|| anyhow!("no DKG round for the flows of epoch {}", flow_epoch))?;
+            let mut decryptions = reader.flow_decryptions(flow_epoch).await?;
+            decryptions.extend(
+                pending_block
+                    .flow_decryptions
+                    .iter()
+                    .filter(|decryption| decryption.epoch_index == flow_epoch)
+                    .cloned(),
+            );
+            let decrypted = flow::decrypt(&aggregates, &round, &decryptions);
+            events.push(flow_decryption_event(flow_epoch, &decrypted));
+            match decrypted {
+                Ok(changes) => {
+                    tracing::info!(
+                        epoch = flow_epoch,
+                        validators = changes.len(),
+                        "decrypted encrypted flows"
+                    );
+                    for (id_key, delta) in changes {
+                        *pending_block.delegation_changes.entry(id_key).or_insert(0) += delta;
+                    }
+                    pending_block.decrypted_flow_epochs.push(flow_epoch);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        epoch = flow_epoch,
+                        error = %e,
+                        "couldn't decrypt encrypted flows, will try again at the end of the next epoch"
+                    );
+                }
+            }
+        }
+
+        let mut delegation_changes = reader.delegation_changes(prev_epoch.index).await?;
+        for (id_key, delta) in &pending_block.delegation_changes {
+            *delegation_changes.entry(id_key.clone()).or_insert(0) += delta;
+        }
+
+        for current_rate in &current_rates {
+            let identity_key = current_rate.identity_key.clone();
+
+            let funding_streams = reader.funding_streams(identity_key.clone()).await?;
+            let next_rate = current_rate.next(&next_base_rate, funding_streams.as_ref());
+
+            // TODO: if a validator isn't part of the consensus set, should we ignore them
+            // and not update their rates?
+            let delegation_delta = delegation_changes.get(&identity_key).unwrap_or(&0i64);
+
+            let delegation_amount = delegation_delta.abs() as u64;
+            let unbonded_amount = current_rate.unbonded_amount(delegation_amount);
+
+            let mut delegation_token_supply = reader
+                .asset_lookup(identity_key.delegation_token().id())
+                .await?
+                .map(|info| info.total_supply)
+                .unwrap_or(0);
+
+            if *delegation_delta > 0 {
+                // net delegation: subtract the unbonded amount from the staking token supply
+                delegated += unbonded_amount;
+                staking_token_supply = staking_token_supply.checked_sub(unbonded_amount).unwrap();
+                delegation_token_supply = delegation_token_supply
+                    .checked_add(delegation_amount)
+                    .unwrap();
+            } else {
+                // net undelegation: add the unbonded amount to the staking token supply
+                undelegated += unbonded_amount;
+                staking_token_supply = staking_token_supply.checked_add(unbonded_amount).unwrap();
+                delegation_token_supply = delegation_token_supply
+                    .checked_sub(delegation_amount)
+                    .unwrap();
+            }
+
+            // update the delegation token supply
+            pending_block.supply_updates.insert(
+                identity_key.delegation_token().id(),
+                (
+                    identity_key.delegation_token().denom(),
+                    delegation_token_supply,
+                ),
+            );
+
+            let voting_power = next_rate.voting_power(delegation_token_supply, &next_base_rate);
+            let next_status = ValidatorStatus {
+                identity_key,
+                voting_power,
+                // TODO: this state needs to be set correctly based on current state and any changes
+                // within the current block. This will be fixed by #375.
+                state: ValidatorState::Active,
+            };
+
+            fee_recipients.push((voting_power, funding_streams.clone()));
+
+            // distribute validator commission
+            for stream in funding_streams {
+                let commission_reward_amount = stream.reward_amount(
+                    delegation_token_supply,
+                    &next_base_rate,
+                    &current_base_rate,
+                );
+
+                rewards_distributed += commission_reward_amount;
+                *pending_block
+                    .commission
+                    .entry(current_rate.identity_key.clone())
+                    .or_default() += commission_reward_amount;
+                pending_block.add_validator_reward_note(commission_reward_amount, stream.address);
+            }
+
+            // rename to curr_rate so it lines up with next_rate (same # chars)
+            tracing::debug!(curr_rate = ?current_rate);
+            tracing::debug!(?next_rate);
+            tracing::debug!(?delegation_delta);
+            tracing::debug!(?delegation_token_supply);
+            tracing::debug!(?next_status);
+
+            next_rates.push(next_rate);
+            next_validator_statuses.push(next_status);
+        }
+
+        // The fees collected during the epoch left circulation when they
+        // were paid; the configured share of them is now paid out to the
+        // active validators, and the rest stays burned.
+        let collected_fees = reader.epoch_fees(prev_epoch.index).await? + pending_block.fees;
+        let fee_distribution_bps = reader
+            .chain_params_rx()
+            .borrow()
+            .fee_distribution_bps
+            .min(10_000);
+        let distributable_fees =
+            (collected_fees as u128 * fee_distribution_bps as u128 / 10_000) as u64;
+        let distributed_fees = pending_block.distribute_fees(distributable_fees, &fee_recipients);
+        staking_token_supply = staking_token_supply
+            .checked_sub(collected_fees)
+            .unwrap()
+            .checked_add(distributed_fees)
+            .unwrap();
+        tracing::debug!(?collected_fees, ?distributed_fees);
+
+        tracing::debug!(?staking_token_supply);
+
+        let summary = EpochSummary {
+            epoch_index: prev_epoch.index,
+            end_height: height,
+            rewards_distributed,
+            fees_collected: collected_fees,
+            fees_distributed: distributed_fees,
+            delegated,
+            undelegated,
+            validators: next_validator_statuses.len() as u32,
+            validators_changed: next_validator_statuses
+                .iter()
+                .filter(|status| {
+                    current_voting_power.get(&status.identity_key) != Some(&status.voting_power)
+                })
+                .count() as u32,
+            staking_token_supply,
+        };
+        tracing::info!(?summary, "finished epoch");
+        events.push(epoch_summary_event(&summary));
+        pending_block.epoch_summary = Some(summary);
+
+        // The epoch's DKG round is finished with the dealings submitted
+        // during it and the complaints about them, including any in this
+        // block.
+        if let Some(round) = reader.dkg_round(prev_epoch.index).await? {
+            let mut dealings = reader.dkg_dealings(prev_epoch.index).await?;
+            dealings.extend(
+                pending_block
+                    .dkg_dealings
+                    .iter()
+                    .map(|dealing| (height, dealing.clone())),
+            );
+            let mut complaints = reader.dkg_complaints(prev_epoch.index).await?;
+            complaints.extend(pending_block.dkg_complaints.iter().cloned());
+            let transcript = round.finish(dealings, &complaints, height);
+            tracing::info!(
+                epoch = transcript.epoch_index,
+                dealers = transcript.dealers.len(),
+                succeeded = transcript.group_key.is_some(),
+                "finished DKG round"
+            );
+            events.push(dkg_round_event(&transcript));
+            pending_block.dkg_transcript = Some(transcript);
+        }
+
+        pending_block.next_rates = Some(next_rates);
+        pending_block.next_base_rate = Some(next_base_rate);
+        pending_block.next_validator_statuses = Some(next_validator_statuses);
+        pending_block.supply_updates.insert(
+            *STAKING_TOKEN_ASSET_ID,
+            (STAKING_TOKEN_DENOM.clone(), staking_token_supply),
+        );
+
+        Ok(events)
+    }
+}
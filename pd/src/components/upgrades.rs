@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use penumbra_transaction::action::UpgradePlan;
+use tendermint::abci;
+
+use super::Component;
+use crate::{
+    consensus::events::upgrade_scheduled_event,
+    scheduler::{Due, ScheduledAction},
+    state,
+    verify::VerifiedTransaction,
+    PendingBlock,
+};
+
+/// The names of the upgrades this release supports.
+///
+/// A release implementing an upgrade adds its name here, so that it carries on
+/// past the upgrade's height, where earlier releases halt.
+pub const SUPPORTED_UPGRADES: &[&str] = &[];
+
+/// The names of the emergency halts this release resolves.
+///
+/// An emergency halt stops every release at its height, so the release fixing
+/// whatever it was called for adds its name here to carry on.
+pub const RESOLVED_HALTS: &[&str] = &[];
+
+/// Upgrade plans: tallies validators' votes for them, and halts at the height
+/// of the scheduled plan unless this release supports it.
+///
+/// Validators can also vote for an emergency halt, a plan which stops every
+/// node at its height until it's restarted with a release that resolves it.
+///
+/// A plan is scheduled once active validators with more than two thirds of the
+/// voting power have voted for it, by queueing a halt at its height and
+/// cancelling the halt of any plan it replaces.  Halting leaves the block at
+/// the plan's height uncommitted, so a node restarted with a release that
+/// supports the upgrade picks up from there.
+#[derive(Debug, Default)]
+pub struct Upgrades {}
+
+#[async_trait]
+impl Component for Upgrades {
+    async fn check_tx(
+        &self,
+        reader: &state::Reader,
+        pending_block: &PendingBlock,
+        transaction: &VerifiedTransaction,
+    ) -> Result<()> {
+        // Each vote's sequence number was checked against the committed
+        // votes, but must also exceed those of the pending block's.
+        let pending_proposals = &pending_block.upgrade_proposals;
+        if !transaction.upgrade_proposals.is_empty() && !pending_proposals.is_empty() {
+            reader
+                .check_upgrade_proposals(&transaction.upgrade_proposals, pending_proposals)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_scheduled(
+        &mut self,
+        _reader: &state::Reader,
+        _pending_block: &mut PendingBlock,
+        action: &ScheduledAction,
+    ) -> Result<()> {
+        let ScheduledAction::UpgradeHalt(plan) = action;
+        if plan.emergency_halt {
+            if RESOLVED_HALTS.contains(&plan.name.as_str()) {
+                tracing::info!(%plan, "this release resolves the emergency halt, carrying on");
+                return Ok(());
+            }
+            tracing::error!(%plan, "halting for the emergency halt");
+            return Err(anyhow!(
+                "halting for {}; restart with a release that resolves it",
+                plan
+            ));
+        }
+
+        if SUPPORTED_UPGRADES.contains(&plan.name.as_str()) {
+            tracing::info!(%plan, "this release supports the scheduled upgrade, carrying on");
+            return Ok(());
+        }
+        tracing::error!(%plan, "halting for the scheduled upgrade");
+        Err(anyhow!(
+            "halting for upgrade {}, which this release doesn't support; restart with a release that does",
+            plan
+        ))
+    }
+
+    /// Schedules the plan, if any, that this block's votes give more than two
+    /// thirds of the active voting power.
+    async fn end_block(
+        &mut self,
+        reader: &state::Reader,
+        pending_block: &mut PendingBlock,
+    ) -> Result<Vec<abci::Event>> {
+        // The tally only changes when votes are cast.
+        if pending_block.upgrade_proposals.is_empty() {
+            return Ok(Vec::new());
+        }
+        let height = pending_block
+            .height
+            .expect("height must be set in EndBlock");
+
+        let mut votes = reader.upgrade_votes().await?;
+        for signed in &pending_block.upgrade_proposals {
+            votes.insert(
+                signed.proposal.validator_identity.clone(),
+                signed.proposal.plan.clone(),
+            );
+        }
+        let voting_power = reader.active_voting_power().await?;
+        let total_power = voting_power
+            .values()
+            .map(|power| *power as u128)
+            .sum::<u128>();
+
+        let mut tally = BTreeMap::<&UpgradePlan, u128>::new();
+        for (identity_key, plan) in &votes {
+            // Votes for plans whose height is too soon to halt at are stale.
+            if plan.height <= height + 1 {
+                continue;
+            }
+            *tally.entry(plan).or_default() +=
+                voting_power.get(identity_key).copied().unwrap_or(0) as u128;
+        }
+        let passed = tally
+            .into_iter()
+            .find(|(_, power)| 3 * power > 2 * total_power)
+            .map(|(plan, _)| plan.clone());
+
+        let plan = match passed {
+            Some(plan) => plan,
+            None => return Ok(Vec::new()),
+        };
+
+        // The halts still queued, other than any carried out at the start of
+        // this block, are those of the plan in effect.
+        let queued = reader
+            .scheduled_actions()
+            .await?
+            .into_iter()
+            .filter(|scheduled| matches!(scheduled.action, ScheduledAction::UpgradeHalt(_)))
+            .filter(|scheduled| !pending_block.completed_actions.contains(scheduled))
+            .collect::<Vec<_>>();
+        if queued
+            .iter()
+            .any(|scheduled| scheduled.action == ScheduledAction::UpgradeHalt(plan.clone()))
+        {
+            return Ok(Vec::new());
+        }
+
+        for scheduled in queued {
+            tracing::info!(
+                ?scheduled,
+                "cancelling the halt for a replaced upgrade plan"
+            );
+            pending_block.cancel(scheduled);
+        }
+        tracing::info!(%plan, "scheduled upgrade");
+        let event = upgrade_scheduled_event(&plan);
+        pending_block.schedule(Due::Height(plan.height), ScheduledAction::UpgradeHalt(plan));
+        Ok(vec![event])
+    }
+}
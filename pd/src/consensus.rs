@@ -1,7 +1,10 @@
+pub(crate) mod events;
 mod message;
 mod service;
+mod shadow;
 mod worker;
 
 use message::Message;
 pub use service::Consensus;
 use worker::Worker;
+pub use worker::DOUBLE_SPEND_CODE;
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use tendermint::abci::{Event, EventAttribute};
+
+use penumbra_proto::thin_wallet::EpochSummary;
+use penumbra_stake::{FlowDirection, IdentityKey};
+use penumbra_transaction::action::UpgradePlan;
+
+use crate::{dkg, verify::VerifiedTransaction};
+
+/// Builds the events describing a transaction's effects, for the `DeliverTx`
+/// response.
+///
+/// The attributes identifying nullifiers, note commitments, and validators
+/// are marked as indexed, so that Tendermint's indexer can answer queries like
+/// `tx_search "spend.nullifier='<hex>'"`.  Nullifiers and note commitments are
+/// hex-encoded, and validator identity keys are bech32-encoded.
+pub fn transaction_events(transaction: &VerifiedTransaction) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    for nullifier in &transaction.spent_nullifiers {
+        events.push(event(
+            "spend",
+            vec![indexed("nullifier", hex::encode(nullifier.to_bytes()))],
+        ));
+    }
+    for commitment in transaction.new_notes.keys() {
+        events.push(event(
+            "output",
+            vec![indexed(
+                "note_commitment",
+                hex::encode(<[u8; 32]>::from(*commitment)),
+            )],
+        ));
+    }
+    for (identity_key, change) in &transaction.delegation_changes {
+        events.push(event(
+            "delegation_change",
+            vec![
+                indexed("validator", identity_key.to_string()),
+                attribute("change", change.to_string(), false),
+            ],
+        ));
+    }
+    for redelegation in &transaction.redelegations {
+        events.push(event(
+            "redelegation",
+            vec![
+                indexed(
+                    "from_validator",
+                    redelegation.from_validator_identity.to_string(),
+                ),
+                indexed(
+                    "to_validator",
+                    redelegation.to_validator_identity.to_string(),
+                ),
+                attribute(
+                    "unbonded_amount",
+                    redelegation.unbonded_amount.to_string(),
+                    false,
+                ),
+            ],
+        ));
+    }
+    for dealing in &transaction.dkg_dealings {
+        events.push(event(
+            "dkg_dealing",
+            vec![
+                indexed("validator", dealing.dealing.validator_identity.to_string()),
+                indexed("epoch", dealing.dealing.epoch_index.to_string()),
+            ],
+        ));
+    }
+    for complaint in &transaction.dkg_complaints {
+        events.push(event(
+            "dkg_complaint",
+            vec![
+                indexed("validator", complaint.validator_identity.to_string()),
+                indexed("dealer", complaint.dealer_identity.to_string()),
+                indexed("epoch", complaint.epoch_index.to_string()),
+            ],
+        ));
+    }
+    for flow in &transaction.encrypted_flows {
+        events.push(event(
+            "encrypted_flow",
+            vec![
+                indexed("validator", flow.validator_identity.to_string()),
+                attribute(
+                    "direction",
+                    match flow.direction {
+                        FlowDirection::Delegate => "delegate",
+                        FlowDirection::Undelegate => "undelegate",
+                    }
+                    .to_string(),
+                    false,
+                ),
+            ],
+        ));
+    }
+    for decryption in &transaction.flow_decryptions {
+        events.push(event(
+            "flow_decryption",
+            vec![
+                indexed("validator", decryption.validator_identity.to_string()),
+                indexed("epoch", decryption.epoch_index.to_string()),
+            ],
+        ));
+    }
+    for metadata in &transaction.denom_metadata {
+        events.push(event(
+            "denom_metadata",
+            vec![
+                indexed("denom", metadata.denom.to_string()),
+                indexed("symbol", metadata.symbol.clone()),
+            ],
+        ));
+    }
+    for signed in &transaction.upgrade_proposals {
+        let proposal = &signed.proposal;
+        events.push(event(
+            "upgrade_proposal",
+            vec![
+                indexed("validator", proposal.validator_identity.to_string()),
+                indexed("name", proposal.plan.name.clone()),
+                attribute("height", proposal.plan.height.to_string(), false),
+            ],
+        ));
+    }
+
+    events
+}
+
+/// Builds the event summarizing an epoch, for the `EndBlock` response of its
+/// last block.  Only the epoch index is indexed.
+pub fn epoch_summary_event(summary: &EpochSummary) -> Event {
+    event(
+        "epoch_summary",
+        vec![
+            indexed("epoch", summary.epoch_index.to_string()),
+            attribute(
+                "rewards_distributed",
+                summary.rewards_distributed.to_string(),
+                false,
+            ),
+            attribute("fees_collected", summary.fees_collected.to_string(), false),
+            attribute(
+                "fees_distributed",
+                summary.fees_distributed.to_string(),
+                false,
+            ),
+            attribute("delegated", summary.delegated.to_string(), false),
+            attribute("undelegated", summary.undelegated.to_string(), false),
+            attribute("validators", summary.validators.to_string(), false),
+            attribute(
+                "validators_changed",
+                summary.validators_changed.to_string(),
+                false,
+            ),
+            attribute(
+                "staking_token_supply",
+                summary.staking_token_supply.to_string(),
+                false,
+            ),
+        ],
+    )
+}
+
+/// Builds the event describing the outcome of an epoch's DKG round, for the
+/// `EndBlock` response of its last block.  Only the epoch index is indexed.
+pub fn dkg_round_event(transcript: &dkg::Transcript) -> Event {
+    event(
+        "dkg_round",
+        vec![
+            indexed("epoch", transcript.epoch_index.to_string()),
+            attribute("dealers", transcript.dealers.len().to_string(), false),
+            attribute(
+                "group_key",
+                transcript
+                    .group_key
+                    .map(|key| hex::encode(key.compress().0))
+                    .unwrap_or_default(),
+                false,
+            ),
+        ],
+    )
+}
+
+/// Builds the event describing the decryption of an epoch's encrypted flows,
+/// for the `EndBlock` response of the last block of the following epoch.
+/// Only the epoch index is indexed.
+pub fn flow_decryption_event(
+    epoch_index: u64,
+    decrypted: &anyhow::Result<BTreeMap<IdentityKey, i64>>,
+) -> Event {
+    event(
+        "decrypted_flows",
+        vec![
+            indexed("epoch", epoch_index.to_string()),
+            attribute("succeeded", decrypted.is_ok().to_string(), false),
+            attribute(
+                "validators",
+                decrypted
+                    .as_ref()
+                    .map_or(0, |changes| changes.len())
+                    .to_string(),
+                false,
+            ),
+        ],
+    )
+}
+
+/// Builds the event announcing that an upgrade plan was scheduled, for the
+/// `EndBlock` response of the block whose votes scheduled it.  The plan's name
+/// is indexed.
+pub fn upgrade_scheduled_event(plan: &UpgradePlan) -> Event {
+    event(
+        "upgrade_scheduled",
+        vec![
+            indexed("name", plan.name.clone()),
+            attribute("height", plan.height.to_string(), false),
+            attribute("emergency_halt", plan.emergency_halt.to_string(), false),
+        ],
+    )
+}
+
+fn event(type_str: &str, attributes: Vec<EventAttribute>) -> Event {
+    Event {
+        type_str: type_str.to_string(),
+        attributes,
+    }
+}
+
+fn indexed(key: &str, value: String) -> EventAttribute {
+    attribute(key, value, true)
+}
+
+fn attribute(key: &str, value: String, index: bool) -> EventAttribute {
+    EventAttribute {
+        key: key.to_string(),
+        value,
+        index,
+    }
+}
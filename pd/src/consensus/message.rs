@@ -1,10 +1,15 @@
 use tendermint::abci::{ConsensusRequest, ConsensusResponse};
-use tokio::sync::oneshot;
+use tokio::{sync::oneshot, task::JoinHandle};
 use tracing::Span;
 
+use crate::verify::PendingTransaction;
+
 #[derive(Debug)]
 pub struct Message {
     pub req: ConsensusRequest,
     pub rsp_sender: oneshot::Sender<ConsensusResponse>,
     pub span: Span,
+    /// For `DeliverTx` requests, the in-flight stateless verification of the
+    /// transaction, started as soon as the request was received.
+    pub stateless: Option<JoinHandle<anyhow::Result<PendingTransaction>>>,
 }
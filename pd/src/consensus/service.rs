@@ -6,15 +6,26 @@ use std::{
 
 use futures::{ready, FutureExt};
 use tendermint::abci::{ConsensusRequest, ConsensusResponse};
-use tokio::sync::{
-    mpsc::{self, error::SendError, OwnedPermit},
-    oneshot,
+use tokio::{
+    sync::{
+        mpsc::{self, error::SendError, OwnedPermit},
+        oneshot,
+    },
+    task::JoinHandle,
 };
 use tokio_util::sync::ReusableBoxFuture;
 use tower_abci::BoxError;
+use tracing::Span;
 
-use super::{Message, Worker};
-use crate::{state, RequestExt};
+use super::{
+    shadow::{Shadow, ShadowSender},
+    Message, Worker,
+};
+use crate::{
+    state,
+    verify::{decode_canonical, on_verification_pool, PendingTransaction, StatelessTransactionExt},
+    RequestExt,
+};
 
 enum State {
     NoPermit,
@@ -24,6 +35,7 @@ enum State {
 
 pub struct Consensus {
     queue: mpsc::Sender<Message>,
+    shadow: Option<ShadowSender>,
     future: ReusableBoxFuture<Result<OwnedPermit<Message>, SendError<()>>>,
     state: State,
 }
@@ -36,16 +48,52 @@ impl Consensus {
 
         Ok(Self {
             queue: queue_tx,
+            shadow: None,
             state: State::NoPermit,
             future: ReusableBoxFuture::new(async { unreachable!() }),
         })
     }
+
+    /// Creates a consensus service that also applies every request to a second
+    /// database through `shadow`, comparing the results with the primary's.
+    ///
+    /// This lets a new database deployment be burned in against live traffic
+    /// before cutting over to it.  The shadow runs behind the primary and never
+    /// affects its responses; divergences are logged and counted in the
+    /// `node_shadow_divergences_total` metric.  If it falls too far behind, it's
+    /// stopped rather than slowing the primary down.  Both databases must start
+    /// out at the same height, e.g. by restoring the same backup into each.
+    pub async fn with_shadow(state: state::Writer, shadow: state::Writer) -> anyhow::Result<Self> {
+        let (height, shadow_height) = (
+            state.private_reader().height().await?,
+            shadow.private_reader().height().await?,
+        );
+        if height != shadow_height {
+            return Err(anyhow::anyhow!(
+                "the shadow database is at height {}, but the primary is at height {}",
+                shadow_height.value(),
+                height.value()
+            ));
+        }
+
+        let (shadow, shadow_tx) = Shadow::new(shadow).await?;
+        tokio::spawn(async move {
+            if let Err(e) = shadow.run().await {
+                tracing::error!(?e, "shadow worker stopped");
+            }
+        });
+
+        let mut consensus = Self::new(state).await?;
+        consensus.shadow = Some(shadow_tx);
+        Ok(consensus)
+    }
 }
 
 impl Clone for Consensus {
     fn clone(&self) -> Self {
         Self {
             queue: self.queue.clone(),
+            shadow: self.shadow.clone(),
             state: State::NoPermit,
             future: ReusableBoxFuture::new(async { unreachable!() }),
         }
@@ -84,12 +132,54 @@ impl tower::Service<ConsensusRequest> for Consensus {
         let span = req.create_span();
         let (tx, rx) = oneshot::channel();
 
+        // Transactions within a block are independent as far as stateless
+        // checks are concerned, and Tendermint sends DeliverTx requests without
+        // waiting for the previous response. So we start stateless verification
+        // on the blocking pool right away, letting the proofs and signatures of
+        // every queued transaction be checked concurrently, while the worker
+        // still awaits the results (and applies the stateful checks) strictly
+        // in delivery order.
+        let stateless = start_stateless(&req, &span);
+
+        // The shadow worker gets its own copy of the request, and of the
+        // primary worker's response to compare its own with.
+        let primary_rsp = self.shadow.as_ref().and_then(|shadow| shadow.send(&req));
+
         permit.send(Message {
             req,
             rsp_sender: tx,
             span,
+            stateless,
         });
 
-        async move { Ok(rx.await.expect("worker error??")) }.boxed()
+        async move {
+            let rsp = rx.await.expect("worker error??");
+            if let Some(primary_rsp) = primary_rsp {
+                let _ = primary_rsp.send(rsp.clone());
+            }
+            Ok(rsp)
+        }
+        .boxed()
+    }
+}
+
+/// Starts the stateless verification of a `DeliverTx` request's transaction
+/// on the verification pool.
+pub(super) fn start_stateless(
+    req: &ConsensusRequest,
+    span: &Span,
+) -> Option<JoinHandle<anyhow::Result<PendingTransaction>>> {
+    match req {
+        ConsensusRequest::DeliverTx(deliver_tx) => {
+            let tx_bytes = deliver_tx.tx.clone();
+            let span = span.clone();
+            Some(tokio::spawn(async move {
+                on_verification_pool(move || {
+                    span.in_scope(|| decode_canonical(&tx_bytes)?.verify_stateless())
+                })
+                .await?
+            }))
+        }
+        _ => None,
     }
 }
@@ -0,0 +1,160 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::Result;
+use metrics::{gauge, increment_counter};
+use tendermint::abci::{ConsensusRequest as Request, ConsensusResponse as Response};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+use tracing::Instrument;
+
+use super::{service::start_stateless, Message, Worker};
+use crate::{state, RequestExt};
+
+/// How many requests the shadow worker may fall behind the primary by before
+/// it's stopped.
+const MAX_BACKLOG: usize = 1_000;
+
+/// A copy of a consensus request, along with the primary worker's response to it.
+pub type ShadowRequest = (Request, oneshot::Receiver<Response>);
+
+/// Applies copies of the primary worker's requests to a worker writing to a
+/// shadow database, and compares the two workers' responses.
+///
+/// The shadow is stopped for good as soon as it misses a request, whether
+/// because it fell too far behind or because its worker failed, since every
+/// later response would diverge.  This is logged, and reported by the
+/// `node_shadow_stopped` gauge.
+pub struct Shadow {
+    queue: mpsc::Sender<Message>,
+    requests: mpsc::Receiver<ShadowRequest>,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Sends copies of requests to a [`Shadow`], from every clone of the
+/// consensus service.
+#[derive(Clone)]
+pub struct ShadowSender {
+    requests: mpsc::Sender<ShadowRequest>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Shadow {
+    pub async fn new(state: state::Writer) -> Result<(Self, ShadowSender)> {
+        let (queue_tx, queue_rx) = mpsc::channel(10);
+        tokio::spawn(Worker::new(state, queue_rx).await?.run());
+
+        let (requests_tx, requests_rx) = mpsc::channel(MAX_BACKLOG);
+        let stopped = Arc::new(AtomicBool::new(false));
+        gauge!("node_shadow_stopped", 0.0);
+
+        Ok((
+            Self {
+                queue: queue_tx,
+                requests: requests_rx,
+                stopped: stopped.clone(),
+            },
+            ShadowSender {
+                requests: requests_tx,
+                stopped,
+            },
+        ))
+    }
+
+    pub async fn run(mut self) -> Result<()> {
+        let result = self.forward().await;
+        self.stopped.store(true, Ordering::SeqCst);
+        gauge!("node_shadow_stopped", 1.0);
+        result
+    }
+
+    async fn forward(&mut self) -> Result<()> {
+        while let Some((req, primary_rsp)) = self.requests.recv().await {
+            // Requests queued before the shadow was stopped are dropped.
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            let span = tracing::error_span!("shadow");
+            let req_span = span.in_scope(|| req.create_span());
+            let stateless = start_stateless(&req, &req_span);
+
+            let (tx, rx) = oneshot::channel();
+            self.queue
+                .send(Message {
+                    req,
+                    rsp_sender: tx,
+                    span: req_span.clone(),
+                    stateless,
+                })
+                .await
+                .map_err(|_| anyhow::anyhow!("shadow worker stopped"))?;
+
+            // Responses are compared off the request path, so the shadow
+            // worker's queue is kept full just like the primary's.
+            tokio::spawn(
+                async move {
+                    if let (Ok(primary), Ok(shadow)) = (primary_rsp.await, rx.await) {
+                        compare(&primary, &shadow);
+                    }
+                }
+                .instrument(req_span),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ShadowSender {
+    /// Sends a copy of `req` to the shadow, returning the sender for the
+    /// primary worker's response to it, or `None` if the shadow has stopped.
+    pub fn send(&self, req: &Request) -> Option<oneshot::Sender<Response>> {
+        if self.stopped.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let (primary_rsp_tx, primary_rsp_rx) = oneshot::channel();
+        match self.requests.try_send((req.clone(), primary_rsp_rx)) {
+            Ok(()) => return Some(primary_rsp_tx),
+            Err(TrySendError::Full(_)) => {
+                increment_counter!("node_shadow_overflows_total");
+                self.stop("the shadow fell too far behind the primary");
+            }
+            Err(TrySendError::Closed(_)) => self.stop("the shadow worker exited"),
+        }
+        None
+    }
+
+    fn stop(&self, reason: &str) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            tracing::error!(
+                reason,
+                max_backlog = MAX_BACKLOG,
+                "stopped shadowing requests"
+            );
+            gauge!("node_shadow_stopped", 1.0);
+        }
+    }
+}
+
+/// Logs any difference between the primary and shadow workers' responses that
+/// would be visible to consensus.
+fn compare(primary: &Response, shadow: &Response) {
+    let diverged = match (primary, shadow) {
+        (Response::InitChain(primary), Response::InitChain(shadow)) => {
+            primary.app_hash != shadow.app_hash
+        }
+        (Response::DeliverTx(primary), Response::DeliverTx(shadow)) => {
+            primary.code != shadow.code || primary.log != shadow.log
+        }
+        (Response::Commit(primary), Response::Commit(shadow)) => primary.data != shadow.data,
+        _ => false,
+    };
+    if diverged {
+        tracing::error!(?primary, ?shadow, "shadow database diverged from primary");
+        metrics::increment_counter!("node_shadow_divergences_total");
+    }
+}
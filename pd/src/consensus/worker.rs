@@ -1,34 +1,54 @@
 use anyhow::{anyhow, Result};
-use penumbra_crypto::{asset, merkle::NoteCommitmentTree};
-use penumbra_proto::Protobuf;
-use penumbra_stake::{
-    ValidatorState, ValidatorStatus, STAKING_TOKEN_ASSET_ID, STAKING_TOKEN_DENOM,
-};
+use penumbra_stake::Epoch;
 use penumbra_transaction::Transaction;
-use tendermint::abci::{self, ConsensusRequest as Request, ConsensusResponse as Response};
-use tokio::sync::mpsc;
+use tendermint::{
+    abci::{self, ConsensusRequest as Request, ConsensusResponse as Response},
+    block,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
 use tracing::Instrument;
 
-use super::Message;
-use crate::{genesis, state, verify::StatelessTransactionExt, PendingBlock};
+use super::{events::transaction_events, Message};
+use crate::{
+    components::{self, AlreadySpent, Component, DoubleSpend},
+    genesis,
+    scheduler::Scheduler,
+    state,
+    verify::{PendingTransaction, VerifiedTransaction, REGISTRY},
+    PendingBlock,
+};
+
+/// The `DeliverTx` response code for a transaction that spends a nullifier
+/// already spent, either earlier in the same block or in a committed one.
+pub const DOUBLE_SPEND_CODE: u32 = 2;
 
 pub struct Worker {
     state: state::Writer,
     queue: mpsc::Receiver<Message>,
-    // todo: split up and modularize
     pending_block: Option<PendingBlock>,
-    note_commitment_tree: NoteCommitmentTree,
+    components: Vec<Box<dyn Component>>,
+    scheduler: Scheduler,
+    /// The chain id recorded at genesis, if genesis has been committed.
+    chain_id: Option<String>,
 }
 
 impl Worker {
     pub async fn new(state: state::Writer, queue: mpsc::Receiver<Message>) -> Result<Self> {
-        let note_commitment_tree = state.private_reader().note_commitment_tree().await?;
+        let components = components::all(state.private_reader()).await?;
+        let scheduler = Scheduler::new(state.private_reader()).await?;
+        let chain_id = state
+            .private_reader()
+            .chain_identity()
+            .await?
+            .map(|identity| identity.chain_id);
 
         Ok(Self {
             state,
             queue,
             pending_block: None,
-            note_commitment_tree,
+            components,
+            scheduler,
+            chain_id,
         })
     }
 
@@ -37,6 +57,7 @@ impl Worker {
             req,
             rsp_sender,
             span,
+            stateless,
         }) = self.queue.recv().await
         {
             // The send only fails if the receiver was dropped, which happens
@@ -56,14 +77,45 @@ impl Worker {
                         .expect("begin_block must succeed"),
                 ),
                 Request::DeliverTx(deliver_tx) => {
-                    Response::DeliverTx(match self.deliver_tx(deliver_tx).instrument(span).await {
-                        Ok(()) => abci::response::DeliverTx::default(),
-                        Err(e) => abci::response::DeliverTx {
-                            code: 1,
-                            log: e.to_string(),
+                    let stateless =
+                        stateless.expect("DeliverTx messages carry stateless verification");
+                    let result = self
+                        .deliver_tx(deliver_tx, stateless)
+                        .instrument(span)
+                        .await;
+                    let rsp = match result {
+                        Ok(events) => abci::response::DeliverTx {
+                            events,
                             ..Default::default()
                         },
-                    })
+                        Err(e) => {
+                            // Only a transaction's own faults may reject it.  If
+                            // the state couldn't be read, another node may have
+                            // accepted the transaction, so continuing would
+                            // diverge from consensus.
+                            if let Some(error) = e.downcast_ref::<state::StateError>() {
+                                if !matches!(error, state::StateError::NotFound(_)) {
+                                    panic!("deliver_tx couldn't read the state: {}", error);
+                                }
+                            }
+                            abci::response::DeliverTx {
+                                code: if e.is::<DoubleSpend>() || e.is::<AlreadySpent>() {
+                                    DOUBLE_SPEND_CODE
+                                } else {
+                                    1
+                                },
+                                log: e.to_string(),
+                                ..Default::default()
+                            }
+                        }
+                    };
+                    // Record the result for pd's own transaction index.
+                    self.pending_block
+                        .as_mut()
+                        .unwrap()
+                        .transaction_results
+                        .push((rsp.code, rsp.log.clone()));
+                    Response::DeliverTx(rsp)
                 }
                 Request::EndBlock(end_block) => Response::EndBlock(
                     self.end_block(end_block)
@@ -91,16 +143,22 @@ impl Worker {
         let app_state: genesis::AppState = serde_json::from_slice(&init_chain.app_state_bytes)
             .expect("can parse app_state in genesis file");
 
+        // Refuse to initialize a database that belongs to a different chain.
+        self.state
+            .private_reader()
+            .check_chain_identity(&init_chain.chain_id, Some(&init_chain.app_state_bytes))
+            .await?;
+
         // Initialize the database with the app state.
-        self.state.commit_genesis(&app_state).await?;
+        self.state.commit_genesis(&init_chain, &app_state).await?;
+        self.chain_id = Some(init_chain.chain_id.clone());
 
         // Now start building the genesis block:
-        self.note_commitment_tree = NoteCommitmentTree::new(0);
-        let mut genesis_block = PendingBlock::new(
-            self.note_commitment_tree.clone(),
-            app_state.chain_params.epoch_duration,
-        );
+        let mut genesis_block = PendingBlock::new(app_state.chain_params.epoch_duration);
         genesis_block.set_height(0);
+        for component in &mut self.components {
+            component.init_chain(&app_state, &mut genesis_block);
+        }
 
         // Create a genesis transaction to record genesis notes.
         // TODO: eliminate this (#374)
@@ -112,26 +170,6 @@ impl Worker {
             tracing::info!(?allocation, "processing allocation");
 
             tx_builder.add_output(allocation.note().expect("genesis allocations are valid"));
-
-            let denom = asset::REGISTRY
-                .parse_denom(&allocation.denom)
-                .expect("genesis allocations must have valid denominations");
-
-            // Accumulate the allocation amount into the supply updates for this denom.
-            genesis_block
-                .supply_updates
-                .entry(denom.id())
-                .or_insert((denom, 0))
-                .1 += allocation.amount;
-        }
-
-        // We might not have any allocations of delegation tokens, but we should record the denoms.
-        for genesis::ValidatorPower { validator, .. } in app_state.validators.iter() {
-            let denom = validator.identity_key.delegation_token().denom();
-            genesis_block
-                .supply_updates
-                .entry(denom.id())
-                .or_insert((denom, 0));
         }
 
         let genesis_tx = tx_builder
@@ -141,10 +179,10 @@ impl Worker {
         let verified_transaction = crate::verify::mark_genesis_as_verified(genesis_tx);
 
         // Now add the transaction and its note fragments to the pending state changes.
-        genesis_block.add_transaction(verified_transaction);
+        self.pending_block = Some(genesis_block);
+        self.apply_transaction(verified_transaction);
 
         // Commit the genesis block to the state
-        self.pending_block = Some(genesis_block);
         let app_hash = self.commit().await?.data;
 
         // Extract the Tendermint validators from the genesis app state
@@ -177,19 +215,120 @@ impl Worker {
     ) -> Result<abci::response::BeginBlock> {
         tracing::debug!(?begin_block);
 
+        if let Some(chain_id) = &self.chain_id {
+            if begin_block.header.chain_id.as_str() != chain_id {
+                return Err(anyhow!(
+                    "received a block for chain {:?}, but the database belongs to chain {:?}",
+                    begin_block.header.chain_id.as_str(),
+                    chain_id
+                ));
+            }
+        }
+
+        self.check_continuity(&begin_block.header).await?;
+
         assert!(self.pending_block.is_none());
-        self.pending_block = Some(PendingBlock::new(
-            self.note_commitment_tree.clone(),
-            self.state
+        let chain_params = self
+            .state
+            .private_reader()
+            .chain_params_rx()
+            .borrow()
+            .clone();
+        let mut pending_block = PendingBlock::new(chain_params.epoch_duration);
+        // TODO: the misbehavior evidence in `byzantine_validators` isn't acted
+        // on yet.  Until validators are slashed for it, there are no slashing
+        // events to record, and so no slashing history to serve.
+        pending_block.begin_block = Some(begin_block);
+
+        // Time-based epochs can't be found from the height, so whether this
+        // block ends its epoch is decided here, from the block time.
+        if chain_params.epoch_duration_secs > 0 {
+            let epoch = self
+                .state
                 .private_reader()
-                .chain_params_rx()
-                .borrow()
-                .epoch_duration,
-        ));
+                .current_epoch()
+                .await?
+                .ok_or_else(|| anyhow!("the start of the current epoch wasn't recorded"))?;
+            pending_block.set_timed_epoch(
+                epoch.index,
+                epoch.start_time,
+                chain_params.epoch_duration_secs,
+            );
+        }
+
+        // Carry out the scheduled actions that have come due, before the
+        // components begin the block.
+        let height = pending_block
+            .begin_block
+            .as_ref()
+            .expect("begin_block was just set")
+            .header
+            .height
+            .value();
+        let epoch_index = match &pending_block.epoch {
+            Some(epoch) => epoch.index,
+            None => Epoch::from_height(height, chain_params.epoch_duration).index,
+        };
+        for scheduled in self.scheduler.due(height, epoch_index) {
+            tracing::debug!(?scheduled, "carrying out scheduled action");
+            for component in &mut self.components {
+                component
+                    .run_scheduled(
+                        self.state.private_reader(),
+                        &mut pending_block,
+                        &scheduled.action,
+                    )
+                    .await?;
+            }
+            pending_block.completed_actions.push(scheduled);
+        }
+
+        for component in &mut self.components {
+            component
+                .begin_block(self.state.private_reader(), &mut pending_block)
+                .await?;
+        }
+        self.pending_block = Some(pending_block);
 
         Ok(Default::default())
     }
 
+    /// Checks that a block Tendermint is about to deliver extends the chain
+    /// stored in the database.
+    ///
+    /// Each header carries the app hash we returned when committing the
+    /// previous block.  If the database was restored from a different node,
+    /// modified, or produced by a pd with a state-machine bug, the stored app
+    /// hash won't match, and continuing would silently build on a fork, so
+    /// this reports the divergence and refuses to proceed.
+    async fn check_continuity(&self, header: &block::Header) -> Result<()> {
+        let (stored_height, stored_app_hash) =
+            match self.state.private_reader().latest_block_info().await? {
+                Some(block) => (block.height as u64, block.app_hash),
+                None => return Err(anyhow!("received a block before genesis was committed")),
+            };
+
+        let height = header.height.value();
+        let expected_app_hash: &[u8] = header.app_hash.as_ref();
+        if height != stored_height + 1 {
+            return Err(anyhow!(
+                "Tendermint delivered height {}, but the database ends at height {}",
+                height,
+                stored_height
+            ));
+        }
+        if expected_app_hash != &stored_app_hash[..] {
+            return Err(anyhow!(
+                "app hash divergence at height {}: Tendermint expects {}, but the database has {}",
+                stored_height,
+                hex::encode(expected_app_hash),
+                hex::encode(&stored_app_hash)
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Perform full transaction validation via `DeliverTx`.
     ///
     /// State changes are only applied for valid transactions. Invalid transaction are ignored.
@@ -197,38 +336,94 @@ impl Worker {
     /// We must perform all checks again here even though they are performed in `CheckTx`, as a
     /// Byzantine node may propose a block containing double spends or other disallowed behavior,
     /// so it is not safe to assume all checks performed in `CheckTx` were done.
-    async fn deliver_tx(&mut self, deliver_tx: abci::request::DeliverTx) -> Result<()> {
-        // Verify the transaction is well-formed...
-        let transaction = Transaction::decode(deliver_tx.tx)?
-            // ... and that it is internally consistent ...
-            .verify_stateless()?;
-        // ... and that it is consistent with the existing chain state.
+    ///
+    /// Decoding and stateless verification are started by the [`Consensus`](super::Consensus)
+    /// service when the request arrives, so here we only wait for their result
+    /// before performing the stateful checks in delivery order.
+    ///
+    /// Returns the events describing the transaction's effects.
+    async fn deliver_tx(
+        &mut self,
+        deliver_tx: abci::request::DeliverTx,
+        stateless: JoinHandle<Result<PendingTransaction>>,
+    ) -> Result<Vec<abci::Event>> {
+        // Record the raw transaction, whether or not it turns out to be valid.
+        let raw_transactions = &mut self.pending_block.as_mut().unwrap().raw_transactions;
+        raw_transactions.push(deliver_tx.tx);
+
+        // Enforce the block limits from the chain parameters, rather than
+        // relying on each node's Tendermint configuration.  Every transaction
+        // in the block counts, whether or not it's valid.
+        let (max_block_transactions, max_block_bytes) = {
+            let chain_params = self.state.private_reader().chain_params_rx().borrow();
+            (
+                chain_params.max_block_transactions,
+                chain_params.max_block_bytes,
+            )
+        };
+        if raw_transactions.len() as u64 > max_block_transactions {
+            return Err(anyhow!(
+                "block has more than the maximum of {} transactions",
+                max_block_transactions
+            ));
+        }
+        let block_bytes = raw_transactions
+            .iter()
+            .map(|tx| tx.len() as u64)
+            .sum::<u64>();
+        if block_bytes > max_block_bytes {
+            return Err(anyhow!(
+                "block's transactions exceed the maximum of {} bytes",
+                max_block_bytes
+            ));
+        }
+
+        // Wait for the checks that the transaction is well-formed and internally consistent...
+        let transaction = stateless.await??;
+        // ... and check that it is consistent with the existing chain state.
+        // The height comes from the block being executed: the height watch
+        // channel lags behind while the last block's deferred writes finish.
+        let pending_block = self.pending_block.as_mut().unwrap();
+        let height = pending_block
+            .begin_block
+            .as_ref()
+            .expect("begin_block must be set")
+            .header
+            .height
+            .value();
         let transaction = self
             .state
             .private_reader()
-            .verify_stateful(transaction)
+            .verify_stateful_in_block(height, transaction, &mut pending_block.anchor_verdicts)
             .await?;
 
-        let mut conflicts = self
-            .pending_block
-            .as_ref()
-            .unwrap()
-            .spent_nullifiers
-            .intersection(&transaction.spent_nullifiers);
-
-        if let Some(conflict) = conflicts.next() {
-            return Err(anyhow!(
-                "nullifier {:?} is already spent in the pending block",
-                conflict
-            ));
+        // Check the transaction against the pending block with every
+        // component before any of them applies it.
+        let pending_block = self.pending_block.as_ref().unwrap();
+        for component in &self.components {
+            component
+                .check_tx(self.state.private_reader(), pending_block, &transaction)
+                .await?;
         }
 
-        self.pending_block
-            .as_mut()
-            .unwrap()
-            .add_transaction(transaction);
+        let events = transaction_events(&transaction);
+        self.apply_transaction(transaction);
 
-        Ok(())
+        Ok(events)
+    }
+
+    /// Adds a transaction that passed every check to the pending block.
+    fn apply_transaction(&mut self, transaction: VerifiedTransaction) {
+        let pending_block = self.pending_block.as_mut().unwrap();
+        for handler in REGISTRY.handlers() {
+            handler.execute(&transaction, pending_block);
+        }
+        for component in &mut self.components {
+            component.deliver_tx(pending_block, &transaction);
+        }
+        pending_block.fees += transaction.fee;
+        pending_block.weight += transaction.weight;
+        pending_block.num_transactions += 1;
     }
 
     async fn end_block(
@@ -248,162 +443,22 @@ impl Worker {
             .try_into()
             .expect("height should be nonnegative");
         let epoch = pending_block.set_height(height);
+        let mut events = Vec::new();
 
-        tracing::debug!(?height, ?epoch, end_height = ?epoch.end_height());
-
-        if epoch.end_height().value() == height {
-            // We've finished processing the last block of `epoch`, so we've
-            // crossed the epoch boundary, and (prev | current | next) are:
-            let prev_epoch = epoch;
-            let current_epoch = prev_epoch.next();
-            let next_epoch = current_epoch.next();
-
-            tracing::info!(
-                ?height,
-                ?prev_epoch,
-                ?current_epoch,
-                ?next_epoch,
-                "crossed epoch boundary, processing rate updates"
-            );
-            metrics::increment_counter!("epoch");
-
-            // TODO (optimization): batch these queries
-            let current_base_rate = reader.base_rate_data(current_epoch.index).await?;
-            let current_rates = reader.rate_data(current_epoch.index).await?;
-
-            let mut staking_token_supply = reader
-                .asset_lookup(*STAKING_TOKEN_ASSET_ID)
-                .await?
-                .map(|info| info.total_supply)
-                .unwrap();
-
-            // steps (foreach validator):
-            // - get the total token supply for the validator's delegation tokens
-            // - process the updates to the token supply:
-            //   - collect all delegations occurring in previous epoch and apply them (adds to supply);
-            //   - collect all undelegations started in previous epoch and apply them (reduces supply);
-            // - feed the updated (current) token supply into current_rates.voting_power()
-            // - persist both the current voting power and the current supply
-            //
-
-            /// FIXME: set this less arbitrarily, and allow this to be set per-epoch
-            /// 3bps -> 11% return over 365 epochs, why not
-            const BASE_REWARD_RATE: u64 = 3_0000;
-
-            let next_base_rate = current_base_rate.next(BASE_REWARD_RATE);
-
-            // rename to curr_rate so it lines up with next_rate (same # chars)
-            tracing::debug!(curr_base_rate = ?current_base_rate);
-            tracing::debug!(?next_base_rate);
-
-            let mut next_rates = Vec::new();
-            let mut next_validator_statuses = Vec::new();
-
-            // this is a bit complicated: because we're in the EndBlock phase, and the
-            // delegations in this block have not yet been committed, we have to combine
-            // the delegations in pending_block with the ones already committed to the
-            // state. otherwise the delegations committed in the epoch threshold block
-            // would be lost.
-            let mut delegation_changes = reader.delegation_changes(prev_epoch.index).await?;
-            for (id_key, delta) in &pending_block.delegation_changes {
-                *delegation_changes.entry(id_key.clone()).or_insert(0) += delta;
-            }
-
-            for current_rate in &current_rates {
-                let identity_key = current_rate.identity_key.clone();
-
-                let funding_streams = reader.funding_streams(identity_key.clone()).await?;
-                let next_rate = current_rate.next(&next_base_rate, funding_streams.as_ref());
-
-                // TODO: if a validator isn't part of the consensus set, should we ignore them
-                // and not update their rates?
-                let delegation_delta = delegation_changes.get(&identity_key).unwrap_or(&0i64);
-
-                let delegation_amount = delegation_delta.abs() as u64;
-                let unbonded_amount = current_rate.unbonded_amount(delegation_amount);
-
-                let mut delegation_token_supply = reader
-                    .asset_lookup(identity_key.delegation_token().id())
-                    .await?
-                    .map(|info| info.total_supply)
-                    .unwrap_or(0);
-
-                if *delegation_delta > 0 {
-                    // net delegation: subtract the unbonded amount from the staking token supply
-                    staking_token_supply =
-                        staking_token_supply.checked_sub(unbonded_amount).unwrap();
-                    delegation_token_supply = delegation_token_supply
-                        .checked_add(delegation_amount)
-                        .unwrap();
-                } else {
-                    // net undelegation: add the unbonded amount to the staking token supply
-                    staking_token_supply =
-                        staking_token_supply.checked_add(unbonded_amount).unwrap();
-                    delegation_token_supply = delegation_token_supply
-                        .checked_sub(delegation_amount)
-                        .unwrap();
-                }
-
-                // update the delegation token supply
-                pending_block.supply_updates.insert(
-                    identity_key.delegation_token().id(),
-                    (
-                        identity_key.delegation_token().denom(),
-                        delegation_token_supply,
-                    ),
-                );
-
-                let voting_power = next_rate.voting_power(delegation_token_supply, &next_base_rate);
-                let next_status = ValidatorStatus {
-                    identity_key,
-                    voting_power,
-                    // TODO: this state needs to be set correctly based on current state and any changes
-                    // within the current block. This will be fixed by #375.
-                    state: ValidatorState::Active,
-                };
-
-                // distribute validator commission
-                for stream in funding_streams {
-                    let commission_reward_amount = stream.reward_amount(
-                        delegation_token_supply,
-                        &next_base_rate,
-                        &current_base_rate,
-                    );
-
-                    pending_block
-                        .add_validator_reward_note(commission_reward_amount, stream.address);
-                }
-
-                // rename to curr_rate so it lines up with next_rate (same # chars)
-                tracing::debug!(curr_rate = ?current_rate);
-                tracing::debug!(?next_rate);
-                tracing::debug!(?delegation_delta);
-                tracing::debug!(?delegation_token_supply);
-                tracing::debug!(?next_status);
+        tracing::debug!(?height, ?epoch, ends_epoch = pending_block.ends_epoch);
 
-                next_rates.push(next_rate);
-                next_validator_statuses.push(next_status);
-            }
-
-            tracing::debug!(?staking_token_supply);
-
-            pending_block.next_rates = Some(next_rates);
-            pending_block.next_base_rate = Some(next_base_rate);
-            pending_block.next_validator_statuses = Some(next_validator_statuses);
-            pending_block.supply_updates.insert(
-                *STAKING_TOKEN_ASSET_ID,
-                (STAKING_TOKEN_DENOM.clone(), staking_token_supply),
-            );
-
-            // TODO: later, set the EndBlock response to add validators
-            // at the epoch boundary
+        for component in &mut self.components {
+            events.extend(component.end_block(reader, pending_block).await?);
         }
 
         // TODO: right now we are not writing the updated voting power from validator statuses
         // back to tendermint, so that we can see how the statuses are computed without risking
         // halting the testnet. in the future we want to add code here to send the next voting
         // powers back to tendermint.
-        Ok(Default::default())
+        Ok(abci::response::EndBlock {
+            events,
+            ..Default::default()
+        })
     }
 
     async fn commit(&mut self) -> Result<abci::response::Commit> {
@@ -412,10 +467,26 @@ impl Worker {
             .take()
             .expect("pending_block must be Some in Commit");
 
-        // Pull the updated note commitment tree, for use in the next block.
-        self.note_commitment_tree = pending_block.note_commitment_tree.clone();
-
-        let app_hash = self.state.commit_block(pending_block).await?;
+        for component in &mut self.components {
+            component.commit(&pending_block);
+        }
+        self.scheduler.commit(&pending_block);
+
+        // A transient failure leaves the block either uncommitted or fully
+        // committed, both of which a retry handles, so the node waits out
+        // database outages.  Any other failure means the block can't be
+        // stored as it was executed, which is fatal.
+        let mut backoff = state::Backoff::default();
+        let app_hash = loop {
+            match self.state.commit_block(pending_block.clone()).await {
+                Ok(app_hash) => break app_hash,
+                Err(error) if error.is_transient() => {
+                    tracing::warn!(%error, delay = ?backoff.delay(), "block commit failed, retrying");
+                    backoff.wait().await;
+                }
+                Err(error) => return Err(error.into()),
+            }
+        };
 
         tracing::info!(app_hash = ?hex::encode(&app_hash), "finished block commit");
 
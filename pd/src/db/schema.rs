@@ -16,14 +16,41 @@ pub struct BlocksRow {
 #[derive(Debug, sqlx::FromRow)]
 pub struct NotesRow {
     pub note_commitment: note::Commitment,
-    pub ephemeral_key: Vec<u8>,
-    pub encrypted_note: Vec<u8>,
     pub transaction_id: Vec<u8>,
     pub height: i64,
 }
 
+#[derive(Debug, sqlx::FromRow)]
+pub struct NoteCiphertextsRow {
+    pub note_commitment: note::Commitment,
+    pub ephemeral_key: Vec<u8>,
+    pub encrypted_note: Vec<u8>,
+    pub encrypted_memo: Option<Vec<u8>>,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct NullifiersRow {
     pub nullifier: Nullifier,
     pub height: i64,
 }
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct RawBlocksRow {
+    pub height: i64,
+    pub block_hash: Vec<u8>,
+    pub begin_block: Vec<u8>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct RawTransactionsRow {
+    pub height: i64,
+    pub position: i32,
+    pub tx_hash: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ChainIdentityRow {
+    pub chain_id: String,
+    pub genesis_hash: Vec<u8>,
+}
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use jmt::{hash::HashValue, node_type::Node};
+use penumbra_crypto::merkle;
+use sqlx::{
+    postgres::{PgPool, PgPoolOptions},
+    query,
+};
+
+use crate::{backup::TABLES, state::jellyfish};
+
+/// Compares the state in two pd databases, such as those of a healthy node and
+/// one whose app hash diverged, and reports where they first differ.
+///
+/// The first height at which the recorded app hashes differ is found, and
+/// then the JMT leaves written at that height and the rows of every table are
+/// compared.  Rows of tables with a `height` column are only compared at the
+/// diverging height; other tables are compared in full, so their differences
+/// may include changes made after it.  At most `max_rows` differing rows are
+/// logged per table.
+///
+/// Neither database is modified.
+pub async fn diff_state(uri: &str, other_uri: &str, max_rows: usize) -> Result<()> {
+    let pools = (
+        PgPoolOptions::new().max_connections(1).connect(uri).await?,
+        PgPoolOptions::new()
+            .max_connections(1)
+            .connect(other_uri)
+            .await?,
+    );
+
+    let app_hashes = (app_hashes(&pools.0).await?, app_hashes(&pools.1).await?);
+    let height = match app_hashes
+        .0
+        .iter()
+        .find(|(height, app_hash)| {
+            app_hashes
+                .1
+                .get(height)
+                .map_or(false, |other| other != *app_hash)
+        })
+        .map(|(height, _)| *height)
+    {
+        Some(height) => height,
+        None => {
+            tracing::info!(
+                heights = app_hashes.0.len(),
+                other_heights = app_hashes.1.len(),
+                "the app hashes of all common heights match"
+            );
+            return Ok(());
+        }
+    };
+    tracing::warn!(
+        height,
+        app_hash = %hex::encode(&app_hashes.0[&height]),
+        other_app_hash = %hex::encode(&app_hashes.1[&height]),
+        "found first diverging height"
+    );
+
+    let leaves = (
+        jmt_leaves(&pools.0, height).await?,
+        jmt_leaves(&pools.1, height).await?,
+    );
+    for key_hash in leaves
+        .0
+        .keys()
+        .chain(leaves.1.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let (value, other_value) = (leaves.0.get(key_hash), leaves.1.get(key_hash));
+        if value != other_value {
+            let key = jellyfish::Key::from_hash(*key_hash, height);
+            tracing::warn!(
+                key_hash = %hex::encode(key_hash.to_vec()),
+                ?key,
+                in_first = value.is_some(),
+                in_second = other_value.is_some(),
+                "JMT leaf differs"
+            );
+        }
+    }
+
+    let height_tables = query!(
+        r#"SELECT table_name AS "table_name!" FROM information_schema.columns
+            WHERE table_schema = 'public' AND column_name = 'height'"#
+    )
+    .fetch_all(&pools.0)
+    .await?
+    .into_iter()
+    .map(|row| row.table_name)
+    .collect::<BTreeSet<_>>();
+
+    // The JMT nodes were compared above, as decoded leaves.  Which stale nodes
+    // are still indexed depends on each database's pruning.
+    for table in TABLES
+        .iter()
+        .filter(|table| !matches!(**table, "jmt" | "jmt_stale_nodes"))
+    {
+        let filter = if height_tables.contains(*table) {
+            Some(height)
+        } else {
+            None
+        };
+        let hashes = (
+            row_hashes(&pools.0, table, filter).await?,
+            row_hashes(&pools.1, table, filter).await?,
+        );
+
+        let only_first = hashes.0.difference(&hashes.1).cloned().collect::<Vec<_>>();
+        let only_second = hashes.1.difference(&hashes.0).cloned().collect::<Vec<_>>();
+        if only_first.is_empty() && only_second.is_empty() {
+            continue;
+        }
+        tracing::warn!(
+            %table,
+            only_first = only_first.len(),
+            only_second = only_second.len(),
+            "table differs"
+        );
+        for row in rows(&pools.0, table, &only_first, max_rows).await? {
+            tracing::warn!(%table, %row, "row only in first database");
+        }
+        for row in rows(&pools.1, table, &only_second, max_rows).await? {
+            tracing::warn!(%table, %row, "row only in second database");
+        }
+    }
+
+    Err(anyhow!("the databases diverge at height {}", height))
+}
+
+async fn app_hashes(pool: &PgPool) -> Result<BTreeMap<u64, Vec<u8>>> {
+    Ok(query!("SELECT height, app_hash FROM blocks")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.height as u64, row.app_hash))
+        .collect())
+}
+
+/// Returns the encoded leaves written to the JMT at `height`, by key hash.
+async fn jmt_leaves(pool: &PgPool, height: u64) -> Result<BTreeMap<HashValue, Vec<u8>>> {
+    let mut leaves = BTreeMap::new();
+    // Node keys start with the big-endian version the node was written at.
+    for row in query!(
+        "SELECT value FROM jmt WHERE substring(key FROM 1 FOR 8) = $1",
+        &height.to_be_bytes()[..]
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        if let Node::<merkle::Root>::Leaf(leaf) = Node::decode(&row.value)? {
+            leaves.insert(leaf.account_key(), row.value);
+        }
+    }
+    Ok(leaves)
+}
+
+/// Hashes the text representation of each row of `table`, optionally only
+/// those at the given height.
+async fn row_hashes(pool: &PgPool, table: &str, height: Option<u64>) -> Result<BTreeSet<String>> {
+    let hashes: Vec<String> = match height {
+        Some(height) => {
+            sqlx::query_scalar(&format!(
+                "SELECT md5(t::text) FROM {} t WHERE height = $1",
+                table
+            ))
+            .bind(height as i64)
+            .fetch_all(pool)
+            .await?
+        }
+        None => {
+            sqlx::query_scalar(&format!("SELECT md5(t::text) FROM {} t", table))
+                .fetch_all(pool)
+                .await?
+        }
+    };
+    Ok(hashes.into_iter().collect())
+}
+
+/// Returns the text representation of up to `limit` rows of `table` with the
+/// given hashes.
+async fn rows(pool: &PgPool, table: &str, hashes: &[String], limit: usize) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar::<_, String>(&format!(
+        "SELECT t::text FROM {} t WHERE md5(t::text) = ANY($1) LIMIT $2",
+        table
+    ))
+    .bind(hashes)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await?)
+}
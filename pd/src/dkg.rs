@@ -0,0 +1,217 @@
+//! The distributed key generation (DKG) run by the active validators of each
+//! epoch to establish a threshold decryption key for the next epoch.
+//!
+//! When an epoch begins, its round is opened with the validators that are
+//! active with nonzero voting power as participants, numbered from 1 in
+//! identity key order.  During the epoch, each participant may submit one
+//! signed dealing, with a share for every participant and a proof of knowledge
+//! of its contribution to the group key, so that no dealer can choose its
+//! contribution to cancel out the others'.
+//!
+//! Shares are checked against the dealer's commitments only by their
+//! recipients.  A participant whose share doesn't match can complain about the
+//! dealer by revealing the secret its share was masked with, along with a
+//! proof that it was computed with the participant's key, so that anyone can
+//! check the complaint.  A dealer with a justified complaint is disqualified,
+//! and to leave time for complaints, dealings submitted in the last
+//! [`COMPLAINT_BLOCKS`] blocks of the epoch aren't counted.
+//!
+//! When the epoch ends, the round is finished: if at least `threshold`
+//! qualified participants dealt, the group key is the sum of their
+//! contributions, and any `threshold` participants can jointly decrypt to it
+//! during the next epoch.  Otherwise the round fails, and the next epoch has
+//! no threshold key.  Either way, the round's transcript is committed to in
+//! the JMT.
+
+use penumbra_crypto::{dkg, merkle};
+use penumbra_proto::Protobuf;
+use penumbra_stake::{DkgComplaint, DkgDealing, IdentityKey, SignedDkgDealing};
+
+use crate::state::jellyfish;
+
+/// The number of blocks before the end of an epoch in which dealings are no
+/// longer counted, so that their recipients have time to complain about them.
+pub const COMPLAINT_BLOCKS: u64 = 10;
+
+/// The number of participants needed to decrypt to the group key of a round
+/// with `participants` participants: more than two thirds of them.
+pub fn threshold(participants: usize) -> u64 {
+    (participants * 2 / 3 + 1) as u64
+}
+
+/// A DKG round, as recorded in the state.
+#[derive(Debug, Clone)]
+pub struct Round {
+    pub epoch_index: u64,
+    pub threshold: u64,
+    /// The participants, in index order.
+    pub participants: Vec<IdentityKey>,
+    /// Whether the round has finished.
+    pub finished: bool,
+    /// The group key, if the round finished successfully.
+    pub group_key: Option<decaf377::Element>,
+    /// The public key share of each participant, in index order, if the round
+    /// finished successfully.
+    pub public_key_shares: Vec<decaf377::Element>,
+}
+
+/// The outcome of a finished DKG round.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub epoch_index: u64,
+    /// The dealers whose dealings were combined, in identity key order.
+    pub dealers: Vec<IdentityKey>,
+    /// The group key, if enough participants dealt.
+    pub group_key: Option<decaf377::Element>,
+    /// The public key share of each participant, in index order, if enough
+    /// participants dealt.
+    pub public_key_shares: Vec<decaf377::Element>,
+    /// The commitment to the transcript stored in the JMT.
+    pub commitment: merkle::Root,
+}
+
+impl Round {
+    /// Returns the index of the participant with the given identity key.
+    pub fn participant_index(&self, identity_key: &IdentityKey) -> Option<u32> {
+        self.participants
+            .iter()
+            .position(|participant| participant == identity_key)
+            .map(|position| position as u32 + 1)
+    }
+
+    /// Checks that a dealing is shaped for this round: that it's from one of
+    /// the participants, can be combined by `threshold` of them, and deals a
+    /// share to each of them.
+    pub fn check_dealing(&self, dealing: &DkgDealing) -> anyhow::Result<()> {
+        if self.finished {
+            return Err(anyhow::anyhow!(
+                "the DKG round of epoch {} has finished",
+                self.epoch_index
+            ));
+        }
+        if self
+            .participant_index(&dealing.validator_identity)
+            .is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "validator {} is not a participant in the DKG round of epoch {}",
+                dealing.validator_identity,
+                self.epoch_index
+            ));
+        }
+        if dealing.dealing.threshold() as u64 != self.threshold {
+            return Err(anyhow::anyhow!(
+                "DKG dealing has threshold {}, but the round's threshold is {}",
+                dealing.dealing.threshold(),
+                self.threshold
+            ));
+        }
+        if dealing.dealing.encrypted_shares.len() != self.participants.len() {
+            return Err(anyhow::anyhow!(
+                "DKG dealing has {} shares, but the round has {} participants",
+                dealing.dealing.encrypted_shares.len(),
+                self.participants.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that a complaint about `dealing`, which was submitted to this
+    /// round, is from one of the participants and is justified.
+    pub fn check_complaint(
+        &self,
+        complaint: &DkgComplaint,
+        dealing: &DkgDealing,
+    ) -> anyhow::Result<()> {
+        if self.finished {
+            return Err(anyhow::anyhow!(
+                "the DKG round of epoch {} has finished",
+                self.epoch_index
+            ));
+        }
+        let index = self
+            .participant_index(&complaint.validator_identity)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "validator {} is not a participant in the DKG round of epoch {}",
+                    complaint.validator_identity,
+                    self.epoch_index
+                )
+            })?;
+        complaint
+            .complaint
+            .verify(
+                &dealing.dealing,
+                index,
+                &complaint.validator_identity.dkg_key(),
+            )
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "unjustified complaint about the DKG dealing from {}: {}",
+                    complaint.dealer_identity,
+                    e
+                )
+            })
+    }
+
+    /// Finishes the round at `end_height` with the dealings submitted to it
+    /// and the heights they were submitted at, which must have been checked
+    /// with [`Round::check_dealing`] and come from distinct dealers.
+    ///
+    /// Dealings submitted within [`COMPLAINT_BLOCKS`] of the end, and those
+    /// with a justified complaint against them, are left out.
+    pub fn finish(
+        &self,
+        dealings: Vec<(u64, SignedDkgDealing)>,
+        complaints: &[DkgComplaint],
+        end_height: u64,
+    ) -> Transcript {
+        let mut dealings = dealings
+            .into_iter()
+            .filter(|(height, _)| height + COMPLAINT_BLOCKS <= end_height)
+            .map(|(_, dealing)| dealing)
+            .filter(|dealing| {
+                !complaints.iter().any(|complaint| {
+                    complaint.dealer_identity == dealing.dealing.validator_identity
+                })
+            })
+            .collect::<Vec<_>>();
+        dealings.sort_by(|a, b| {
+            a.dealing
+                .validator_identity
+                .cmp(&b.dealing.validator_identity)
+        });
+
+        let (group_key, public_key_shares) = if dealings.len() as u64 >= self.threshold {
+            let dealings = dealings.iter().map(|signed| &signed.dealing.dealing);
+            (
+                Some(dkg::group_key(dealings.clone())),
+                (1..=self.participants.len() as u32)
+                    .map(|index| dkg::public_key_share(dealings.clone(), index))
+                    .collect(),
+            )
+        } else {
+            (None, Vec::new())
+        };
+
+        let commitment = jellyfish::dkg_transcript_commitment(
+            self.epoch_index,
+            group_key.as_ref(),
+            &dealings
+                .iter()
+                .map(|dealing| dealing.encode_to_vec())
+                .collect::<Vec<_>>(),
+        );
+
+        Transcript {
+            epoch_index: self.epoch_index,
+            dealers: dealings
+                .into_iter()
+                .map(|dealing| dealing.dealing.validator_identity)
+                .collect(),
+            group_key,
+            public_key_shares,
+            commitment,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Fault injection, for exercising pd's recovery logic in tests.
+//!
+//! With the `fault-injection` feature enabled, failures can be injected into
+//! the state writer and the wallet RPC services, either by calling [`inject`]
+//! or by setting the `PD_FAULTS` environment variable to a JSON-encoded
+//! [`Faults`] before pd starts.  Faults are counted rather than random, so a
+//! test knows exactly which operations will fail.  Without the feature, every
+//! hook is a no-op.
+
+#[cfg(feature = "fault-injection")]
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::Result;
+#[cfg(feature = "fault-injection")]
+use once_cell::sync::Lazy;
+#[cfg(feature = "fault-injection")]
+use serde::{Deserialize, Serialize};
+
+/// The faults to inject.
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    /// Fail this many of the upcoming block commits with a connection error,
+    /// rolling back everything they wrote.
+    pub commit_errors: u32,
+    /// Skip this many of the upcoming notifications that a block has been
+    /// fully written.
+    pub dropped_notifications: u32,
+    /// Delay every wallet RPC response by this many milliseconds.
+    pub rpc_delay_ms: u64,
+}
+
+#[cfg(feature = "fault-injection")]
+static FAULTS: Lazy<Mutex<Faults>> = Lazy::new(|| {
+    Mutex::new(match std::env::var("PD_FAULTS") {
+        Ok(faults) => serde_json::from_str(&faults).expect("PD_FAULTS is valid JSON"),
+        Err(_) => Faults::default(),
+    })
+});
+
+/// Replaces the faults to inject from now on.
+#[cfg(feature = "fault-injection")]
+pub fn inject(faults: Faults) {
+    tracing::warn!(?faults, "injecting faults");
+    *FAULTS.lock().unwrap() = faults;
+}
+
+/// Fails if a block commit failure should be injected.
+pub(crate) fn commit_error() -> Result<(), sqlx::Error> {
+    #[cfg(feature = "fault-injection")]
+    {
+        let mut faults = FAULTS.lock().unwrap();
+        if faults.commit_errors > 0 {
+            faults.commit_errors -= 1;
+            return Err(sqlx::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "injected block commit failure",
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether a notification should be dropped rather than sent.
+pub(crate) fn drop_notification() -> bool {
+    #[cfg(feature = "fault-injection")]
+    {
+        let mut faults = FAULTS.lock().unwrap();
+        if faults.dropped_notifications > 0 {
+            faults.dropped_notifications -= 1;
+            tracing::warn!("dropping notification");
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns a layer that injects delays into the wallet RPC services.
+#[cfg(feature = "fault-injection")]
+pub fn rpc_layer() -> RpcDelayLayer {
+    RpcDelayLayer
+}
+
+/// Returns a layer that injects delays into the wallet RPC services.
+#[cfg(not(feature = "fault-injection"))]
+pub fn rpc_layer() -> tower::layer::util::Identity {
+    tower::layer::util::Identity::new()
+}
+
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Copy)]
+pub struct RpcDelayLayer;
+
+#[cfg(feature = "fault-injection")]
+impl<S> tower::Layer<S> for RpcDelayLayer {
+    type Service = RpcDelay<S>;
+
+    fn layer(&self, inner: S) -> RpcDelay<S> {
+        RpcDelay { inner }
+    }
+}
+
+/// Delays each response of the wrapped service by the configured RPC delay.
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone)]
+pub struct RpcDelay<S> {
+    inner: S,
+}
+
+#[cfg(feature = "fault-injection")]
+impl<S, Req> tower::Service<Req> for RpcDelay<S>
+where
+    S: tower::Service<Req>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let delay = Duration::from_millis(FAULTS.lock().unwrap().rpc_delay_ms);
+        let rsp = self.inner.call(req);
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            rsp.await
+        })
+    }
+}
@@ -0,0 +1,170 @@
+//! Fee estimation from the fullness of recent blocks.
+//!
+//! Fees are priced in units of verification weight, the same estimate of the
+//! cost of verifying a transaction that the mempool limits.  While recent
+//! blocks have had room to spare, every transaction is included regardless of
+//! its fee, so none is needed.  Once they're filling up, Tendermint's mempool
+//! favors the transactions paying the highest fees, so the estimate is the
+//! average fee per unit of weight paid in those blocks.
+
+use penumbra_chain::params::ChainParams;
+use penumbra_proto::thin_wallet as pb;
+use penumbra_transaction::{Action, Transaction};
+use serde::Serialize;
+
+/// The number of recent blocks fees are estimated from.
+pub const RECENT_BLOCKS: u64 = 20;
+
+/// The average fullness of recent blocks, in basis points, above which a fee
+/// is needed for prompt inclusion.
+const CONGESTED_FULLNESS_BPS: u64 = 5_000;
+
+/// The number of each kind of action in a transaction.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransactionSkeleton {
+    pub spends: u64,
+    pub outputs: u64,
+    pub delegations: u64,
+    pub undelegations: u64,
+    pub redelegations: u64,
+    pub validator_definitions: u64,
+    pub denom_metadata: u64,
+    pub upgrade_proposals: u64,
+    pub dkg_dealings: u64,
+    pub dkg_complaints: u64,
+    pub encrypted_flows: u64,
+    /// The total number of decryption shares in the flow decryptions.
+    pub flow_decryption_shares: u64,
+}
+
+impl TransactionSkeleton {
+    /// Estimates the cost of verifying a transaction of this shape, counting
+    /// one unit for each signature or proof it contains.
+    ///
+    /// Registering metadata involves no signatures or proofs, so it's free.
+    pub fn weight(&self) -> u64 {
+        // One unit for the binding signature.
+        1
+            // A spend has both a spend auth signature and a proof.
+            + self.spends * 2
+            + self.outputs
+            + self.delegations
+            + self.undelegations
+            + self.redelegations
+            + self.validator_definitions
+            + self.upgrade_proposals
+            + self.dkg_dealings
+            + self.dkg_complaints
+            // An encrypted flow has a proof.
+            + self.encrypted_flows
+            // A decryption share has a proof for each limb.
+            + self.flow_decryption_shares * penumbra_crypto::flow::NUM_LIMBS as u64
+    }
+}
+
+impl From<&Transaction> for TransactionSkeleton {
+    fn from(transaction: &Transaction) -> Self {
+        let mut skeleton = TransactionSkeleton::default();
+        for action in &transaction.transaction_body.actions {
+            match action {
+                Action::Spend(_) => skeleton.spends += 1,
+                Action::Output(_) => skeleton.outputs += 1,
+                Action::Delegate(_) => skeleton.delegations += 1,
+                Action::Undelegate(_) => skeleton.undelegations += 1,
+                Action::Redelegate(_) => skeleton.redelegations += 1,
+                Action::ValidatorDefinition(_) => skeleton.validator_definitions += 1,
+                Action::DenomMetadata(_) => skeleton.denom_metadata += 1,
+                Action::UpgradeProposal(_) => skeleton.upgrade_proposals += 1,
+                Action::DkgDealing(_) => skeleton.dkg_dealings += 1,
+                Action::DkgComplaint(_) => skeleton.dkg_complaints += 1,
+                Action::EncryptedFlow(_) => skeleton.encrypted_flows += 1,
+                Action::FlowDecryption(decryption) => {
+                    skeleton.flow_decryption_shares += decryption.shares.len() as u64
+                }
+            }
+        }
+        skeleton
+    }
+}
+
+impl From<pb::TransactionSkeleton> for TransactionSkeleton {
+    fn from(msg: pb::TransactionSkeleton) -> Self {
+        TransactionSkeleton {
+            spends: msg.spends,
+            outputs: msg.outputs,
+            delegations: msg.delegations,
+            undelegations: msg.undelegations,
+            redelegations: msg.redelegations,
+            validator_definitions: msg.validator_definitions,
+            denom_metadata: msg.denom_metadata,
+            upgrade_proposals: msg.upgrade_proposals,
+            dkg_dealings: msg.dkg_dealings,
+            dkg_complaints: msg.dkg_complaints,
+            encrypted_flows: msg.encrypted_flows,
+            flow_decryption_shares: msg.flow_decryption_shares,
+        }
+    }
+}
+
+/// How much of a block's capacity was used, and what was paid for it.
+#[derive(Debug, Clone)]
+pub struct BlockLoad {
+    /// The number of transactions in the block, including rejected ones.
+    pub transactions: u64,
+    /// The total size of the transactions in the block.
+    pub bytes: u64,
+    /// The total verification weight of the applied transactions.
+    pub weight: u64,
+    /// The total fees paid by the applied transactions.
+    pub fees: u64,
+}
+
+impl BlockLoad {
+    /// The fraction of the block limits in `chain_params` used by the block,
+    /// in basis points.
+    fn fullness_bps(&self, chain_params: &ChainParams) -> u64 {
+        let fullness = |used: u64, limit: u64| {
+            (used as u128 * 10_000 / limit.max(1) as u128).min(10_000) as u64
+        };
+        fullness(self.transactions, chain_params.max_block_transactions)
+            .max(fullness(self.bytes, chain_params.max_block_bytes))
+    }
+}
+
+/// Estimates the fee a transaction shaped like `skeleton` needs to pay, given
+/// the `recent` blocks.
+pub fn estimate(
+    skeleton: &TransactionSkeleton,
+    recent: &[BlockLoad],
+    chain_params: &ChainParams,
+) -> pb::FeeEstimate {
+    let weight = skeleton.weight();
+    let blocks = recent.len() as u64;
+
+    let fullness_bps = recent
+        .iter()
+        .map(|block| block.fullness_bps(chain_params))
+        .sum::<u64>()
+        .checked_div(blocks)
+        .unwrap_or(0);
+    let (fees, total_weight) = recent.iter().fold((0u128, 0u128), |(fees, weight), block| {
+        (fees + block.fees as u128, weight + block.weight as u128)
+    });
+    // Round up, so that paying the estimate is never below the average.
+    let fee_per_weight =
+        u64::try_from((fees + total_weight.max(1) - 1) / total_weight.max(1)).unwrap_or(u64::MAX);
+
+    let fee = if fullness_bps > CONGESTED_FULLNESS_BPS {
+        weight.saturating_mul(fee_per_weight)
+    } else {
+        0
+    };
+
+    pb::FeeEstimate {
+        weight,
+        fee,
+        fee_per_weight,
+        blocks,
+        fullness_bps,
+    }
+}
@@ -0,0 +1,161 @@
+//! Aggregation and threshold decryption of encrypted delegation flows.
+//!
+//! The amounts of the encrypted delegations and undelegations performed in an
+//! epoch are encrypted to the group key established by the previous epoch's
+//! DKG round.  As they're committed, they're summed per validator and
+//! direction.  Once the epoch has ended, the participants in that round
+//! publish decryption shares of the sums during the following epoch, and at
+//! the end of the following epoch, if at least `threshold` participants did,
+//! the sums are decrypted and applied along with that epoch's transparent
+//! delegation changes.  The individual amounts are never decrypted.
+//!
+//! If too few participants publish decryption shares by then, the sums can't
+//! be decrypted yet.  They're carried forward: the participants may still
+//! publish decryption shares in later epochs, and the sums are tried again at
+//! the end of each epoch until they're decrypted and applied.
+
+use std::collections::BTreeMap;
+
+use penumbra_crypto::flow::{self, Ciphertext};
+use penumbra_stake::{FlowDecryption, FlowDirection, IdentityKey};
+
+use crate::dkg;
+
+/// The sums of a validator's encrypted flows in an epoch.
+#[derive(Debug, Clone, Default)]
+pub struct Aggregate {
+    /// The sum of the delegation tokens produced by delegations.
+    pub delegated: Ciphertext,
+    /// The number of delegations summed.
+    pub delegations: u64,
+    /// The sum of the delegation tokens consumed by undelegations.
+    pub undelegated: Ciphertext,
+    /// The number of undelegations summed.
+    pub undelegations: u64,
+}
+
+/// The sums of each validator's encrypted flows in an epoch.
+pub type Aggregates = BTreeMap<IdentityKey, Aggregate>;
+
+impl Aggregate {
+    /// Adds an encrypted flow to the sums.
+    pub fn add(&mut self, direction: FlowDirection, ciphertext: Ciphertext) {
+        match direction {
+            FlowDirection::Delegate => {
+                self.delegated = self.delegated + ciphertext;
+                self.delegations += 1;
+            }
+            FlowDirection::Undelegate => {
+                self.undelegated = self.undelegated + ciphertext;
+                self.undelegations += 1;
+            }
+        }
+    }
+}
+
+/// The summed ciphertexts to be decrypted, in the order their decryption
+/// shares are given in a [`FlowDecryption`], with the number of summands.
+fn ciphertexts(aggregates: &Aggregates) -> impl Iterator<Item = (&Ciphertext, u64)> {
+    aggregates.values().flat_map(|aggregate| {
+        [
+            (&aggregate.delegated, aggregate.delegations),
+            (&aggregate.undelegated, aggregate.undelegations),
+        ]
+    })
+}
+
+/// Checks a decryption of the flows summed in `aggregates` against the
+/// public key share of its sender in `round`, the DKG round that
+/// established the key the flows were encrypted to.
+pub fn check_decryption(
+    aggregates: &Aggregates,
+    round: &dkg::Round,
+    decryption: &FlowDecryption,
+) -> anyhow::Result<()> {
+    let index = round
+        .participant_index(&decryption.validator_identity)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "validator {} is not a participant in the DKG round of epoch {}",
+                decryption.validator_identity,
+                round.epoch_index
+            )
+        })?;
+    let public_key_share = round
+        .public_key_shares
+        .get(index as usize - 1)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "the DKG round of epoch {} didn't establish a key",
+                round.epoch_index
+            )
+        })?;
+
+    let expected = aggregates.len() * 2;
+    if decryption.shares.len() != expected {
+        return Err(anyhow::anyhow!(
+            "flow decryption has {} shares, but {} sums were encrypted in epoch {}",
+            decryption.shares.len(),
+            expected,
+            decryption.epoch_index
+        ));
+    }
+    for ((ciphertext, _), share) in ciphertexts(aggregates).zip(decryption.shares.iter()) {
+        share.verify(ciphertext, public_key_share)?;
+    }
+
+    Ok(())
+}
+
+/// Decrypts the flows summed in `aggregates` with the checked `decryptions`
+/// from participants in `round`, returning the net change to each validator's
+/// delegation token supply.
+pub fn decrypt(
+    aggregates: &Aggregates,
+    round: &dkg::Round,
+    decryptions: &[FlowDecryption],
+) -> anyhow::Result<BTreeMap<IdentityKey, i64>> {
+    let decryptions = decryptions
+        .iter()
+        .filter_map(|decryption| {
+            round
+                .participant_index(&decryption.validator_identity)
+                .map(|index| (index, decryption))
+        })
+        .take(round.threshold as usize)
+        .collect::<Vec<_>>();
+    if (decryptions.len() as u64) < round.threshold {
+        return Err(anyhow::anyhow!(
+            "only {} of the {} needed decryptions were submitted",
+            decryptions.len(),
+            round.threshold
+        ));
+    }
+
+    let mut amounts = Vec::with_capacity(aggregates.len() * 2);
+    for (i, (ciphertext, summands)) in ciphertexts(aggregates).enumerate() {
+        let shares = decryptions
+            .iter()
+            .map(|(index, decryption)| (*index, &decryption.shares[i]))
+            .collect::<Vec<_>>();
+        amounts.push(flow::decrypt(ciphertext, &shares, summands)?);
+    }
+
+    aggregates
+        .keys()
+        .zip(amounts.chunks(2))
+        .map(|(identity_key, amounts)| {
+            let change = i64::try_from(amounts[0])
+                .ok()
+                .zip(i64::try_from(amounts[1]).ok())
+                .and_then(|(delegated, undelegated)| delegated.checked_sub(undelegated))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "decrypted flows for validator {} are too large",
+                        identity_key
+                    )
+                })?;
+            Ok((identity_key.clone(), change))
+        })
+        .collect()
+}
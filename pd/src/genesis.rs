@@ -1,10 +1,14 @@
 use ark_ff::Zero;
 use decaf377::Fq;
-use penumbra_chain::params::ChainParams;
+use penumbra_chain::params::{
+    ChainParams, DEFAULT_MAX_BLOCK_BYTES, DEFAULT_MAX_BLOCK_TRANSACTIONS,
+    DEFAULT_NUM_RECENT_ANCHORS,
+};
 use penumbra_crypto::{asset, Address, Note, Value};
 use penumbra_proto::{genesis as pb, Protobuf};
 use penumbra_stake::Validator;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 /// A (transparent) genesis allocation.
 #[derive(Clone, Serialize, Deserialize)]
@@ -160,12 +164,29 @@ impl TryFrom<pb::GenesisAppState> for AppState {
 
 impl Protobuf<pb::GenesisAppState> for AppState {}
 
+/// A hash of the genesis state, identifying the network it starts.
+///
+/// This hashes the app state exactly as Tendermint delivers it in `InitChain`,
+/// rather than as pd parses it, so that it doesn't change when a later release
+/// parses the same genesis state differently, e.g. by defaulting a new field.
+pub fn hash(app_state_bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(app_state_bytes).into()
+}
+
 impl Default for AppState {
     fn default() -> Self {
         AppState {
             chain_params: ChainParams {
                 chain_id: "".to_string(),
                 epoch_duration: 8640,
+                num_recent_anchors: DEFAULT_NUM_RECENT_ANCHORS,
+                max_block_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS,
+                max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
+                fee_distribution_bps: 0,
+                epoch_duration_secs: 0,
+                max_validator_stake_bps: 0,
+                min_delegation_amount: 0,
+                action_activations: Default::default(),
             },
             allocations: Vec::default(),
             validators: Vec::default(),
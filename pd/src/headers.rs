@@ -0,0 +1,86 @@
+use anyhow::Result;
+use sqlx::{postgres::PgPoolOptions, query, Pool, Postgres};
+use tendermint_proto::Protobuf;
+use tendermint_rpc::{Client, HttpClient};
+
+use crate::state;
+
+/// Where to fetch signed headers from, and how many to keep.
+#[derive(Debug, Clone)]
+pub struct HeaderConfig {
+    /// The address of Tendermint's RPC endpoint.
+    pub tendermint_rpc: String,
+    /// The number of recent blocks whose signed headers are kept.
+    pub retain: u64,
+}
+
+/// Continuously stores the signed headers of recently committed blocks,
+/// fetched from Tendermint's RPC, so that they can be served to counterparty
+/// chains creating or updating a light client of this chain.
+///
+/// Tendermint only has the canonical commit for a block once the next block
+/// has been committed, so headers are stored one block behind the chain.
+/// Fetch failures are logged and retried after the next block.
+pub async fn store_headers(
+    database_uri: String,
+    state: state::Reader,
+    config: HeaderConfig,
+) -> Result<()> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_uri)
+        .await?;
+    let client = HttpClient::new(config.tendermint_rpc.as_str())?;
+    let mut height_rx = state.height_rx().clone();
+
+    let mut stored = query!("SELECT MAX(height) AS height FROM tendermint_headers")
+        .fetch_one(&pool)
+        .await?
+        .height
+        .map(|height| height as u64);
+
+    loop {
+        height_rx.changed().await?;
+        let height = height_rx.borrow().value();
+
+        let mut next = height.saturating_sub(config.retain).max(1);
+        if let Some(stored) = stored {
+            next = next.max(stored + 1);
+        }
+        for header_height in next..height {
+            if let Err(e) = store_header(&pool, &client, header_height).await {
+                tracing::warn!(?e, height = header_height, "failed to store signed header");
+                break;
+            }
+            stored = Some(header_height);
+        }
+
+        query!(
+            "DELETE FROM tendermint_headers WHERE height <= $1",
+            height.saturating_sub(config.retain) as i64
+        )
+        .execute(&pool)
+        .await?;
+    }
+}
+
+async fn store_header(pool: &Pool<Postgres>, client: &HttpClient, height: u64) -> Result<()> {
+    let rsp = client
+        .commit(tendermint::block::Height::try_from(height)?)
+        .await?;
+    if !rsp.canonical {
+        return Err(anyhow::anyhow!("the commit is not canonical yet"));
+    }
+
+    query!(
+        "INSERT INTO tendermint_headers (height, signed_header) VALUES ($1, $2)
+            ON CONFLICT (height) DO NOTHING",
+        height as i64,
+        Protobuf::<tendermint_proto::types::SignedHeader>::encode_vec(&rsp.signed_header)?,
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::debug!(height, "stored signed header");
+    Ok(())
+}
@@ -4,28 +4,53 @@
 #![recursion_limit = "512"]
 #![allow(clippy::clone_on_copy)]
 
+mod audit;
+mod backup;
+mod components;
 mod consensus;
 mod db;
+mod diff;
+mod dkg;
+mod fee;
+mod flow;
+mod headers;
 mod info;
+mod maintenance;
 mod mempool;
 mod pd_metrics;
 mod pending_block;
+mod pruning;
+mod reindex;
 mod request_ext;
+mod scheduler;
 mod snapshot;
+mod tx_report;
+mod validator_definition;
 mod verify;
 mod wallet;
 
+pub mod faults;
 pub mod genesis;
 pub mod state;
 pub mod testnet;
 
-pub use consensus::Consensus;
+pub use audit::audit_supply;
+pub use backup::{backup, import, upload_backups, UploadConfig};
+pub use components::{RESOLVED_HALTS, SUPPORTED_UPGRADES};
+pub use consensus::{Consensus, DOUBLE_SPEND_CODE};
+pub use diff::diff_state;
+pub use headers::{store_headers, HeaderConfig};
 pub use info::Info;
-pub use mempool::Mempool;
-pub use pd_metrics::register_all_metrics;
+pub use maintenance::{maintain_database, MaintenanceConfig};
+pub use mempool::{Mempool, MempoolConfig, ReplacementPolicy};
+pub use pd_metrics::{register_all_metrics, report_resource_metrics};
 use pending_block::PendingBlock;
+pub use pruning::{prune_jmt, PruningConfig};
+pub use reindex::{reindex, replay};
 use request_ext::RequestExt;
 pub use snapshot::Snapshot;
-
-/// The age limit, in blocks, on anchors accepted in transaction verification.
-pub const NUM_RECENT_ANCHORS: usize = 256;
+pub use tx_report::{verify_transaction, CheckResult, TransactionReport};
+pub use validator_definition::{
+    sign_definition, sign_upgrade_proposal, FundingStreamConfig, ValidatorConfig,
+};
+pub use verify::{start_verification_pool, ActionKind, VerificationPoolConfig};
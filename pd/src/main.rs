@@ -35,6 +35,30 @@ enum Command {
         /// The URI used to connect to the Postgres database.
         #[structopt(short, long)]
         database_uri: String,
+        /// Also apply every block to this second Postgres database, logging
+        /// any divergence from the primary one.
+        ///
+        /// This is for burning in a new database deployment before switching
+        /// to it; it must start out at the same height as the primary.
+        #[structopt(long)]
+        shadow_database_uri: Option<String>,
+        /// Seconds any single database statement may run before it's
+        /// cancelled, or 0 for no limit.
+        #[structopt(long, default_value = "30")]
+        db_statement_timeout: u64,
+        /// Seconds the consensus-critical writes of a block commit may take
+        /// before they're cancelled and retried, or 0 for no limit.
+        #[structopt(long, default_value = "120")]
+        db_commit_timeout: u64,
+        /// Milliseconds a block commit may take before it's logged and
+        /// counted as slow, while it's still running, or 0 to not report
+        /// slow commits.
+        #[structopt(long, default_value = "2000")]
+        commit_warn_threshold_ms: u64,
+        /// Also log the statements the database is running whenever a block
+        /// commit is slow.
+        #[structopt(long)]
+        commit_dump_statements: bool,
         /// Bind the services to this host.
         #[structopt(short, long, default_value = "127.0.0.1")]
         host: String,
@@ -50,6 +74,178 @@ enum Command {
         /// Bind the metrics endpoint to this port.
         #[structopt(short, long, default_value = "9000")]
         metrics_port: u16,
+        /// Seconds between measurements of the database and tree sizes
+        /// reported as metrics, or 0 to disable them.
+        #[structopt(long, default_value = "60")]
+        resource_metrics_interval: u64,
+        /// Refuse to start unless the database was initialized from this
+        /// Tendermint genesis file.
+        #[structopt(short, long, parse(from_os_str))]
+        genesis_file: Option<PathBuf>,
+        /// Continuously upload backups to this S3-compatible bucket.
+        #[structopt(long)]
+        backup_bucket: Option<String>,
+        /// The endpoint of the object storage service holding the backup bucket.
+        #[structopt(long, default_value = "https://s3.amazonaws.com")]
+        backup_endpoint: String,
+        /// The region of the backup bucket.
+        #[structopt(long, default_value = "us-east-1")]
+        backup_region: String,
+        /// The number of blocks between full snapshots uploaded to the backup bucket.
+        #[structopt(long, default_value = "1000")]
+        backup_snapshot_interval: u64,
+        /// Store the signed headers of this many recent blocks, for
+        /// counterparty chains' light clients.  Headers aren't stored if unset.
+        #[structopt(long)]
+        header_retention: Option<u64>,
+        /// Keep the JMT state of this many recent blocks, pruning the tree's
+        /// nodes that only older states contain, so that values and proofs can
+        /// only be fetched at the retained heights.  States whose signed
+        /// headers are stored are always kept.  All states are kept if unset.
+        #[structopt(long)]
+        state_retention: Option<u64>,
+        /// The address of Tendermint's RPC endpoint, to fetch signed headers from.
+        #[structopt(long, default_value = "http://127.0.0.1:26657")]
+        tendermint_rpc: String,
+        /// Seconds between database maintenance passes, or 0 to disable maintenance.
+        #[structopt(long, default_value = "21600")]
+        maintenance_interval: u64,
+        /// Also rebuild indexes on every this-many-th maintenance pass, or 0 to never do so.
+        #[structopt(long, default_value = "4")]
+        maintenance_reindex_every: u32,
+        /// The maximum total size, in bytes, of the transactions admitted to the mempool.
+        #[structopt(long, default_value = "67108864")]
+        mempool_max_bytes: u64,
+        /// The maximum total verification weight (signatures and proofs) of the
+        /// transactions admitted to the mempool.
+        #[structopt(long, default_value = "100000")]
+        mempool_max_weight: u64,
+        /// Let a transaction replace conflicting mempool transactions if it pays
+        /// a fee at least this many percent higher than each of theirs.  If
+        /// unset, conflicting transactions are always rejected.
+        #[structopt(long)]
+        mempool_replacement_fee_increase: Option<u64>,
+        /// The number of threads verifying transactions' proofs and
+        /// signatures, or 0 for one per CPU core.
+        #[structopt(long, default_value = "0")]
+        verification_threads: usize,
+        /// Pin each verification thread to its own CPU core.
+        #[structopt(long)]
+        verification_pin_cores: bool,
+        /// Compress the compact blocks stored for wallet sync.  Blocks already
+        /// stored are left as they are.
+        #[structopt(long)]
+        compress_compact_blocks: bool,
+    },
+
+    /// Rebuild all derived state by replaying the raw blocks stored in the database.
+    ///
+    /// This is for recovering from bugs in how pd derives its state, without
+    /// resyncing from the network.  pd must not be running while this is done.
+    Reindex {
+        /// The URI used to connect to the Postgres database.
+        #[structopt(short, long)]
+        database_uri: String,
+    },
+
+    /// Replay the raw blocks stored in one database into an empty scratch
+    /// database, checking that every app hash matches the recorded one.
+    ///
+    /// This is for validating changes to how pd executes blocks against an
+    /// existing chain before deploying them.  The source database is not
+    /// modified, but pd should not be writing to it while this is done.
+    Replay {
+        /// The URI used to connect to the Postgres database with the recorded blocks.
+        #[structopt(short, long)]
+        database_uri: String,
+        /// The URI used to connect to the empty Postgres database to replay into.
+        #[structopt(short, long)]
+        scratch_database_uri: String,
+        /// Stop after replaying this height, instead of the latest recorded block.
+        #[structopt(long)]
+        end_height: Option<u64>,
+    },
+
+    /// Compare the state in two databases, reporting the first height where
+    /// their app hashes differ and the JMT leaves and rows that differ there.
+    ///
+    /// This is for investigating a node whose app hash diverged from the rest
+    /// of the network.  Neither database is modified.
+    DiffState {
+        /// The URI used to connect to the first Postgres database.
+        #[structopt(short, long)]
+        database_uri: String,
+        /// The URI used to connect to the second Postgres database.
+        #[structopt(short, long)]
+        other_database_uri: String,
+        /// The maximum number of differing rows to log for each table.
+        #[structopt(long, default_value = "10")]
+        max_rows: usize,
+    },
+
+    /// Verify an encoded transaction without a running node, printing a
+    /// report of each check as JSON.
+    ///
+    /// This is for debugging rejected transactions.  The stateful checks are
+    /// only run if a database is given, against its latest committed state,
+    /// which isn't modified.  Exits with an error if any check fails.
+    VerifyTx {
+        /// Path to the encoded transaction.  It's read from stdin if unset.
+        #[structopt(short, long, parse(from_os_str))]
+        input_file: Option<PathBuf>,
+        /// Read the transaction as hex, rather than raw bytes.
+        #[structopt(long)]
+        hex: bool,
+        /// The URI used to connect to a Postgres database to run the stateful
+        /// checks against.
+        #[structopt(short, long)]
+        database_uri: Option<String>,
+    },
+
+    /// Generate a new validator identity key and consensus key.
+    ///
+    /// The identity key is written to `validator_signingkey.json`, and the
+    /// consensus key to `priv_validator_key.json` in the format Tendermint
+    /// expects.  Existing key files are never overwritten.
+    Keygen {
+        /// Directory to write the key files to.
+        #[structopt(short, long, parse(from_os_str))]
+        output_dir: PathBuf,
+    },
+
+    /// Manage a validator.
+    Validator(ValidatorCmd),
+
+    /// Check each validator's delegation token supply and voting power against
+    /// the history of delegation changes.
+    ///
+    /// pd should not be running while this is done.
+    AuditSupply {
+        /// The URI used to connect to the Postgres database.
+        #[structopt(short, long)]
+        database_uri: String,
+    },
+
+    /// Write a consistent snapshot of the application state to a compressed archive.
+    ///
+    /// This can be done while pd is running.
+    Backup {
+        /// The URI used to connect to the Postgres database.
+        #[structopt(short, long)]
+        database_uri: String,
+        /// Path to write the archive to.
+        #[structopt(short, long, parse(from_os_str))]
+        output_file: PathBuf,
+    },
+
+    /// Restore a snapshot written by `pd backup` into an empty database.
+    Import {
+        /// The URI used to connect to the Postgres database.
+        #[structopt(short, long)]
+        database_uri: String,
+        /// Path to the archive to restore.
+        #[structopt(short, long, parse(from_os_str))]
+        input_file: PathBuf,
     },
 
     /// Generates a directory structure containing necessary files to run a
@@ -61,6 +257,36 @@ enum Command {
         /// Number of blocks per epoch.
         #[structopt(short, long, default_value = "60")]
         epoch_duration: u64,
+        /// Length of each epoch in seconds of block time.  If set, this is
+        /// used instead of the number of blocks per epoch.
+        #[structopt(long, default_value = "0")]
+        epoch_duration_secs: u64,
+        /// Number of recent blocks whose note commitment tree roots are accepted as anchors.
+        #[structopt(long, default_value = "256")]
+        num_recent_anchors: u64,
+        /// Maximum number of transactions in a block.
+        #[structopt(long, default_value = "1000")]
+        max_block_transactions: u64,
+        /// Maximum total size, in bytes, of the transactions in a block.
+        #[structopt(long, default_value = "4194304")]
+        max_block_bytes: u64,
+        /// Share of each epoch's fees, in basis points, distributed to the
+        /// active validators rather than burned.
+        #[structopt(long, default_value = "0")]
+        fee_distribution_bps: u64,
+        /// Largest share of the total delegated stake, in basis points, that
+        /// a single validator may hold.  Zero means there is no cap.
+        #[structopt(long, default_value = "0")]
+        max_validator_stake_bps: u64,
+        /// Smallest amount of unbonded stake that can be delegated,
+        /// undelegated, or redelegated at once.
+        #[structopt(long, default_value = "0")]
+        min_delegation_amount: u64,
+        /// Height at which a kind of action becomes valid, as
+        /// `<action>=<height>`, such as `encrypted_flow=1000`.  Can be given
+        /// more than once.
+        #[structopt(long = "activate-action", parse(try_from_str = parse_action_activation))]
+        action_activations: Vec<(String, u64)>,
         /// Path to CSV file containing initial allocations.
         #[structopt(
             short,
@@ -89,6 +315,58 @@ enum Command {
     },
 }
 
+#[derive(Debug, StructOpt)]
+enum ValidatorCmd {
+    /// Sign a validator definition with the validator's identity key.
+    ///
+    /// The definition is read from a JSON or TOML file, and its sequence number
+    /// is incremented, both in the signed definition and in the file.  The
+    /// encoded definition is written to the output file, or printed as hex.
+    SignDefinition {
+        /// Path to the validator configuration to sign.
+        #[structopt(short, long, parse(from_os_str))]
+        file: PathBuf,
+        /// Path to the validator's identity signing key, as written by `pd keygen`.
+        #[structopt(short, long, parse(from_os_str))]
+        signing_key: PathBuf,
+        /// Path to write the encoded definition to.
+        #[structopt(short, long, parse(from_os_str))]
+        output_file: Option<PathBuf>,
+    },
+    /// Sign a vote to schedule an upgrade plan with the validator's identity key.
+    ///
+    /// Nodes halt at the plan's height once validators with more than two
+    /// thirds of the voting power have voted for it, and only carry on when
+    /// restarted with a release that supports the named upgrade, or for an
+    /// emergency halt, one that resolves it.  The encoded proposal is written
+    /// to the output file, or printed as hex.
+    SignUpgrade {
+        /// The name of the upgrade, as declared by the releases that support it.
+        #[structopt(short, long)]
+        name: String,
+        /// The height of the first block the upgraded release processes.
+        #[structopt(long)]
+        height: u64,
+        /// Vote for an emergency halt, which stops every release at the given
+        /// height until nodes are restarted with one that resolves it.
+        #[structopt(long)]
+        emergency_halt: bool,
+        /// The ID of the chain to vote on.
+        #[structopt(long)]
+        chain_id: String,
+        /// The vote's sequence number, which must be greater than that of
+        /// the validator's previous vote.
+        #[structopt(long)]
+        sequence_number: u64,
+        /// Path to the validator's identity signing key, as written by `pd keygen`.
+        #[structopt(short, long, parse(from_os_str))]
+        signing_key: PathBuf,
+        /// Path to write the encoded proposal to.
+        #[structopt(short, long, parse(from_os_str))]
+        output_file: Option<PathBuf>,
+    },
+}
+
 // Extracted from tonic's remote_addr implementation; we'd like to instrument
 // spans with the remote addr at the server level rather than at the individual
 // request level, but the hook available to do that gives us an http::Request
@@ -102,6 +380,15 @@ fn remote_addr(req: &http::Request<()>) -> Option<SocketAddr> {
         .and_then(|i| i.remote_addr())
 }
 
+/// Parses an `<action>=<height>` activation for `pd generate-testnet`.
+fn parse_action_activation(s: &str) -> anyhow::Result<(String, u64)> {
+    let (action, height) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected <action>=<height>, got {:?}", s))?;
+    let kind = action.parse::<pd::ActionKind>()?;
+    Ok((kind.to_string(), height.parse()?))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
@@ -111,10 +398,32 @@ async fn main() -> anyhow::Result<()> {
         Command::Start {
             host,
             database_uri,
+            shadow_database_uri,
+            db_statement_timeout,
+            db_commit_timeout,
+            commit_warn_threshold_ms,
+            commit_dump_statements,
             abci_port,
             light_wallet_port,
             thin_wallet_port,
             metrics_port,
+            resource_metrics_interval,
+            genesis_file,
+            backup_bucket,
+            backup_endpoint,
+            backup_region,
+            backup_snapshot_interval,
+            header_retention,
+            state_retention,
+            tendermint_rpc,
+            maintenance_interval,
+            maintenance_reindex_every,
+            mempool_max_bytes,
+            mempool_max_weight,
+            mempool_replacement_fee_increase,
+            verification_threads,
+            verification_pin_cores,
+            compress_compact_blocks,
         } => {
             tracing::info!(
                 ?host,
@@ -122,13 +431,130 @@ async fn main() -> anyhow::Result<()> {
                 ?abci_port,
                 ?light_wallet_port,
                 ?thin_wallet_port,
+                supported_upgrades = ?pd::SUPPORTED_UPGRADES,
+                resolved_halts = ?pd::RESOLVED_HALTS,
                 "starting pd"
             );
+            let mut verification_pool = pd::VerificationPoolConfig::default();
+            if verification_threads != 0 {
+                verification_pool.threads = verification_threads;
+            }
+            verification_pool.pin_cores = verification_pin_cores;
+            pd::start_verification_pool(verification_pool)?;
             // Initialize state
-            let (state_reader, state_writer) = pd::state::new(&database_uri).await?;
+            let timeouts = pd::state::Timeouts {
+                statement: (db_statement_timeout != 0)
+                    .then(|| std::time::Duration::from_secs(db_statement_timeout)),
+                commit: (db_commit_timeout != 0)
+                    .then(|| std::time::Duration::from_secs(db_commit_timeout)),
+            };
+            let (state_reader, mut state_writer) = pd::state::new(&database_uri).await?;
+            state_writer.set_timeouts(timeouts);
+            state_writer.set_commit_watchdog((commit_warn_threshold_ms != 0).then(|| {
+                pd::state::CommitWatchdog {
+                    threshold: std::time::Duration::from_millis(commit_warn_threshold_ms),
+                    dump_statements: commit_dump_statements,
+                }
+            }));
+            if compress_compact_blocks {
+                state_writer.set_compact_block_compression(pd::state::Compression::Deflate);
+            }
+
+            if let Some(genesis_file) = genesis_file {
+                // The app state is hashed as it appears in the file, which is
+                // how Tendermint delivers it in `InitChain`.
+                let genesis: tendermint::Genesis<Box<serde_json::value::RawValue>> =
+                    serde_json::from_slice(&std::fs::read(&genesis_file)?)?;
+                state_reader
+                    .check_chain_identity(
+                        genesis.chain_id.as_str(),
+                        Some(genesis.app_state.get().as_bytes()),
+                    )
+                    .await?;
+            }
+
+            if let Some(bucket) = backup_bucket {
+                let config = pd::UploadConfig {
+                    endpoint: backup_endpoint,
+                    region: backup_region,
+                    bucket,
+                    snapshot_interval: backup_snapshot_interval,
+                };
+                tracing::info!(?config, "uploading backups");
+                let uploads =
+                    pd::upload_backups(database_uri.clone(), state_reader.clone(), config);
+                tokio::spawn(async move {
+                    if let Err(e) = uploads.await {
+                        tracing::error!(?e, "stopped uploading backups");
+                    }
+                });
+            }
+
+            if let Some(retain) = header_retention {
+                let config = pd::HeaderConfig {
+                    tendermint_rpc,
+                    retain,
+                };
+                tracing::info!(?config, "storing signed headers");
+                let headers = pd::store_headers(database_uri.clone(), state_reader.clone(), config);
+                tokio::spawn(async move {
+                    if let Err(e) = headers.await {
+                        tracing::error!(?e, "stopped storing signed headers");
+                    }
+                });
+            }
+
+            if let Some(retain) = state_retention {
+                // Proofs against a state are only checkable by light clients
+                // that can get the signed header of its block.
+                let config = pd::PruningConfig {
+                    retain: retain.max(header_retention.unwrap_or(0)),
+                };
+                tracing::info!(?config, "pruning JMT states");
+                let pruning = pd::prune_jmt(database_uri.clone(), state_reader.clone(), config);
+                tokio::spawn(async move {
+                    if let Err(e) = pruning.await {
+                        tracing::error!(?e, "stopped pruning JMT states");
+                    }
+                });
+            }
+
+            if maintenance_interval != 0 {
+                let config = pd::MaintenanceConfig {
+                    interval: std::time::Duration::from_secs(maintenance_interval),
+                    reindex_every: maintenance_reindex_every,
+                };
+                let maintenance =
+                    pd::maintain_database(database_uri.clone(), state_reader.clone(), config);
+                tokio::spawn(async move {
+                    if let Err(e) = maintenance.await {
+                        tracing::error!(?e, "stopped database maintenance");
+                    }
+                });
+            }
 
-            let consensus = pd::Consensus::new(state_writer).await?;
-            let mempool = pd::Mempool::new(state_reader.clone());
+            let consensus = match shadow_database_uri {
+                Some(shadow_database_uri) => {
+                    tracing::info!(?shadow_database_uri, "writing to shadow database");
+                    let (_, mut shadow_writer) = pd::state::new(&shadow_database_uri).await?;
+                    shadow_writer.set_timeouts(timeouts);
+                    pd::Consensus::with_shadow(state_writer, shadow_writer).await?
+                }
+                None => pd::Consensus::new(state_writer).await?,
+            };
+            let mempool = pd::Mempool::new(
+                state_reader.clone(),
+                pd::MempoolConfig {
+                    max_bytes: mempool_max_bytes,
+                    max_weight: mempool_max_weight,
+                    replacement: match mempool_replacement_fee_increase {
+                        Some(min_increase_percent) => pd::ReplacementPolicy::HigherFee {
+                            min_increase_percent,
+                        },
+                        None => pd::ReplacementPolicy::Reject,
+                    },
+                },
+            );
             let info = pd::Info::new(state_reader.clone());
             let snapshot = pd::Snapshot {};
 
@@ -149,7 +575,12 @@ async fn main() -> anyhow::Result<()> {
                         Some(remote_addr) => tracing::error_span!("light_wallet", ?remote_addr),
                         None => tracing::error_span!("light_wallet"),
                     })
-                    .add_service(LightWalletServer::new(state_reader.clone()))
+                    .layer(pd::faults::rpc_layer())
+                    .add_service(
+                        LightWalletServer::new(state_reader.clone())
+                            .send_gzip()
+                            .accept_gzip(),
+                    )
                     .serve(
                         format!("{}:{}", host, light_wallet_port)
                             .parse()
@@ -162,7 +593,12 @@ async fn main() -> anyhow::Result<()> {
                         Some(remote_addr) => tracing::error_span!("thin_wallet", ?remote_addr),
                         None => tracing::error_span!("thin_wallet"),
                     })
-                    .add_service(ThinWalletServer::new(state_reader.clone()))
+                    .layer(pd::faults::rpc_layer())
+                    .add_service(
+                        ThinWalletServer::new(state_reader.clone())
+                            .send_gzip()
+                            .accept_gzip(),
+                    )
                     .serve(
                         format!("{}:{}", host, thin_wallet_port)
                             .parse()
@@ -181,6 +617,17 @@ async fn main() -> anyhow::Result<()> {
                 .expect("metrics service set up");
 
             pd::register_all_metrics();
+            if resource_metrics_interval != 0 {
+                let reporting = pd::report_resource_metrics(
+                    state_reader.clone(),
+                    std::time::Duration::from_secs(resource_metrics_interval),
+                );
+                tokio::spawn(async move {
+                    if let Err(e) = reporting.await {
+                        tracing::error!(?e, "stopped reporting resource metrics");
+                    }
+                });
+            }
 
             // TODO: better error reporting
             // We error out if either service errors, rather than keep running
@@ -190,6 +637,130 @@ async fn main() -> anyhow::Result<()> {
                 x = thin_wallet_server => x?.map_err(|e| anyhow::anyhow!(e))?,
             };
         }
+        Command::Reindex { database_uri } => {
+            tracing::info!(?database_uri, "reindexing pd state");
+            pd::reindex(&database_uri).await?;
+        }
+        Command::Replay {
+            database_uri,
+            scratch_database_uri,
+            end_height,
+        } => {
+            tracing::info!(?database_uri, ?scratch_database_uri, "replaying pd state");
+            pd::replay(&database_uri, &scratch_database_uri, end_height).await?;
+        }
+        Command::DiffState {
+            database_uri,
+            other_database_uri,
+            max_rows,
+        } => {
+            pd::diff_state(&database_uri, &other_database_uri, max_rows).await?;
+        }
+        Command::VerifyTx {
+            input_file,
+            hex,
+            database_uri,
+        } => {
+            let input = match input_file {
+                Some(input_file) => std::fs::read(input_file)?,
+                None => {
+                    let mut input = Vec::new();
+                    std::io::Read::read_to_end(&mut std::io::stdin(), &mut input)?;
+                    input
+                }
+            };
+            let tx_bytes = if hex {
+                hex::decode(String::from_utf8(input)?.trim())?
+            } else {
+                input
+            };
+
+            let report = pd::verify_transaction(&tx_bytes, database_uri.as_deref()).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.passed() {
+                return Err(anyhow::anyhow!("transaction failed verification"));
+            }
+        }
+        Command::Keygen { output_dir } => {
+            use penumbra_stake::IdentityKey;
+            use tendermint_config::PrivValidatorKey;
+
+            let identity_sk = SigningKey::<SpendAuth>::new(OsRng);
+            let identity_key = IdentityKey(VerificationKey::from(&identity_sk));
+            let consensus_sk =
+                tendermint::PrivateKey::Ed25519(ed25519_consensus::SigningKey::new(OsRng));
+            let consensus_pk = consensus_sk.public_key();
+            let priv_validator_key = PrivValidatorKey {
+                address: consensus_pk.into(),
+                pub_key: consensus_pk,
+                priv_key: consensus_sk,
+            };
+
+            std::fs::create_dir_all(&output_dir)?;
+            pd::testnet::write_new_file(
+                &output_dir.join("validator_signingkey.json"),
+                serde_json::to_string_pretty(&identity_sk)?.as_bytes(),
+            )?;
+            pd::testnet::write_new_file(
+                &output_dir.join("priv_validator_key.json"),
+                serde_json::to_string_pretty(&priv_validator_key)?.as_bytes(),
+            )?;
+
+            println!("Wrote keys to {}", output_dir.display());
+            println!("Identity key: {}", identity_key);
+            println!("Consensus key: {}", serde_json::to_string(&consensus_pk)?);
+        }
+        Command::Validator(ValidatorCmd::SignDefinition {
+            file,
+            signing_key,
+            output_file,
+        }) => {
+            let definition = pd::sign_definition(&file, &signing_key)?;
+            match output_file {
+                Some(output_file) => std::fs::write(output_file, definition)?,
+                None => println!("{}", hex::encode(definition)),
+            }
+        }
+        Command::Validator(ValidatorCmd::SignUpgrade {
+            name,
+            height,
+            emergency_halt,
+            chain_id,
+            sequence_number,
+            signing_key,
+            output_file,
+        }) => {
+            let plan: penumbra_transaction::action::UpgradePlan =
+                penumbra_proto::transaction::UpgradePlan {
+                    name,
+                    height,
+                    emergency_halt,
+                }
+                .try_into()?;
+            let proposal =
+                pd::sign_upgrade_proposal(plan, chain_id, sequence_number, &signing_key)?;
+            match output_file {
+                Some(output_file) => std::fs::write(output_file, proposal)?,
+                None => println!("{}", hex::encode(proposal)),
+            }
+        }
+        Command::AuditSupply { database_uri } => {
+            pd::audit_supply(&database_uri).await?;
+        }
+        Command::Backup {
+            database_uri,
+            output_file,
+        } => {
+            let manifest = pd::backup(&database_uri, &output_file).await?;
+            tracing::info!(?manifest, ?output_file, "finished backup");
+        }
+        Command::Import {
+            database_uri,
+            input_file,
+        } => {
+            let manifest = pd::import(&database_uri, &input_file).await?;
+            tracing::info!(?manifest, ?input_file, "finished import");
+        }
         Command::GenerateTestnet {
             num_validator_nodes,
             // TODO this config is gated on a "populate persistent peers"
@@ -198,6 +769,14 @@ async fn main() -> anyhow::Result<()> {
             // works.
             starting_ip: _,
             epoch_duration,
+            epoch_duration_secs,
+            num_recent_anchors,
+            max_block_transactions,
+            max_block_bytes,
+            fee_distribution_bps,
+            max_validator_stake_bps,
+            min_delegation_amount,
+            action_activations,
             allocations_input_file,
             validators_input_file,
             output_dir,
@@ -221,6 +800,14 @@ async fn main() -> anyhow::Result<()> {
                 num_validator_nodes > 0,
                 "must have at least one validator node"
             );
+            assert!(
+                fee_distribution_bps <= 10_000,
+                "can't distribute more than 100% of fees"
+            );
+            assert!(
+                max_validator_stake_bps <= 10_000,
+                "can't cap validators at more than 100% of the stake"
+            );
 
             let genesis_time = Time::from_unix_timestamp(
                 SystemTime::now()
@@ -242,13 +829,16 @@ async fn main() -> anyhow::Result<()> {
 
             // Parse validators from input file
             let validators = parse_validators_file(validators_input_file)?;
+            for signer in validators.iter().filter_map(|v| v.remote_signer.as_ref()) {
+                signer.validate()?;
+            }
 
             struct ValidatorKeys {
                 // Penumbra spending key and viewing key for this node.
                 pub validator_id_sk: SigningKey<SpendAuth>,
                 pub validator_id_vk: VerificationKey<SpendAuth>,
-                // Consensus key for tendermint.
-                pub validator_cons_sk: tendermint::PrivateKey,
+                // Consensus key for tendermint, unless it's held by a remote signer.
+                pub validator_cons_sk: Option<tendermint::PrivateKey>,
                 pub validator_cons_pk: tendermint::PublicKey,
                 // P2P auth key for tendermint.
                 pub node_key_sk: tendermint::PrivateKey,
@@ -257,15 +847,24 @@ async fn main() -> anyhow::Result<()> {
             }
             let mut validator_keys = Vec::<ValidatorKeys>::new();
             // Generate a keypair for each validator
-            for _ in 0..num_validator_nodes {
+            for n in 0..num_validator_nodes {
                 // Create spending key and viewing key for this node.
                 let validator_id_sk = SigningKey::<SpendAuth>::new(OsRng);
                 let validator_id_vk = VerificationKey::from(&validator_id_sk);
 
-                // generate consensus key for tendermint.
-                let validator_cons_sk =
-                    tendermint::PrivateKey::Ed25519(ed25519_consensus::SigningKey::new(OsRng));
-                let validator_cons_pk = validator_cons_sk.public_key();
+                // generate consensus key for tendermint, or use the one held by
+                // the node's remote signer.
+                let (validator_cons_sk, validator_cons_pk) =
+                    match validators.get(n).and_then(|v| v.remote_signer.as_ref()) {
+                        Some(signer) => (None, signer.consensus_key),
+                        None => {
+                            let sk = tendermint::PrivateKey::Ed25519(
+                                ed25519_consensus::SigningKey::new(OsRng),
+                            );
+                            let pk = sk.public_key();
+                            (Some(sk), pk)
+                        }
+                    };
 
                 // generate P2P auth key for tendermint.
                 let node_key_sk =
@@ -292,6 +891,14 @@ async fn main() -> anyhow::Result<()> {
                     chain_params: ChainParams {
                         chain_id: chain_id.clone(),
                         epoch_duration,
+                        num_recent_anchors,
+                        max_block_transactions,
+                        max_block_bytes,
+                        fee_distribution_bps,
+                        epoch_duration_secs,
+                        max_validator_stake_bps,
+                        min_delegation_amount,
+                        action_activations: action_activations.iter().cloned().collect(),
                     },
                     validators: validators
                         .iter()
@@ -302,7 +909,12 @@ async fn main() -> anyhow::Result<()> {
                                     // manually editing the genesis.json. Otherwise they
                                     // will be randomly generated keys.
                                     identity_key: IdentityKey(vk.validator_id_vk),
-                                    consensus_key: vk.validator_cons_pk,
+                                    // A validator with a remote signer must be defined with the
+                                    // key the signer holds, or its votes won't be counted.
+                                    consensus_key: v
+                                        .remote_signer
+                                        .as_ref()
+                                        .map_or(vk.validator_cons_pk, |signer| signer.consensus_key),
                                     name: v.name.clone(),
                                     website: v.website.clone(),
                                     description: v.description.clone(),
@@ -399,7 +1011,8 @@ async fn main() -> anyhow::Result<()> {
                 // Note that this isn't a re-implementation of the `Config` type from
                 // Tendermint (https://github.com/tendermint/tendermint/blob/6291d22f46f4c4f9121375af700dbdafa51577e7/config/config.go#L92)
                 // so if they change their defaults or the available fields, that won't be reflected in our template.
-                let tm_config = generate_tm_config(&node_name);
+                let remote_signer = validators.get(n).and_then(|v| v.remote_signer.as_ref());
+                let tm_config = generate_tm_config(&node_name, remote_signer);
                 let mut config_file_path = node_config_dir.clone();
                 config_file_path.push("config.toml");
                 println!(
@@ -426,39 +1039,51 @@ async fn main() -> anyhow::Result<()> {
                 let mut node_key_file = File::create(node_key_file_path)?;
                 node_key_file.write_all(serde_json::to_string_pretty(&node_key)?.as_bytes())?;
 
-                // Write this node's priv_validator_key.json
-                let address: Id = vk.validator_cons_pk.into();
+                // Write this node's priv_validator_key.json and initial
+                // validator state, unless its consensus key is held by a remote
+                // signer, which keeps its own state.  Without the key file,
+                // Tendermint can't fall back to signing with a local key.
+                if let Some(cons_sk) = &vk.validator_cons_sk {
+                    let address: Id = vk.validator_cons_pk.into();
 
-                // the underlying type doesn't implement Copy or Clone (for the best)
-                let priv_key = tendermint::PrivateKey::Ed25519(
-                    vk.validator_cons_sk.ed25519_signing_key().unwrap().clone(),
-                );
-                let priv_validator_key = PrivValidatorKey {
-                    address,
-                    pub_key: vk.validator_cons_pk,
-                    priv_key,
-                };
-                let mut priv_validator_key_file_path = node_config_dir.clone();
-                priv_validator_key_file_path.push("priv_validator_key.json");
-                println!(
-                    "Writing {} priv validator key file to: {}",
-                    &node_name,
-                    priv_validator_key_file_path.display()
-                );
-                let mut priv_validator_key_file = File::create(priv_validator_key_file_path)?;
-                priv_validator_key_file
-                    .write_all(serde_json::to_string_pretty(&priv_validator_key)?.as_bytes())?;
+                    // the underlying type doesn't implement Copy or Clone (for the best)
+                    let priv_key = tendermint::PrivateKey::Ed25519(
+                        cons_sk.ed25519_signing_key().unwrap().clone(),
+                    );
+                    let priv_validator_key = PrivValidatorKey {
+                        address,
+                        pub_key: vk.validator_cons_pk,
+                        priv_key,
+                    };
+                    let mut priv_validator_key_file_path = node_config_dir.clone();
+                    priv_validator_key_file_path.push("priv_validator_key.json");
+                    println!(
+                        "Writing {} priv validator key file to: {}",
+                        &node_name,
+                        priv_validator_key_file_path.display()
+                    );
+                    let mut priv_validator_key_file = File::create(priv_validator_key_file_path)?;
+                    priv_validator_key_file
+                        .write_all(serde_json::to_string_pretty(&priv_validator_key)?.as_bytes())?;
 
-                // Write the initial validator state:
-                let mut priv_validator_state_file_path = node_data_dir.clone();
-                priv_validator_state_file_path.push("priv_validator_state.json");
-                println!(
-                    "Writing {} priv validator state file to: {}",
-                    &node_name,
-                    priv_validator_state_file_path.display()
-                );
-                let mut priv_validator_state_file = File::create(priv_validator_state_file_path)?;
-                priv_validator_state_file.write_all(get_validator_state().as_bytes())?;
+                    // Write the initial validator state:
+                    let mut priv_validator_state_file_path = node_data_dir.clone();
+                    priv_validator_state_file_path.push("priv_validator_state.json");
+                    println!(
+                        "Writing {} priv validator state file to: {}",
+                        &node_name,
+                        priv_validator_state_file_path.display()
+                    );
+                    let mut priv_validator_state_file =
+                        File::create(priv_validator_state_file_path)?;
+                    priv_validator_state_file.write_all(get_validator_state().as_bytes())?;
+                } else {
+                    println!(
+                        "{} signs with the remote signer at {}",
+                        &node_name,
+                        remote_signer.expect("remote signer is configured").laddr
+                    );
+                }
 
                 // Write the validator's signing key:
                 let mut validator_signingkey_file_path = node_config_dir.clone();
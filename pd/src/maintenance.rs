@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::{postgres::PgPoolOptions, Executor};
+
+use crate::state;
+
+/// The tables that change with every block, and so accumulate dead rows and
+/// stale planner statistics fastest.
+const HOT_TABLES: &[&str] = &[
+    "blobs",
+    "jmt",
+    "jmt_stale_nodes",
+    "blocks",
+    "notes",
+    "unique_note_commitments",
+    "note_ciphertexts",
+    "nullifiers",
+    "unique_nullifiers",
+    "compact_blocks",
+    "raw_blocks",
+    "raw_transactions",
+    "transaction_results",
+    "block_stats",
+    "epoch_stats",
+    "validator_rates",
+    "delegation_changes",
+];
+
+/// How often to run database maintenance.
+#[derive(Debug, Clone)]
+pub struct MaintenanceConfig {
+    /// The time between maintenance passes.
+    pub interval: Duration,
+    /// Rebuild the hot tables' indexes on every `reindex_every`-th pass, or
+    /// never if zero.
+    pub reindex_every: u32,
+}
+
+/// Periodically vacuums, analyzes, and reindexes pd's hot tables.
+///
+/// The work is split up by table, and each piece is started right after a
+/// block has been fully written, so that it runs in the quiet period before the
+/// next block is committed rather than competing with it.
+pub async fn maintain_database(
+    database_uri: String,
+    state: state::Reader,
+    config: MaintenanceConfig,
+) -> Result<()> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_uri)
+        .await?;
+    let mut height_rx = state.height_rx().clone();
+
+    for pass in 1u32.. {
+        tokio::time::sleep(config.interval).await;
+        let reindex = config.reindex_every != 0 && pass % config.reindex_every == 0;
+        tracing::info!(pass, reindex, "starting database maintenance");
+
+        for table in HOT_TABLES {
+            height_rx.changed().await?;
+
+            // VACUUM and REINDEX CONCURRENTLY can't run inside a transaction, so
+            // these are sent as plain statements.  Failures are only logged, so
+            // that one bad table doesn't stop maintenance of the rest.
+            let vacuum = format!("VACUUM (ANALYZE) {}", table);
+            if let Err(e) = pool.execute(vacuum.as_str()).await {
+                tracing::warn!(?e, ?table, "failed to vacuum table");
+            }
+            if reindex {
+                height_rx.changed().await?;
+                let statement = format!("REINDEX TABLE CONCURRENTLY {}", table);
+                if let Err(e) = pool.execute(statement.as_str()).await {
+                    tracing::warn!(?e, ?table, "failed to reindex table");
+                }
+            }
+
+            tracing::debug!(?table, "finished table maintenance");
+        }
+
+        tracing::info!(pass, "finished database maintenance");
+    }
+
+    Ok(())
+}
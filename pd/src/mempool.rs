@@ -1,5 +1,5 @@
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     future::Future,
     pin::Pin,
     sync::Arc,
@@ -9,12 +9,11 @@ use std::{
 use anyhow::anyhow;
 use futures::FutureExt;
 use penumbra_crypto::Nullifier;
-use penumbra_proto::Protobuf;
-use penumbra_transaction::Transaction;
 use tendermint::{
     abci::{
-        request::CheckTx as CheckTxRequest, response::CheckTx as CheckTxResponse, MempoolRequest,
-        MempoolResponse,
+        request::{CheckTx as CheckTxRequest, CheckTxKind},
+        response::CheckTx as CheckTxResponse,
+        MempoolRequest, MempoolResponse,
     },
     block,
 };
@@ -22,11 +21,147 @@ use tokio::sync::{watch, Mutex as AsyncMutex};
 use tower_abci::BoxError;
 use tracing::Instrument;
 
-use crate::{state, verify::StatelessTransactionExt, RequestExt};
+use crate::{
+    fee, state,
+    verify::{decode_canonical, on_verification_pool, StatelessTransactionExt},
+    RequestExt,
+};
+
+/// The number of blocks for which an admitted transaction's id is remembered.
+const RECENT_BLOCKS: u64 = 100;
+/// The maximum number of transaction ids to remember, regardless of their age.
+const MAX_RECENT_IDS: usize = 100_000;
+
+/// The `CheckTx` response code for a transaction that was already admitted.
+const ALREADY_SEEN_CODE: u32 = 2;
+
+/// Node-local configuration of the transactions admitted to the mempool by `CheckTx`.
+#[derive(Clone, Copy, Debug)]
+pub struct MempoolConfig {
+    /// The maximum total size, in bytes, of the admitted transactions.
+    pub max_bytes: u64,
+    /// The maximum total verification weight of the admitted transactions.
+    pub max_weight: u64,
+    /// What to do with transactions that conflict with admitted ones.
+    pub replacement: ReplacementPolicy,
+}
+
+/// Decides whether a transaction may replace admitted transactions that spend
+/// some of the same nullifiers.
+#[derive(Clone, Copy, Debug)]
+pub enum ReplacementPolicy {
+    /// Reject every conflicting transaction.
+    Reject,
+    /// Replace the conflicting transactions if the new one pays a fee at least
+    /// `min_increase_percent` percent higher than each of theirs.
+    HigherFee { min_increase_percent: u64 },
+}
+
+impl ReplacementPolicy {
+    /// Returns whether a transaction paying `new_fee` may replace an admitted
+    /// transaction paying `old_fee`.
+    pub fn allows(&self, old_fee: u64, new_fee: u64) -> bool {
+        match self {
+            ReplacementPolicy::Reject => false,
+            ReplacementPolicy::HigherFee {
+                min_increase_percent,
+            } => {
+                new_fee > old_fee
+                    && new_fee as u128 * 100
+                        >= old_fee as u128 * (100 + *min_increase_percent as u128)
+            }
+        }
+    }
+}
+
+/// A transaction admitted to the mempool since the last block.
+#[derive(Debug)]
+struct AdmittedTx {
+    fee: u64,
+    bytes: u64,
+    weight: u64,
+    nullifiers: Vec<Nullifier>,
+}
+
+/// The transactions admitted to the mempool since the last block.
+#[derive(Debug, Default)]
+struct Admitted {
+    transactions: BTreeMap<[u8; 32], AdmittedTx>,
+    /// The id of the admitted transaction spending each nullifier.
+    nullifiers: BTreeMap<Nullifier, [u8; 32]>,
+    bytes: u64,
+    weight: u64,
+}
+
+impl Admitted {
+    fn insert(&mut self, id: [u8; 32], tx: AdmittedTx) {
+        for nf in &tx.nullifiers {
+            self.nullifiers.insert(nf.clone(), id);
+        }
+        self.bytes += tx.bytes;
+        self.weight += tx.weight;
+        self.transactions.insert(id, tx);
+    }
+
+    fn remove(&mut self, id: &[u8; 32]) {
+        if let Some(tx) = self.transactions.remove(id) {
+            for nf in &tx.nullifiers {
+                self.nullifiers.remove(nf);
+            }
+            self.bytes -= tx.bytes;
+            self.weight -= tx.weight;
+        }
+    }
+}
+
+/// The ids of recently admitted transactions, so that resubmissions can be
+/// rejected without verifying them again.
+#[derive(Debug, Default)]
+struct RecentIds {
+    ids: BTreeSet<[u8; 32]>,
+    /// The ids in the order they were admitted, with the height at the time.
+    order: VecDeque<(u64, [u8; 32])>,
+}
+
+impl RecentIds {
+    fn contains(&self, id: &[u8; 32]) -> bool {
+        self.ids.contains(id)
+    }
+
+    fn insert(&mut self, height: u64, id: [u8; 32]) {
+        if self.ids.insert(id) {
+            self.order.push_back((height, id));
+        }
+        while self.order.len() > MAX_RECENT_IDS {
+            self.pop();
+        }
+    }
+
+    /// Forgets the ids admitted more than [`RECENT_BLOCKS`] blocks before `height`.
+    fn expire(&mut self, height: u64) {
+        while matches!(self.order.front(), Some((admitted, _)) if admitted + RECENT_BLOCKS < height)
+        {
+            self.pop();
+        }
+    }
+
+    fn pop(&mut self) {
+        if let Some((_, id)) = self.order.pop_front() {
+            self.ids.remove(&id);
+        }
+    }
+}
+
+/// The error returned for a transaction that was already admitted.
+#[derive(thiserror::Error, Debug)]
+#[error("transaction {} was already seen", hex::encode(.0))]
+struct AlreadySeen([u8; 32]);
 
 #[derive(Clone, Debug)]
 pub struct Mempool {
-    nullifiers: Arc<AsyncMutex<BTreeSet<Nullifier>>>,
+    admitted: Arc<AsyncMutex<Admitted>>,
+    recent_ids: Arc<AsyncMutex<RecentIds>>,
+    config: MempoolConfig,
     state: state::Reader,
     // We keep our own copy of the height watcher rather than borrowing from our
     // state::Reader so we can mutate it while tracking height updates.
@@ -34,11 +169,14 @@ pub struct Mempool {
 }
 
 impl Mempool {
-    pub fn new(state: state::Reader) -> Self {
-        let nullifiers = Arc::new(AsyncMutex::new(Default::default()));
+    pub fn new(state: state::Reader, config: MempoolConfig) -> Self {
+        let admitted = Arc::new(AsyncMutex::new(Default::default()));
+        let recent_ids = Arc::new(AsyncMutex::new(Default::default()));
         let height_rx = state.height_rx().clone();
         Self {
-            nullifiers,
+            admitted,
+            recent_ids,
+            config,
             state,
             height_rx,
         }
@@ -51,20 +189,45 @@ impl Mempool {
     ///
     /// * All binding and auth sigs signatures verify (stateless),
     /// * All proofs verify (stateless and stateful),
-    /// * The transaction does not reveal nullifiers already revealed in another transaction
-    /// in the mempool or in the database,
+    /// * The transaction does not reveal nullifiers already revealed in the database, or in
+    /// another transaction in the mempool that the replacement policy doesn't let it replace,
+    /// * Admitting the transaction keeps the mempool within its size and weight limits.
+    ///
+    /// Before any of that, a new transaction that was already admitted recently
+    /// is rejected with a distinct response code, so that clients resubmitting
+    /// it don't cost us another verification.
     ///
     /// If a transaction does not pass these checks, we return a non-zero `CheckTx` response
     /// code, and the transaction will not be added into the mempool.
     ///
     /// We do not queue up any state changes into `PendingBlock` until `DeliverTx` where these
     /// checks are repeated.
-    async fn check_tx(&self, check_tx: CheckTxRequest) -> Result<(), anyhow::Error> {
+    ///
+    /// On success, returns the transaction's mempool priority, which is its fee.
+    /// ABCI 0.35 has no `PrepareProposal`, so we can't build blocks ourselves, but
+    /// Tendermint's prioritized (v1) mempool reaps transactions for proposals in
+    /// priority order and evicts the lowest-priority ones when it's full, which
+    /// gives fee-ordered blocks.
+    async fn check_tx(&self, check_tx: CheckTxRequest) -> Result<i64, anyhow::Error> {
         // Verify the transaction is well-formed...
-        let transaction = Transaction::decode(check_tx.tx)?;
+        let transaction = decode_canonical(&check_tx.tx)?;
         tracing::info!(?transaction, ?check_tx.kind);
+        let id = transaction.id();
+        let height = self.height_rx.borrow().value();
+        {
+            let mut recent_ids = self.recent_ids.lock().await;
+            recent_ids.expire(height);
+            // Rechecks are of transactions we admitted ourselves, so they're
+            // expected to have been seen.
+            if matches!(check_tx.kind, CheckTxKind::New) && recent_ids.contains(&id) {
+                return Err(AlreadySeen(id).into());
+            }
+        }
+        let fee = transaction.transaction_body.fee.0;
+        let bytes = check_tx.tx.len() as u64;
+        let weight = fee::TransactionSkeleton::from(&transaction).weight();
         // ... and that it is internally consistent ...
-        let transaction = transaction.verify_stateless()?;
+        let transaction = on_verification_pool(move || transaction.verify_stateless()).await??;
         // ... and that it is consistent with the existing chain state.
         let transaction = self.state.verify_stateful(transaction).await?;
 
@@ -74,27 +237,77 @@ impl Mempool {
         // ABCI++ and can control block proposal.  (At that time, we can allow
         // conflicting transactions in the mempool, but only include one of them
         // in a block.)
+        //
+        // The one exception is a replacement allowed by the replacement policy.
+        // We can't remove the replaced transactions from Tendermint's mempool,
+        // but we stop accounting for them here, so whichever of the conflicting
+        // transactions is included in a block first wins, and the others are
+        // rejected when they're rechecked or delivered.
 
         // There are two kinds of transaction checks, New and Recheck.  Rechecks
         // are done on any transactions still in the mempool following a block
-        // commit. Since we clear the mempool nullifier set on block commits (in
+        // commit. Since we clear the admitted transactions on block commits (in
         // the poll_ready implementation), we don't need to handle that case specially.
 
         // We need to check-and-insert the whole batch transactionally,
         // so we need to hold the lock for the whole check.
-        let mut nullifiers = self.nullifiers.lock().await;
+        let mut admitted = self.admitted.lock().await;
 
-        for nf in &transaction.spent_nullifiers {
-            if nullifiers.contains(nf) {
-                return Err(anyhow!("nullifier {:?} already spent in mempool", nf));
+        let conflicts = transaction
+            .spent_nullifiers
+            .iter()
+            .filter_map(|nf| admitted.nullifiers.get(nf).copied())
+            .collect::<BTreeSet<_>>();
+        for conflict in &conflicts {
+            let old_fee = admitted.transactions[conflict].fee;
+            if !self.config.replacement.allows(old_fee, fee) {
+                return Err(anyhow!(
+                    "transaction conflicts with mempool transaction {} (fee {}), and its fee of {} isn't enough to replace it",
+                    hex::encode(conflict),
+                    old_fee,
+                    fee
+                ));
             }
         }
 
-        for nf in transaction.spent_nullifiers {
-            nullifiers.insert(nf);
+        let (replaced_bytes, replaced_weight) = conflicts
+            .iter()
+            .map(|conflict| &admitted.transactions[conflict])
+            .fold((0, 0), |(bytes, weight), tx| {
+                (bytes + tx.bytes, weight + tx.weight)
+            });
+        if admitted.bytes - replaced_bytes + bytes > self.config.max_bytes {
+            return Err(anyhow!(
+                "mempool is full: admitting this {}-byte transaction would exceed the limit of {} bytes",
+                bytes,
+                self.config.max_bytes
+            ));
         }
+        if admitted.weight - replaced_weight + weight > self.config.max_weight {
+            return Err(anyhow!(
+                "mempool is full: admitting this transaction with weight {} would exceed the limit of {}",
+                weight,
+                self.config.max_weight
+            ));
+        }
+
+        for conflict in &conflicts {
+            tracing::info!(replaced = %hex::encode(conflict), "replacing mempool transaction");
+            admitted.remove(conflict);
+        }
+        admitted.insert(
+            id,
+            AdmittedTx {
+                fee,
+                bytes,
+                weight,
+                nullifiers: transaction.spent_nullifiers.into_iter().collect(),
+            },
+        );
 
-        Ok(())
+        self.recent_ids.lock().await.insert(height, id);
+
+        Ok(i64::try_from(fee).unwrap_or(i64::MAX))
     }
 }
 
@@ -106,14 +319,14 @@ impl tower::Service<MempoolRequest> for Mempool {
     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         // Check whether a new block has arrived since our last CheckTx request.
         if self.height_rx.has_changed()? {
-            // Wipe our mempool nullifier set.  Notice that this leaves any
-            // *clones* of the previous version of the mempool nullifier set
+            // Wipe our record of admitted transactions; Tendermint rechecks the
+            // ones still in its mempool, which admits them again.  Notice that
+            // this leaves any *clones* of the previous version of the record
             // unchanged, so any in-flight CheckTx requests will continue to use
             // the previous version.  This is so that we don't need to wait to
             // acquire a lock; it's fine because use of the old copy is more
-            // restrictive than use of the new copy (which has no nullifiers in
-            // it).
-            self.nullifiers = Arc::new(AsyncMutex::new(Default::default()));
+            // restrictive than use of the new copy (which is empty).
+            self.admitted = Arc::new(AsyncMutex::new(Default::default()));
             // Finally, mark the new height as having been seen.
             self.height_rx.borrow_and_update();
         }
@@ -127,9 +340,16 @@ impl tower::Service<MempoolRequest> for Mempool {
 
         async move {
             match mempool.check_tx(check_tx).await {
-                Ok(()) => Ok(MempoolResponse::CheckTx(CheckTxResponse::default())),
+                Ok(priority) => Ok(MempoolResponse::CheckTx(CheckTxResponse {
+                    priority,
+                    ..Default::default()
+                })),
                 Err(e) => Ok(MempoolResponse::CheckTx(CheckTxResponse {
-                    code: 1,
+                    code: if e.is::<AlreadySeen>() {
+                        ALREADY_SEEN_CODE
+                    } else {
+                        1
+                    },
                     log: e.to_string(),
                     ..Default::default()
                 })),
@@ -1,7 +1,51 @@
-use metrics::register_counter;
+use std::time::Duration;
+
+use anyhow::Result;
+use metrics::{gauge, register_counter, register_gauge, register_histogram};
+
+use crate::state;
 
 /// Registers all metrics tracked by `pd`.
 pub fn register_all_metrics() {
     register_counter!("node_spent_nullifiers_total");
     register_counter!("node_transactions_total");
+    register_counter!("node_shadow_divergences_total");
+    register_counter!("node_shadow_overflows_total");
+    register_counter!("node_jmt_nodes_pruned_total");
+    register_counter!("node_slow_commits_total");
+
+    register_gauge!("node_db_table_bytes");
+    register_gauge!("node_jmt_nodes");
+    register_gauge!("node_jmt_depth");
+    register_gauge!("node_nct_bytes");
+    register_gauge!("node_notes");
+    register_gauge!("node_nullifiers");
+    register_gauge!("node_shadow_stopped");
+
+    // Labeled by the kind of proof or signature checked.
+    register_histogram!("node_proof_verification_seconds");
+    register_histogram!("node_signature_verification_seconds");
+    register_histogram!("node_commit_seconds");
+}
+
+/// Periodically measures the size of pd's state and reports it as gauges, so
+/// that validators can plan capacity from metrics alone.
+pub async fn report_resource_metrics(state: state::Reader, interval: Duration) -> Result<()> {
+    loop {
+        match state.resource_usage().await {
+            Ok(usage) => {
+                for (table, bytes) in usage.table_bytes {
+                    gauge!("node_db_table_bytes", bytes as f64, "table" => table);
+                }
+                gauge!("node_jmt_nodes", usage.jmt_nodes as f64);
+                gauge!("node_jmt_depth", usage.jmt_depth as f64);
+                gauge!("node_nct_bytes", usage.nct_bytes as f64);
+                gauge!("node_notes", usage.notes as f64);
+                gauge!("node_nullifiers", usage.nullifiers as f64);
+            }
+            Err(e) => tracing::warn!(?e, "failed to measure resource usage"),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
 }
@@ -1,19 +1,31 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 
 use ark_ff::PrimeField;
+use bytes::Bytes;
 use decaf377::Fr;
 use penumbra_crypto::{
     asset, ka,
-    merkle::{Frontier, NoteCommitmentTree},
+    merkle::{Frontier, NoteCommitmentTree, TreeExt},
     note, Address, Fq, Note, Nullifier, One, Value,
 };
+use penumbra_proto::{
+    light_wallet::{CompactBlock, StateFragment},
+    thin_wallet::EpochSummary,
+};
 use penumbra_stake::{
-    BaseRateData, Epoch, IdentityKey, RateData, ValidatorState, ValidatorStatus,
+    BaseRateData, DkgComplaint, EncryptedFlow, Epoch, FlowDecryption, FundingStreams, IdentityKey,
+    RateData, Redelegate, SignedDkgDealing, ValidatorState, ValidatorStatus,
     STAKING_TOKEN_ASSET_ID,
 };
+use penumbra_transaction::action::{DenomMetadata, SignedUpgradeProposal};
+use tendermint::abci;
 use tracing::instrument;
 
-use crate::verify::{NoteData, PositionedNoteData, VerifiedTransaction};
+use crate::{
+    dkg,
+    scheduler::{Due, Scheduled, ScheduledAction},
+    verify::{AnchorVerdicts, NoteData, PositionedNoteData},
+};
 
 /// Stores pending state changes from transactions.
 #[derive(Debug, Clone)]
@@ -22,56 +34,191 @@ pub struct PendingBlock {
     pub note_commitment_tree: NoteCommitmentTree,
     /// Stores note commitments for convienience when updating the NCT.
     pub notes: BTreeMap<note::Commitment, PositionedNoteData>,
-    /// Nullifiers that were spent in this block.
-    pub spent_nullifiers: BTreeSet<Nullifier>,
+    /// Nullifiers that were spent in this block, with the ID of the
+    /// transaction that spent each of them.
+    pub spent_nullifiers: BTreeMap<Nullifier, [u8; 32]>,
     /// Records any updates to the token supply of some asset that happened in this block.
     pub supply_updates: BTreeMap<asset::Id, (asset::Denom, u64)>,
     /// Indicates the epoch the block belongs to.
     pub epoch: Option<Epoch>,
     /// Indicates the duration in blocks of each epoch.
     pub epoch_duration: u64,
+    /// Whether this is the last block of its epoch.
+    pub ends_epoch: bool,
+    /// If this is the last block of an epoch, the start time of the next
+    /// epoch, in seconds since the Unix epoch.
+    pub next_epoch_start_time: Option<i64>,
     /// If this is the last block of an epoch, base rates for the next epoch go here.
     pub next_base_rate: Option<BaseRateData>,
     /// If this is the last block of an epoch, validator rates for the next epoch go here.
     pub next_rates: Option<Vec<RateData>>,
     /// If this is the last block of an epoch, validator statuses for the next epoch go here.
     pub next_validator_statuses: Option<Vec<ValidatorStatus>>,
+    /// If this is the last block of an epoch, a summary of what was applied at its end.
+    pub epoch_summary: Option<EpochSummary>,
+    /// If this is the last block of an epoch, the outcome of its DKG round.
+    pub dkg_transcript: Option<dkg::Transcript>,
     /// The net delegations performed in this block per validator.
     pub delegation_changes: BTreeMap<IdentityKey, i64>,
+    /// The redelegations performed in this block, which are also included in
+    /// `delegation_changes`.
+    pub redelegations: Vec<Redelegate>,
+    /// The DKG dealings submitted in this block.
+    pub dkg_dealings: Vec<SignedDkgDealing>,
+    /// The DKG complaints submitted in this block.
+    pub dkg_complaints: Vec<DkgComplaint>,
+    /// The encrypted delegations and undelegations performed in this block.
+    pub encrypted_flows: Vec<EncryptedFlow>,
+    /// The flow decryptions submitted in this block.
+    pub flow_decryptions: Vec<FlowDecryption>,
+    /// The epochs whose encrypted flows were decrypted and applied at the end
+    /// of the epoch this block ends, if it ends one.
+    pub decrypted_flow_epochs: Vec<u64>,
+    /// The delegation tokens minted and burned in this block per validator,
+    /// for statistics.
+    pub delegation_volume: BTreeMap<IdentityKey, (u64, u64)>,
+    /// The commission paid to each validator's funding streams at the end of
+    /// the epoch this block ends, if it ends one.
+    pub commission: BTreeMap<IdentityKey, u64>,
+    /// The number of transactions applied in this block.
+    pub num_transactions: u64,
+    /// The total fees paid by the transactions applied in this block.
+    pub fees: u64,
+    /// The total verification weight of the transactions applied in this block.
+    pub weight: u64,
+    /// Denom metadata registered in this block, by asset.
+    pub denom_metadata: BTreeMap<asset::Id, DenomMetadata>,
+    /// Validators' votes for upgrade plans in this block, in delivery order.
+    pub upgrade_proposals: Vec<SignedUpgradeProposal>,
+    /// The actions scheduled for future blocks by this block, in the order
+    /// they were scheduled.
+    pub scheduled_actions: Vec<(Due, ScheduledAction)>,
+    /// The scheduled actions carried out at the start of this block.
+    pub completed_actions: Vec<Scheduled>,
+    /// The queued actions this block cancelled before they were due.
+    pub cancelled_actions: Vec<Scheduled>,
     /// The counter containing the number of rewards notes in the epoch. we need this to keep the
     /// blinding factor of the reward notes unique.
     reward_counter: u64,
     /// Records pending state changes to validators.
     pub validator_state_changes: BTreeMap<IdentityKey, ValidatorState>,
+    /// The BeginBlock request that started this block, if any (the genesis
+    /// block has none).
+    pub begin_block: Option<abci::request::BeginBlock>,
+    /// The raw bytes of every transaction delivered in this block, in order,
+    /// whether or not it passed verification.
+    pub raw_transactions: Vec<Bytes>,
+    /// The `DeliverTx` result code and log of each transaction in
+    /// `raw_transactions`.
+    pub transaction_results: Vec<(u32, String)>,
+    /// Whether each anchor referenced by this block's transactions is valid.
+    pub anchor_verdicts: AnchorVerdicts,
 }
 
 impl PendingBlock {
-    pub fn new(note_commitment_tree: NoteCommitmentTree, epoch_duration: u64) -> Self {
+    /// Creates an empty pending block, whose note commitment tree is set by
+    /// the shielded pool component.
+    pub fn new(epoch_duration: u64) -> Self {
         Self {
             height: None,
-            note_commitment_tree,
+            note_commitment_tree: NoteCommitmentTree::new(0),
             notes: BTreeMap::new(),
-            spent_nullifiers: BTreeSet::new(),
+            spent_nullifiers: BTreeMap::new(),
             supply_updates: BTreeMap::new(),
             epoch: None,
             epoch_duration,
+            ends_epoch: false,
+            next_epoch_start_time: None,
             next_base_rate: None,
             next_rates: None,
             next_validator_statuses: None,
+            epoch_summary: None,
+            dkg_transcript: None,
             delegation_changes: BTreeMap::new(),
+            redelegations: Vec::new(),
+            dkg_dealings: Vec::new(),
+            dkg_complaints: Vec::new(),
+            encrypted_flows: Vec::new(),
+            flow_decryptions: Vec::new(),
+            decrypted_flow_epochs: Vec::new(),
+            delegation_volume: BTreeMap::new(),
+            commission: BTreeMap::new(),
+            num_transactions: 0,
+            fees: 0,
+            weight: 0,
+            denom_metadata: BTreeMap::new(),
+            upgrade_proposals: Vec::new(),
+            scheduled_actions: Vec::new(),
+            completed_actions: Vec::new(),
+            cancelled_actions: Vec::new(),
             reward_counter: 0,
             validator_state_changes: BTreeMap::new(),
+            begin_block: None,
+            raw_transactions: Vec::new(),
+            transaction_results: Vec::new(),
+            anchor_verdicts: AnchorVerdicts::default(),
         }
     }
 
     /// We only get the height from ABCI in EndBlock, so this allows setting it in-place.
+    ///
+    /// Unless the block was already placed in a time-based epoch by
+    /// [`PendingBlock::set_timed_epoch`], its epoch is determined by its height.
     pub fn set_height(&mut self, height: u64) -> Epoch {
         self.height = Some(height);
+        if let Some(epoch) = &self.epoch {
+            return epoch.clone();
+        }
+
         let epoch = Epoch::from_height(height, self.epoch_duration);
+        if epoch.end_height().value() == height {
+            self.ends_epoch = true;
+            self.next_epoch_start_time = self
+                .begin_block
+                .as_ref()
+                .map(|begin_block| begin_block.header.time.unix_timestamp());
+        }
         self.epoch = Some(epoch.clone());
         epoch
     }
 
+    /// Places the block in a time-based epoch, given the index and start time
+    /// of the epoch following the last committed block, and the duration of
+    /// each epoch in seconds.  Must be called after `begin_block` is set.
+    ///
+    /// The block ends the epoch if its timestamp is at least the epoch's
+    /// duration past the epoch's start.  Epoch start times stay on a fixed
+    /// schedule from genesis, so that ending the epoch late doesn't delay
+    /// every later epoch; if the chain halted for longer than an epoch, the
+    /// epochs that would have started in the meantime are skipped.
+    pub fn set_timed_epoch(&mut self, index: u64, start_time: i64, epoch_duration_secs: u64) {
+        let time = self
+            .begin_block
+            .as_ref()
+            .expect("begin_block must be set")
+            .header
+            .time
+            .unix_timestamp();
+        let duration = epoch_duration_secs as i64;
+        let elapsed = time - start_time;
+
+        self.epoch = Some(Epoch { index, duration: 0 });
+        if elapsed >= duration {
+            self.ends_epoch = true;
+            self.next_epoch_start_time = Some(start_time + elapsed / duration * duration);
+        }
+    }
+
+    /// Schedules an action to be carried out at the start of a future block.
+    pub fn schedule(&mut self, due: Due, action: ScheduledAction) {
+        self.scheduled_actions.push((due, action));
+    }
+
+    /// Removes a queued action before it's due.
+    pub fn cancel(&mut self, scheduled: Scheduled) {
+        self.cancelled_actions.push(scheduled);
+    }
+
     /// Adds a reward output for a validator's funding stream.
     #[instrument(skip(self, destination), fields(destination = %destination))]
     pub fn add_validator_reward_note(&mut self, amount: u64, destination: Address) {
@@ -85,11 +232,15 @@ impl PendingBlock {
             asset_id: *STAKING_TOKEN_ASSET_ID,
         };
 
+        // Binding the blinding factor to the current note commitment tree
+        // root means a transaction can't create this note ahead of time: its
+        // own output would already have changed the root.
         let blinding_factor_input = blake2b_simd::Params::default()
             .personal(b"fundingstrm_note")
             .to_state()
             .update(&self.epoch.as_ref().unwrap().index.to_le_bytes())
             .update(&self.reward_counter.to_le_bytes())
+            .update(&self.note_commitment_tree.root2().to_bytes())
             .finalize();
 
         let note = Note::from_parts(
@@ -109,10 +260,18 @@ impl PendingBlock {
         let note_data = NoteData {
             ephemeral_key: esk.diversified_public(&note.diversified_generator()),
             encrypted_note,
+            encrypted_memo: None,
             transaction_id: [0; 32],
         };
 
-        self.note_commitment_tree.append(&commitment);
+        self.add_note(commitment, note_data);
+
+        self.reward_counter += 1;
+    }
+
+    /// Appends a new note to the note commitment tree.
+    pub fn add_note(&mut self, note_commitment: note::Commitment, data: NoteData) {
+        self.note_commitment_tree.append(&note_commitment);
 
         let position = self
             .note_commitment_tree
@@ -122,42 +281,73 @@ impl PendingBlock {
             // If there are no bridges, the tree is empty
             .unwrap_or(0u64);
 
-        self.notes.insert(
-            commitment,
-            PositionedNoteData {
-                position,
-                data: note_data,
-            },
-        );
-
-        self.reward_counter += 1;
+        self.notes
+            .insert(note_commitment, PositionedNoteData { position, data });
     }
 
-    /// Adds the state changes from a verified transaction.
-    pub fn add_transaction(&mut self, transaction: VerifiedTransaction) {
-        for (note_commitment, data) in transaction.new_notes {
-            self.note_commitment_tree.append(&note_commitment);
-
-            let position = self
-                .note_commitment_tree
-                .bridges()
-                .last()
-                .map(|b| b.frontier().position().into())
-                // If there are no bridges, the tree is empty
-                .unwrap_or(0u64);
-
-            self.notes
-                .insert(note_commitment, PositionedNoteData { position, data });
+    /// Distributes `amount` of the staking token among validators in proportion
+    /// to their voting power, splitting each validator's share among its
+    /// funding streams in proportion to their rates.
+    ///
+    /// Returns the amount actually paid out.  The shares of validators without
+    /// funding streams, and whatever is lost to rounding, are not paid out.
+    pub fn distribute_fees(&mut self, amount: u64, validators: &[(u64, FundingStreams)]) -> u64 {
+        let total_power = validators
+            .iter()
+            .map(|(voting_power, _)| *voting_power as u128)
+            .sum::<u128>();
+        if total_power == 0 {
+            return 0;
         }
 
-        // Collect the nullifiers in this transaction
-        for nullifier in transaction.spent_nullifiers {
-            self.spent_nullifiers.insert(nullifier);
+        let mut distributed = 0;
+        for (voting_power, funding_streams) in validators {
+            let share = (amount as u128 * *voting_power as u128 / total_power) as u64;
+            let total_rate = funding_streams
+                .as_ref()
+                .iter()
+                .map(|stream| stream.rate_bps as u128)
+                .sum::<u128>();
+            if total_rate == 0 {
+                continue;
+            }
+
+            for stream in funding_streams.as_ref() {
+                let reward = (share as u128 * stream.rate_bps as u128 / total_rate) as u64;
+                self.add_validator_reward_note(reward, stream.address);
+                distributed += reward;
+            }
         }
 
-        // Tally the delegation changes in this transaction
-        for (identity_key, delegation_change) in transaction.delegation_changes {
-            *self.delegation_changes.entry(identity_key).or_insert(0) += delegation_change;
+        distributed
+    }
+
+    /// Builds the [`CompactBlock`] describing this block's new notes and spent nullifiers.
+    pub fn compact_block(&self) -> CompactBlock {
+        let mut notes = self.notes.iter().collect::<Vec<_>>();
+        notes.sort_by_key(|(_, positioned_note)| positioned_note.position);
+
+        CompactBlock {
+            height: self.height.expect("height must be set") as u32,
+            fragments: notes
+                .into_iter()
+                .map(|(note_commitment, positioned_note)| StateFragment {
+                    note_commitment: Bytes::copy_from_slice(&<[u8; 32]>::from(*note_commitment)),
+                    ephemeral_key: Bytes::copy_from_slice(&positioned_note.data.ephemeral_key.0),
+                    encrypted_note: Bytes::copy_from_slice(&positioned_note.data.encrypted_note),
+                    encrypted_memo: positioned_note
+                        .data
+                        .encrypted_memo
+                        .as_ref()
+                        .map(|memo| Bytes::copy_from_slice(&memo.0))
+                        .unwrap_or_default(),
+                })
+                .collect(),
+            nullifiers: self
+                .spent_nullifiers
+                .keys()
+                .map(|nullifier| Bytes::copy_from_slice(&nullifier.to_bytes()))
+                .collect(),
         }
     }
 }
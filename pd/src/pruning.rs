@@ -0,0 +1,83 @@
+use anyhow::Result;
+use metrics::counter;
+use sqlx::{postgres::PgPoolOptions, query, Pool, Postgres};
+
+use crate::state;
+
+/// The most stale nodes deleted by one statement, so that pruning a long
+/// backlog doesn't hold one huge transaction open.
+const BATCH_SIZE: i64 = 10_000;
+
+/// How many recent states of the JMT to keep.
+#[derive(Debug, Clone)]
+pub struct PruningConfig {
+    /// The number of recent blocks whose JMT state is kept, and so can still
+    /// be read and proven against.
+    pub retain: u64,
+}
+
+/// Continuously deletes the JMT nodes that are only part of states older than
+/// the retained ones, as recorded in the tree's stale node index.
+///
+/// Nodes written before the stale node index was recorded are never pruned.
+/// Failures are logged and retried after the next block.
+pub async fn prune_jmt(
+    database_uri: String,
+    state: state::Reader,
+    config: PruningConfig,
+) -> Result<()> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_uri)
+        .await?;
+    let mut height_rx = state.height_rx().clone();
+
+    loop {
+        height_rx.changed().await?;
+        let height = height_rx.borrow().value();
+
+        // A node stale since version `v` is only part of the states before
+        // `v`, so it can go once the oldest retained state is at `v` or later.
+        // The latest state is always retained.
+        let oldest_retained = (height + 1).saturating_sub(config.retain.max(1));
+        match prune_stale_nodes(&pool, oldest_retained).await {
+            Ok(0) => {}
+            Ok(pruned) => {
+                tracing::debug!(pruned, oldest_retained, "pruned stale JMT nodes");
+                counter!("node_jmt_nodes_pruned_total", pruned);
+            }
+            Err(e) => tracing::warn!(?e, oldest_retained, "failed to prune stale JMT nodes"),
+        }
+    }
+}
+
+/// Deletes the nodes stale since `oldest_retained` or earlier, returning how
+/// many were deleted.
+async fn prune_stale_nodes(pool: &Pool<Postgres>, oldest_retained: u64) -> Result<u64> {
+    let mut pruned = 0;
+    loop {
+        // Each batch removes nodes from the stale node index and the tree
+        // together, so that an interrupted pass leaves no node unaccounted for.
+        let deleted = query!(
+            "WITH stale AS (
+                DELETE FROM jmt_stale_nodes WHERE (stale_since_version, node_key) IN (
+                    SELECT stale_since_version, node_key FROM jmt_stale_nodes
+                    WHERE stale_since_version <= $1
+                    LIMIT $2
+                )
+                RETURNING node_key
+            )
+            DELETE FROM jmt WHERE key IN (SELECT node_key FROM stale)",
+            oldest_retained as i64,
+            BATCH_SIZE
+        )
+        .execute(pool)
+        .await?
+        .rows_affected();
+        pruned += deleted;
+
+        if deleted < BATCH_SIZE as u64 {
+            return Ok(pruned);
+        }
+    }
+}
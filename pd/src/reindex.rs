@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::{postgres::PgPoolOptions, query, PgConnection};
+use tendermint::abci::{self, ConsensusRequest, ConsensusResponse};
+use tower::{Service, ServiceExt};
+
+use crate::{state, Consensus};
+
+/// Rebuilds all derived state in the database from the raw data recorded in it.
+///
+/// The recorded `InitChain` request and raw blocks are kept, every other table
+/// is cleared, and the recorded requests are then replayed through the
+/// consensus state machine, just as Tendermint delivered them.  The app hash
+/// produced at each height is checked against the one recorded before the
+/// reindex.
+pub async fn reindex(uri: &str) -> Result<()> {
+    let pool = PgPoolOptions::new().max_connections(1).connect(uri).await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+
+    let (app_hashes, last_height) = recorded_chain(&mut *pool.acquire().await?, None).await?;
+
+    tracing::info!(?last_height, "clearing derived tables");
+    let mut dbtx = pool.begin().await?;
+    query!(
+        "TRUNCATE
+            blocks,
+            jmt,
+            jmt_stale_nodes,
+            compact_blocks,
+            deferred_writes,
+            transaction_results,
+            block_stats,
+            epoch_stats,
+            epoch_summaries,
+            validator_epoch_stats,
+            validator_epoch_redelegations,
+            validator_set_snapshots,
+            validator_set_commitments,
+            dkg_rounds,
+            dkg_participants,
+            dkg_dealings,
+            dkg_complaints,
+            encrypted_flows,
+            flow_decryptions,
+            decrypted_flows,
+            notes,
+            note_ciphertexts,
+            unique_note_commitments,
+            nullifiers,
+            unique_nullifiers,
+            assets,
+            validators,
+            validator_fundingstreams,
+            base_rates,
+            validator_rates,
+            delegation_changes,
+            epoch_fees,
+            epochs,
+            denom_metadata,
+            upgrade_votes,
+            scheduled_actions,
+            unbonding_notes,
+            unbonding_nullifiers"
+    )
+    .execute(&mut dbtx)
+    .await?;
+    query!("DELETE FROM blobs WHERE id IN ('gc', 'nct')")
+        .execute(&mut dbtx)
+        .await?;
+    dbtx.commit().await?;
+
+    let (reader, writer) = state::new(uri).await?;
+    let mut consensus = Consensus::new(writer).await?;
+    let mut source = pool.acquire().await?;
+    replay_chain(
+        &mut source,
+        &reader,
+        &mut consensus,
+        &app_hashes,
+        last_height,
+    )
+    .await?;
+    drop(source);
+    pool.close().await;
+
+    tracing::info!(?last_height, "finished reindexing");
+
+    Ok(())
+}
+
+/// Re-executes the raw blocks recorded in one database into another, empty
+/// database, checking that the app hash at each height matches the recorded one.
+///
+/// Unlike [`reindex`], this leaves the source database untouched, so it can be
+/// used to check that changes to how pd executes blocks still reproduce an
+/// existing chain, before deploying them.  Blocks are replayed up to
+/// `end_height`, or up to the source's latest block if it's `None`.
+///
+/// The source is only read, from a single read-only snapshot, and isn't
+/// migrated: it must already have exactly the migrations this release has.
+pub async fn replay(source_uri: &str, scratch_uri: &str, end_height: Option<u64>) -> Result<()> {
+    if source_uri == scratch_uri {
+        return Err(anyhow!(
+            "the scratch database must be different from the source database"
+        ));
+    }
+
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(source_uri)
+        .await?;
+    let mut source = pool.begin().await?;
+    query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut source)
+        .await?;
+    check_migrations(&mut source).await?;
+    let (app_hashes, last_height) = recorded_chain(&mut source, end_height).await?;
+
+    let scratch_pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(scratch_uri)
+        .await?;
+    sqlx::migrate!("./migrations").run(&scratch_pool).await?;
+    let has_blocks = query!("SELECT height FROM blocks LIMIT 1")
+        .fetch_optional(&scratch_pool)
+        .await?
+        .is_some();
+    if has_blocks {
+        return Err(anyhow!(
+            "blocks can only be replayed into an empty database"
+        ));
+    }
+    scratch_pool.close().await;
+
+    let (scratch, writer) = state::new(scratch_uri).await?;
+    let mut consensus = Consensus::new(writer).await?;
+    replay_chain(
+        &mut source,
+        &scratch,
+        &mut consensus,
+        &app_hashes,
+        last_height,
+    )
+    .await?;
+    source.rollback().await?;
+    pool.close().await;
+
+    tracing::info!(?last_height, "replayed all blocks with matching app hashes");
+
+    Ok(())
+}
+
+/// Checks that the schema migrations applied to the database over `conn` are
+/// exactly the ones this release has, so that its raw data can be read without
+/// migrating it.
+async fn check_migrations(conn: &mut PgConnection) -> Result<()> {
+    let applied: BTreeMap<i64, (Vec<u8>, bool)> = sqlx::query_as::<_, (i64, Vec<u8>, bool)>(
+        "SELECT version, checksum, success FROM _sqlx_migrations",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context("the source database has no schema migrations recorded")?
+    .into_iter()
+    .map(|(version, checksum, success)| (version, (checksum, success)))
+    .collect();
+
+    let migrator = sqlx::migrate!("./migrations");
+    let mut mismatched = Vec::new();
+    for migration in migrator.iter() {
+        match applied.get(&migration.version) {
+            Some((checksum, true)) if checksum[..] == migration.checksum[..] => {}
+            _ => mismatched.push(migration.version),
+        }
+    }
+    mismatched.extend(
+        applied
+            .keys()
+            .copied()
+            .filter(|version| !migrator.iter().any(|m| m.version == *version)),
+    );
+    if !mismatched.is_empty() {
+        mismatched.sort_unstable();
+        return Err(anyhow!(
+            "the source database's schema migrations don't match this release's (differing versions: {:?}); \
+            migrate a copy of it with this release first",
+            mismatched
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the app hash recorded at each height up to `end_height`, and checks
+/// that the raw data needed to replay those blocks is present.
+///
+/// Returns the app hashes along with the last height to replay.
+async fn recorded_chain(
+    conn: &mut PgConnection,
+    end_height: Option<u64>,
+) -> Result<(BTreeMap<u64, Vec<u8>>, u64)> {
+    let app_hashes = query!("SELECT height, app_hash FROM blocks")
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.height as u64, row.app_hash))
+        .filter(|(height, _)| end_height.map_or(true, |end| *height <= end))
+        .collect::<BTreeMap<_, _>>();
+    let last_height = match app_hashes.keys().next_back() {
+        Some(height) => *height,
+        None => return Err(anyhow!("the database contains no blocks to replay")),
+    };
+
+    let has_init_chain = query!("SELECT id FROM blobs WHERE id = 'init_chain'")
+        .fetch_optional(&mut *conn)
+        .await?
+        .is_some();
+    if !has_init_chain {
+        return Err(anyhow!(
+            "no InitChain request was recorded, so the chain can't be replayed"
+        ));
+    }
+    let raw_blocks = query!(
+        r#"SELECT COUNT(*) AS "count!" FROM raw_blocks WHERE height BETWEEN 1 AND $1"#,
+        last_height as i64
+    )
+    .fetch_one(&mut *conn)
+    .await?
+    .count;
+    if raw_blocks != last_height as i64 {
+        return Err(anyhow!(
+            "raw data was only recorded for {} of {} blocks, so the chain can't be replayed",
+            raw_blocks,
+            last_height
+        ));
+    }
+
+    Ok((app_hashes, last_height))
+}
+
+/// Replays the InitChain request and raw blocks read over `source` through
+/// `consensus`, just as Tendermint delivered them, and waits for the last
+/// block to be fully written to `target`.
+async fn replay_chain(
+    source: &mut PgConnection,
+    target: &state::Reader,
+    consensus: &mut Consensus,
+    app_hashes: &BTreeMap<u64, Vec<u8>>,
+    last_height: u64,
+) -> Result<()> {
+    let init_chain = state::init_chain_request(&mut *source)
+        .await?
+        .expect("InitChain request was checked to be present");
+    match call(consensus, ConsensusRequest::InitChain(init_chain)).await? {
+        ConsensusResponse::InitChain(rsp) => check_app_hash(app_hashes, 0, &rsp.app_hash)?,
+        _ => unreachable!("InitChain requests get InitChain responses"),
+    }
+
+    for height in 1..=last_height {
+        let (begin_block, transactions) = state::raw_block(&mut *source, height)
+            .await?
+            .expect("raw blocks were checked to be present");
+
+        call(consensus, ConsensusRequest::BeginBlock(begin_block)).await?;
+        for tx in transactions {
+            call(
+                consensus,
+                ConsensusRequest::DeliverTx(abci::request::DeliverTx { tx }),
+            )
+            .await?;
+        }
+        call(
+            consensus,
+            ConsensusRequest::EndBlock(abci::request::EndBlock {
+                height: height as i64,
+            }),
+        )
+        .await?;
+        match call(consensus, ConsensusRequest::Commit).await? {
+            ConsensusResponse::Commit(rsp) => check_app_hash(app_hashes, height, &rsp.data)?,
+            _ => unreachable!("Commit requests get Commit responses"),
+        }
+
+        tracing::info!(?height, "replayed block");
+    }
+
+    // The height is only advanced once the last block's deferred writes are done.
+    let mut height_rx = target.height_rx().clone();
+    while height_rx.borrow().value() < last_height {
+        height_rx.changed().await?;
+    }
+
+    Ok(())
+}
+
+async fn call(consensus: &mut Consensus, req: ConsensusRequest) -> Result<ConsensusResponse> {
+    consensus
+        .ready()
+        .await
+        .map_err(|e| anyhow!(e))?
+        .call(req)
+        .await
+        .map_err(|e| anyhow!(e))
+}
+
+fn check_app_hash(app_hashes: &BTreeMap<u64, Vec<u8>>, height: u64, app_hash: &[u8]) -> Result<()> {
+    let recorded = &app_hashes[&height];
+    if recorded[..] != app_hash[..] {
+        return Err(anyhow!(
+            "replaying height {} produced app hash {}, but {} was recorded",
+            height,
+            hex::encode(app_hash),
+            hex::encode(recorded),
+        ));
+    }
+    Ok(())
+}
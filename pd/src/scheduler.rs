@@ -0,0 +1,124 @@
+//! The queue of state changes scheduled for future blocks.
+//!
+//! A component schedules an action by adding it to the pending block, and the
+//! action is recorded when the block is committed.  At the start of the first
+//! block at or after the height, or in the epoch, the action is due, the
+//! consensus worker hands it to every component's
+//! [`run_scheduled`](crate::components::Component::run_scheduled) before any
+//! of them begins the block.  Actions due in the same block are carried out in
+//! the order they were scheduled, so every node carries them out alike.
+//! A queued action can also be cancelled by a later block before it's due.
+
+use anyhow::{anyhow, Result};
+use penumbra_proto::Protobuf;
+use penumbra_transaction::action::UpgradePlan;
+
+use crate::{state, PendingBlock};
+
+#[cfg(test)]
+mod tests;
+
+/// When a scheduled action is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Due {
+    /// At the start of the block at this height.
+    Height(u64),
+    /// At the start of the first block of the epoch with this index.
+    Epoch(u64),
+}
+
+impl Due {
+    fn reached(&self, height: u64, epoch_index: u64) -> bool {
+        match self {
+            Due::Height(due_height) => *due_height <= height,
+            Due::Epoch(due_epoch) => *due_epoch <= epoch_index,
+        }
+    }
+}
+
+/// A state change scheduled for a future block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduledAction {
+    /// Halt for the upgrade, unless this release supports it.
+    UpgradeHalt(UpgradePlan),
+}
+
+impl ScheduledAction {
+    /// The name of the kind of action, stored in the `kind` column.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScheduledAction::UpgradeHalt(_) => "upgrade_halt",
+        }
+    }
+
+    /// Encodes the action's data, stored in the `data` column.
+    pub fn encode_data(&self) -> Vec<u8> {
+        match self {
+            ScheduledAction::UpgradeHalt(plan) => plan.encode_to_vec(),
+        }
+    }
+
+    /// Decodes an action from its kind and data.
+    pub fn decode(kind: &str, data: &[u8]) -> Result<Self> {
+        match kind {
+            "upgrade_halt" => Ok(ScheduledAction::UpgradeHalt(UpgradePlan::decode(data)?)),
+            kind => Err(anyhow!("unknown kind of scheduled action {:?}", kind)),
+        }
+    }
+}
+
+/// An action in the queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheduled {
+    /// The height of the block that scheduled the action.
+    pub height: u64,
+    /// The action's position among those scheduled by the same block.
+    pub position: u32,
+    pub due: Due,
+    pub action: ScheduledAction,
+}
+
+/// The actions that haven't been carried out yet, kept in memory so that
+/// finding the ones due in each block doesn't need the database.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// The queued actions, in the order they were scheduled.
+    queue: Vec<Scheduled>,
+}
+
+impl Scheduler {
+    pub async fn new(reader: &state::Reader) -> Result<Self> {
+        Ok(Self {
+            queue: reader.scheduled_actions().await?,
+        })
+    }
+
+    /// The actions due in the block at `height`, in the epoch with index
+    /// `epoch_index`, in the order they were scheduled.
+    pub fn due(&self, height: u64, epoch_index: u64) -> Vec<Scheduled> {
+        self.queue
+            .iter()
+            .filter(|scheduled| scheduled.due.reached(height, epoch_index))
+            .cloned()
+            .collect()
+    }
+
+    /// Updates the queue from the block about to be committed, removing the
+    /// actions it carried out or cancelled and adding those it scheduled.
+    pub fn commit(&mut self, pending_block: &PendingBlock) {
+        self.queue.retain(|scheduled| {
+            !pending_block.completed_actions.contains(scheduled)
+                && !pending_block.cancelled_actions.contains(scheduled)
+        });
+
+        let height = pending_block.height.expect("height must be set in Commit");
+        for (position, (due, action)) in pending_block.scheduled_actions.iter().enumerate() {
+            self.queue.push(Scheduled {
+                height,
+                position: position as u32,
+                due: *due,
+                action: action.clone(),
+            });
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use penumbra_transaction::action::UpgradePlan;
+
+use super::*;
+
+fn halt(name: &str, height: u64) -> ScheduledAction {
+    ScheduledAction::UpgradeHalt(UpgradePlan {
+        name: name.to_string(),
+        height,
+        emergency_halt: false,
+    })
+}
+
+fn block(height: u64) -> PendingBlock {
+    let mut pending_block = PendingBlock::new(10);
+    pending_block.height = Some(height);
+    pending_block
+}
+
+#[test]
+fn due_actions_are_returned_in_the_order_they_were_scheduled() {
+    let mut scheduler = Scheduler { queue: Vec::new() };
+
+    let mut first = block(1);
+    first.schedule(Due::Height(5), halt("a", 5));
+    first.schedule(Due::Epoch(1), halt("b", 10));
+    scheduler.commit(&first);
+    let mut second = block(2);
+    second.schedule(Due::Height(4), halt("c", 4));
+    scheduler.commit(&second);
+
+    assert!(scheduler.due(3, 0).is_empty());
+    let names = |due: Vec<Scheduled>| {
+        due.into_iter()
+            .map(|scheduled| match scheduled.action {
+                ScheduledAction::UpgradeHalt(plan) => plan.name,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(scheduler.due(4, 0)), ["c"]);
+    // Actions that came due at once are carried out by order of scheduling,
+    // not by when they were due.
+    assert_eq!(names(scheduler.due(10, 1)), ["a", "b", "c"]);
+    // An action is still due past its height until it's carried out.
+    assert_eq!(names(scheduler.due(6, 0)), ["a", "c"]);
+}
+
+#[test]
+fn commit_removes_carried_out_and_cancelled_actions() {
+    let mut scheduler = Scheduler { queue: Vec::new() };
+
+    let mut first = block(1);
+    first.schedule(Due::Height(3), halt("a", 3));
+    first.schedule(Due::Height(5), halt("b", 5));
+    first.schedule(Due::Height(6), halt("c", 6));
+    scheduler.commit(&first);
+    let positions = scheduler
+        .queue
+        .iter()
+        .map(|scheduled| (scheduled.height, scheduled.position))
+        .collect::<Vec<_>>();
+    assert_eq!(positions, [(1, 0), (1, 1), (1, 2)]);
+
+    let mut third = block(3);
+    for scheduled in scheduler.due(3, 0) {
+        third.completed_actions.push(scheduled);
+    }
+    third.cancel(scheduler.queue[1].clone());
+    third.schedule(Due::Height(7), halt("d", 7));
+    scheduler.commit(&third);
+
+    let remaining = scheduler
+        .queue
+        .iter()
+        .map(|scheduled| {
+            (
+                scheduled.height,
+                scheduled.position,
+                scheduled.action.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(remaining, [(1, 2, halt("c", 6)), (3, 0, halt("d", 7))]);
+}
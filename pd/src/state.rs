@@ -1,33 +1,51 @@
-use anyhow::Result;
-use sqlx::postgres::PgPoolOptions;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use tokio::sync::watch;
 use tracing::instrument;
 
-mod jellyfish;
+mod anchors;
+mod backoff;
+mod blob;
+mod compact_block;
+mod data_migrations;
+mod error;
+pub(crate) mod jellyfish;
+mod partitions;
 mod reader;
+mod watchdog;
+mod witness_tree;
 mod writer;
 
-pub use reader::Reader;
-pub use writer::Writer;
+pub use anchors::AnchorWindow;
+pub use backoff::Backoff;
+pub use compact_block::Compression;
+use error::Result;
+pub use error::StateError;
+pub(crate) use partitions::create_partitions;
+pub(crate) use reader::{init_chain_request, raw_block};
+pub use reader::{ActiveStatement, NoteRecord, Reader, ResourceUsage};
+pub use watchdog::CommitWatchdog;
+pub use writer::{Timeouts, Writer};
+
+/// How long to keep retrying to connect to the database on startup.
+const MAX_CONNECT_WAIT: Duration = Duration::from_secs(300);
+/// How long to wait for a single connection to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[instrument]
 pub async fn new(uri: &str) -> Result<(Reader, Writer)> {
     // Maintain two connection pools, so that reader contention cannot starve
     // the writer.
-    let (reader_pool, writer_pool) = (
-        PgPoolOptions::new()
-            .max_connections(16)
-            .connect(uri)
-            .await?,
-        PgPoolOptions::new()
-            .max_connections(04)
-            .connect(uri)
-            .await?,
-    );
+    let (reader_pool, writer_pool) = (connect(uri, 16).await?, connect(uri, 4).await?);
     // Run migrations prior to building the Reader/Writer so
     // that all of their methods can assume valid db state
     tracing::info!("running migrations");
     sqlx::migrate!("./migrations").run(&writer_pool).await?;
+    data_migrations::run(&writer_pool).await?;
     tracing::info!("finished initializing state");
 
     // using evmap causes Problems because the read handle isn't Sync,
@@ -52,6 +70,7 @@ pub async fn new(uri: &str) -> Result<(Reader, Writer)> {
         height_rx,
         next_rate_data_rx,
         valid_anchors_rx,
+        witness_tree: Default::default(),
     };
 
     // Create a private reader instance for the writer's use
@@ -64,12 +83,44 @@ pub async fn new(uri: &str) -> Result<(Reader, Writer)> {
         private_reader,
         //tmp: writer_tmp,
         chain_params_tx,
-        height_tx,
+        height_tx: Arc::new(height_tx),
         next_rate_data_tx,
         valid_anchors_tx,
+        deferred_writes: None,
+        timeouts: Timeouts::default(),
+        commit_watchdog: None,
+        compact_block_compression: Compression::default(),
     };
 
     writer.init_caches().await?;
 
     Ok((reader, writer))
 }
+
+/// Connects a pool to the database, retrying with backoff while it's
+/// unreachable, so that pd can be started before its database is up.
+///
+/// Each connection is checked before it's handed out, so connections broken
+/// by a database restart or failover are discarded and replaced by new ones
+/// to the same address, rather than failing every request that uses them.
+async fn connect(uri: &str, max_connections: u32) -> Result<Pool<Postgres>> {
+    let started = Instant::now();
+    let mut backoff = Backoff::default();
+    loop {
+        let error = match PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .test_before_acquire(true)
+            .connect(uri)
+            .await
+        {
+            Ok(pool) => return Ok(pool),
+            Err(error) => StateError::from(error),
+        };
+        if !error.is_transient() || started.elapsed() >= MAX_CONNECT_WAIT {
+            return Err(error);
+        }
+        tracing::warn!(%error, delay = ?backoff.delay(), "database is unavailable, retrying");
+        backoff.wait().await;
+    }
+}
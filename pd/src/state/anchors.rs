@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+use penumbra_crypto::merkle;
+
+/// The note commitment tree roots of the most recent blocks, which are the
+/// anchors transactions may be proven against.
+///
+/// Roots that have recently aged out of the window are kept for as many blocks
+/// again, so that transactions built against them can be rejected with a
+/// precise explanation without consulting the database.
+#[derive(Debug, Clone, Default)]
+pub struct AnchorWindow {
+    /// The number of most recent blocks whose roots are valid anchors.
+    size: u64,
+    /// The root at the end of each retained block, newest first.
+    roots: VecDeque<(u64, merkle::Root)>,
+}
+
+impl AnchorWindow {
+    /// Creates a window of the given size from the roots at the end of the
+    /// most recent blocks, newest first.
+    pub fn new(size: u64, roots: impl IntoIterator<Item = (u64, merkle::Root)>) -> Self {
+        let mut window = Self {
+            // The window always includes at least the anchor of the latest block.
+            size: size.max(1),
+            roots: roots.into_iter().collect(),
+        };
+        window.prune();
+        window
+    }
+
+    /// The number of roots retained, including the expired ones, for a window
+    /// of the given size.
+    pub fn retained(size: u64) -> usize {
+        2 * size.max(1) as usize
+    }
+
+    /// Records the root at the end of a newly committed block.
+    pub fn push(&mut self, height: u64, root: merkle::Root) {
+        self.roots.push_front((height, root));
+        self.prune();
+    }
+
+    /// The lowest and highest heights whose roots are valid anchors, or `None`
+    /// if no blocks have been committed.
+    pub fn heights(&self) -> Option<(u64, u64)> {
+        let (latest, _) = self.roots.front()?;
+        let oldest = self
+            .roots
+            .iter()
+            .take_while(|(height, _)| self.is_recent(*height, *latest))
+            .last()
+            .map(|(height, _)| *height)
+            .unwrap_or(*latest);
+        Some((oldest, *latest))
+    }
+
+    /// Returns whether the root is a valid anchor.
+    pub fn contains(&self, root: &merkle::Root) -> bool {
+        self.valid().any(|(_, valid)| valid == root)
+    }
+
+    /// Returns the most recent height at which the root was the note
+    /// commitment tree root, if it's among the retained roots.
+    pub fn last_height(&self, root: &merkle::Root) -> Option<u64> {
+        self.roots
+            .iter()
+            .find(|(_, retained)| retained == root)
+            .map(|(height, _)| *height)
+    }
+
+    /// The number of most recent blocks whose roots are valid anchors.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn valid(&self) -> impl Iterator<Item = &(u64, merkle::Root)> {
+        let latest = self.roots.front().map(|(height, _)| *height).unwrap_or(0);
+        self.roots
+            .iter()
+            .take_while(move |(height, _)| self.is_recent(*height, latest))
+    }
+
+    fn is_recent(&self, height: u64, latest: u64) -> bool {
+        height + self.size > latest
+    }
+
+    fn prune(&mut self) {
+        self.roots.truncate(Self::retained(self.size));
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// The delay before the first retry.
+const INITIAL_DELAY: Duration = Duration::from_millis(100);
+/// The longest delay between retries.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Exponentially increasing delays between retries of an operation that
+/// failed with a [transient](super::StateError::is_transient) error.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: INITIAL_DELAY,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before the next retry.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Sleeps until the next retry, doubling the delay before the one after.
+    pub async fn wait(&mut self) {
+        tokio::time::sleep(self.delay).await;
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+    }
+}
@@ -0,0 +1,62 @@
+//! Encodings of the values stored in the `blobs` table.
+//!
+//! Each blob is prefixed with a version byte, so that changing the encoding
+//! of a stored structure doesn't make existing databases unreadable: decoding
+//! dispatches on the version, and old versions either keep a decode path here
+//! or get rewritten by a data migration.  Blobs written before versioning was
+//! introduced were rewritten as version 1 by the `version_blobs` migration.
+//!
+//! The recorded `InitChain` request isn't included: it's stored as protobuf,
+//! which already has its own rules for evolving compatibly.
+
+use anyhow::{anyhow, Context, Result};
+use penumbra_crypto::merkle::NoteCommitmentTree;
+
+use crate::genesis;
+
+/// The note commitment tree, encoded with `bincode`.
+const NCT_V1: u8 = 1;
+/// The genesis configuration, encoded as JSON.
+const GENESIS_V1: u8 = 1;
+
+pub(super) fn encode_nct(nct: &NoteCommitmentTree) -> Result<Vec<u8>> {
+    let mut data = vec![NCT_V1];
+    bincode::serialize_into(&mut data, nct)?;
+    Ok(data)
+}
+
+pub(super) fn decode_nct(data: &[u8]) -> Result<NoteCommitmentTree> {
+    match split_version(data)? {
+        (NCT_V1, data) => {
+            bincode::deserialize(data).context("Could not parse saved note commitment tree")
+        }
+        (version, _) => Err(anyhow!(
+            "unknown note commitment tree encoding version {}",
+            version
+        )),
+    }
+}
+
+pub(super) fn encode_genesis(genesis_config: &genesis::AppState) -> Result<Vec<u8>> {
+    let mut data = vec![GENESIS_V1];
+    serde_json::to_writer(&mut data, genesis_config)?;
+    Ok(data)
+}
+
+pub(super) fn decode_genesis(data: &[u8]) -> Result<genesis::AppState> {
+    match split_version(data)? {
+        (GENESIS_V1, data) => {
+            serde_json::from_slice(data).context("Could not parse saved genesis config")
+        }
+        (version, _) => Err(anyhow!(
+            "unknown genesis config encoding version {}",
+            version
+        )),
+    }
+}
+
+fn split_version(data: &[u8]) -> Result<(u8, &[u8])> {
+    data.split_first()
+        .map(|(version, data)| (*version, data))
+        .ok_or_else(|| anyhow!("stored blob is empty"))
+}
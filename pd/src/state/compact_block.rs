@@ -0,0 +1,80 @@
+//! The encoding of the compact blocks stored in the `compact_blocks` table.
+//!
+//! Most of a compact block is the ciphertexts and memos of its notes, which are
+//! already stored in `note_ciphertexts`, so only the rest of the block, its
+//! framing, is stored here: the height, the nullifiers, and the note
+//! commitments.  The ciphertexts are filled back in when the block is served.
+
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+use penumbra_proto::{light_wallet::CompactBlock, Message};
+
+/// How the framing of the stored compact blocks is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// zlib-wrapped DEFLATE.
+    Deflate,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
+impl Compression {
+    /// The code stored in the `compression` column.
+    pub(super) fn code(self) -> i16 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+        }
+    }
+
+    fn from_code(code: i16) -> Result<Self> {
+        match code {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            code => Err(anyhow!("unknown compact block compression {}", code)),
+        }
+    }
+}
+
+/// Encodes the framing of a compact block, leaving out its note ciphertexts.
+pub(super) fn encode(compact_block: &CompactBlock, compression: Compression) -> Result<Vec<u8>> {
+    let mut framing = compact_block.clone();
+    for fragment in &mut framing.fragments {
+        fragment.ephemeral_key.clear();
+        fragment.encrypted_note.clear();
+        fragment.encrypted_memo.clear();
+    }
+    let data = framing.encode_to_vec();
+
+    match compression {
+        Compression::None => Ok(data),
+        Compression::Deflate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+/// Decodes the framing of a stored compact block.  Its fragments' note
+/// ciphertexts are empty, unless it was stored before they were left out.
+pub(super) fn decode(compression: i16, data: &[u8]) -> Result<CompactBlock> {
+    let data = match Compression::from_code(compression)? {
+        Compression::None => data.to_vec(),
+        Compression::Deflate => {
+            let mut decompressed = Vec::new();
+            flate2::read::ZlibDecoder::new(data)
+                .read_to_end(&mut decompressed)
+                .context("Could not decompress saved compact block")?;
+            decompressed
+        }
+    };
+    CompactBlock::decode(data.as_slice()).context("Could not parse saved compact block")
+}
@@ -0,0 +1,240 @@
+//! Transformations of stored state that run once, when a node upgrades.
+//!
+//! Schema changes live in the SQL migrations, which sqlx runs first.  Changes
+//! that need application logic, like re-encoding blobs or backfilling a new
+//! table from existing data, are registered here instead.  Each one runs in its
+//! own database transaction, along with the `data_migrations` row recording
+//! that it was applied, so it runs exactly once.
+
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use penumbra_crypto::asset;
+use penumbra_proto::light_wallet::{CompactBlock, StateFragment};
+use sqlx::{query, Pool, Postgres, Transaction};
+use tendermint::abci;
+
+use super::compact_block::{self, Compression};
+use crate::genesis;
+
+type MigrationFn = for<'a> fn(&'a mut Transaction<'static, Postgres>) -> BoxFuture<'a, Result<()>>;
+
+struct DataMigration {
+    /// A unique, stable identifier for the migration.  Never rename one that
+    /// has been released.
+    id: &'static str,
+    run: MigrationFn,
+}
+
+/// All data migrations, in the order they're applied.  New migrations are added
+/// to the end.
+static MIGRATIONS: &[DataMigration] = &[
+    DataMigration {
+        id: "backfill_compact_blocks",
+        run: backfill_compact_blocks,
+    },
+    DataMigration {
+        id: "version_blobs",
+        run: version_blobs,
+    },
+    DataMigration {
+        id: "record_chain_identity",
+        run: record_chain_identity,
+    },
+    DataMigration {
+        id: "backfill_asset_display_units",
+        run: backfill_asset_display_units,
+    },
+    DataMigration {
+        id: "strip_compact_block_ciphertexts",
+        run: strip_compact_block_ciphertexts,
+    },
+];
+
+/// Applies any data migrations that haven't been applied yet.
+pub(super) async fn run(pool: &Pool<Postgres>) -> Result<()> {
+    let applied = query!("SELECT id FROM data_migrations")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.id)
+        .collect::<BTreeSet<_>>();
+
+    for migration in MIGRATIONS {
+        if applied.contains(migration.id) {
+            continue;
+        }
+
+        tracing::info!(id = migration.id, "applying data migration");
+        let mut dbtx = pool.begin().await?;
+        (migration.run)(&mut dbtx).await?;
+        query!("INSERT INTO data_migrations (id) VALUES ($1)", migration.id)
+            .execute(&mut dbtx)
+            .await?;
+        dbtx.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Builds the compact blocks for heights committed before they were precomputed.
+fn backfill_compact_blocks(dbtx: &mut Transaction<'static, Postgres>) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let heights = query!(
+            "SELECT height FROM blocks
+                WHERE height NOT IN (SELECT height FROM compact_blocks)
+                ORDER BY height ASC"
+        )
+        .fetch_all(&mut *dbtx)
+        .await?;
+
+        for row in heights {
+            let height = row.height;
+
+            // The note ciphertexts are left out of the stored block, and are
+            // filled in from `note_ciphertexts` when it's served.
+            let fragments = query!(
+                "SELECT note_commitment
+                    FROM notes
+                    WHERE height = $1
+                    ORDER BY position ASC",
+                height
+            )
+            .fetch_all(&mut *dbtx)
+            .await?
+            .into_iter()
+            .map(|row| StateFragment {
+                note_commitment: row.note_commitment.into(),
+                ..Default::default()
+            })
+            .collect();
+            let nullifiers = query!("SELECT nullifier FROM nullifiers WHERE height = $1", height)
+                .fetch_all(&mut *dbtx)
+                .await?
+                .into_iter()
+                .map(|row| row.nullifier.into())
+                .collect();
+
+            let compact_block = CompactBlock {
+                height: height as u32,
+                fragments,
+                nullifiers,
+            };
+            query!(
+                "INSERT INTO compact_blocks (height, data, compression) VALUES ($1, $2, $3)",
+                height,
+                compact_block::encode(&compact_block, Compression::None)?,
+                Compression::None.code()
+            )
+            .execute(&mut *dbtx)
+            .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Prefixes the note commitment tree and genesis config blobs, which were
+/// stored unversioned, with version 1 of their encodings (see `state::blob`).
+fn version_blobs(dbtx: &mut Transaction<'static, Postgres>) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        query!(r#"UPDATE blobs SET data = '\x01'::bytea || data WHERE id IN ('nct', 'gc')"#)
+            .execute(&mut *dbtx)
+            .await?;
+
+        Ok(())
+    })
+}
+
+/// Records the chain identity of databases initialized before it was pinned,
+/// from the recorded `InitChain` request, as genesis records it.
+fn record_chain_identity(dbtx: &mut Transaction<'static, Postgres>) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let init_chain = match query!("SELECT data FROM blobs WHERE id = 'init_chain'")
+            .fetch_optional(&mut *dbtx)
+            .await?
+        {
+            Some(row) => <abci::request::InitChain as tendermint_proto::Protobuf<
+                tendermint_proto::abci::RequestInitChain,
+            >>::decode_vec(&row.data)?,
+            // Either genesis hasn't been committed yet, and will record the
+            // identity itself, or it was committed before its request was
+            // recorded, in which case the app state it was hashed from is gone.
+            None => return Ok(()),
+        };
+
+        query!(
+            "INSERT INTO chain_identity (chain_id, genesis_hash) VALUES ($1, $2)
+            ON CONFLICT (chain_id) DO NOTHING",
+            init_chain.chain_id,
+            &genesis::hash(&init_chain.app_state_bytes)[..]
+        )
+        .execute(&mut *dbtx)
+        .await?;
+
+        Ok(())
+    })
+}
+
+/// Records the display unit of assets recorded before it was stored.
+fn backfill_asset_display_units(
+    dbtx: &mut Transaction<'static, Postgres>,
+) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let assets = query!("SELECT asset_id, denom FROM assets WHERE display_denom IS NULL")
+            .fetch_all(&mut *dbtx)
+            .await?;
+
+        for row in assets {
+            let denom = asset::REGISTRY
+                .parse_denom(&row.denom)
+                .ok_or_else(|| anyhow!("invalid denom {} in database", row.denom))?;
+            let display_unit = denom.default_unit();
+            query!(
+                "UPDATE assets SET display_denom = $2, display_exponent = $3 WHERE asset_id = $1",
+                row.asset_id,
+                display_unit.to_string(),
+                display_unit.exponent() as i32
+            )
+            .execute(&mut *dbtx)
+            .await?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Leaves the note ciphertexts out of the compact blocks stored before they
+/// were moved to `note_ciphertexts`.
+fn strip_compact_block_ciphertexts(
+    dbtx: &mut Transaction<'static, Postgres>,
+) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let heights = query!("SELECT height FROM compact_blocks ORDER BY height ASC")
+            .fetch_all(&mut *dbtx)
+            .await?;
+
+        // Blocks are re-encoded one at a time, so that the whole table is
+        // never held in memory.
+        for row in heights {
+            let stored = query!(
+                "SELECT data, compression FROM compact_blocks WHERE height = $1",
+                row.height
+            )
+            .fetch_one(&mut *dbtx)
+            .await?;
+            let compact_block = compact_block::decode(stored.compression, &stored.data)?;
+            query!(
+                "UPDATE compact_blocks SET data = $2, compression = $3 WHERE height = $1",
+                row.height,
+                compact_block::encode(&compact_block, Compression::None)?,
+                Compression::None.code()
+            )
+            .execute(&mut *dbtx)
+            .await?;
+        }
+
+        Ok(())
+    })
+}
@@ -0,0 +1,110 @@
+/// An error reading or writing the state, classified so that callers can
+/// decide whether to retry, to reject the request that caused it, or to halt.
+#[derive(thiserror::Error, Debug)]
+pub enum StateError {
+    /// A record that was required to exist is missing.
+    #[error("{0}")]
+    NotFound(String),
+    /// The stored state is invalid, or isn't the state that was expected, so
+    /// continuing would risk acting on it.
+    #[error("corrupt state: {0:#}")]
+    Corrupt(anyhow::Error),
+    /// The database couldn't be reached, or dropped the connection.
+    #[error("database connection error: {0}")]
+    Connection(sqlx::Error),
+    /// A write violated a database constraint, such as a uniqueness or
+    /// foreign key constraint.
+    #[error("database constraint violated: {0}")]
+    Constraint(sqlx::Error),
+    /// The database aborted a transaction because of a conflicting
+    /// concurrent one.
+    #[error("database serialization failure: {0}")]
+    Serialization(sqlx::Error),
+    /// An operation took longer than its configured timeout, and was
+    /// cancelled.
+    #[error("database operation timed out: {0}")]
+    Timeout(String),
+}
+
+/// The result of a state operation.
+pub type Result<T> = std::result::Result<T, StateError>;
+
+impl StateError {
+    /// Wraps an error found while decoding or encoding stored data.
+    pub(crate) fn corrupt(error: impl Into<anyhow::Error>) -> Self {
+        StateError::Corrupt(error.into())
+    }
+
+    /// Returns whether the operation may succeed if it's retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            StateError::Connection(_) | StateError::Serialization(_) | StateError::Timeout(_)
+        )
+    }
+}
+
+impl From<sqlx::Error> for StateError {
+    fn from(error: sqlx::Error) -> Self {
+        if matches!(error, sqlx::Error::RowNotFound) {
+            return StateError::NotFound("no matching row in the database".to_string());
+        }
+        if matches!(
+            error,
+            sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_)
+                | sqlx::Error::Protocol(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed
+        ) {
+            return StateError::Connection(error);
+        }
+
+        // Otherwise, classify database errors by their SQLSTATE code.
+        let code = match &error {
+            sqlx::Error::Database(e) => e.code().map(|code| code.into_owned()),
+            _ => None,
+        };
+        match code.as_deref() {
+            // Integrity constraint violations.
+            Some(code) if code.starts_with("23") => StateError::Constraint(error),
+            Some("40001" | "40P01") => StateError::Serialization(error),
+            // Statements and transactions cancelled by their timeouts.
+            Some("57014" | "25P03") => StateError::Timeout(error.to_string()),
+            // Connection exceptions, insufficient resources, and operator
+            // intervention, such as the server shutting down.
+            Some(code)
+                if code.starts_with("08") || code.starts_with("53") || code.starts_with("57P") =>
+            {
+                StateError::Connection(error)
+            }
+            // Anything else means the database doesn't hold what pd expects.
+            _ => StateError::Corrupt(error.into()),
+        }
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for StateError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        match error {
+            sqlx::migrate::MigrateError::Execute(error) => error.into(),
+            error => StateError::corrupt(error),
+        }
+    }
+}
+
+impl From<anyhow::Error> for StateError {
+    /// Recovers the classification of state errors that passed through
+    /// `anyhow`, such as the JMT's; any other error is about the stored data.
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<StateError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        match error.downcast::<sqlx::Error>() {
+            Ok(error) => error.into(),
+            Err(error) => StateError::Corrupt(error),
+        }
+    }
+}
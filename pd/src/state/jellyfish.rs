@@ -1,19 +1,30 @@
+use std::collections::BTreeSet;
+
 use anyhow::Result;
+use ark_ff::PrimeField;
+use decaf377::Fq;
 use futures::future::BoxFuture;
 use jmt::{
     define_hasher,
     hash::{CryptoHasher, DefaultHasher, HashValue},
     node_type::{LeafNode, Node, NodeKey},
-    NodeBatch, TreeReaderAsync, TreeWriterAsync, Value,
+    NodeBatch, StaleNodeIndex, TreeReaderAsync, TreeWriterAsync, Value,
 };
 use once_cell::sync::{Lazy, OnceCell};
+use penumbra_crypto::merkle;
 use sqlx::{query, Postgres};
 use tracing::instrument;
 
 use crate::state;
 
+#[derive(Debug)]
 pub enum Key {
     NoteCommitmentAnchor,
+    /// The commitment to the validator set of the epoch with this index.
+    ValidatorSet(u64),
+    /// The commitment to the transcript of the DKG round of the epoch with
+    /// this index.
+    DkgTranscript(u64),
 }
 
 impl Key {
@@ -24,8 +35,36 @@ impl Key {
                 state.update(b"");
                 state.finish()
             }
+            Key::ValidatorSet(epoch_index) => {
+                let mut state = ValidatorSetHasher::default();
+                state.update(&epoch_index.to_le_bytes());
+                state.finish()
+            }
+            Key::DkgTranscript(epoch_index) => {
+                let mut state = DkgTranscriptHasher::default();
+                state.update(&epoch_index.to_le_bytes());
+                state.finish()
+            }
         }
     }
+
+    /// Finds the key with the given hash, among the keys that can have been
+    /// written by the end of epoch `max_epoch_index`.
+    pub fn from_hash(key_hash: HashValue, max_epoch_index: u64) -> Option<Key> {
+        if key_hash == Key::NoteCommitmentAnchor.hash() {
+            return Some(Key::NoteCommitmentAnchor);
+        }
+        // The set for the next epoch is committed at the end of each epoch.
+        if let Some(key) = (0..=max_epoch_index + 1)
+            .find(|epoch_index| Key::ValidatorSet(*epoch_index).hash() == key_hash)
+            .map(Key::ValidatorSet)
+        {
+            return Some(key);
+        }
+        (0..=max_epoch_index)
+            .find(|epoch_index| Key::DkgTranscript(*epoch_index).hash() == key_hash)
+            .map(Key::DkgTranscript)
+    }
 }
 
 define_hasher! {
@@ -37,10 +76,120 @@ define_hasher! {
     )
 }
 
+define_hasher! {
+    (
+        ValidatorSetHasher,
+        VALIDATOR_SET_HASHER,
+        VALIDATOR_SET_SEED,
+        b"validator_set"
+    )
+}
+
+define_hasher! {
+    (
+        DkgTranscriptHasher,
+        DKG_TRANSCRIPT_HASHER,
+        DKG_TRANSCRIPT_SEED,
+        b"dkg_transcript"
+    )
+}
+
+/// One validator's entry in a validator set commitment.
+pub struct ValidatorSetEntry<'a> {
+    /// The protobuf encoding of the validator's identity key.
+    pub identity_key: &'a [u8],
+    pub consensus_key: &'a [u8],
+    pub voting_power: u64,
+    pub state: &'a str,
+}
+
+/// Computes the commitment to the validator set of an epoch that is stored in
+/// the JMT under [`Key::ValidatorSet`].
+///
+/// The commitment is the BLAKE2b-512 hash of the domain separator
+/// `penumbra.validator_set`, the little-endian epoch index, the previous
+/// epoch's commitment (or 32 zero bytes for the first), and each validator's
+/// entry in the order given, reduced to an `Fq`.  Each entry is the
+/// length-prefixed identity key, the length-prefixed consensus key, the
+/// little-endian voting power, and the length-prefixed state name, with
+/// lengths as little-endian `u64`s.  Chaining each commitment to the previous
+/// one lets a light client that trusts one epoch's validator set verify the
+/// sets of every later epoch.
+pub fn validator_set_commitment(
+    epoch_index: u64,
+    previous: Option<&merkle::Root>,
+    entries: &[ValidatorSetEntry<'_>],
+) -> merkle::Root {
+    fn write_prefixed(state: &mut blake2b_simd::State, bytes: &[u8]) {
+        state.update(&(bytes.len() as u64).to_le_bytes());
+        state.update(bytes);
+    }
+
+    let mut state = blake2b_simd::State::new();
+    state.update(b"penumbra.validator_set");
+    state.update(&epoch_index.to_le_bytes());
+    state.update(&previous.map(|root| root.to_bytes()).unwrap_or([0; 32]));
+    for entry in entries {
+        write_prefixed(&mut state, entry.identity_key);
+        write_prefixed(&mut state, entry.consensus_key);
+        state.update(&entry.voting_power.to_le_bytes());
+        write_prefixed(&mut state, entry.state.as_bytes());
+    }
+
+    merkle::Root(Fq::from_le_bytes_mod_order(state.finalize().as_bytes()))
+}
+
+/// Computes the commitment to the transcript of a finished DKG round that is
+/// stored in the JMT under [`Key::DkgTranscript`].
+///
+/// The commitment is the BLAKE2b-512 hash of the domain separator
+/// `penumbra.dkg_transcript`, the little-endian epoch index, the compressed
+/// group key (or 32 zero bytes if the round failed), and the protobuf encoding
+/// of each signed dealing in the order given, length-prefixed with its length
+/// as a little-endian `u64`, reduced to an `Fq`.
+pub fn dkg_transcript_commitment(
+    epoch_index: u64,
+    group_key: Option<&decaf377::Element>,
+    dealings: &[Vec<u8>],
+) -> merkle::Root {
+    let mut state = blake2b_simd::State::new();
+    state.update(b"penumbra.dkg_transcript");
+    state.update(&epoch_index.to_le_bytes());
+    state.update(&group_key.map(|key| key.compress().0).unwrap_or([0; 32]));
+    for dealing in dealings {
+        state.update(&(dealing.len() as u64).to_le_bytes());
+        state.update(dealing);
+    }
+
+    merkle::Root(Fq::from_le_bytes_mod_order(state.finalize().as_bytes()))
+}
+
 /// Wrapper struct used to implement [`jmt::TreeWriterAsync`] for a Postgres
 /// transaction, without violating the orphan rules.
 pub struct DbTx<'conn, 'tx>(pub &'tx mut sqlx::Transaction<'conn, Postgres>);
 
+impl<'conn, 'tx> DbTx<'conn, 'tx> {
+    /// Records the nodes that a newly written version of the tree superseded,
+    /// in the `jmt_stale_nodes` table that pruning works from.
+    #[instrument(skip(self, stale_node_index_batch))]
+    pub async fn write_stale_node_index(
+        &mut self,
+        stale_node_index_batch: &BTreeSet<StaleNodeIndex>,
+    ) -> Result<()> {
+        for index in stale_node_index_batch {
+            query!(
+                "INSERT INTO jmt_stale_nodes (stale_since_version, node_key) VALUES ($1, $2)",
+                index.stale_since_version as i64,
+                &index.node_key.encode()?
+            )
+            .execute(&mut *self.0)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'conn, 'tx, V> TreeWriterAsync<V> for DbTx<'conn, 'tx>
 where
     V: Value,
@@ -0,0 +1,61 @@
+//! The height-range partitions of the `notes` and `nullifiers` tables.
+//!
+//! The partitions for the heights stored when the tables were partitioned
+//! were created by their migration; the partitions for later heights are
+//! created here, one range ahead of the chain, so that no block has to wait
+//! for one to be created.
+//!
+//! Partitioning keeps the indexes written at the head of the chain small.
+//! Old partitions aren't detached or dropped, since witnesses are built from
+//! the notes at every height.  Spent nullifiers are looked up in
+//! `unique_nullifiers`, which isn't partitioned.
+
+use std::collections::BTreeSet;
+
+use sqlx::{query, Pool, Postgres};
+
+use super::Result;
+
+/// The number of heights in each partition.  This must match the migration
+/// that partitioned the tables.
+pub(super) const PARTITION_HEIGHTS: u64 = 100_000;
+
+/// The tables partitioned by height.
+const PARTITIONED_TABLES: &[&str] = &["notes", "nullifiers"];
+
+/// Creates any missing partitions for the heights up to and including the
+/// range after the one `height` is in.
+///
+/// Creating a partition locks its parent table, so this is done outside of
+/// the transactions writing blocks, and only when a partition is missing.
+pub(crate) async fn create_partitions(pool: &Pool<Postgres>, height: u64) -> Result<()> {
+    let existing =
+        query!(r#"SELECT relname::text AS "relname!" FROM pg_class WHERE relispartition"#)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|row| row.relname)
+            .collect::<BTreeSet<_>>();
+
+    for index in 0..=height / PARTITION_HEIGHTS + 1 {
+        for table in PARTITIONED_TABLES {
+            let partition = format!("{}_p{}", table, index);
+            if existing.contains(&partition) {
+                continue;
+            }
+
+            tracing::info!(%partition, "creating partition");
+            sqlx::query(&format!(
+                "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ({}) TO ({})",
+                partition,
+                table,
+                index * PARTITION_HEIGHTS,
+                (index + 1) * PARTITION_HEIGHTS
+            ))
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,34 +1,97 @@
 use std::{
-    collections::{BTreeMap, BTreeSet, VecDeque},
-    pin::Pin,
+    collections::{BTreeMap, BTreeSet},
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context};
 use async_stream::try_stream;
+use bytes::Bytes;
 use futures::stream::{Stream, StreamExt};
+use jmt::{hash::HashValue, node_type::NodeKey};
 use penumbra_chain::params::ChainParams;
 use penumbra_crypto::{
     asset,
-    merkle::{self, NoteCommitmentTree},
-    Address, FieldExt, Fq, Nullifier,
+    flow::Ciphertext,
+    merkle::{self, NoteCommitmentTree, TreeExt},
+    note, Address, FieldExt, Fq, Nullifier,
 };
 use penumbra_proto::{
-    chain,
+    chain, crypto,
     light_wallet::{CompactBlock, StateFragment},
-    thin_wallet::{Asset, TransactionDetail},
-    Protobuf,
+    thin_wallet::{
+        Asset, AssetSupply, BlockStats, CurrentEpoch, DelegationChange, DelegationVolume,
+        EpochStats, EpochSummary, KeyProof, TransactionByHashResponse, TransactionDetail,
+        ValidatorOverview, ValidatorSet, ValidatorSetEntry, ValidatorSetProof, ValidatorUptime,
+        ValidatorUptimes,
+    },
+    transaction, Message, Protobuf,
 };
 use penumbra_stake::{
-    BaseRateData, FundingStream, FundingStreams, IdentityKey, RateData, RateDataById, Validator,
+    BaseRateData, DkgComplaint, Epoch, FlowDecryption, FlowDirection, FundingStream,
+    FundingStreams, IdentityKey, RateData, RateDataById, SignedDkgDealing, Validator,
     ValidatorInfo, ValidatorState, ValidatorStateName, ValidatorStatus,
 };
-use sqlx::{query, query_as, Pool, Postgres};
-use tendermint::block;
-use tokio::sync::watch;
+use penumbra_transaction::action::{DenomMetadata, UpgradePlan};
+use sqlx::{query, query_as, PgConnection, Pool, Postgres};
+use tendermint::{abci, block};
+use tokio::sync::{watch, Mutex};
 use tracing::instrument;
 
-use crate::{db::schema, genesis};
+use super::{
+    anchors::AnchorWindow,
+    blob, compact_block,
+    error::{Result, StateError},
+    jellyfish,
+    witness_tree::WitnessTree,
+};
+use crate::{
+    components::BASE_REWARD_RATE,
+    db::schema,
+    dkg, fee, flow, genesis,
+    scheduler::{Due, Scheduled, ScheduledAction},
+};
+
+/// The size of the state stored by pd, for capacity planning.
+#[derive(Debug, Clone)]
+pub struct ResourceUsage {
+    /// The size on disk of each table, including its indexes.
+    pub table_bytes: Vec<(String, u64)>,
+    pub jmt_nodes: u64,
+    /// The length, in nibbles, of the longest path to any JMT node.
+    pub jmt_depth: u64,
+    /// The size of the serialized note commitment tree.
+    pub nct_bytes: u64,
+    pub notes: u64,
+    pub nullifiers: u64,
+}
+
+/// A statement the database is running, as listed when diagnosing a slow
+/// block commit.
+#[derive(Debug, Clone)]
+pub struct ActiveStatement {
+    /// The process ID of the backend running the statement.
+    pub pid: i32,
+    pub state: String,
+    /// What the backend is waiting on, if anything, as `type:event`.
+    pub wait_event: Option<String>,
+    /// How long the statement has been running.
+    pub running: Duration,
+    pub query: String,
+}
+
+/// A note stored in the state, with where it was included.
+#[derive(Debug, Clone)]
+pub struct NoteRecord {
+    /// The height of the block that included the note.
+    pub height: u64,
+    /// The note's position in the note commitment tree.
+    pub position: u64,
+    /// The ID of the transaction that created the note.
+    pub transaction_id: Vec<u8>,
+    pub fragment: StateFragment,
+}
 
 #[derive(Debug, Clone)]
 pub struct Reader {
@@ -37,7 +100,9 @@ pub struct Reader {
     pub(super) chain_params_rx: watch::Receiver<ChainParams>,
     pub(super) height_rx: watch::Receiver<block::Height>,
     pub(super) next_rate_data_rx: watch::Receiver<RateDataById>,
-    pub(super) valid_anchors_rx: watch::Receiver<VecDeque<merkle::Root>>,
+    pub(super) valid_anchors_rx: watch::Receiver<AnchorWindow>,
+    /// Shared by every clone, so that the tree is only built once.
+    pub(super) witness_tree: Arc<Mutex<WitnessTree>>,
 }
 
 impl Reader {
@@ -68,12 +133,12 @@ impl Reader {
         &self.next_rate_data_rx
     }
 
-    /// Returns a borrowed [`watch::Receiver`] for the latest set of valid anchors.
+    /// Returns a borrowed [`watch::Receiver`] for the latest window of valid anchors.
     ///
     /// This receiver can be used to access an in-memory copy of the latest data
     /// without accessing the database, but note the warning on
     /// [`watch::Receiver::borrow`] about potential deadlocks.
-    pub fn valid_anchors_rx(&self) -> &watch::Receiver<VecDeque<merkle::Root>> {
+    pub fn valid_anchors_rx(&self) -> &watch::Receiver<AnchorWindow> {
         &self.valid_anchors_rx
     }
 
@@ -81,7 +146,7 @@ impl Reader {
     pub async fn nullifier(&self, nullifier: Nullifier) -> Result<Option<schema::NullifiersRow>> {
         let mut conn = self.pool.acquire().await?;
         let nullifier_row = query!(
-            r#"SELECT height FROM nullifiers WHERE nullifier = $1 LIMIT 1"#,
+            r#"SELECT height FROM unique_nullifiers WHERE nullifier = $1"#,
             &<[u8; 32]>::from(nullifier.clone())[..]
         )
         .fetch_optional(&mut conn)
@@ -104,7 +169,7 @@ impl Reader {
         .fetch_optional(&mut conn)
         .await?
         {
-            bincode::deserialize(&data).context("Could not parse saved note commitment tree")?
+            blob::decode_nct(&data)?
         } else {
             NoteCommitmentTree::new(0)
         };
@@ -112,6 +177,110 @@ impl Reader {
         Ok(note_commitment_tree)
     }
 
+    /// Computes authentication paths for the given note commitments against the
+    /// note commitment tree as of the current height.
+    ///
+    /// The node only keeps the frontier of the committed tree, so the paths
+    /// come from an in-memory copy of the whole tree, which is built from the
+    /// stored notes on the first call, and extended with the notes of newly
+    /// committed blocks on later ones.  Calls are served one at a time.
+    /// Returns the height and anchor the paths are valid against, along with a
+    /// path for each requested commitment present in the tree.
+    pub async fn witness(
+        &self,
+        note_commitments: BTreeSet<note::Commitment>,
+    ) -> Result<(
+        block::Height,
+        merkle::Root,
+        Vec<(note::Commitment, merkle::Path)>,
+    )> {
+        // Only notes from fully written blocks are visible, so read up to the
+        // height announced on the watch channel and check against its anchor.
+        let height = *self.height_rx().borrow();
+        let mut conn = self.pool.acquire().await?;
+
+        let expected_anchor = query!(
+            "SELECT nct_anchor FROM blocks WHERE height = $1",
+            height.value() as i64
+        )
+        .fetch_one(&mut conn)
+        .await?
+        .nct_anchor;
+        let requested = note_commitments
+            .iter()
+            .map(|note_commitment| <[u8; 32]>::from(*note_commitment).to_vec())
+            .collect::<Vec<_>>();
+        let positions = query!(
+            "SELECT note_commitment, position FROM notes
+                WHERE note_commitment = ANY($1) AND height <= $2",
+            &requested[..],
+            height.value() as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                note::Commitment::try_from(&row.note_commitment[..])
+                    .map_err(StateError::corrupt)?,
+                row.position as u64,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        let mut witness_tree = self.witness_tree.lock().await;
+        // The tree only moves forward, unless the state was rebuilt under it.
+        if witness_tree.height() > Some(height.value()) {
+            *witness_tree = WitnessTree::default();
+        }
+        let new_notes = query!(
+            "SELECT note_commitment, position FROM notes
+                WHERE height > $1 AND height <= $2
+                ORDER BY position ASC",
+            witness_tree.height().map_or(-1, |height| height as i64),
+            height.value() as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        // Hashing is CPU-bound, so keep it off the async executor.  The tree is
+        // left empty if this fails, and rebuilt by the next call.
+        let mut tree = std::mem::take(&mut *witness_tree);
+        let (tree, anchor, paths) = tokio::task::spawn_blocking(move || {
+            for row in new_notes {
+                if row.position as u64 != tree.len() {
+                    return Err(anyhow!(
+                        "note at position {} is missing from the database",
+                        tree.len()
+                    ));
+                }
+                tree.append(note::Commitment::try_from(&row.note_commitment[..])?);
+            }
+            tree.set_height(height.value());
+
+            let paths = positions
+                .into_iter()
+                .map(|(note_commitment, position)| (note_commitment, tree.path(position)))
+                .collect::<Vec<_>>();
+            let anchor = tree.root();
+            Ok((tree, anchor, paths))
+        })
+        .await
+        .map_err(StateError::corrupt)??;
+
+        if anchor.to_bytes()[..] != expected_anchor[..] {
+            return Err(StateError::corrupt(anyhow!(
+                "rebuilt note commitment tree has anchor {}, but {} was recorded at height {}",
+                hex::encode(anchor.to_bytes()),
+                hex::encode(&expected_anchor),
+                height
+            )));
+        }
+        *witness_tree = tree;
+
+        Ok((height, anchor, paths))
+    }
+
     /// Returns the intersection of the provided nullifiers with the nullifiers
     /// in the database.
     pub async fn check_nullifiers(
@@ -127,7 +296,7 @@ impl Reader {
             .map(|nf| nf.to_bytes().to_vec())
             .collect::<Vec<_>>();
         let existing = query!(
-            "SELECT nullifier FROM nullifiers WHERE nullifier = ANY($1)",
+            "SELECT nullifier FROM unique_nullifiers WHERE nullifier = ANY($1)",
             &nullifiers[..],
         )
         .fetch_all(&mut conn)
@@ -144,6 +313,68 @@ impl Reader {
         Ok(existing)
     }
 
+    /// Returns the intersection of the provided note commitments with the note
+    /// commitments already created on chain.
+    pub async fn check_note_commitments(
+        &self,
+        note_commitments: &BTreeSet<note::Commitment>,
+    ) -> Result<BTreeSet<note::Commitment>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let note_commitments = note_commitments
+            .iter()
+            .map(|nc| <[u8; 32]>::from(*nc).to_vec())
+            .collect::<Vec<_>>();
+        let existing = query!(
+            "SELECT note_commitment FROM unique_note_commitments WHERE note_commitment = ANY($1)",
+            &note_commitments[..],
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            row.note_commitment
+                .as_slice()
+                .try_into()
+                .expect("db data is valid")
+        })
+        .collect();
+
+        Ok(existing)
+    }
+
+    /// Returns the height at which each of the provided nullifiers was spent,
+    /// for those that have been, in a single query.
+    pub async fn nullifier_heights(
+        &self,
+        nullifiers: &BTreeSet<Nullifier>,
+    ) -> Result<BTreeMap<Nullifier, u64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let nullifiers = nullifiers
+            .iter()
+            .map(|nf| nf.to_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let spent = query!(
+            "SELECT nullifier, height FROM unique_nullifiers WHERE nullifier = ANY($1)",
+            &nullifiers[..],
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let nullifier: Nullifier = row
+                .nullifier
+                .as_slice()
+                .try_into()
+                .map_err(StateError::corrupt)?;
+            Ok((nullifier, row.height as u64))
+        })
+        .collect::<Result<_>>()?;
+
+        Ok(spent)
+    }
+
     /// Retrieve the node genesis configuration.
     pub async fn genesis_configuration(&self) -> Result<genesis::AppState> {
         let mut conn = self.pool.acquire().await?;
@@ -154,7 +385,7 @@ impl Reader {
         .fetch_optional(&mut conn)
         .await?
         {
-            serde_json::from_slice(&data).context("Could not parse saved genesis config")?
+            blob::decode_genesis(&data)?
         } else {
             // This is only reached on the initial startup.
             // The default value here will be overridden by `InitChain`.
@@ -164,6 +395,63 @@ impl Reader {
         Ok(genesis_config)
     }
 
+    /// Retrieve the `InitChain` request the chain was started with, if it was recorded.
+    pub async fn init_chain_request(&self) -> Result<Option<abci::request::InitChain>> {
+        let mut conn = self.pool.acquire().await?;
+        init_chain_request(&mut conn).await
+    }
+
+    /// Retrieve the identity of the chain this database belongs to, if genesis
+    /// has been committed.
+    pub async fn chain_identity(&self) -> Result<Option<schema::ChainIdentityRow>> {
+        let mut conn = self.pool.acquire().await?;
+        let identity = query_as!(
+            schema::ChainIdentityRow,
+            "SELECT chain_id, genesis_hash FROM chain_identity"
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(identity)
+    }
+
+    /// Checks that the given chain id, and genesis app state if provided, match
+    /// the chain this database belongs to.  The app state is given as the raw
+    /// bytes Tendermint delivers in `InitChain`.
+    ///
+    /// This catches a database from one network being reused with another.
+    /// A database without a recorded identity matches anything.
+    pub async fn check_chain_identity(
+        &self,
+        chain_id: &str,
+        app_state_bytes: Option<&[u8]>,
+    ) -> Result<()> {
+        let identity = match self.chain_identity().await? {
+            Some(identity) => identity,
+            None => return Ok(()),
+        };
+
+        if identity.chain_id != chain_id {
+            return Err(StateError::corrupt(anyhow!(
+                "the database belongs to chain {:?}, not {:?}",
+                identity.chain_id,
+                chain_id
+            )));
+        }
+        if let Some(app_state_bytes) = app_state_bytes {
+            let genesis_hash = genesis::hash(app_state_bytes);
+            if identity.genesis_hash[..] != genesis_hash[..] {
+                return Err(StateError::corrupt(anyhow!(
+                    "the database was initialized from a genesis state with hash {}, not {}",
+                    hex::encode(&identity.genesis_hash),
+                    hex::encode(genesis_hash)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Retrieve the latest block info, if any.
     pub async fn latest_block_info(&self) -> Result<Option<schema::BlocksRow>> {
         let mut conn = self.pool.acquire().await?;
@@ -177,22 +465,54 @@ impl Reader {
         Ok(latest)
     }
 
-    // retrieve the `last` latest node commitment tree anchors from the database
-    pub async fn recent_anchors(&self, last: usize) -> Result<VecDeque<merkle::Root>> {
+    // retrieve the `last` latest node commitment tree anchors from the database,
+    // along with their heights, newest first
+    pub async fn recent_anchors(&self, last: usize) -> Result<Vec<(u64, merkle::Root)>> {
         let mut conn = self.pool.acquire().await?;
         let anchor_rows = query!(
-            r#"SELECT nct_anchor AS "nct_anchor: merkle::Root" FROM blocks ORDER BY height DESC LIMIT $1"#,
+            r#"SELECT height, nct_anchor AS "nct_anchor: merkle::Root" FROM blocks ORDER BY height DESC LIMIT $1"#,
             last as i64,
         )
         .fetch_all(&mut conn)
         .await?;
 
-        let mut nct_vec: VecDeque<merkle::Root> = VecDeque::new();
-        for block in anchor_rows {
-            nct_vec.push_back(block.nct_anchor)
-        }
+        Ok(anchor_rows
+            .into_iter()
+            .map(|block| (block.height as u64, block.nct_anchor))
+            .collect())
+    }
+
+    /// Retrieve the note commitment tree root at the end of the given height.
+    pub async fn anchor_at_height(&self, height: u64) -> Result<Option<merkle::Root>> {
+        let mut conn = self.pool.acquire().await?;
+        let row = query!(
+            r#"SELECT nct_anchor AS "nct_anchor: merkle::Root" FROM blocks WHERE height = $1"#,
+            height as i64,
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.map(|row| row.nct_anchor))
+    }
+
+    /// Retrieve the most recent height at which the given anchor was the note
+    /// commitment tree root.
+    ///
+    /// Blocks with no new notes leave the root unchanged, so an anchor can be the
+    /// root at several heights; the most recent one is what determines whether
+    /// it's still among the valid anchors.
+    pub async fn height_for_anchor(&self, anchor: &merkle::Root) -> Result<Option<block::Height>> {
+        let mut conn = self.pool.acquire().await?;
+        let row = query!(
+            "SELECT MAX(height) AS height FROM blocks WHERE nct_anchor = $1",
+            anchor.to_bytes().to_vec(),
+        )
+        .fetch_one(&mut conn)
+        .await?;
 
-        Ok(nct_vec)
+        row.height
+            .map(|height| block::Height::try_from(height).map_err(StateError::corrupt))
+            .transpose()
     }
 
     /// Retrieve the latest block height.
@@ -307,7 +627,7 @@ impl Reader {
 
             streams.push(FundingStream {
                 address: addr,
-                rate_bps: row.rate_bps.try_into()?,
+                rate_bps: row.rate_bps.try_into().map_err(StateError::corrupt)?,
             })
         }
 
@@ -383,9 +703,146 @@ impl Reader {
             .collect()
     }
 
+    /// Summarizes the standing of the validator with the given identity key,
+    /// measuring its uptime over the last `uptime_window` blocks.
+    ///
+    /// Returns `None` if there's no such validator.
+    pub async fn validator_overview(
+        &self,
+        identity_key: &IdentityKey,
+        uptime_window: u64,
+    ) -> Result<Option<ValidatorOverview>> {
+        let ValidatorInfo {
+            mut validator,
+            status,
+            rate_data: next_rate,
+        } = match self
+            .validator_info(true)
+            .await?
+            .into_iter()
+            .find(|info| &info.validator.identity_key == identity_key)
+        {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let funding_streams = self.funding_streams(identity_key.clone()).await?;
+        let next_base_rate = self.base_rate_data(next_rate.epoch_index).await?;
+        let projected_rate = next_rate.next(
+            &next_base_rate.next(BASE_REWARD_RATE),
+            funding_streams.as_ref(),
+        );
+        validator.funding_streams = funding_streams;
+
+        let delegation_token_supply = self
+            .asset_lookup(identity_key.delegation_token().id())
+            .await?
+            .map(|info| info.total_supply)
+            .unwrap_or(0);
+
+        let mut conn = self.pool.acquire().await?;
+
+        let commission_paid = query!(
+            r#"SELECT COALESCE(SUM(commission), 0)::bigint AS "commission!"
+                FROM validator_epoch_stats
+                WHERE validator_identity_key = $1"#,
+            identity_key.encode_to_vec(),
+        )
+        .fetch_one(&mut conn)
+        .await?
+        .commission;
+
+        let (blocks, uptimes) = self.uptimes(uptime_window).await?;
+        let uptime = uptimes
+            .get(&consensus_address(&validator.consensus_key))
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(Some(ValidatorOverview {
+            validator: Some(validator.into()),
+            status: Some(status.into()),
+            delegation_token_supply,
+            delegation_pool: next_rate.unbonded_amount(delegation_token_supply),
+            next_rate: Some(next_rate.into()),
+            projected_rate: Some(projected_rate.into()),
+            commission_paid: commission_paid as u64,
+            uptime_window: blocks,
+            uptime_expected: uptime.signed + uptime.missed,
+            uptime_signed: uptime.signed,
+        }))
+    }
+
+    /// Retrieves how many of the last `window` blocks' commits each validator
+    /// signed and missed.
+    pub async fn validator_uptimes(&self, window: u64) -> Result<ValidatorUptimes> {
+        let height = self.height().await?.value();
+        let (blocks, uptimes) = self.uptimes(window).await?;
+
+        let mut validators = self.validator_info(true).await?;
+        validators.sort_by(|a, b| b.status.voting_power.cmp(&a.status.voting_power));
+
+        Ok(ValidatorUptimes {
+            height,
+            window: blocks,
+            validators: validators
+                .into_iter()
+                .map(|info| {
+                    let uptime = uptimes
+                        .get(&consensus_address(&info.validator.consensus_key))
+                        .cloned()
+                        .unwrap_or_default();
+                    ValidatorUptime {
+                        identity_key: Some(info.validator.identity_key.into()),
+                        name: info.validator.name,
+                        state: info.status.state.name().to_str().to_string(),
+                        voting_power: info.status.voting_power,
+                        signed: uptime.signed,
+                        missed: uptime.missed,
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Counts the commits each validator, by consensus address, signed and
+    /// missed in the last `window` blocks, returning the number of blocks
+    /// counted along with the counts.
+    async fn uptimes(&self, window: u64) -> Result<(u64, BTreeMap<Vec<u8>, Uptime>)> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = query!(
+            "SELECT begin_block FROM raw_blocks ORDER BY height DESC LIMIT $1",
+            window as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        // Each block's BeginBlock records which of the validators in the set
+        // signed the previous block's commit.
+        let mut uptimes = BTreeMap::<_, Uptime>::new();
+        for row in &rows {
+            let begin_block = <abci::request::BeginBlock as tendermint_proto::Protobuf<
+                tendermint_proto::abci::RequestBeginBlock,
+            >>::decode_vec(&row.begin_block)
+            .context("Could not parse saved BeginBlock request")?;
+            for vote in begin_block.last_commit_info.votes {
+                let uptime = uptimes.entry(vote.validator.address.to_vec()).or_default();
+                if vote.signed_last_block {
+                    uptime.signed += 1;
+                } else {
+                    uptime.missed += 1;
+                }
+            }
+        }
+
+        Ok((rows.len() as u64, uptimes))
+    }
+
     /// Retrieve a stream of [`CompactBlock`]s for the given (inclusive) range.
     ///
     /// If the range corresponds to blocks that don't exist, the stream will be empty.
+    /// If a block in the range was committed without a precomputed compact block,
+    /// the stream ends with an error.
     #[instrument(skip(self))]
     pub fn compact_blocks(
         &self,
@@ -394,159 +851,1318 @@ impl Reader {
     ) -> impl Stream<Item = Result<CompactBlock>> + Send + Unpin {
         let pool = self.pool.clone();
         Box::pin(try_stream! {
-            let mut nullifiers = query!(
-                "SELECT height, nullifier
-                    FROM nullifiers
+            let mut rows = query!(
+                "SELECT height, data, compression
+                    FROM compact_blocks
                     WHERE height BETWEEN $1 AND $2
                     ORDER BY height ASC",
                 start_height,
                 end_height
             )
-            .fetch(&pool)
-            .peekable();
+            .fetch(&pool);
 
-            let mut fragments = query!(
-                "SELECT height, note_commitment, ephemeral_key, encrypted_note
-                    FROM notes
-                    WHERE height BETWEEN $1 AND $2
-                    ORDER BY position ASC",
-                start_height,
-                end_height
-            )
-            .fetch(&pool)
-            .peekable();
-
-            for height in start_height..=end_height {
-                let mut compact_block = CompactBlock {
-                    height: height as u32,
-                    fragments: vec![],
-                    nullifiers: vec![],
-                };
-
-                while let Some(row) = Pin::new(&mut nullifiers).peek().await {
-                    // Bail out of the loop if the next iteration would be a different height
-                    if let Ok(row) = row {
-                        if row.height != height {
-                            break;
-                        }
-                    }
-
-                    let row = Pin::new(&mut nullifiers)
-                        .next()
-                        .await
-                        .expect("we already peeked, so there is a next row")?;
-                    compact_block.nullifiers.push(row.nullifier.into());
+            let mut expected_height = start_height;
+            while let Some(row) = rows.next().await {
+                let row = row?;
+                if row.height != expected_height {
+                    Err(anyhow!(
+                        "no compact block is stored for height {}; run `pd reindex` to rebuild it",
+                        expected_height
+                    ))?;
                 }
 
-                while let Some(row) = Pin::new(&mut fragments).peek().await {
-                    // Bail out of the loop if the next iteration would be a different height
-                    if let Ok(row) = row {
-                        if row.height != height {
-                            break;
-                        }
-                    }
+                let mut compact_block = compact_block::decode(row.compression, &row.data)
+                    .map_err(StateError::corrupt)?;
+                if !compact_block.fragments.is_empty() {
+                    let mut ciphertexts = query!(
+                        "SELECT note_ciphertexts.note_commitment,
+                                note_ciphertexts.ephemeral_key,
+                                note_ciphertexts.encrypted_note,
+                                note_ciphertexts.encrypted_memo
+                            FROM note_ciphertexts
+                            JOIN notes ON notes.note_commitment = note_ciphertexts.note_commitment
+                            WHERE notes.height = $1",
+                        row.height
+                    )
+                    .fetch_all(&pool)
+                    .await?
+                    .into_iter()
+                    .map(|row| {
+                        (
+                            row.note_commitment,
+                            (row.ephemeral_key, row.encrypted_note, row.encrypted_memo),
+                        )
+                    })
+                    .collect::<BTreeMap<_, _>>();
 
-                    let row = Pin::new(&mut fragments)
-                        .next()
-                        .await
-                        .expect("we already peeked, so there is a next row")?;
-                    compact_block.fragments.push(StateFragment {
-                        note_commitment: row.note_commitment.into(),
-                        ephemeral_key: row.ephemeral_key.into(),
-                        encrypted_note: row.encrypted_note.into(),
-                    });
+                    for fragment in &mut compact_block.fragments {
+                        let (ephemeral_key, encrypted_note, encrypted_memo) = ciphertexts
+                            .remove(&fragment.note_commitment[..])
+                            .ok_or_else(|| {
+                                StateError::corrupt(anyhow!(
+                                    "no note ciphertext is stored for a note at height {}",
+                                    row.height
+                                ))
+                            })?;
+                        fragment.ephemeral_key = ephemeral_key.into();
+                        fragment.encrypted_note = encrypted_note.into();
+                        fragment.encrypted_memo = encrypted_memo.unwrap_or_default().into();
+                    }
                 }
-
                 tracing::debug!(
-                    ?height,
+                    height = ?row.height,
                     nullifiers_size = compact_block.nullifiers.len(),
                     fragments_size = compact_block.fragments.len(),
                     "yielding compact block"
                 );
 
                 yield compact_block;
+                expected_height += 1;
             }
         })
     }
 
-    /// Retrieve the [`TransactionDetail`] for a given note commitment.
-    pub async fn transaction_by_note(&self, note_commitment: Vec<u8>) -> Result<TransactionDetail> {
+    /// Retrieve a stream of the notes included in blocks at or after
+    /// `start_height`, in the order they were added to the note commitment
+    /// tree.
+    ///
+    /// Rows are decoded as they're fetched, rather than buffered, so this is
+    /// suitable for forwarding long ranges.  Only notes from fully written
+    /// blocks are included: the stream ends at the height announced on the
+    /// watch channel when it's called.
+    #[instrument(skip(self))]
+    pub fn notes_since(
+        &self,
+        start_height: u64,
+    ) -> impl Stream<Item = Result<NoteRecord>> + Send + Unpin {
+        let pool = self.pool.clone();
+        let end_height = self.height_rx().borrow().value();
+        Box::pin(try_stream! {
+            let mut rows = query!(
+                "SELECT height, position, transaction_id, notes.note_commitment, ephemeral_key, encrypted_note, encrypted_memo
+                    FROM notes
+                    JOIN note_ciphertexts ON note_ciphertexts.note_commitment = notes.note_commitment
+                    WHERE height BETWEEN $1 AND $2
+                    ORDER BY position ASC",
+                start_height as i64,
+                end_height as i64
+            )
+            .fetch(&pool);
+
+            while let Some(row) = rows.next().await {
+                let row = row?;
+                yield NoteRecord {
+                    height: row.height as u64,
+                    position: row.position as u64,
+                    transaction_id: row.transaction_id,
+                    fragment: StateFragment {
+                        note_commitment: row.note_commitment.into(),
+                        ephemeral_key: row.ephemeral_key.into(),
+                        encrypted_note: row.encrypted_note.into(),
+                        encrypted_memo: row.encrypted_memo.unwrap_or_default().into(),
+                    },
+                };
+            }
+        })
+    }
+
+    /// Looks up a transaction included in a block by its Tendermint hash,
+    /// returning where it was included and the result of delivering it.
+    ///
+    /// If the same transaction was included more than once, the first
+    /// inclusion is returned.
+    pub async fn transaction_by_hash(
+        &self,
+        tx_hash: &[u8],
+    ) -> Result<Option<TransactionByHashResponse>> {
         let mut conn = self.pool.acquire().await?;
 
         let row = query!(
-            "SELECT transaction_id FROM notes WHERE note_commitment = $1",
-            note_commitment
+            "SELECT raw_transactions.height, raw_transactions.position, code, log, data
+                FROM raw_transactions
+                JOIN transaction_results USING (height, position)
+                WHERE tx_hash = $1
+                ORDER BY raw_transactions.height ASC, raw_transactions.position ASC
+                LIMIT 1",
+            tx_hash
         )
-        .fetch_one(&mut conn)
+        .fetch_optional(&mut conn)
         .await?;
-        Ok(TransactionDetail {
-            id: row.transaction_id,
-        })
+
+        Ok(row.map(|row| TransactionByHashResponse {
+            height: row.height as u64,
+            index: row.position as u32,
+            code: row.code as u32,
+            log: row.log,
+            tx: row.data,
+        }))
     }
 
-    /// Retrieve the [`Asset`] for a given asset ID.
-    pub async fn asset_lookup(&self, asset_id: asset::Id) -> Result<Option<chain::AssetInfo>> {
+    /// Retrieves the aggregate statistics for the block at `height`.
+    pub async fn block_stats(&self, height: u64) -> Result<Option<BlockStats>> {
         let mut conn = self.pool.acquire().await?;
 
-        let asset = query!(
-            "SELECT denom, asset_id, total_supply FROM assets WHERE asset_id = $1",
-            asset_id.to_bytes().to_vec(),
+        let row = query!(
+            "SELECT height, epoch, transactions, failed_transactions, notes_created, nullifiers_spent, bytes, weight, fees
+                FROM block_stats
+                WHERE height = $1",
+            height as i64
         )
         .fetch_optional(&mut conn)
         .await?;
 
-        let height = self.height().await?;
-
-        // TODO: should we be returning proto types from our state methods, or domain types?
-        Ok(asset.map(|asset| {
-            let inner = Fq::from_bytes(asset.asset_id.try_into().unwrap())
-                .expect("invalid asset id in database");
-
-            chain::AssetInfo {
-                denom: Some(
-                    asset::REGISTRY
-                        .parse_denom(asset.denom.as_str())
-                        .unwrap()
-                        .into(),
-                ),
-                asset_id: Some(asset::Id(inner).into()),
-                total_supply: asset.total_supply as u64, // postgres only has i64....
-                as_of_block_height: u64::from(height),
-            }
+        Ok(row.map(|row| BlockStats {
+            height: row.height as u64,
+            epoch_index: row.epoch as u64,
+            transactions: row.transactions as u64,
+            failed_transactions: row.failed_transactions.max(0) as u64,
+            notes_created: row.notes_created as u64,
+            nullifiers_spent: row.nullifiers_spent as u64,
+            bytes: row.bytes as u64,
+            weight: row.weight as u64,
+            fees: row.fees as u64,
         }))
     }
 
-    /// Retrieves the entire Asset Registry.
-    pub async fn asset_list(&self) -> Result<Vec<Asset>> {
+    /// Retrieves the load of the last `limit` blocks, most recent first.
+    pub async fn recent_block_loads(&self, limit: u64) -> Result<Vec<fee::BlockLoad>> {
         let mut conn = self.pool.acquire().await?;
 
-        Ok(query!("SELECT denom, asset_id FROM assets")
-            .fetch_all(&mut conn)
-            .await?
+        let rows = query!(
+            "SELECT transactions, failed_transactions, bytes, weight, fees
+                FROM block_stats
+                ORDER BY height DESC
+                LIMIT $1",
+            limit as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        Ok(rows
             .into_iter()
-            .map(|row| Asset {
-                asset_denom: row.denom,
-                asset_id: row.asset_id,
+            .map(|row| fee::BlockLoad {
+                transactions: (row.transactions + row.failed_transactions.max(0)) as u64,
+                bytes: row.bytes as u64,
+                weight: row.weight as u64,
+                fees: row.fees as u64,
             })
             .collect())
     }
 
-    /// Retrieve the delegation changes for the supplied epoch
-    /// TODO: should we have a DelegationChanges struct instead of just returning a BTreeMap?
-    pub async fn delegation_changes(&self, epoch: u64) -> Result<BTreeMap<IdentityKey, i64>> {
+    /// Retrieves the epoch the next block will belong to.
+    ///
+    /// Block-count epochs are found from the height, while time-based epochs
+    /// are found from the recorded epoch starts, so this returns `None` for a
+    /// chain with time-based epochs before genesis.
+    pub async fn current_epoch(&self) -> Result<Option<CurrentEpoch>> {
         let mut conn = self.pool.acquire().await?;
+        let latest = query!(
+            "SELECT epoch, start_height, start_time FROM epochs ORDER BY epoch DESC LIMIT 1"
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-        let rows = query!("SELECT validator_identity_key, delegation_change FROM delegation_changes WHERE epoch = $1",
-               epoch as i64
-            ).fetch_all(&mut conn)
-            .await?;
-
-        let mut changes: BTreeMap<IdentityKey, i64> = BTreeMap::new();
-        for row in rows {
-            let id_key = IdentityKey::decode(row.validator_identity_key.as_slice())?;
-            changes.insert(id_key, row.delegation_change);
+        let chain_params = self.chain_params_rx().borrow().clone();
+        if chain_params.epoch_duration_secs > 0 {
+            return Ok(latest.map(|row| CurrentEpoch {
+                index: row.epoch as u64,
+                start_height: row.start_height as u64,
+                start_time: row.start_time,
+                epoch_duration: chain_params.epoch_duration,
+                epoch_duration_secs: chain_params.epoch_duration_secs,
+                blocks_remaining: 0,
+                end_time: row.start_time + chain_params.epoch_duration_secs as i64,
+            }));
         }
 
-        Ok(changes)
+        let height = self.height().await?.value();
+        let epoch = Epoch::from_height(height + 1, chain_params.epoch_duration);
+        Ok(Some(CurrentEpoch {
+            index: epoch.index,
+            start_height: epoch.start_height().value(),
+            start_time: latest
+                .filter(|row| row.epoch as u64 == epoch.index)
+                .map_or(0, |row| row.start_time),
+            epoch_duration: chain_params.epoch_duration,
+            epoch_duration_secs: 0,
+            blocks_remaining: epoch.end_height().value() - height,
+            end_time: 0,
+        }))
     }
+
+    /// Retrieves the delegation tokens redelegated away from and to the given
+    /// validator for the epoch with index `epoch_index`.
+    pub async fn redelegation_volume(
+        &self,
+        identity_key: &IdentityKey,
+        epoch_index: u64,
+    ) -> Result<(u64, u64)> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(query!(
+            "SELECT redelegated_out, redelegated_in FROM validator_epoch_redelegations
+                WHERE validator_identity_key = $1 AND epoch = $2",
+            identity_key.encode_to_vec(),
+            epoch_index as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map_or((0, 0), |row| {
+            (row.redelegated_out as u64, row.redelegated_in as u64)
+        }))
+    }
+
+    /// Retrieves the summary of the ended epoch with index `epoch_index`.
+    pub async fn epoch_summary(&self, epoch_index: u64) -> Result<Option<EpochSummary>> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(query!(
+            "SELECT * FROM epoch_summaries WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map(|row| EpochSummary {
+            epoch_index: row.epoch as u64,
+            end_height: row.end_height as u64,
+            rewards_distributed: row.rewards_distributed as u64,
+            fees_collected: row.fees_collected as u64,
+            fees_distributed: row.fees_distributed as u64,
+            delegated: row.delegated as u64,
+            undelegated: row.undelegated as u64,
+            validators: row.validators as u32,
+            validators_changed: row.validators_changed as u32,
+            staking_token_supply: row.staking_token_supply as u64,
+        }))
+    }
+
+    /// Retrieves the aggregate statistics for the blocks committed so far in
+    /// the epoch with index `epoch_index`.
+    pub async fn epoch_stats(&self, epoch_index: u64) -> Result<Option<EpochStats>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = if let Some(row) = query!(
+            "SELECT epoch, blocks, transactions, failed_transactions, notes_created, nullifiers_spent
+                FROM epoch_stats
+                WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        {
+            row
+        } else {
+            return Ok(None);
+        };
+
+        let delegation_volumes = query!(
+            "SELECT validator_identity_key, delegated, undelegated
+                FROM validator_epoch_stats
+                WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(DelegationVolume {
+                identity_key: Some(
+                    IdentityKey::decode(row.validator_identity_key.as_slice())?.into(),
+                ),
+                delegated: row.delegated as u64,
+                undelegated: row.undelegated as u64,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+        Ok(Some(EpochStats {
+            epoch_index: row.epoch as u64,
+            blocks: row.blocks as u64,
+            transactions: row.transactions as u64,
+            failed_transactions: row.failed_transactions.max(0) as u64,
+            notes_created: row.notes_created as u64,
+            nullifiers_spent: row.nullifiers_spent as u64,
+            delegation_volumes,
+        }))
+    }
+
+    /// Retrieves the validator set as it was when the epoch with index
+    /// `epoch_index` began, ordered by identity key.
+    pub async fn validator_set(&self, epoch_index: u64) -> Result<Option<ValidatorSet>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = query!(
+            "SELECT validator_identity_key, consensus_key, voting_power, validator_state
+                FROM validator_set_snapshots
+                WHERE epoch = $1
+                ORDER BY validator_identity_key ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let validators = rows
+            .into_iter()
+            .map(|row| {
+                Ok(ValidatorSetEntry {
+                    identity_key: Some(
+                        IdentityKey::decode(row.validator_identity_key.as_slice())?.into(),
+                    ),
+                    consensus_key: row.consensus_key,
+                    voting_power: row.voting_power as u64,
+                    state: row.validator_state,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(ValidatorSet {
+            epoch_index,
+            validators,
+        }))
+    }
+
+    /// Retrieves the DKG round of the epoch with index `epoch_index`, if one
+    /// was opened.
+    pub async fn dkg_round(&self, epoch_index: u64) -> Result<Option<dkg::Round>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let round = match query!(
+            "SELECT threshold, group_key, commitment FROM dkg_rounds WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        {
+            Some(round) => round,
+            None => return Ok(None),
+        };
+        let rows = query!(
+            "SELECT identity_key, public_key_share FROM dkg_participants
+                WHERE epoch = $1
+                ORDER BY participant_index ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+        let participants = rows
+            .iter()
+            .map(|row| IdentityKey::decode(row.identity_key.as_slice()))
+            .collect::<anyhow::Result<_>>()?;
+        let group_key = round
+            .group_key
+            .map(|bytes| decode_element(&bytes).context("invalid group key"))
+            .transpose()?;
+        let public_key_shares = if group_key.is_some() {
+            rows.iter()
+                .map(|row| {
+                    decode_element(row.public_key_share.as_deref().unwrap_or_default())
+                        .context("invalid public key share")
+                })
+                .collect::<anyhow::Result<_>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Some(dkg::Round {
+            epoch_index,
+            threshold: round.threshold as u64,
+            participants,
+            finished: round.commitment.is_some(),
+            group_key,
+            public_key_shares,
+        }))
+    }
+
+    /// Retrieves the dealings submitted to the DKG round of the epoch with
+    /// index `epoch_index`, with the heights they were submitted at, ordered
+    /// by dealer.
+    pub async fn dkg_dealings(&self, epoch_index: u64) -> Result<Vec<(u64, SignedDkgDealing)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT height, dealing FROM dkg_dealings WHERE epoch = $1 ORDER BY identity_key ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                row.height as u64,
+                SignedDkgDealing::decode(row.dealing.as_slice())?,
+            ))
+        })
+        .collect()
+    }
+
+    /// Retrieves the complaints accepted against dealings in the DKG round of
+    /// the epoch with index `epoch_index`.
+    pub async fn dkg_complaints(&self, epoch_index: u64) -> Result<Vec<DkgComplaint>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT complaint FROM dkg_complaints
+                WHERE epoch = $1
+                ORDER BY dealer_identity_key ASC, identity_key ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| Ok(DkgComplaint::decode(row.complaint.as_slice())?))
+        .collect()
+    }
+
+    /// Retrieves the threshold key that flows in the epoch with index
+    /// `epoch_index` are encrypted to, which was established by the previous
+    /// epoch's DKG round, if that round succeeded.
+    pub async fn flow_key(&self, epoch_index: u64) -> Result<Option<decaf377::Element>> {
+        let previous_epoch = match epoch_index.checked_sub(1) {
+            Some(previous_epoch) => previous_epoch,
+            None => return Ok(None),
+        };
+        Ok(self
+            .dkg_round(previous_epoch)
+            .await?
+            .and_then(|round| round.group_key))
+    }
+
+    /// Retrieves the sums of each validator's encrypted flows in the epoch
+    /// with index `epoch_index`.
+    pub async fn flow_aggregates(&self, epoch_index: u64) -> Result<flow::Aggregates> {
+        let mut conn = self.pool.acquire().await?;
+
+        let mut aggregates = flow::Aggregates::new();
+        let rows = query!(
+            "SELECT validator_identity_key, undelegation, ciphertext
+                FROM encrypted_flows
+                WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+        for row in rows {
+            let direction = if row.undelegation {
+                FlowDirection::Undelegate
+            } else {
+                FlowDirection::Delegate
+            };
+            aggregates
+                .entry(IdentityKey::decode(row.validator_identity_key.as_slice())?)
+                .or_default()
+                .add(direction, Ciphertext::decode(row.ciphertext.as_slice())?);
+        }
+
+        Ok(aggregates)
+    }
+
+    /// Retrieves the indices of the epochs before `epoch_index` whose
+    /// encrypted flows haven't been decrypted yet, in ascending order.
+    pub async fn undecrypted_flow_epochs(&self, epoch_index: u64) -> Result<Vec<u64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(query!(
+            "SELECT DISTINCT epoch FROM encrypted_flows
+                WHERE epoch < $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM decrypted_flows WHERE decrypted_flows.epoch = encrypted_flows.epoch
+                    )
+                ORDER BY epoch ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| row.epoch as u64)
+        .collect())
+    }
+
+    /// Retrieves the decryptions of the encrypted flows of the epoch with
+    /// index `epoch_index`, ordered by key share holder.
+    pub async fn flow_decryptions(&self, epoch_index: u64) -> Result<Vec<FlowDecryption>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT decryption FROM flow_decryptions WHERE epoch = $1 ORDER BY identity_key ASC",
+            epoch_index as i64
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| Ok(FlowDecryption::decode(row.decryption.as_slice())?))
+        .collect()
+    }
+
+    /// Retrieves the validator set of the epoch with index `epoch_index`, with
+    /// a proof that the app hash at the latest height commits to it.
+    pub async fn validator_set_proof(&self, epoch_index: u64) -> Result<Option<ValidatorSetProof>> {
+        let validator_set = match self.validator_set(epoch_index).await? {
+            Some(validator_set) => validator_set,
+            None => return Ok(None),
+        };
+
+        let mut conn = self.pool.acquire().await?;
+        let commitment = query!(
+            "SELECT commitment FROM validator_set_commitments WHERE epoch = $1",
+            epoch_index as i64
+        )
+        .fetch_one(&mut conn)
+        .await?
+        .commitment;
+        let previous_commitment = match epoch_index.checked_sub(1) {
+            Some(previous_epoch) => query!(
+                "SELECT commitment FROM validator_set_commitments WHERE epoch = $1",
+                previous_epoch as i64
+            )
+            .fetch_optional(&mut conn)
+            .await?
+            .map(|row| row.commitment)
+            .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        let key_proof = self
+            .key_proof(jellyfish::Key::ValidatorSet(epoch_index).hash())
+            .await?;
+
+        let commitment = merkle::Root::try_from(commitment.as_slice())?;
+        if key_proof.value != Some(commitment.clone().into()) {
+            // The epoch's validator set was recorded by a block whose deferred
+            // writes are still in progress.
+            return Ok(None);
+        }
+
+        Ok(Some(ValidatorSetProof {
+            validator_set: Some(validator_set),
+            previous_commitment,
+            commitment: Some(commitment.into()),
+            height: key_proof.height,
+            app_hash: key_proof.app_hash,
+            proof: key_proof.proof,
+        }))
+    }
+
+    /// Proves the value stored under a JMT key at the latest fully written
+    /// height, or proves that the key holds no value.
+    pub async fn key_proof(&self, key_hash: HashValue) -> Result<KeyProof> {
+        let height = self.height_rx().borrow().value();
+        self.key_proof_at(key_hash, height).await
+    }
+
+    /// Proves the value stored under a JMT key as of the end of the block at
+    /// `height`, or proves that the key held no value then.
+    ///
+    /// The proof is against that block's app hash, so it can be checked by a
+    /// client that verified an older header, as long as the state at that
+    /// height hasn't been pruned.
+    pub async fn key_proof_at(&self, key_hash: HashValue, height: u64) -> Result<KeyProof> {
+        self.check_jmt_version(height).await?;
+        let app_hash = query!(
+            "SELECT app_hash FROM blocks WHERE height = $1",
+            height as i64
+        )
+        .fetch_one(&mut self.pool.acquire().await?)
+        .await?
+        .app_hash;
+        let (value, proof) = jmt::JellyfishMerkleTree::<_, merkle::Root>::new(self)
+            .get_with_proof(key_hash, height)
+            .await?;
+
+        Ok(KeyProof {
+            key_hash: key_hash.to_vec(),
+            value: value.map(Into::into),
+            height,
+            app_hash,
+            proof: bincode::serialize(&proof).map_err(StateError::corrupt)?,
+        })
+    }
+
+    /// Returns the value stored under a JMT key as of the end of the block at
+    /// `height`, if the state at that height hasn't been pruned.
+    pub async fn value_at(&self, key_hash: HashValue, height: u64) -> Result<Option<merkle::Root>> {
+        self.check_jmt_version(height).await?;
+        let (value, _) = jmt::JellyfishMerkleTree::<_, merkle::Root>::new(self)
+            .get_with_proof(key_hash, height)
+            .await?;
+
+        Ok(value)
+    }
+
+    /// Checks that the JMT holds the state at the end of the block at
+    /// `height`: that the block has been fully written, and that the tree's
+    /// version for it hasn't been pruned.
+    ///
+    /// Each block writes the JMT at the version equal to its height, and
+    /// always updates the note commitment anchor, so every version has its
+    /// own root node for as long as it's retained.
+    async fn check_jmt_version(&self, height: u64) -> Result<()> {
+        let latest = self.height_rx().borrow().value();
+        if height == 0 || height > latest {
+            return Err(StateError::NotFound(format!(
+                "no state at height {}, the latest height is {}",
+                height, latest
+            )));
+        }
+
+        let root_key = NodeKey::new_empty_path(height)
+            .encode()
+            .map_err(StateError::corrupt)?;
+        let retained = query!(
+            r#"SELECT EXISTS (SELECT 1 FROM jmt WHERE key = $1) AS "retained!""#,
+            &root_key
+        )
+        .fetch_one(&mut self.pool.acquire().await?)
+        .await?
+        .retained;
+        if !retained {
+            return Err(StateError::NotFound(format!(
+                "the state at height {} has been pruned",
+                height
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the [`TransactionDetail`] for a given note commitment.
+    pub async fn transaction_by_note(&self, note_commitment: Vec<u8>) -> Result<TransactionDetail> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = query!(
+            "SELECT transaction_id FROM notes WHERE note_commitment = $1",
+            note_commitment
+        )
+        .fetch_one(&mut conn)
+        .await?;
+        Ok(TransactionDetail {
+            id: row.transaction_id,
+        })
+    }
+
+    /// Retrieve the [`StateFragment`]s for the notes created by the given transaction,
+    /// in the order they were added to the note commitment tree.
+    pub async fn notes_by_transaction(
+        &self,
+        transaction_id: Vec<u8>,
+    ) -> Result<Vec<StateFragment>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let fragments = query!(
+            "SELECT notes.note_commitment, ephemeral_key, encrypted_note, encrypted_memo
+                FROM notes
+                JOIN note_ciphertexts ON note_ciphertexts.note_commitment = notes.note_commitment
+                WHERE transaction_id = $1
+                ORDER BY position ASC",
+            transaction_id
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| StateFragment {
+            note_commitment: row.note_commitment.into(),
+            ephemeral_key: row.ephemeral_key.into(),
+            encrypted_note: row.encrypted_note.into(),
+            encrypted_memo: row.encrypted_memo.unwrap_or_default().into(),
+        })
+        .collect();
+
+        Ok(fragments)
+    }
+
+    /// Retrieve the [`Asset`] for a given asset ID.
+    ///
+    /// The display unit is the asset registry's, if it has one for the asset,
+    /// and otherwise the one in any registered metadata.
+    pub async fn asset_lookup(&self, asset_id: asset::Id) -> Result<Option<chain::AssetInfo>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let asset = query!(
+            r#"SELECT
+                assets.denom,
+                assets.asset_id,
+                assets.total_supply,
+                CASE WHEN assets.display_exponent > 0 OR denom_metadata.asset_id IS NULL
+                    THEN assets.display_denom ELSE denom_metadata.symbol END AS "display_denom!",
+                CASE WHEN assets.display_exponent > 0 OR denom_metadata.asset_id IS NULL
+                    THEN assets.display_exponent ELSE denom_metadata.display_exponent END AS "display_exponent!"
+            FROM assets LEFT JOIN denom_metadata USING (asset_id)
+            WHERE asset_id = $1"#,
+            asset_id.to_bytes().to_vec(),
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        let height = self.height().await?;
+
+        // TODO: should we be returning proto types from our state methods, or domain types?
+        Ok(asset.map(|asset| {
+            let inner = Fq::from_bytes(asset.asset_id.try_into().unwrap())
+                .expect("invalid asset id in database");
+
+            let total_supply = asset.total_supply as u64; // postgres only has i64....
+            chain::AssetInfo {
+                denom: Some(
+                    asset::REGISTRY
+                        .parse_denom(asset.denom.as_str())
+                        .unwrap()
+                        .into(),
+                ),
+                asset_id: Some(asset::Id(inner).into()),
+                total_supply,
+                as_of_block_height: u64::from(height),
+                display_total_supply: format_display_amount(
+                    total_supply,
+                    asset.display_exponent as u32,
+                ),
+                display_denom: asset.display_denom,
+                display_exponent: asset.display_exponent as u32,
+            }
+        }))
+    }
+
+    /// Retrieves the entire Asset Registry.
+    pub async fn asset_list(&self) -> Result<Vec<Asset>> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(query!(
+            r#"SELECT
+                assets.denom,
+                assets.asset_id,
+                denom_metadata.description AS "description?",
+                denom_metadata.display_exponent AS "display_exponent?",
+                denom_metadata.symbol AS "symbol?",
+                CASE WHEN assets.display_exponent > 0 OR denom_metadata.asset_id IS NULL
+                    THEN assets.display_denom ELSE denom_metadata.symbol END AS "display_denom!",
+                CASE WHEN assets.display_exponent > 0 OR denom_metadata.asset_id IS NULL
+                    THEN assets.display_exponent ELSE denom_metadata.display_exponent END AS "display_exponent!"
+            FROM assets LEFT JOIN denom_metadata USING (asset_id)"#
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| Asset {
+            display_denom: row.display_denom,
+            display_exponent: row.display_exponent as u32,
+            metadata: match (row.description, row.display_exponent, row.symbol) {
+                (Some(description), Some(display_exponent), Some(symbol)) => {
+                    Some(transaction::DenomMetadata {
+                        denom: Some(crypto::Denom {
+                            denom: row.denom.clone(),
+                        }),
+                        description,
+                        display_exponent: display_exponent as u32,
+                        symbol,
+                    })
+                }
+                _ => None,
+            },
+            asset_denom: row.denom,
+            asset_id: row.asset_id,
+        })
+        .collect())
+    }
+
+    /// Retrieves the supply of an asset, along with the amounts of it minted and burned.
+    pub async fn asset_supply(&self, asset_id: asset::Id) -> Result<Option<AssetSupply>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = query!(
+            "SELECT denom, total_supply, minted, burned FROM assets WHERE asset_id = $1",
+            &asset_id.to_bytes()[..]
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+        let height = self.height().await?;
+
+        Ok(row.map(|row| AssetSupply {
+            asset_id: Some(asset_id.into()),
+            denom: Some(crypto::Denom { denom: row.denom }),
+            height: height.value(),
+            total_supply: row.total_supply as u64,
+            minted: row.minted as u64,
+            burned: row.burned as u64,
+        }))
+    }
+
+    /// Retrieves the display metadata registered for an asset, if any.
+    pub async fn denom_metadata(&self, asset_id: asset::Id) -> Result<Option<DenomMetadata>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = query!(
+            "SELECT denom, description, display_exponent, symbol FROM denom_metadata WHERE asset_id = $1",
+            &asset_id.to_bytes()[..]
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(|row| {
+            Ok(DenomMetadata {
+                denom: asset::REGISTRY
+                    .parse_denom(&row.denom)
+                    .ok_or_else(|| anyhow!("invalid denom {} in database", row.denom))?,
+                description: row.description,
+                display_exponent: row.display_exponent as u8,
+                symbol: row.symbol,
+            })
+        })
+        .transpose()
+    }
+
+    /// Looks up the asset whose registered metadata uses `symbol`.
+    pub async fn asset_id_by_symbol(&self, symbol: &str) -> Result<Option<asset::Id>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT asset_id FROM denom_metadata WHERE symbol = $1",
+            symbol
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map(|row| {
+            let bytes: [u8; 32] = row
+                .asset_id
+                .try_into()
+                .map_err(|_| anyhow!("invalid asset id in database"))?;
+            let inner =
+                Fq::from_bytes(bytes).map_err(|_| anyhow!("invalid asset id in database"))?;
+            Ok(asset::Id(inner))
+        })
+        .transpose()
+    }
+
+    /// The voting power of each active validator.
+    pub async fn active_voting_power(&self) -> Result<BTreeMap<IdentityKey, u64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT identity_key, voting_power FROM validators
+                WHERE validator_state = $1 AND voting_power > 0",
+            ValidatorStateName::Active.to_str()
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                IdentityKey::decode(row.identity_key.as_slice())?,
+                row.voting_power as u64,
+            ))
+        })
+        .collect()
+    }
+
+    /// Each validator's current vote for an upgrade plan.
+    pub async fn upgrade_votes(&self) -> Result<BTreeMap<IdentityKey, UpgradePlan>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT validator_identity_key, name, upgrade_height, emergency_halt FROM upgrade_votes"
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                IdentityKey::decode(row.validator_identity_key.as_slice())?,
+                UpgradePlan {
+                    name: row.name,
+                    height: row.upgrade_height as u64,
+                    emergency_halt: row.emergency_halt,
+                },
+            ))
+        })
+        .collect()
+    }
+
+    /// The sequence number of each validator's current vote for an upgrade
+    /// plan.
+    pub async fn upgrade_vote_sequence_numbers(&self) -> Result<BTreeMap<IdentityKey, u64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!("SELECT validator_identity_key, sequence_number FROM upgrade_votes")
+            .fetch_all(&mut conn)
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok((
+                    IdentityKey::decode(row.validator_identity_key.as_slice())?,
+                    row.sequence_number as u64,
+                ))
+            })
+            .collect()
+    }
+
+    /// The scheduled actions that haven't been carried out yet, in the order
+    /// they were scheduled.
+    pub async fn scheduled_actions(&self) -> Result<Vec<Scheduled>> {
+        let mut conn = self.pool.acquire().await?;
+
+        query!(
+            "SELECT height, position, due_height, due_epoch, kind, data
+                FROM scheduled_actions
+                ORDER BY height, position"
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let due = match (row.due_height, row.due_epoch) {
+                (Some(due_height), None) => Due::Height(due_height as u64),
+                (None, Some(due_epoch)) => Due::Epoch(due_epoch as u64),
+                _ => {
+                    return Err(StateError::corrupt(anyhow!(
+                        "scheduled action {} at height {} isn't due at one height or epoch",
+                        row.position,
+                        row.height
+                    )));
+                }
+            };
+            Ok(Scheduled {
+                height: row.height as u64,
+                position: row.position as u32,
+                due,
+                action: ScheduledAction::decode(&row.kind, &row.data)
+                    .map_err(StateError::corrupt)?,
+            })
+        })
+        .collect()
+    }
+
+    /// Measures the size of the stored state.
+    pub async fn resource_usage(&self) -> Result<ResourceUsage> {
+        let mut conn = self.pool.acquire().await?;
+
+        let table_bytes = query!(
+            r#"SELECT relname AS "table!", pg_total_relation_size(relid) AS "bytes!"
+                FROM pg_catalog.pg_statio_user_tables"#
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.table, row.bytes as u64))
+        .collect();
+        // Node keys are encoded as the big-endian version, followed by the
+        // number of nibbles in the node's path.
+        let jmt = query!(
+            r#"SELECT COUNT(*) AS "nodes!", COALESCE(MAX(get_byte(key, 8)), 0) AS "depth!" FROM jmt"#
+        )
+        .fetch_one(&mut conn)
+        .await?;
+        let nct_bytes = query!(
+            r#"SELECT COALESCE(octet_length(data), 0) AS "bytes!" FROM blobs WHERE id = 'nct'"#
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map(|row| row.bytes as u64)
+        .unwrap_or(0);
+        let notes = query!(r#"SELECT COUNT(*) AS "count!" FROM notes"#)
+            .fetch_one(&mut conn)
+            .await?
+            .count;
+        let nullifiers = query!(r#"SELECT COUNT(*) AS "count!" FROM nullifiers"#)
+            .fetch_one(&mut conn)
+            .await?
+            .count;
+
+        Ok(ResourceUsage {
+            table_bytes,
+            jmt_nodes: jmt.nodes as u64,
+            jmt_depth: jmt.depth as u64,
+            nct_bytes,
+            notes: notes as u64,
+            nullifiers: nullifiers as u64,
+        })
+    }
+
+    /// The statements running on pd's database, other than this one, longest
+    /// running first.
+    pub async fn active_statements(&self) -> Result<Vec<ActiveStatement>> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(query!(
+            r#"SELECT
+                pid AS "pid!",
+                COALESCE(state, '') AS "state!",
+                wait_event_type || ':' || wait_event AS "wait_event?",
+                COALESCE(EXTRACT(EPOCH FROM now() - query_start), 0)::float8 AS "seconds!",
+                COALESCE(query, '') AS "query!"
+            FROM pg_catalog.pg_stat_activity
+            WHERE datname = current_database()
+                AND state <> 'idle'
+                AND pid <> pg_backend_pid()
+            ORDER BY query_start"#
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|row| ActiveStatement {
+            pid: row.pid,
+            state: row.state,
+            wait_event: row.wait_event,
+            running: Duration::from_secs_f64(row.seconds.max(0.0)),
+            query: row.query,
+        })
+        .collect())
+    }
+
+    /// Returns the total fees collected in the committed blocks of `epoch`.
+    pub async fn epoch_fees(&self, epoch: u64) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+
+        let collected = query!(
+            "SELECT collected FROM epoch_fees WHERE epoch = $1",
+            epoch as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map(|row| row.collected as u64)
+        .unwrap_or(0);
+
+        Ok(collected)
+    }
+
+    /// Sums the net delegation changes of every epoch before `end_epoch`, per
+    /// validator.
+    pub async fn total_delegation_changes(
+        &self,
+        end_epoch: u64,
+    ) -> Result<BTreeMap<IdentityKey, i64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = query!(
+            r#"SELECT validator_identity_key, SUM(delegation_change)::bigint AS "total!"
+                FROM delegation_changes
+                WHERE epoch < $1
+                GROUP BY validator_identity_key"#,
+            end_epoch as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    IdentityKey::decode(row.validator_identity_key.as_slice())?,
+                    row.total,
+                ))
+            })
+            .collect()
+    }
+
+    /// Retrieve the delegation changes for the supplied epoch
+    /// TODO: should we have a DelegationChanges struct instead of just returning a BTreeMap?
+    pub async fn delegation_changes(&self, epoch: u64) -> Result<BTreeMap<IdentityKey, i64>> {
+        let mut conn = self.pool.acquire().await?;
+
+        // A change is recorded for each block, so they're summed per validator.
+        let rows = query!(
+            r#"SELECT validator_identity_key, SUM(delegation_change)::bigint AS "delegation_change!"
+                FROM delegation_changes
+                WHERE epoch = $1
+                GROUP BY validator_identity_key"#,
+            epoch as i64
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        let mut changes: BTreeMap<IdentityKey, i64> = BTreeMap::new();
+        for row in rows {
+            let id_key = IdentityKey::decode(row.validator_identity_key.as_slice())?;
+            changes.insert(id_key, row.delegation_change);
+        }
+
+        Ok(changes)
+    }
+
+    /// Retrieves the net delegation changes of each validator, or only the
+    /// given one, in the epochs from `start_epoch` to `end_epoch` inclusive,
+    /// along with the size of its delegation pool after each.
+    pub async fn delegation_change_history(
+        &self,
+        start_epoch: u64,
+        end_epoch: u64,
+        identity_key: Option<&IdentityKey>,
+    ) -> Result<Vec<DelegationChange>> {
+        // Delegation tokens allocated at genesis are in the pools from the
+        // start, but aren't delegation changes.
+        let mut genesis_pools = BTreeMap::<String, u64>::new();
+        for allocation in self.genesis_configuration().await?.allocations {
+            *genesis_pools.entry(allocation.denom).or_default() += allocation.amount;
+        }
+
+        let mut conn = self.pool.acquire().await?;
+
+        // The pool sizes are summed from genesis, so the range is only
+        // applied once the running totals have been computed.
+        let rows = query!(
+            r#"SELECT epoch AS "epoch!", validator_identity_key AS "validator_identity_key!", net_change AS "net_change!", total AS "total!"
+                FROM (
+                    SELECT
+                        epoch,
+                        validator_identity_key,
+                        net_change,
+                        SUM(net_change) OVER (
+                            PARTITION BY validator_identity_key ORDER BY epoch
+                        )::bigint AS total
+                    FROM (
+                        SELECT epoch, validator_identity_key, SUM(delegation_change)::bigint AS net_change
+                        FROM delegation_changes
+                        WHERE epoch <= $2 AND ($3::bytea IS NULL OR validator_identity_key = $3)
+                        GROUP BY epoch, validator_identity_key
+                    ) AS per_epoch
+                ) AS running
+                WHERE epoch >= $1
+                ORDER BY epoch, validator_identity_key"#,
+            start_epoch.min(i64::MAX as u64) as i64,
+            end_epoch.min(i64::MAX as u64) as i64,
+            identity_key.map(|identity_key| identity_key.encode_to_vec()),
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let identity_key = IdentityKey::decode(row.validator_identity_key.as_slice())?;
+                let genesis_pool = genesis_pools
+                    .get(&identity_key.delegation_token().denom().to_string())
+                    .copied()
+                    .unwrap_or(0);
+                Ok(DelegationChange {
+                    epoch_index: row.epoch as u64,
+                    identity_key: Some(identity_key.into()),
+                    net_change: row.net_change,
+                    pool_size: (genesis_pool as i64 + row.total)
+                        .try_into()
+                        .map_err(StateError::corrupt)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Retrieves the stored signed header of the block at `height`, in
+    /// Tendermint's protobuf encoding.
+    pub async fn signed_header(&self, height: u64) -> Result<Option<Vec<u8>>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let row = query!(
+            "SELECT signed_header FROM tendermint_headers WHERE height = $1",
+            height as i64
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.map(|row| row.signed_header))
+    }
+
+    /// Retrieve the raw data processed at the given height, if it was recorded.
+    ///
+    /// Returns the BeginBlock request that started the block and the raw bytes
+    /// of every transaction delivered in it, in delivery order.
+    pub async fn raw_block(
+        &self,
+        height: u64,
+    ) -> Result<Option<(abci::request::BeginBlock, Vec<Bytes>)>> {
+        let mut conn = self.pool.acquire().await?;
+        raw_block(&mut conn, height).await
+    }
+}
+
+/// Retrieve the recorded `InitChain` request over `conn`, which needn't belong
+/// to a [`Reader`], so that it can be read inside a caller's transaction.
+pub(crate) async fn init_chain_request(
+    conn: &mut PgConnection,
+) -> Result<Option<abci::request::InitChain>> {
+    Ok(query_as!(
+        schema::BlobsRow,
+        "SELECT id, data FROM blobs WHERE id = 'init_chain';"
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|schema::BlobsRow { data, .. }| {
+        <abci::request::InitChain as tendermint_proto::Protobuf<
+            tendermint_proto::abci::RequestInitChain,
+        >>::decode_vec(&data)
+        .context("Could not parse saved InitChain request")
+    })
+    .transpose()?)
+}
+
+/// Retrieve the raw data processed at the given height over `conn`, like
+/// [`Reader::raw_block`].
+pub(crate) async fn raw_block(
+    conn: &mut PgConnection,
+    height: u64,
+) -> Result<Option<(abci::request::BeginBlock, Vec<Bytes>)>> {
+    let block = if let Some(row) = query_as!(
+        schema::RawBlocksRow,
+        "SELECT height, block_hash, begin_block FROM raw_blocks WHERE height = $1",
+        height as i64
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    {
+        row
+    } else {
+        return Ok(None);
+    };
+
+    let begin_block = <abci::request::BeginBlock as tendermint_proto::Protobuf<
+        tendermint_proto::abci::RequestBeginBlock,
+    >>::decode_vec(&block.begin_block)
+    .context("Could not parse saved BeginBlock request")?;
+
+    let transactions = query_as!(
+        schema::RawTransactionsRow,
+        "SELECT height, position, tx_hash, data FROM raw_transactions WHERE height = $1 ORDER BY position ASC",
+        height as i64
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| row.data.into())
+    .collect();
+
+    Ok(Some((begin_block, transactions)))
+}
+
+/// The number of commits a validator signed and missed while in the
+/// validator set.
+#[derive(Debug, Clone, Default)]
+struct Uptime {
+    signed: u64,
+    missed: u64,
+}
+
+/// The address identifying a validator's votes, derived from its consensus
+/// key.
+fn consensus_address(consensus_key: &tendermint::PublicKey) -> Vec<u8> {
+    tendermint::account::Id::from(consensus_key.clone())
+        .as_bytes()
+        .to_vec()
+}
+
+/// Formats an amount of an asset's base unit in a display unit `exponent`
+/// powers of ten larger, without trailing zeros.
+fn format_display_amount(amount: u64, exponent: u32) -> String {
+    let power_of_ten = 10u128.pow(exponent);
+    let (whole, fraction) = (amount as u128 / power_of_ten, amount as u128 % power_of_ten);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = exponent as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Decodes a compressed decaf377 element stored in the database.
+fn decode_element(bytes: &[u8]) -> anyhow::Result<decaf377::Element> {
+    decaf377::Encoding(
+        bytes
+            .try_into()
+            .map_err(|_| anyhow!("group elements must be 32 bytes"))?,
+    )
+    .decompress()
+    .map_err(|_| anyhow!("invalid group element"))
 }
@@ -0,0 +1,78 @@
+use std::{future::Future, time::Duration};
+
+use metrics::increment_counter;
+
+use super::Reader;
+
+/// The longest listing the database's in-flight statements may take, since a
+/// struggling database may be why the commit is slow in the first place.
+const DUMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reports block commits that take longer than a threshold, so that storage
+/// degradation is noticed well before commits take long enough to miss blocks.
+///
+/// Slow commits are logged and counted in the `node_slow_commits_total`
+/// metric as soon as they cross the threshold, while they're still running.
+#[derive(Debug, Clone)]
+pub struct CommitWatchdog {
+    /// How long a commit may take before it's reported.
+    pub threshold: Duration,
+    /// Also log the statements the database is running when a commit crosses
+    /// the threshold.
+    pub dump_statements: bool,
+}
+
+impl CommitWatchdog {
+    /// Runs the commit of the block at `height`, reporting it if it crosses
+    /// the threshold.
+    pub(super) async fn watch<T>(
+        &self,
+        reader: &Reader,
+        height: u64,
+        commit: impl Future<Output = T>,
+    ) -> T {
+        tokio::pin!(commit);
+        tokio::select! {
+            output = &mut commit => output,
+            _ = tokio::time::sleep(self.threshold) => {
+                // The commit keeps going while the alert is raised.
+                let (output, ()) = tokio::join!(commit, self.alert(reader, height));
+                tracing::info!(height, "slow block commit finished");
+                output
+            }
+        }
+    }
+
+    async fn alert(&self, reader: &Reader, height: u64) {
+        increment_counter!("node_slow_commits_total");
+        tracing::warn!(
+            height,
+            threshold = ?self.threshold,
+            "block commit is taking longer than the threshold"
+        );
+        if !self.dump_statements {
+            return;
+        }
+
+        match tokio::time::timeout(DUMP_TIMEOUT, reader.active_statements()).await {
+            Ok(Ok(statements)) => {
+                for statement in statements {
+                    tracing::warn!(
+                        height,
+                        pid = statement.pid,
+                        state = %statement.state,
+                        wait_event = ?statement.wait_event,
+                        running = ?statement.running,
+                        query = %statement.query,
+                        "statement in flight during slow block commit"
+                    );
+                }
+            }
+            Ok(Err(e)) => tracing::warn!(?e, "failed to list in-flight statements"),
+            Err(_) => tracing::warn!(
+                timeout = ?DUMP_TIMEOUT,
+                "listing in-flight statements timed out"
+            ),
+        }
+    }
+}
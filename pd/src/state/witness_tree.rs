@@ -0,0 +1,123 @@
+use std::fmt;
+
+use penumbra_crypto::{
+    merkle::{self, Altitude, Hashable, Position},
+    note,
+};
+
+/// Every note commitment in the note commitment tree, kept in memory so that
+/// the authentication path of any note can be computed without rebuilding the
+/// tree.
+///
+/// The committed tree only keeps its frontier, so this is built from the
+/// stored notes the first time it's needed, and extended with the notes of
+/// each block committed since.  It keeps the root of every complete subtree,
+/// which is about two hashes per note.
+pub(super) struct WitnessTree {
+    /// The height of the last block whose notes were added, or `None` if no
+    /// notes have been added yet.
+    height: Option<u64>,
+    /// The roots of the complete subtrees at each altitude, left to right: the
+    /// notes themselves, then the roots of each pair of notes, and so on.
+    levels: Vec<Vec<note::Commitment>>,
+    /// The root of an empty subtree at each altitude.
+    empty: Vec<note::Commitment>,
+}
+
+impl fmt::Debug for WitnessTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WitnessTree")
+            .field("height", &self.height)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Default for WitnessTree {
+    fn default() -> Self {
+        let mut empty = vec![note::Commitment::empty_leaf()];
+        for level in 0..merkle::DEPTH {
+            let below = empty[level];
+            empty.push(note::Commitment::combine(
+                Altitude::from(level as u8),
+                &below,
+                &below,
+            ));
+        }
+
+        Self {
+            height: None,
+            levels: vec![Vec::new(); merkle::DEPTH + 1],
+            empty,
+        }
+    }
+}
+
+impl WitnessTree {
+    /// The height of the last block whose notes were added.
+    pub fn height(&self) -> Option<u64> {
+        self.height
+    }
+
+    /// Records that the notes of every block up to `height` have been added.
+    pub fn set_height(&mut self, height: u64) {
+        self.height = Some(height);
+    }
+
+    /// The number of notes in the tree, which is the position of the next one.
+    pub fn len(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /// Adds the note at the next position.
+    pub fn append(&mut self, note_commitment: note::Commitment) {
+        self.levels[0].push(note_commitment);
+
+        // Each subtree the note completes is hashed once, as it's completed.
+        let mut index = self.levels[0].len() - 1;
+        for level in 0..merkle::DEPTH {
+            if index % 2 == 0 {
+                break;
+            }
+            let parent = note::Commitment::combine(
+                Altitude::from(level as u8),
+                &self.levels[level][index - 1],
+                &self.levels[level][index],
+            );
+            self.levels[level + 1].push(parent);
+            index /= 2;
+        }
+    }
+
+    /// The root of the tree.
+    pub fn root(&self) -> merkle::Root {
+        merkle::Root(self.node(merkle::DEPTH, 0).0)
+    }
+
+    /// The authentication path of the note at `position`, which must be in
+    /// the tree.
+    pub fn path(&self, position: u64) -> merkle::Path {
+        let siblings = (0..merkle::DEPTH)
+            .map(|level| self.node(level, (position >> level) ^ 1))
+            .collect();
+        (Position::from(position as usize), siblings)
+    }
+
+    /// The root of the `index`th subtree at altitude `level`.
+    fn node(&self, level: usize, index: u64) -> note::Commitment {
+        if let Some(node) = self.levels[level].get(index as usize) {
+            return *node;
+        }
+        if index << level >= self.len() {
+            return self.empty[level];
+        }
+
+        // Only the subtree on the right edge of the tree is partly filled, so
+        // this recurses down a single path.
+        note::Commitment::combine(
+            Altitude::from(level as u8 - 1),
+            &self.node(level - 1, 2 * index),
+            &self.node(level - 1, 2 * index + 1),
+        )
+    }
+}
@@ -1,17 +1,47 @@
-use std::collections::VecDeque;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
 use jmt::TreeWriterAsync;
+use metrics::histogram;
 use penumbra_chain::params::ChainParams;
-use penumbra_crypto::merkle::{self, TreeExt};
+use penumbra_crypto::{
+    merkle::{self, TreeExt},
+    note,
+};
 use penumbra_proto::Protobuf;
-use penumbra_stake::{FundingStream, RateDataById, ValidatorStateName};
-use sqlx::{query, Pool, Postgres};
-use tendermint::block;
-use tokio::sync::watch;
+use penumbra_stake::{FlowDirection, FundingStream, RateData, RateDataById, ValidatorStateName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{query, Pool, Postgres, Transaction};
+use tendermint::{abci, block};
+use tokio::{sync::watch, task::JoinHandle};
 
-use super::jellyfish;
-use crate::{genesis, PendingBlock, NUM_RECENT_ANCHORS};
+use super::{
+    anchors::AnchorWindow,
+    backoff::Backoff,
+    blob,
+    compact_block::{self, Compression},
+    error::{Result, StateError},
+    jellyfish, partitions,
+    watchdog::CommitWatchdog,
+};
+use crate::{dkg, faults, genesis, scheduler::Due, verify::PositionedNoteData, PendingBlock};
+
+/// Limits on how long the writer's database operations may take, so that a
+/// stalled database fails them instead of hanging the node.
+///
+/// Operations that time out fail with a [transient](StateError::is_transient)
+/// error, and may be retried.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeouts {
+    /// The longest any single statement may run.
+    pub statement: Option<Duration>,
+    /// The longest the consensus-critical writes of a block commit may take.
+    pub commit: Option<Duration>,
+}
 
 #[derive(Debug)]
 pub struct Writer {
@@ -23,9 +53,17 @@ pub struct Writer {
     //pub(super) tmp: evmap::WriteHandle<&'static str, String>,
     // Push channels for chain state
     pub(super) chain_params_tx: watch::Sender<ChainParams>,
-    pub(super) height_tx: watch::Sender<block::Height>,
+    // Shared with the deferred write task, which announces the new height once
+    // all of a block's data has been written.
+    pub(super) height_tx: Arc<watch::Sender<block::Height>>,
     pub(super) next_rate_data_tx: watch::Sender<RateDataById>,
-    pub(super) valid_anchors_tx: watch::Sender<VecDeque<merkle::Root>>,
+    pub(super) valid_anchors_tx: watch::Sender<AnchorWindow>,
+    // The background task flushing the deferred writes of the last committed
+    // block, if it hasn't been awaited yet.
+    pub(super) deferred_writes: Option<JoinHandle<Result<()>>>,
+    pub(super) timeouts: Timeouts,
+    pub(super) commit_watchdog: Option<CommitWatchdog>,
+    pub(super) compact_block_compression: Compression,
 }
 
 impl Writer {
@@ -38,11 +76,15 @@ impl Writer {
             .await?
             .chain_params;
         let height = self.private_reader.height().await?;
+        self.recover_deferred_writes(height.value()).await?;
+        partitions::create_partitions(&self.pool, height.value()).await?;
         let next_rate_data = self.private_reader.next_rate_data().await?;
-        let valid_anchors = self
-            .private_reader
-            .recent_anchors(NUM_RECENT_ANCHORS)
-            .await?;
+        let valid_anchors = AnchorWindow::new(
+            chain_params.num_recent_anchors,
+            self.private_reader
+                .recent_anchors(AnchorWindow::retained(chain_params.num_recent_anchors))
+                .await?,
+        );
 
         // Sends fail if every receiver has been dropped, which is not our problem.
         let _ = self.chain_params_tx.send(chain_params);
@@ -53,6 +95,62 @@ impl Writer {
         Ok(())
     }
 
+    /// Finishes the deferred writes that pd stopped before finishing, from
+    /// their journal.
+    ///
+    /// Fails if the latest block's notes and compact block are still missing
+    /// afterwards, which can only happen to a block committed before deferred
+    /// writes were journaled, rather than serving the block without them.
+    async fn recover_deferred_writes(&self, height: u64) -> Result<()> {
+        let journaled = query!("SELECT height, data FROM deferred_writes ORDER BY height")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in journaled {
+            tracing::warn!(
+                height = row.height,
+                "finishing interrupted deferred block writes"
+            );
+            let deferred = DeferredWrites::decode(&row.data)?;
+            try_write_deferred(&self.pool, &self.timeouts, row.height as u64, &deferred).await?;
+        }
+
+        let has_block = query!("SELECT height FROM blocks WHERE height = $1", height as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        let has_compact_block = query!(
+            "SELECT height FROM compact_blocks WHERE height = $1",
+            height as i64
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some();
+        if has_block && !has_compact_block {
+            return Err(StateError::corrupt(anyhow::anyhow!(
+                "the notes and compact block of block {} are missing, and weren't journaled; \
+                `pd reindex` rebuilds them from the raw blocks",
+                height
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sets the timeouts for the writer's database operations.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Sets how slow a block commit may be before it's reported.  Slow
+    /// commits aren't reported if unset.
+    pub fn set_commit_watchdog(&mut self, watchdog: Option<CommitWatchdog>) {
+        self.commit_watchdog = watchdog;
+    }
+
+    /// Sets how the compact blocks of newly committed blocks are compressed.
+    pub fn set_compact_block_compression(&mut self, compression: Compression) {
+        self.compact_block_compression = compression;
+    }
+
     /// Borrow a private `state::Reader` instance that uses the same connection
     /// pool as this writer.  This allows the writer to read data from the
     /// database without contention from other `state::Reader`s.
@@ -61,10 +159,42 @@ impl Writer {
     }
 
     /// Commits the genesis config to the database, prior to the first block commit.
-    pub async fn commit_genesis(&self, genesis_config: &genesis::AppState) -> Result<()> {
+    ///
+    /// The `InitChain` request carrying the genesis config is recorded as well,
+    /// so that the chain can be replayed from its raw data, along with the
+    /// chain identity that later requests are checked against.
+    pub async fn commit_genesis(
+        &self,
+        init_chain: &abci::request::InitChain,
+        genesis_config: &genesis::AppState,
+    ) -> Result<()> {
         let mut dbtx = self.pool.begin().await?;
 
-        let genesis_bytes = serde_json::to_vec(&genesis_config)?;
+        // This is kept across a `pd reindex`, which replays it.
+        query!(
+            r#"
+            INSERT INTO blobs (id, data) VALUES ('init_chain', $1)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+            tendermint_proto::Protobuf::<tendermint_proto::abci::RequestInitChain>::encode_vec(
+                init_chain
+            )
+            .map_err(StateError::corrupt)?,
+        )
+        .execute(&mut dbtx)
+        .await?;
+
+        // Like the InitChain request, the chain identity is kept across a `pd reindex`.
+        query!(
+            "INSERT INTO chain_identity (chain_id, genesis_hash) VALUES ($1, $2)
+            ON CONFLICT (chain_id) DO NOTHING",
+            init_chain.chain_id,
+            &genesis::hash(&init_chain.app_state_bytes)[..]
+        )
+        .execute(&mut dbtx)
+        .await?;
+
+        let genesis_bytes = blob::encode_genesis(&genesis_config)?;
 
         // ON CONFLICT is excluded here so that an error is raised
         // if genesis config is attempted to be set more than once
@@ -172,8 +302,22 @@ impl Writer {
             );
         }
 
+        // The genesis validators are the validator set for the first epoch,
+        // and the participants in its DKG round.
+        snapshot_validator_set(&mut dbtx, 0).await?;
+        open_dkg_round(&mut dbtx, 0).await?;
+
+        // The first epoch starts at genesis.
+        query!(
+            "INSERT INTO epochs (epoch, start_height, start_time) VALUES (0, 0, $1)",
+            init_chain.time.unix_timestamp()
+        )
+        .execute(&mut dbtx)
+        .await?;
+
         let chain_params = genesis_config.chain_params.clone();
         // Finally, commit the transaction and then update subscribers
+        faults::commit_error()?;
         dbtx.commit().await?;
         // Sends fail if every receiver has been dropped, which is not our problem.
         // We wrote these, so push updates to subscribers.
@@ -186,13 +330,110 @@ impl Writer {
         Ok(())
     }
 
+    /// Waits for the deferred writes of the last committed block to finish.
+    ///
+    /// This is cancellation safe: if it's cancelled, the writes are still
+    /// awaited by the next call.
+    pub async fn flush_deferred_writes(&mut self) -> Result<()> {
+        if let Some(deferred_writes) = &mut self.deferred_writes {
+            let result = deferred_writes.await;
+            self.deferred_writes = None;
+            result.map_err(StateError::corrupt)??;
+        }
+        Ok(())
+    }
+
     /// Commits a block to the state, returning the new app hash.
-    pub async fn commit_block(&self, block: PendingBlock) -> Result<Vec<u8>> {
+    ///
+    /// Only the consensus-critical writes (the JMT, the block's app hash, and
+    /// everything later blocks are verified against) happen before this
+    /// returns.  The remaining writes, which only serve clients, are flushed by
+    /// a background task overlapping with the processing of the next block;
+    /// the height watch channel isn't advanced until they're done.  At most one
+    /// block's deferred writes are in flight: they're awaited before the next
+    /// block is committed.  They're journaled with the block, so if pd stops
+    /// before they're done, they're finished when it starts again.
+    ///
+    /// A commit that fails with a [transient](StateError::is_transient) error
+    /// can be retried with the same block.  If the connection was lost while
+    /// the database transaction was being committed, it may have gone through,
+    /// in which case the retry finds the block already recorded and only
+    /// finishes the commit.
+    ///
+    /// If the commit takes longer than the commit timeout, it's cancelled and
+    /// fails with [`StateError::Timeout`].  Its database transaction is either
+    /// rolled back or, if the cancellation came while it was being committed,
+    /// committed in full, so the same retry applies.
+    ///
+    /// How long each attempt takes is recorded in the `node_commit_seconds`
+    /// metric, and reported by the [`CommitWatchdog`], if set.
+    pub async fn commit_block(&mut self, block: PendingBlock) -> Result<Vec<u8>> {
+        let height = block.height.expect("height must be set");
+        let start = Instant::now();
+        let result = match self.commit_watchdog.clone() {
+            Some(watchdog) => {
+                let reader = self.private_reader.clone();
+                watchdog
+                    .watch(&reader, height, self.commit_block_in_time(block))
+                    .await
+            }
+            None => self.commit_block_in_time(block).await,
+        };
+        histogram!("node_commit_seconds", start.elapsed().as_secs_f64());
+        result
+    }
+
+    /// Commits a block, once the previous block's deferred writes are done,
+    /// within the commit timeout.
+    async fn commit_block_in_time(&mut self, block: PendingBlock) -> Result<Vec<u8>> {
+        self.flush_deferred_writes().await?;
+
+        match self.timeouts.commit {
+            Some(limit) => tokio::time::timeout(limit, self.try_commit_block(block))
+                .await
+                .map_err(|_| {
+                    StateError::Timeout(format!("block commit took longer than {:?}", limit))
+                })?,
+            None => self.try_commit_block(block).await,
+        }
+    }
+
+    /// Commits a block, with no limit on how long it takes.
+    ///
+    /// Nothing is awaited once the database transaction is committed, so if
+    /// this is cancelled, the block is either not committed at all, or
+    /// committed without the subscriber updates the next attempt makes.
+    async fn try_commit_block(&mut self, block: PendingBlock) -> Result<Vec<u8>> {
+        let height = block.height.expect("height must be set");
+        let deferred = DeferredWrites::new(
+            self.compact_block_compression,
+            compact_block::encode(&block.compact_block(), self.compact_block_compression)?,
+            &block.notes,
+        );
+        let nct_anchor = block.note_commitment_tree.root2();
+
+        if let Some(row) = query!(
+            "SELECT app_hash FROM blocks WHERE height = $1",
+            height as i64
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        {
+            tracing::warn!(height, "block was already committed by a previous attempt");
+            self.finish_commit(height, nct_anchor, block.next_rates, deferred);
+            return Ok(row.app_hash);
+        }
+
+        // Entering a new partition's range, so the one after it is created.
+        if height % partitions::PARTITION_HEIGHTS == 0 {
+            partitions::create_partitions(&self.pool, height).await?;
+        }
+
         // TODO: batch these queries?
         let mut dbtx = self.pool.begin().await?;
+        set_timeouts(&mut dbtx, &self.timeouts).await?;
 
-        let nct_anchor = block.note_commitment_tree.root2();
-        let nct_bytes = bincode::serialize(&block.note_commitment_tree)?;
+        let nct_bytes = blob::encode_nct(&block.note_commitment_tree)?;
         query!(
             r#"
             INSERT INTO blobs (id, data) VALUES ('nct', $1)
@@ -203,25 +444,162 @@ impl Writer {
         .execute(&mut dbtx)
         .await?;
 
-        let height = block.height.expect("height must be set");
+        let epoch_index = block.epoch.unwrap().index;
+
+        let mut jmt_values = vec![(
+            jellyfish::Key::NoteCommitmentAnchor.hash(),
+            nct_anchor.clone(),
+        )];
+
+        // The genesis validator set was recorded with the genesis
+        // configuration, but its commitment is first added to the JMT here.
+        if height == 0 {
+            let commitment =
+                query!("SELECT commitment FROM validator_set_commitments WHERE epoch = 0")
+                    .fetch_one(&mut dbtx)
+                    .await?
+                    .commitment;
+            jmt_values.push((
+                jellyfish::Key::ValidatorSet(0).hash(),
+                merkle::Root::try_from(commitment.as_slice())?,
+            ));
+        }
+
+        if let Some(validator_statuses) = block.next_validator_statuses {
+            for status in validator_statuses {
+                query!(
+                    "UPDATE validators SET voting_power=$1 WHERE identity_key = $2",
+                    status.voting_power as i64,
+                    status.identity_key.encode_to_vec(),
+                )
+                .execute(&mut dbtx)
+                .await?;
+            }
+
+            // This is the last block of the epoch, so the validators now have
+            // the state they'll have for all of the next one.  Committing to
+            // that validator set in the JMT lets light clients follow it.
+            let commitment = snapshot_validator_set(&mut dbtx, epoch_index + 1).await?;
+            jmt_values.push((
+                jellyfish::Key::ValidatorSet(epoch_index + 1).hash(),
+                commitment,
+            ));
+            open_dkg_round(&mut dbtx, epoch_index + 1).await?;
+        }
+
+        // Dealings are recorded as they're submitted, so that the round can
+        // be finished with all of them at the end of the epoch.
+        for dealing in &block.dkg_dealings {
+            query!(
+                "INSERT INTO dkg_dealings (epoch, identity_key, height, dealing) VALUES ($1, $2, $3, $4)",
+                dealing.dealing.epoch_index as i64,
+                dealing.dealing.validator_identity.encode_to_vec(),
+                height as i64,
+                dealing.encode_to_vec(),
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for complaint in &block.dkg_complaints {
+            query!(
+                "INSERT INTO dkg_complaints (epoch, dealer_identity_key, identity_key, height, complaint) VALUES ($1, $2, $3, $4, $5)",
+                complaint.epoch_index as i64,
+                complaint.dealer_identity.encode_to_vec(),
+                complaint.validator_identity.encode_to_vec(),
+                height as i64,
+                complaint.encode_to_vec(),
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        // Encrypted flows are recorded in the epoch of the block, to be summed
+        // with the rest of the epoch's flows once it has ended.
+        for flow in &block.encrypted_flows {
+            query!(
+                "INSERT INTO encrypted_flows (epoch, validator_identity_key, height, undelegation, ciphertext) VALUES ($1, $2, $3, $4, $5)",
+                epoch_index as i64,
+                flow.validator_identity.encode_to_vec(),
+                height as i64,
+                flow.direction == FlowDirection::Undelegate,
+                flow.amount_ciphertext.encode_to_vec(),
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for decryption in &block.flow_decryptions {
+            query!(
+                "INSERT INTO flow_decryptions (epoch, identity_key, height, decryption) VALUES ($1, $2, $3, $4)",
+                decryption.epoch_index as i64,
+                decryption.validator_identity.encode_to_vec(),
+                height as i64,
+                decryption.encode_to_vec(),
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for flow_epoch in &block.decrypted_flow_epochs {
+            query!(
+                "INSERT INTO decrypted_flows (epoch, height) VALUES ($1, $2)",
+                *flow_epoch as i64,
+                height as i64,
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        if let Some(transcript) = &block.dkg_transcript {
+            query!(
+                "UPDATE dkg_rounds SET group_key = $2, commitment = $3 WHERE epoch = $1",
+                transcript.epoch_index as i64,
+                transcript.group_key.map(|key| key.compress().0.to_vec()),
+                &transcript.commitment.to_bytes()[..],
+            )
+            .execute(&mut dbtx)
+            .await?;
+            for (public_key_share, index) in transcript.public_key_shares.iter().zip(1i64..) {
+                query!(
+                    "UPDATE dkg_participants SET public_key_share = $3
+                    WHERE epoch = $1 AND participant_index = $2",
+                    transcript.epoch_index as i64,
+                    index,
+                    &public_key_share.compress().0[..],
+                )
+                .execute(&mut dbtx)
+                .await?;
+            }
+            jmt_values.push((
+                jellyfish::Key::DkgTranscript(transcript.epoch_index).hash(),
+                transcript.commitment.clone(),
+            ));
+        }
+
+        // Time-based epochs are found from the start of the current epoch, so
+        // this is written along with the consensus state.
+        if let Some(start_time) = block.next_epoch_start_time {
+            query!(
+                "INSERT INTO epochs (epoch, start_height, start_time) VALUES ($1, $2, $3)",
+                (epoch_index + 1) as i64,
+                (height + 1) as i64,
+                start_time
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
 
         // The Jellyfish Merkle tree batches writes to its backing store, so we
         // first need to write the JMT kv pairs...
         let (jmt_root, tree_update_batch) = jmt::JellyfishMerkleTree::new(&self.private_reader)
-            .put_value_set(
-                // TODO: create a JmtKey enum, where each variant has
-                // a different domain-separated hash
-                vec![(
-                    jellyfish::Key::NoteCommitmentAnchor.hash(),
-                    nct_anchor.clone(),
-                )],
-                height,
-            )
+            .put_value_set(jmt_values, height)
             .await?;
         // ... and then write the resulting batch update to the backing store:
         jellyfish::DbTx(&mut dbtx)
             .write_node_batch(&tree_update_batch.node_batch)
             .await?;
+        // The nodes this version superseded are recorded, so that they can be
+        // pruned once the states still containing them aren't retained.
+        jellyfish::DbTx(&mut dbtx)
+            .write_stale_node_index(&tree_update_batch.stale_node_index_batch)
+            .await?;
 
         // The app hash is the root of the Jellyfish Merkle Tree.  We save the
         // NCT anchor separately for convenience, but it's already included in
@@ -238,31 +616,206 @@ impl Writer {
         .execute(&mut dbtx)
         .await?;
 
-        // Add newly created notes into the chain state.
-        for (note_commitment, positioned_note) in block.notes.into_iter() {
+        // Record the raw data processed in this block, so that derived data
+        // can be audited and rebuilt later.  When the block is being replayed
+        // by `pd reindex`, this data is already present.
+        if let Some(begin_block) = block.begin_block {
+            query!(
+                "INSERT INTO raw_blocks (height, block_hash, begin_block) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING",
+                height as i64,
+                begin_block.hash.as_bytes(),
+                tendermint_proto::Protobuf::<tendermint_proto::abci::RequestBeginBlock>::encode_vec(
+                    &begin_block
+                )
+                .map_err(StateError::corrupt)?,
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for (position, tx) in block.raw_transactions.iter().enumerate() {
+            query!(
+                "INSERT INTO raw_transactions (height, position, tx_hash, data) VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING",
+                height as i64,
+                position as i32,
+                &Sha256::digest(tx.as_ref())[..],
+                &tx[..],
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for (position, (code, log)) in block.transaction_results.iter().enumerate() {
             query!(
-                r#"
-                INSERT INTO notes (
-                    note_commitment,
-                    ephemeral_key,
-                    encrypted_note,
-                    transaction_id,
-                    position,
-                    height
-                ) VALUES ($1, $2, $3, $4, $5, $6)"#,
-                &<[u8; 32]>::from(note_commitment)[..],
-                &positioned_note.data.ephemeral_key.0[..],
-                &positioned_note.data.encrypted_note[..],
-                &positioned_note.data.transaction_id[..],
-                positioned_note.position as i64,
+                "INSERT INTO transaction_results (height, position, code, log) VALUES ($1, $2, $3, $4)",
                 height as i64,
+                position as i32,
+                *code as i32,
+                log,
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        // Update the aggregate statistics.
+        let transactions = block.num_transactions as i32;
+        // The genesis block has no raw transactions but counts its allocations
+        // as one transaction, so failures are counted from the results.
+        let failed_transactions = query!(
+            "SELECT COUNT(*) AS \"count!\" FROM transaction_results WHERE height = $1 AND code <> 0",
+            height as i64,
+        )
+        .fetch_one(&mut dbtx)
+        .await?
+        .count
+        .max(0) as i32;
+        let notes_created = block.notes.len() as i32;
+        let nullifiers_spent = block.spent_nullifiers.len() as i32;
+        let bytes = block
+            .raw_transactions
+            .iter()
+            .map(|tx| tx.len() as i64)
+            .sum::<i64>();
+        query!(
+            "INSERT INTO block_stats (height, epoch, transactions, failed_transactions, notes_created, nullifiers_spent, bytes, weight, fees)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            height as i64,
+            epoch_index as i64,
+            transactions,
+            failed_transactions,
+            notes_created,
+            nullifiers_spent,
+            bytes,
+            block.weight as i64,
+            block.fees as i64,
+        )
+        .execute(&mut dbtx)
+        .await?;
+        query!(
+            "INSERT INTO epoch_stats (epoch, blocks, transactions, failed_transactions, notes_created, nullifiers_spent)
+            VALUES ($1, 1, $2, $3, $4, $5)
+            ON CONFLICT (epoch) DO UPDATE SET
+                blocks = epoch_stats.blocks + 1,
+                transactions = epoch_stats.transactions + $2,
+                failed_transactions = epoch_stats.failed_transactions + $3,
+                notes_created = epoch_stats.notes_created + $4,
+                nullifiers_spent = epoch_stats.nullifiers_spent + $5",
+            epoch_index as i64,
+            transactions as i64,
+            failed_transactions as i64,
+            notes_created as i64,
+            nullifiers_spent as i64,
+        )
+        .execute(&mut dbtx)
+        .await?;
+        if let Some(summary) = &block.epoch_summary {
+            query!(
+                "INSERT INTO epoch_summaries (
+                    epoch,
+                    end_height,
+                    rewards_distributed,
+                    fees_collected,
+                    fees_distributed,
+                    delegated,
+                    undelegated,
+                    validators,
+                    validators_changed,
+                    staking_token_supply
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                summary.epoch_index as i64,
+                summary.end_height as i64,
+                summary.rewards_distributed as i64,
+                summary.fees_collected as i64,
+                summary.fees_distributed as i64,
+                summary.delegated as i64,
+                summary.undelegated as i64,
+                summary.validators as i32,
+                summary.validators_changed as i32,
+                summary.staking_token_supply as i64,
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for (identity_key, (delegated, undelegated)) in &block.delegation_volume {
+            query!(
+                "INSERT INTO validator_epoch_stats (validator_identity_key, epoch, delegated, undelegated)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (epoch, validator_identity_key) DO UPDATE SET
+                    delegated = validator_epoch_stats.delegated + $3,
+                    undelegated = validator_epoch_stats.undelegated + $4",
+                identity_key.encode_to_vec(),
+                epoch_index as i64,
+                *delegated as i64,
+                *undelegated as i64,
             )
             .execute(&mut dbtx)
             .await?;
         }
+        for (identity_key, commission) in &block.commission {
+            query!(
+                "INSERT INTO validator_epoch_stats (validator_identity_key, epoch, delegated, undelegated, commission)
+                VALUES ($1, $2, 0, 0, $3)
+                ON CONFLICT (epoch, validator_identity_key) DO UPDATE SET
+                    commission = validator_epoch_stats.commission + $3",
+                identity_key.encode_to_vec(),
+                epoch_index as i64,
+                *commission as i64,
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        for redelegation in &block.redelegations {
+            for (identity_key, redelegated_out, redelegated_in) in [
+                (
+                    &redelegation.from_validator_identity,
+                    redelegation.from_delegation_amount,
+                    0,
+                ),
+                (
+                    &redelegation.to_validator_identity,
+                    0,
+                    redelegation.to_delegation_amount,
+                ),
+            ] {
+                query!(
+                    "INSERT INTO validator_epoch_redelegations (validator_identity_key, epoch, redelegated_out, redelegated_in)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (epoch, validator_identity_key) DO UPDATE SET
+                        redelegated_out = validator_epoch_redelegations.redelegated_out + $3,
+                        redelegated_in = validator_epoch_redelegations.redelegated_in + $4",
+                    identity_key.encode_to_vec(),
+                    redelegation.epoch_index as i64,
+                    redelegated_out as i64,
+                    redelegated_in as i64,
+                )
+                .execute(&mut dbtx)
+                .await?;
+            }
+        }
 
-        // Mark spent notes as spent.
-        for nullifier in block.spent_nullifiers.into_iter() {
+        // Mark spent notes as spent.  The nullifiers table is partitioned by
+        // height, so it's the unpartitioned one that rejects a nullifier spent
+        // at an earlier height.
+        // Note commitments are recorded with the block rather than with the
+        // deferred writes, so that verification of the next block sees them.
+        for note_commitment in block.notes.keys() {
+            query!(
+                "INSERT INTO unique_note_commitments (note_commitment) VALUES ($1)",
+                &<[u8; 32]>::from(*note_commitment)[..],
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        for nullifier in block.spent_nullifiers.into_keys() {
+            query!(
+                "INSERT INTO unique_nullifiers (nullifier, height) VALUES ($1, $2)",
+                &<[u8; 32]>::from(nullifier)[..],
+                height as i64,
+            )
+            .execute(&mut dbtx)
+            .await?;
             query!(
                 "INSERT INTO nullifiers VALUES ($1, $2)",
                 &<[u8; 32]>::from(nullifier)[..],
@@ -273,7 +826,6 @@ impl Writer {
         }
 
         // Track the net change in delegations in this block.
-        let epoch_index = block.epoch.unwrap().index;
         for (identity_key, delegation_change) in block.delegation_changes {
             query!(
                 "INSERT INTO delegation_changes VALUES ($1, $2, $3)",
@@ -285,13 +837,112 @@ impl Writer {
             .await?;
         }
 
-        // Save any new assets found in the block to the asset registry.
+        // Track the fees collected in this epoch, to be paid out at its end.
+        if block.fees > 0 {
+            query!(
+                "INSERT INTO epoch_fees (epoch, collected) VALUES ($1, $2)
+                ON CONFLICT (epoch) DO UPDATE SET collected = epoch_fees.collected + $2",
+                epoch_index as i64,
+                block.fees as i64
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        // Record any denom metadata registered in this block.
+        for (asset_id, metadata) in &block.denom_metadata {
+            query!(
+                "INSERT INTO denom_metadata (asset_id, denom, description, display_exponent, symbol, height)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                &asset_id.to_bytes()[..],
+                metadata.denom.to_string(),
+                metadata.description,
+                metadata.display_exponent as i32,
+                metadata.symbol,
+                height as i64
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        // Record validators' votes for upgrade plans, each replacing the
+        // validator's previous vote.  A plan they scheduled is queued as a
+        // halt with the block's other scheduled actions.
+        for signed in &block.upgrade_proposals {
+            let proposal = &signed.proposal;
+            query!(
+                "INSERT INTO upgrade_votes (validator_identity_key, name, upgrade_height, height, sequence_number, emergency_halt)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (validator_identity_key) DO UPDATE SET
+                    name = excluded.name,
+                    upgrade_height = excluded.upgrade_height,
+                    height = excluded.height,
+                    sequence_number = excluded.sequence_number,
+                    emergency_halt = excluded.emergency_halt",
+                proposal.validator_identity.encode_to_vec(),
+                proposal.plan.name,
+                proposal.plan.height as i64,
+                height as i64,
+                proposal.sequence_number as i64,
+                proposal.plan.emergency_halt
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        // Queue the actions the block scheduled, and drop the ones it carried
+        // out or cancelled.
+        for (position, (due, action)) in block.scheduled_actions.iter().enumerate() {
+            let (due_height, due_epoch) = match due {
+                Due::Height(due_height) => (Some(*due_height as i64), None),
+                Due::Epoch(due_epoch) => (None, Some(*due_epoch as i64)),
+            };
+            query!(
+                "INSERT INTO scheduled_actions (height, position, due_height, due_epoch, kind, data)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                height as i64,
+                position as i32,
+                due_height,
+                due_epoch,
+                action.kind(),
+                action.encode_data()
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+        for scheduled in block
+            .completed_actions
+            .iter()
+            .chain(&block.cancelled_actions)
+        {
+            query!(
+                "DELETE FROM scheduled_actions WHERE height = $1 AND position = $2",
+                scheduled.height as i64,
+                scheduled.position as i32
+            )
+            .execute(&mut dbtx)
+            .await?;
+        }
+
+        // Save any new assets found in the block to the asset registry, and
+        // tally the net amount minted or burned since the last update.
         for (id, asset) in block.supply_updates {
+            let display_unit = asset.0.default_unit();
             query!(
-                r#"INSERT INTO assets (asset_id, denom, total_supply) VALUES ($1, $2, $3) ON CONFLICT (asset_id) DO UPDATE SET denom=$2, total_supply=$3"#,
+                r#"INSERT INTO assets (asset_id, denom, total_supply, display_denom, display_exponent, minted, burned)
+                VALUES ($1, $2, $3, $4, $5, $3, 0)
+                ON CONFLICT (asset_id) DO UPDATE SET
+                    denom = $2,
+                    total_supply = $3,
+                    display_denom = $4,
+                    display_exponent = $5,
+                    minted = assets.minted + GREATEST($3 - assets.total_supply, 0),
+                    burned = assets.burned + GREATEST(assets.total_supply - $3, 0)"#,
                 &id.to_bytes()[..],
                 asset.0.to_string(),
-                asset.1 as i64
+                asset.1 as i64,
+                display_unit.to_string(),
+                display_unit.exponent() as i32
             )
             .execute(&mut dbtx)
             .await?;
@@ -322,40 +973,349 @@ impl Writer {
             }
         }
 
-        if let Some(validator_statuses) = block.next_validator_statuses {
-            for status in validator_statuses {
-                query!(
-                    "UPDATE validators SET voting_power=$1 WHERE identity_key = $2",
-                    status.voting_power as i64,
-                    status.identity_key.encode_to_vec(),
-                )
-                .execute(&mut dbtx)
-                .await?;
-            }
-        }
-
-        let mut valid_anchors = self.valid_anchors_tx.borrow().clone();
-        if valid_anchors.len() >= NUM_RECENT_ANCHORS {
-            valid_anchors.pop_back();
-        }
-        valid_anchors.push_front(nct_anchor);
-        let next_rate_data = block.next_rates.map(|next_rates| {
-            next_rates
-                .into_iter()
-                .map(|rd| (rd.identity_key.clone(), rd))
-                .collect::<RateDataById>()
-        });
+        // Journal the deferred writes along with the block, so that they're
+        // finished even if pd stops before the background task does.
+        query!(
+            "INSERT INTO deferred_writes (height, data) VALUES ($1, $2)",
+            height as i64,
+            deferred.encode()?
+        )
+        .execute(&mut dbtx)
+        .await?;
 
         // Finally, commit the transaction and then update subscribers
+        faults::commit_error()?;
         dbtx.commit().await?;
+        self.finish_commit(height, nct_anchor, block.next_rates, deferred);
+
+        Ok(app_hash.to_vec())
+    }
+
+    /// Updates subscribers once a block's database transaction has been
+    /// committed, and starts its deferred writes.
+    fn finish_commit(
+        &mut self,
+        height: u64,
+        nct_anchor: merkle::Root,
+        next_rates: Option<Vec<RateData>>,
+        deferred: DeferredWrites,
+    ) {
+        let mut valid_anchors = self.valid_anchors_tx.borrow().clone();
+        valid_anchors.push(height, nct_anchor);
         // Errors in sends arise only if no one is listening -- not our problem.
-        let _ = self.height_tx.send(height.try_into().unwrap());
         let _ = self.valid_anchors_tx.send(valid_anchors);
-        if let Some(next_rate_data) = next_rate_data {
+        if let Some(next_rates) = next_rates {
+            let next_rate_data = next_rates
+                .into_iter()
+                .map(|rd| (rd.identity_key.clone(), rd))
+                .collect::<RateDataById>();
             let _ = self.next_rate_data_tx.send(next_rate_data);
         }
         // chain_params_tx is a no-op, currently chain params don't change
 
-        Ok(app_hash.to_vec())
+        // The height is announced by the deferred write task.
+        self.deferred_writes = Some(tokio::spawn(write_deferred(
+            self.pool.clone(),
+            self.height_tx.clone(),
+            self.timeouts,
+            height,
+            deferred,
+        )));
+    }
+}
+
+/// The encoding of the deferred writes journaled in the `deferred_writes`
+/// table, with `bincode`.
+const DEFERRED_WRITES_V1: u8 = 1;
+
+/// The parts of a committed block that aren't needed to compute the app hash
+/// or to verify later blocks: its compact block, and its notes.
+#[derive(Debug, Serialize, Deserialize)]
+struct DeferredWrites {
+    /// The code of the compact block's compression.
+    compression: i16,
+    /// The compact block's framing, as it's stored.
+    compact_block: Vec<u8>,
+    notes: Vec<DeferredNote>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DeferredNote {
+    note_commitment: [u8; 32],
+    transaction_id: [u8; 32],
+    position: u64,
+    ephemeral_key: [u8; 32],
+    encrypted_note: Vec<u8>,
+    encrypted_memo: Option<Vec<u8>>,
+}
+
+impl DeferredWrites {
+    fn new(
+        compression: Compression,
+        compact_block: Vec<u8>,
+        notes: &BTreeMap<note::Commitment, PositionedNoteData>,
+    ) -> Self {
+        Self {
+            compression: compression.code(),
+            compact_block,
+            notes: notes
+                .iter()
+                .map(|(note_commitment, positioned_note)| DeferredNote {
+                    note_commitment: (*note_commitment).into(),
+                    transaction_id: positioned_note.data.transaction_id,
+                    position: positioned_note.position,
+                    ephemeral_key: positioned_note.data.ephemeral_key.0,
+                    encrypted_note: positioned_note.data.encrypted_note.to_vec(),
+                    encrypted_memo: positioned_note
+                        .data
+                        .encrypted_memo
+                        .as_ref()
+                        .map(|memo| memo.0.to_vec()),
+                })
+                .collect(),
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut data = vec![DEFERRED_WRITES_V1];
+        bincode::serialize_into(&mut data, self).map_err(StateError::corrupt)?;
+        Ok(data)
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        match data.split_first() {
+            Some((&DEFERRED_WRITES_V1, data)) => {
+                bincode::deserialize(data).map_err(StateError::corrupt)
+            }
+            Some((version, _)) => Err(StateError::corrupt(anyhow::anyhow!(
+                "unknown deferred writes encoding version {}",
+                version
+            ))),
+            None => Err(StateError::corrupt(anyhow::anyhow!(
+                "stored deferred writes are empty"
+            ))),
+        }
+    }
+}
+
+/// Writes the deferred parts of a committed block, then announces the block's
+/// height.
+///
+/// The writes were journaled with the block, so if the node stops before this
+/// completes, they're finished when it starts again.  Transient failures are
+/// retried until the writes go through.
+async fn write_deferred(
+    pool: Pool<Postgres>,
+    height_tx: Arc<watch::Sender<block::Height>>,
+    timeouts: Timeouts,
+    height: u64,
+    deferred: DeferredWrites,
+) -> Result<()> {
+    let mut backoff = Backoff::default();
+    loop {
+        match try_write_deferred(&pool, &timeouts, height, &deferred).await {
+            Ok(()) => break,
+            Err(error) if error.is_transient() => {
+                tracing::warn!(%error, height, delay = ?backoff.delay(), "deferred block writes failed, retrying");
+                backoff.wait().await;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    // Errors in sends arise only if no one is listening -- not our problem.
+    if !faults::drop_notification() {
+        let _ = height_tx.send(height.try_into().unwrap());
+    }
+
+    Ok(())
+}
+
+async fn try_write_deferred(
+    pool: &Pool<Postgres>,
+    timeouts: &Timeouts,
+    height: u64,
+    deferred: &DeferredWrites,
+) -> Result<()> {
+    let mut dbtx = pool.begin().await?;
+    set_timeouts(&mut dbtx, timeouts).await?;
+
+    // A previous attempt may have gone through before its connection was
+    // lost, in which case its journal entry is gone too.
+    let journaled = query!(
+        "DELETE FROM deferred_writes WHERE height = $1 RETURNING height",
+        height as i64
+    )
+    .fetch_optional(&mut dbtx)
+    .await?
+    .is_some();
+    if !journaled {
+        return Ok(());
     }
+
+    query!(
+        "INSERT INTO compact_blocks (height, data, compression) VALUES ($1, $2, $3)",
+        height as i64,
+        &deferred.compact_block[..],
+        deferred.compression,
+    )
+    .execute(&mut dbtx)
+    .await?;
+
+    // Add newly created notes into the chain state.
+    for note in &deferred.notes {
+        query!(
+            r#"
+            INSERT INTO notes (
+                note_commitment,
+                transaction_id,
+                position,
+                height
+            ) VALUES ($1, $2, $3, $4)"#,
+            &note.note_commitment[..],
+            &note.transaction_id[..],
+            note.position as i64,
+            height as i64,
+        )
+        .execute(&mut dbtx)
+        .await?;
+        query!(
+            "INSERT INTO note_ciphertexts (note_commitment, ephemeral_key, encrypted_note, encrypted_memo)
+                VALUES ($1, $2, $3, $4)",
+            &note.note_commitment[..],
+            &note.ephemeral_key[..],
+            &note.encrypted_note[..],
+            note.encrypted_memo.as_deref(),
+        )
+        .execute(&mut dbtx)
+        .await?;
+    }
+
+    dbtx.commit().await?;
+
+    Ok(())
+}
+
+/// Applies the statement timeout, and the commit timeout as the limit on how
+/// long the transaction may sit idle, to a database transaction.
+///
+/// The idle limit makes the database roll back a transaction abandoned by a
+/// cancelled commit, rather than holding its locks until the connection is
+/// next used.
+async fn set_timeouts(
+    dbtx: &mut Transaction<'static, Postgres>,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    if let Some(statement) = timeouts.statement {
+        query!(
+            "SELECT set_config('statement_timeout', $1, true)",
+            format!("{}ms", statement.as_millis())
+        )
+        .fetch_one(&mut *dbtx)
+        .await?;
+    }
+    if let Some(commit) = timeouts.commit {
+        query!(
+            "SELECT set_config('idle_in_transaction_session_timeout', $1, true)",
+            format!("{}ms", commit.as_millis())
+        )
+        .fetch_one(&mut *dbtx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Records the current validator set as the validator set for the epoch with
+/// index `epoch_index`, returning the commitment to it.
+async fn snapshot_validator_set(
+    dbtx: &mut Transaction<'static, Postgres>,
+    epoch_index: u64,
+) -> Result<merkle::Root> {
+    query!(
+        "INSERT INTO validator_set_snapshots (epoch, validator_identity_key, consensus_key, voting_power, validator_state)
+            SELECT $1, identity_key, consensus_key, voting_power, validator_state FROM validators",
+        epoch_index as i64
+    )
+    .execute(&mut *dbtx)
+    .await?;
+
+    let rows = query!(
+        "SELECT validator_identity_key, consensus_key, voting_power, validator_state
+            FROM validator_set_snapshots
+            WHERE epoch = $1
+            ORDER BY validator_identity_key ASC",
+        epoch_index as i64
+    )
+    .fetch_all(&mut *dbtx)
+    .await?;
+    let previous = match epoch_index.checked_sub(1) {
+        Some(previous_epoch) => query!(
+            "SELECT commitment FROM validator_set_commitments WHERE epoch = $1",
+            previous_epoch as i64
+        )
+        .fetch_optional(&mut *dbtx)
+        .await?
+        .map(|row| merkle::Root::try_from(row.commitment.as_slice()))
+        .transpose()?,
+        None => None,
+    };
+
+    let entries = rows
+        .iter()
+        .map(|row| jellyfish::ValidatorSetEntry {
+            identity_key: &row.validator_identity_key,
+            consensus_key: &row.consensus_key,
+            voting_power: row.voting_power as u64,
+            state: &row.validator_state,
+        })
+        .collect::<Vec<_>>();
+    let commitment = jellyfish::validator_set_commitment(epoch_index, previous.as_ref(), &entries);
+
+    query!(
+        "INSERT INTO validator_set_commitments (epoch, commitment) VALUES ($1, $2)",
+        epoch_index as i64,
+        &commitment.to_bytes()[..]
+    )
+    .execute(&mut *dbtx)
+    .await?;
+
+    Ok(commitment)
+}
+
+/// Opens the DKG round of the epoch with index `epoch_index`, whose
+/// participants are the validators in its snapshotted validator set that are
+/// active with nonzero voting power.  No round is opened if there are none.
+async fn open_dkg_round(dbtx: &mut Transaction<'static, Postgres>, epoch_index: u64) -> Result<()> {
+    let participants = query!(
+        "SELECT validator_identity_key FROM validator_set_snapshots
+            WHERE epoch = $1 AND voting_power > 0 AND validator_state = $2
+            ORDER BY validator_identity_key ASC",
+        epoch_index as i64,
+        ValidatorStateName::Active.to_str(),
+    )
+    .fetch_all(&mut *dbtx)
+    .await?;
+    if participants.is_empty() {
+        return Ok(());
+    }
+
+    query!(
+        "INSERT INTO dkg_rounds (epoch, threshold) VALUES ($1, $2)",
+        epoch_index as i64,
+        dkg::threshold(participants.len()) as i64,
+    )
+    .execute(&mut *dbtx)
+    .await?;
+    for (row, index) in participants.iter().zip(1i64..) {
+        query!(
+            "INSERT INTO dkg_participants (epoch, participant_index, identity_key) VALUES ($1, $2, $3)",
+            epoch_index as i64,
+            index,
+            row.validator_identity_key,
+        )
+        .execute(&mut *dbtx)
+        .await?;
+    }
+
+    Ok(())
 }
@@ -1,6 +1,13 @@
-use std::{env::current_dir, fmt, fs::File, path::PathBuf, str::FromStr};
-
-use anyhow::{Context, Result};
+use std::{
+    env::current_dir,
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
 use directories::UserDirs;
 use penumbra_crypto::Address;
 use regex::{Captures, Regex};
@@ -67,11 +74,12 @@ where
 /// Hardcoded Tendermint config template. Should produce tendermint config similar to
 /// https://github.com/tendermint/tendermint/blob/6291d22f46f4c4f9121375af700dbdafa51577e7/cmd/tendermint/commands/init.go#L45
 /// There exists https://github.com/informalsystems/tendermint-rs/blob/a12118978f2ffea4042d6d38ebfb290d12611314/config/src/config.rs#L23 but
-/// this seemed more straightforward as only the moniker is changed right now.
-pub fn generate_tm_config(node_name: &str) -> String {
+/// this seemed more straightforward as only the moniker and remote signer address are changed right now.
+pub fn generate_tm_config(node_name: &str, remote_signer: Option<&TestnetRemoteSigner>) -> String {
     format!(
         include_str!("../../testnets/tm_config_template.toml"),
-        node_name
+        node_name,
+        remote_signer.map_or("", |signer| signer.laddr.as_str()),
     )
 }
 
@@ -100,6 +108,40 @@ pub struct TestnetValidator {
     pub funding_streams: Vec<TestnetFundingStream>,
     pub sequence_number: u32,
     pub voting_power: u32,
+    /// Sign with a remote signer instead of a generated local key.
+    #[serde(default)]
+    pub remote_signer: Option<TestnetRemoteSigner>,
+}
+
+/// A remote signer, such as tmkms, holding a validator's consensus key.
+#[derive(Debug, Deserialize)]
+pub struct TestnetRemoteSigner {
+    /// The address Tendermint listens on for the signer to connect to, e.g.
+    /// `tcp://0.0.0.0:26659`.
+    pub laddr: String,
+    /// The consensus key held by the signer, in Tendermint's JSON format.
+    pub consensus_key: tendermint::PublicKey,
+}
+
+impl TestnetRemoteSigner {
+    /// Checks that Tendermint can use the signer: that its address is one
+    /// Tendermint can listen on, and that its key is of the type the genesis
+    /// file allows.
+    pub fn validate(&self) -> Result<()> {
+        if !["tcp://", "unix://", "grpc://"]
+            .iter()
+            .any(|scheme| self.laddr.starts_with(scheme))
+        {
+            return Err(anyhow!(
+                "remote signer address {} must be a tcp://, unix://, or grpc:// address",
+                self.laddr
+            ));
+        }
+        if self.consensus_key.ed25519().is_none() {
+            return Err(anyhow!("remote signer consensus keys must be Ed25519 keys"));
+        }
+        Ok(())
+    }
 }
 
 impl From<&TestnetAllocation> for genesis::Allocation {
@@ -137,6 +179,18 @@ pub fn get_validator_state() -> String {
     .to_string()
 }
 
+/// Writes `contents` to a new file at `path`, failing if the file already
+/// exists, so that key material is never overwritten.
+pub fn write_new_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("couldn't create {}", path.display()))?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
 /// Expand tildes in a path.
 /// Modified from https://stackoverflow.com/a/68233480
 pub fn canonicalize_path(input: &str) -> PathBuf {
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_with::serde_as;
+
+use crate::{
+    fee::TransactionSkeleton,
+    state,
+    verify::{decode_canonical, StatelessTransactionExt},
+};
+
+/// The outcome of one stage of transaction verification.
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum CheckResult {
+    Passed,
+    Failed {
+        error: String,
+    },
+    /// The check wasn't run, because an earlier one failed or there was no
+    /// state to check against.
+    Skipped,
+}
+
+impl CheckResult {
+    fn failed(error: anyhow::Error) -> Self {
+        CheckResult::Failed {
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+/// A report of the checks pd runs on a transaction before applying it.
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct TransactionReport {
+    /// The transaction's ID, if it could be decoded.
+    #[serde_as(as = "Option<serde_with::hex::Hex>")]
+    pub id: Option<[u8; 32]>,
+    /// The size of the encoded transaction.
+    pub bytes: u64,
+    /// The fee paid by the transaction, if it could be decoded.
+    pub fee: Option<u64>,
+    /// The transaction's verification weight, if it could be decoded.
+    pub weight: Option<u64>,
+    /// The number of each kind of action, if it could be decoded.
+    pub actions: Option<TransactionSkeleton>,
+    /// Whether the transaction decoded from its canonical encoding.
+    pub decoding: CheckResult,
+    /// Whether the transaction is well-formed and its signatures and proofs
+    /// verify.
+    pub stateless: CheckResult,
+    /// The height of the state the stateful checks were run against.
+    pub height: Option<u64>,
+    /// Whether the transaction is consistent with the chain state.
+    pub stateful: CheckResult,
+}
+
+impl TransactionReport {
+    /// Returns whether every check that was run passed.
+    pub fn passed(&self) -> bool {
+        ![&self.decoding, &self.stateless, &self.stateful]
+            .iter()
+            .any(|result| matches!(result, CheckResult::Failed { .. }))
+    }
+}
+
+/// Runs the checks pd runs on a transaction before applying it, without a
+/// running node, for debugging rejected transactions.
+///
+/// The stateful checks are only run if a database is given, against its
+/// latest committed state, and only check the transaction on its own, not
+/// against other transactions in the same block.  The database isn't
+/// modified.
+pub async fn verify_transaction(
+    tx_bytes: &[u8],
+    database_uri: Option<&str>,
+) -> Result<TransactionReport> {
+    let mut report = TransactionReport {
+        id: None,
+        bytes: tx_bytes.len() as u64,
+        fee: None,
+        weight: None,
+        actions: None,
+        decoding: CheckResult::Skipped,
+        stateless: CheckResult::Skipped,
+        height: None,
+        stateful: CheckResult::Skipped,
+    };
+
+    let transaction = match decode_canonical(tx_bytes) {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            report.decoding = CheckResult::failed(e);
+            return Ok(report);
+        }
+    };
+    report.decoding = CheckResult::Passed;
+    let actions = TransactionSkeleton::from(&transaction);
+    report.id = Some(transaction.id());
+    report.fee = Some(transaction.transaction_body.fee.0);
+    report.weight = Some(actions.weight());
+    report.actions = Some(actions);
+
+    let transaction = match transaction.verify_stateless() {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            report.stateless = CheckResult::failed(e);
+            return Ok(report);
+        }
+    };
+    report.stateless = CheckResult::Passed;
+
+    if let Some(database_uri) = database_uri {
+        // Errors reaching the state are the command's, not the transaction's.
+        let (reader, _writer) = state::new(database_uri).await?;
+        report.height = Some(reader.height().await?.value());
+        report.stateful = match reader.verify_stateful(transaction).await {
+            Ok(_) => CheckResult::Passed,
+            Err(e) => CheckResult::failed(e),
+        };
+    }
+
+    Ok(report)
+}
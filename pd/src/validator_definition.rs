@@ -0,0 +1,174 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use penumbra_crypto::{
+    rdsa::{SigningKey, SpendAuth, VerificationKey},
+    Address,
+};
+use penumbra_proto::Protobuf;
+use penumbra_stake::{FundingStream, FundingStreams, IdentityKey, Validator, ValidatorDefinition};
+use penumbra_transaction::action::{SignedUpgradeProposal, UpgradePlan, UpgradeProposal};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+
+/// A validator's configuration, as written by its operator in a JSON or TOML
+/// file.  The identity key isn't included, since it's derived from the key the
+/// definition is signed with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidatorConfig {
+    pub name: String,
+    pub website: String,
+    pub description: String,
+    /// The sequence number of the last definition signed from this file.
+    pub sequence_number: u32,
+    /// The consensus key, in Tendermint's JSON format.
+    pub consensus_key: tendermint::PublicKey,
+    pub funding_streams: Vec<FundingStreamConfig>,
+}
+
+/// One of the funding streams in a [`ValidatorConfig`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FundingStreamConfig {
+    pub address: String,
+    pub rate_bps: u16,
+}
+
+impl ValidatorConfig {
+    /// Builds the validator described by this configuration, with the given
+    /// identity key.
+    pub fn to_validator(&self, identity_key: IdentityKey) -> Result<Validator> {
+        let funding_streams = self
+            .funding_streams
+            .iter()
+            .map(|stream| {
+                Ok(FundingStream {
+                    address: Address::from_str(&stream.address).map_err(|_| {
+                        anyhow!("invalid funding stream address {}", stream.address)
+                    })?,
+                    rate_bps: stream.rate_bps,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Validator {
+            identity_key,
+            consensus_key: self.consensus_key,
+            name: self.name.clone(),
+            website: self.website.clone(),
+            description: self.description.clone(),
+            funding_streams: FundingStreams::try_from(funding_streams)?,
+            sequence_number: self.sequence_number,
+        })
+    }
+}
+
+fn read_signing_key(signing_key_path: &Path) -> Result<SigningKey<SpendAuth>> {
+    serde_json::from_slice(
+        &fs::read(signing_key_path).context("couldn't read validator signing key")?,
+    )
+    .context("couldn't parse validator signing key")
+}
+
+/// Signs the validator definition described by the configuration file at
+/// `config_path` with the identity signing key at `signing_key_path`, and
+/// returns its protobuf encoding, ready to be included in a transaction.  The
+/// signature is over the protobuf encoding of the validator.
+///
+/// The chain only accepts definitions with increasing sequence numbers, so the
+/// sequence number in the file is incremented first, and the file is updated
+/// to match the signed definition.
+pub fn sign_definition(config_path: &Path, signing_key_path: &Path) -> Result<Vec<u8>> {
+    let signing_key = read_signing_key(signing_key_path)?;
+    let identity_key = IdentityKey(VerificationKey::from(&signing_key));
+
+    let format = ConfigFormat::from_path(config_path)?;
+    let config_text =
+        fs::read_to_string(config_path).context("couldn't read validator configuration")?;
+    let mut config = format.parse(&config_text)?;
+    config.sequence_number = config
+        .sequence_number
+        .checked_add(1)
+        .ok_or_else(|| anyhow!("the sequence number can't be incremented any further"))?;
+
+    let validator = config.to_validator(identity_key)?;
+    let auth_sig = signing_key.sign(OsRng, &validator.encode_to_vec());
+    let definition = ValidatorDefinition {
+        validator,
+        auth_sig,
+    };
+
+    fs::write(config_path, format.serialize(&config)?)
+        .context("couldn't update validator configuration")?;
+    tracing::info!(
+        %identity_key,
+        sequence_number = config.sequence_number,
+        "signed validator definition"
+    );
+
+    Ok(definition.encode_to_vec())
+}
+
+/// Signs a vote for the upgrade plan `plan` on the chain `chain_id` with the
+/// identity signing key at `signing_key_path`, and returns its protobuf
+/// encoding, ready to be included in a transaction.  The signature is over the
+/// protobuf encoding of the proposal.
+///
+/// `sequence_number` must be greater than that of the validator's previous
+/// vote.
+pub fn sign_upgrade_proposal(
+    plan: UpgradePlan,
+    chain_id: String,
+    sequence_number: u64,
+    signing_key_path: &Path,
+) -> Result<Vec<u8>> {
+    let signing_key = read_signing_key(signing_key_path)?;
+    let identity_key = IdentityKey(VerificationKey::from(&signing_key));
+
+    let proposal = UpgradeProposal {
+        validator_identity: identity_key,
+        plan,
+        chain_id,
+        sequence_number,
+    };
+    let auth_sig = signing_key.sign(OsRng, &proposal.encode_to_vec());
+    tracing::info!(
+        %identity_key,
+        plan = %proposal.plan,
+        sequence_number = proposal.sequence_number,
+        "signed upgrade proposal"
+    );
+
+    Ok(SignedUpgradeProposal { proposal, auth_sig }.encode_to_vec())
+}
+
+enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            _ => Err(anyhow!(
+                "validator configuration {} must be a .json or .toml file",
+                path.display()
+            )),
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<ValidatorConfig> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(text).context("invalid JSON"),
+            ConfigFormat::Toml => toml::from_str(text).context("invalid TOML"),
+        }
+    }
+
+    fn serialize(&self, config: &ValidatorConfig) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(config)?,
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+        })
+    }
+}
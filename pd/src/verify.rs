@@ -1,14 +1,26 @@
 use std::collections::{BTreeMap, BTreeSet};
 
-use penumbra_crypto::{ka, merkle, note, Nullifier};
-use penumbra_stake::{Delegate, IdentityKey, Undelegate, Validator};
+use penumbra_crypto::{ka, memo::MemoCiphertext, merkle, note, Nullifier};
+use penumbra_stake::{
+    Delegate, DkgComplaint, EncryptedFlow, FlowDecryption, IdentityKey, Redelegate,
+    SignedDkgDealing, Undelegate, Validator,
+};
+use penumbra_transaction::action::{DenomMetadata, SignedUpgradeProposal};
 
+mod action;
+mod pool;
+mod proof;
 mod stateful;
 mod stateless;
+mod structure;
 
+pub use action::{ActionKind, REGISTRY};
+pub use pool::{on_verification_pool, start_verification_pool, VerificationPoolConfig};
+pub use proof::{verify_proof, OutputPublicInputs, Proof, SpendPublicInputs};
 // TODO: eliminate (#374)
-pub use stateful::mark_genesis_as_verified;
-pub use stateless::StatelessTransactionExt;
+pub use stateful::{mark_genesis_as_verified, AnchorVerdicts};
+pub use stateless::{decode_canonical, StatelessTransactionExt};
+pub use structure::{check_structure, StructureError};
 
 #[cfg(test)]
 mod tests;
@@ -17,6 +29,9 @@ mod tests;
 pub struct NoteData {
     pub ephemeral_key: ka::Public,
     pub encrypted_note: [u8; note::NOTE_CIPHERTEXT_BYTES],
+    /// The memo attached by the output that created the note, or `None` for
+    /// notes the chain creates itself.
+    pub encrypted_memo: Option<MemoCiphertext>,
     pub transaction_id: [u8; 32],
 }
 
@@ -28,11 +43,14 @@ pub struct PositionedNoteData {
 
 /// `PendingTransaction` holds data after stateless checks have been applied.
 /// TODO this is a bad name
+#[derive(Debug)]
 pub struct PendingTransaction {
     /// Transaction ID.
     pub id: [u8; 32],
     /// Root of the note commitment tree.
     pub root: merkle::Root,
+    /// The kinds of actions in this transaction.
+    pub action_kinds: BTreeSet<ActionKind>,
     /// Note data to add from outputs in this transaction.
     pub new_notes: BTreeMap<note::Commitment, NoteData>,
     /// List of spent nullifiers from spends in this transaction.
@@ -41,8 +59,26 @@ pub struct PendingTransaction {
     pub delegations: Vec<Delegate>,
     /// Undelegations performed in this transaction.
     pub undelegations: Vec<Undelegate>,
+    /// Redelegations performed in this transaction.
+    pub redelegations: Vec<Redelegate>,
+    /// DKG dealings in this transaction, with verified signatures.
+    pub dkg_dealings: Vec<SignedDkgDealing>,
+    /// Encrypted delegations and undelegations in this transaction.
+    pub encrypted_flows: Vec<EncryptedFlow>,
+    /// Flow decryptions in this transaction.
+    pub flow_decryptions: Vec<FlowDecryption>,
+    /// DKG complaints in this transaction.
+    pub dkg_complaints: Vec<DkgComplaint>,
     /// Validators defined in the transaction.
     pub validators: Vec<Validator>,
+    /// Denom metadata registered in the transaction.
+    pub denom_metadata: Vec<DenomMetadata>,
+    /// Upgrade proposals in the transaction, with verified signatures.
+    pub upgrade_proposals: Vec<SignedUpgradeProposal>,
+    /// The fee paid by the transaction.
+    pub fee: u64,
+    /// The transaction's verification weight.
+    pub weight: u64,
 }
 
 /// `VerifiedTransaction` represents a transaction after all checks have passed.
@@ -56,4 +92,24 @@ pub struct VerifiedTransaction {
     pub spent_nullifiers: BTreeSet<Nullifier>,
     /// Net delegations performed in this transaction.
     pub delegation_changes: BTreeMap<IdentityKey, i64>,
+    /// Redelegations performed in this transaction, which are also included
+    /// in `delegation_changes`.
+    pub redelegations: Vec<Redelegate>,
+    /// DKG dealings in this transaction.
+    pub dkg_dealings: Vec<SignedDkgDealing>,
+    /// Encrypted delegations and undelegations in this transaction, with
+    /// verified proofs.
+    pub encrypted_flows: Vec<EncryptedFlow>,
+    /// Flow decryptions in this transaction, with verified shares.
+    pub flow_decryptions: Vec<FlowDecryption>,
+    /// DKG complaints in this transaction, each justified.
+    pub dkg_complaints: Vec<DkgComplaint>,
+    /// Denom metadata registered in the transaction.
+    pub denom_metadata: Vec<DenomMetadata>,
+    /// Upgrade proposals in the transaction, from active validators.
+    pub upgrade_proposals: Vec<SignedUpgradeProposal>,
+    /// The fee paid by the transaction.
+    pub fee: u64,
+    /// The transaction's verification weight.
+    pub weight: u64,
 }
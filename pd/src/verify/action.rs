@@ -0,0 +1,213 @@
+//! Handlers for each kind of transaction action.
+//!
+//! Verification and execution dispatch on the kind of each action through
+//! the [`REGISTRY`], so supporting a new kind of action means writing an
+//! [`ActionHandler`] for it and registering it, rather than extending the
+//! core verification code.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use penumbra_crypto::merkle;
+use penumbra_transaction::Action;
+
+use super::{PendingTransaction, VerifiedTransaction};
+use crate::{state, PendingBlock};
+
+mod shielded_pool;
+mod stake;
+mod upgrade;
+
+/// The kinds of [`Action`]s a transaction can contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActionKind {
+    Output,
+    Spend,
+    Delegate,
+    Undelegate,
+    Redelegate,
+    DkgDealing,
+    EncryptedFlow,
+    FlowDecryption,
+    DkgComplaint,
+    ValidatorDefinition,
+    DenomMetadata,
+    UpgradeProposal,
+}
+
+impl ActionKind {
+    /// Every kind of action.
+    pub const ALL: [ActionKind; 12] = [
+        ActionKind::Output,
+        ActionKind::Spend,
+        ActionKind::Delegate,
+        ActionKind::Undelegate,
+        ActionKind::Redelegate,
+        ActionKind::DkgDealing,
+        ActionKind::EncryptedFlow,
+        ActionKind::FlowDecryption,
+        ActionKind::DkgComplaint,
+        ActionKind::ValidatorDefinition,
+        ActionKind::DenomMetadata,
+        ActionKind::UpgradeProposal,
+    ];
+
+    /// The name of the kind of action, as used in the chain parameters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionKind::Output => "output",
+            ActionKind::Spend => "spend",
+            ActionKind::Delegate => "delegate",
+            ActionKind::Undelegate => "undelegate",
+            ActionKind::Redelegate => "redelegate",
+            ActionKind::DkgDealing => "dkg_dealing",
+            ActionKind::EncryptedFlow => "encrypted_flow",
+            ActionKind::FlowDecryption => "flow_decryption",
+            ActionKind::DkgComplaint => "dkg_complaint",
+            ActionKind::ValidatorDefinition => "validator_definition",
+            ActionKind::DenomMetadata => "denom_metadata",
+            ActionKind::UpgradeProposal => "upgrade_proposal",
+        }
+    }
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ActionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ActionKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown kind of action {:?}", s))
+    }
+}
+
+impl From<&Action> for ActionKind {
+    fn from(action: &Action) -> Self {
+        match action {
+            Action::Output(_) => ActionKind::Output,
+            Action::Spend(_) => ActionKind::Spend,
+            Action::Delegate(_) => ActionKind::Delegate,
+            Action::Undelegate(_) => ActionKind::Undelegate,
+            Action::Redelegate(_) => ActionKind::Redelegate,
+            Action::DkgDealing(_) => ActionKind::DkgDealing,
+            Action::EncryptedFlow(_) => ActionKind::EncryptedFlow,
+            Action::FlowDecryption(_) => ActionKind::FlowDecryption,
+            Action::DkgComplaint(_) => ActionKind::DkgComplaint,
+            Action::ValidatorDefinition(_) => ActionKind::ValidatorDefinition,
+            Action::DenomMetadata(_) => ActionKind::DenomMetadata,
+            Action::UpgradeProposal(_) => ActionKind::UpgradeProposal,
+        }
+    }
+}
+
+/// The parts of a transaction that stateless checks of its actions depend on.
+pub struct StatelessContext {
+    /// The transaction ID.
+    pub id: [u8; 32],
+    /// The hash signed by the transaction's spend authorization signatures.
+    pub sighash: [u8; 64],
+    /// The note commitment tree root the transaction's spends are proven against.
+    pub merkle_root: merkle::Root,
+}
+
+/// Verifies and executes the actions of one or more kinds.
+///
+/// Stateless checks are made on each action individually, as it's added to
+/// the pending transaction.  Stateful checks and execution handle all of a
+/// transaction's actions of the handler's kinds at once, so that they can be
+/// checked against a single view of the state.
+#[async_trait]
+pub trait ActionHandler: Send + Sync {
+    /// The kinds of actions this handler handles.
+    fn kinds(&self) -> &'static [ActionKind];
+
+    /// Checks an action without reference to the chain state, such as its
+    /// signatures and proofs, and adds it to the pending transaction.
+    fn check_stateless(
+        &self,
+        context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error>;
+
+    /// Checks the handler's actions in a transaction against the chain
+    /// state, and adds their effects to the verified transaction.
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error>;
+
+    /// Adds the effects of the handler's actions in a verified transaction to
+    /// the pending block.
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock);
+}
+
+/// A mapping from each kind of action to the handler for it.
+pub struct Registry {
+    /// The handlers, in the order they were registered, which is the order
+    /// their stateful checks and execution run in.
+    handlers: Vec<Box<dyn ActionHandler>>,
+    by_kind: BTreeMap<ActionKind, usize>,
+}
+
+/// The handlers for the actions pd supports.
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::default);
+
+impl Registry {
+    /// Registers a handler for its kinds of actions.
+    ///
+    /// # Panics
+    ///
+    /// If a handler is already registered for one of the kinds.
+    pub fn register(&mut self, handler: impl ActionHandler + 'static) {
+        let index = self.handlers.len();
+        for kind in handler.kinds() {
+            if self.by_kind.insert(*kind, index).is_some() {
+                panic!("a handler for {:?} actions is already registered", kind);
+            }
+        }
+        self.handlers.push(Box::new(handler));
+    }
+
+    /// Returns the handler for a kind of action, if it's supported.
+    pub fn get(&self, kind: ActionKind) -> Option<&dyn ActionHandler> {
+        self.by_kind
+            .get(&kind)
+            .map(|index| self.handlers[*index].as_ref())
+    }
+
+    /// Returns every registered handler, in the order they were registered.
+    pub fn handlers(&self) -> impl Iterator<Item = &dyn ActionHandler> {
+        self.handlers.iter().map(|handler| handler.as_ref())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            handlers: Vec::new(),
+            by_kind: BTreeMap::new(),
+        };
+        registry.register(shielded_pool::OutputHandler);
+        registry.register(shielded_pool::SpendHandler);
+        registry.register(stake::DelegationHandler);
+        registry.register(stake::DkgDealingHandler);
+        registry.register(stake::DkgComplaintHandler);
+        registry.register(stake::EncryptedFlowHandler);
+        registry.register(stake::FlowDecryptionHandler);
+        registry.register(shielded_pool::DenomMetadataHandler);
+        registry.register(upgrade::UpgradeProposalHandler);
+        registry
+    }
+}
@@ -0,0 +1,259 @@
+use std::time::Instant;
+
+use anyhow::{Context, Error};
+use async_trait::async_trait;
+use metrics::histogram;
+use penumbra_crypto::asset;
+use penumbra_transaction::Action;
+
+use super::{ActionHandler, ActionKind, StatelessContext};
+use crate::{
+    components::AlreadySpent,
+    state,
+    verify::{
+        verify_proof, NoteData, OutputPublicInputs, PendingTransaction, SpendPublicInputs,
+        VerifiedTransaction,
+    },
+    PendingBlock,
+};
+
+/// Handles [`Output`](penumbra_transaction::action::Output)s, which add new
+/// notes to the note commitment tree.
+pub struct OutputHandler;
+
+#[async_trait]
+impl ActionHandler for OutputHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::Output]
+    }
+
+    fn check_stateless(
+        &self,
+        context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let output = match action {
+            Action::Output(output) => output,
+            _ => unreachable!("only outputs are dispatched to the output handler"),
+        };
+
+        let proof_result = verify_proof(
+            &output.body.proof,
+            OutputPublicInputs {
+                value_commitment: output.body.value_commitment,
+                note_commitment: output.body.note_commitment,
+                ephemeral_key: output.body.ephemeral_key,
+            },
+        );
+        if proof_result.is_err() {
+            // TODO should the verification error be bubbled up here?
+            return Err(anyhow::anyhow!("An output proof did not verify"));
+        }
+
+        // Check the note commitment has not been created already in this
+        // transaction.
+        if transaction
+            .new_notes
+            .contains_key(&output.body.note_commitment)
+        {
+            return Err(anyhow::anyhow!("Duplicate note commitment"));
+        }
+
+        transaction.new_notes.insert(
+            output.body.note_commitment,
+            NoteData {
+                ephemeral_key: output.body.ephemeral_key,
+                encrypted_note: output.body.encrypted_note,
+                encrypted_memo: Some(output.encrypted_memo),
+                transaction_id: context.id,
+            },
+        );
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        let existing_note_commitments = reader
+            .check_note_commitments(&transaction.new_notes.keys().copied().collect())
+            .await?;
+        if !existing_note_commitments.is_empty() {
+            return Err(anyhow::anyhow!(
+                "note commitments already created in state: {:?}",
+                existing_note_commitments
+            ));
+        }
+
+        verified.new_notes = transaction.new_notes.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        for (note_commitment, data) in &transaction.new_notes {
+            pending_block.add_note(*note_commitment, data.clone());
+        }
+    }
+}
+
+/// Handles [`Spend`](penumbra_transaction::action::Spend)s, which reveal the
+/// nullifiers of spent notes.
+pub struct SpendHandler;
+
+#[async_trait]
+impl ActionHandler for SpendHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::Spend]
+    }
+
+    fn check_stateless(
+        &self,
+        context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let spend = match action {
+            Action::Spend(spend) => spend,
+            _ => unreachable!("only spends are dispatched to the spend handler"),
+        };
+
+        let start = Instant::now();
+        let auth_sig_result = spend.body.rk.verify(&context.sighash, &spend.auth_sig);
+        histogram!(
+            "node_signature_verification_seconds",
+            start.elapsed().as_secs_f64(),
+            "signature" => "spend_auth"
+        );
+        auth_sig_result.context("spend auth signature failed to verify")?;
+
+        let proof_result = verify_proof(
+            &spend.body.proof,
+            SpendPublicInputs {
+                anchor: context.merkle_root.clone(),
+                value_commitment: spend.body.value_commitment,
+                nullifier: spend.body.nullifier.clone(),
+                rk: spend.body.rk,
+            },
+        );
+        if proof_result.is_err() {
+            // TODO should the verification error be bubbled up here?
+            return Err(anyhow::anyhow!("A spend proof did not verify"));
+        }
+
+        // Check nullifier has not been revealed already in this transaction.
+        if transaction
+            .spent_nullifiers
+            .contains(&spend.body.nullifier.clone())
+        {
+            return Err(anyhow::anyhow!("Double spend"));
+        }
+
+        transaction
+            .spent_nullifiers
+            .insert(spend.body.nullifier.clone());
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        let existing_nullifiers = reader
+            .check_nullifiers(&transaction.spent_nullifiers)
+            .await?;
+        if let Some(nullifier) = existing_nullifiers.into_iter().next() {
+            return Err(AlreadySpent { nullifier }.into());
+        }
+
+        verified.spent_nullifiers = transaction.spent_nullifiers.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        for nullifier in &transaction.spent_nullifiers {
+            pending_block
+                .spent_nullifiers
+                .insert(nullifier.clone(), transaction.id);
+        }
+    }
+}
+
+/// Handles [`DenomMetadata`](penumbra_transaction::action::DenomMetadata)
+/// registrations.
+pub struct DenomMetadataHandler;
+
+#[async_trait]
+impl ActionHandler for DenomMetadataHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::DenomMetadata]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let metadata = match action {
+            Action::DenomMetadata(metadata) => metadata,
+            _ => unreachable!("only denom metadata is dispatched to the denom metadata handler"),
+        };
+
+        // The metadata itself was validated when the transaction was decoded;
+        // its uniqueness depends on the chain state.
+        transaction.denom_metadata.push(metadata);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        for metadata in &transaction.denom_metadata {
+            // The asset registry's units take precedence, so registered
+            // metadata can't change how a known asset is displayed.
+            if metadata.denom.units().len() > 1 {
+                return Err(anyhow::anyhow!(
+                    "{} already has display units in the asset registry",
+                    metadata.denom
+                ));
+            }
+            if asset::REGISTRY.parse_denom(&metadata.symbol).is_none() {
+                return Err(anyhow::anyhow!(
+                    "symbol {} is a display unit in the asset registry",
+                    metadata.symbol
+                ));
+            }
+            if reader.denom_metadata(metadata.denom.id()).await?.is_some() {
+                return Err(anyhow::anyhow!(
+                    "metadata for {} is already registered",
+                    metadata.denom
+                ));
+            }
+            if reader.asset_id_by_symbol(&metadata.symbol).await?.is_some() {
+                return Err(anyhow::anyhow!(
+                    "symbol {} is already registered",
+                    metadata.symbol
+                ));
+            }
+        }
+
+        verified.denom_metadata = transaction.denom_metadata.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        for metadata in &transaction.denom_metadata {
+            pending_block
+                .denom_metadata
+                .insert(metadata.denom.id(), metadata.clone());
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use metrics::histogram;
+use penumbra_transaction::Action;
+
+use super::{ActionHandler, ActionKind, StatelessContext};
+use crate::{
+    state,
+    verify::{stateful::delegation_changes, PendingTransaction, VerifiedTransaction},
+    PendingBlock,
+};
+
+/// Handles [`Delegate`](penumbra_stake::Delegate),
+/// [`Undelegate`](penumbra_stake::Undelegate), and
+/// [`Redelegate`](penumbra_stake::Redelegate) actions together, since they're
+/// all checked against the same rate data and tallied into the same
+/// delegation changes.
+pub struct DelegationHandler;
+
+#[async_trait]
+impl ActionHandler for DelegationHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[
+            ActionKind::Delegate,
+            ActionKind::Undelegate,
+            ActionKind::Redelegate,
+        ]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        // There are currently no stateless verification checks than the ones implied by
+        // the binding signature.
+        match action {
+            Action::Delegate(delegate) => transaction.delegations.push(delegate),
+            Action::Undelegate(undelegate) => transaction.undelegations.push(undelegate),
+            Action::Redelegate(redelegate) => transaction.redelegations.push(redelegate),
+            _ => unreachable!("only delegation actions are dispatched to the delegation handler"),
+        }
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader.check_min_delegation_amount(transaction)?;
+
+        // Check every delegation change against a single snapshot of the rate
+        // data, so that they're all checked against the same epoch even if an
+        // epoch boundary is crossed while this transaction is being verified.
+        let delegation_changes = {
+            let next_rate_data = reader.next_rate_data_rx().borrow();
+            delegation_changes(
+                &next_rate_data,
+                &transaction.delegations,
+                &transaction.undelegations,
+                &transaction.redelegations,
+            )?
+        };
+        reader
+            .check_redelegation_limits(&transaction.redelegations, &[])
+            .await?;
+        reader
+            .check_delegation_cap(&delegation_changes, &BTreeMap::new())
+            .await?;
+
+        verified.delegation_changes = delegation_changes;
+        verified.redelegations = transaction.redelegations.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        // Tally the delegation changes in this transaction
+        for (identity_key, delegation_change) in &transaction.delegation_changes {
+            let delegation_change = *delegation_change;
+            let (delegated, undelegated) = pending_block
+                .delegation_volume
+                .entry(identity_key.clone())
+                .or_default();
+            if delegation_change > 0 {
+                *delegated += delegation_change as u64;
+            } else {
+                *undelegated += delegation_change.unsigned_abs();
+            }
+            *pending_block
+                .delegation_changes
+                .entry(identity_key.clone())
+                .or_insert(0) += delegation_change;
+        }
+
+        pending_block
+            .redelegations
+            .extend(transaction.redelegations.iter().cloned());
+    }
+}
+
+/// Handles [`SignedDkgDealing`](penumbra_stake::SignedDkgDealing)s.
+pub struct DkgDealingHandler;
+
+#[async_trait]
+impl ActionHandler for DkgDealingHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::DkgDealing]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let dealing = match action {
+            Action::DkgDealing(dealing) => dealing,
+            _ => unreachable!("only DKG dealings are dispatched to the DKG dealing handler"),
+        };
+
+        // Whether the dealer may deal depends on the chain state.
+        let start = Instant::now();
+        let dealing_result = dealing.verify();
+        histogram!(
+            "node_signature_verification_seconds",
+            start.elapsed().as_secs_f64(),
+            "signature" => "dkg_dealing"
+        );
+        dealing_result?;
+        transaction.dkg_dealings.push(dealing);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader
+            .check_dkg_dealings(&transaction.dkg_dealings, &[])
+            .await?;
+
+        verified.dkg_dealings = transaction.dkg_dealings.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        pending_block
+            .dkg_dealings
+            .extend(transaction.dkg_dealings.iter().cloned());
+    }
+}
+
+/// Handles [`DkgComplaint`](penumbra_stake::DkgComplaint)s.
+pub struct DkgComplaintHandler;
+
+#[async_trait]
+impl ActionHandler for DkgComplaintHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::DkgComplaint]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let complaint = match action {
+            Action::DkgComplaint(complaint) => complaint,
+            _ => unreachable!("only DKG complaints are dispatched to the DKG complaint handler"),
+        };
+
+        // A complaint carries its own proof, which can only be checked
+        // against the dealing it's about, so it's all checked statefully.
+        transaction.dkg_complaints.push(complaint);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader
+            .check_dkg_complaints(&transaction.dkg_complaints, &[])
+            .await?;
+
+        verified.dkg_complaints = transaction.dkg_complaints.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        pending_block
+            .dkg_complaints
+            .extend(transaction.dkg_complaints.iter().cloned());
+    }
+}
+
+/// Handles [`EncryptedFlow`](penumbra_stake::EncryptedFlow)s.
+pub struct EncryptedFlowHandler;
+
+#[async_trait]
+impl ActionHandler for EncryptedFlowHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::EncryptedFlow]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let flow = match action {
+            Action::EncryptedFlow(flow) => flow,
+            _ => unreachable!("only encrypted flows are dispatched to the encrypted flow handler"),
+        };
+
+        // The flow's proof is checked against the validator's rate data and
+        // the epoch's threshold key, which depend on the chain state.
+        transaction.encrypted_flows.push(flow);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader
+            .check_encrypted_flows(&transaction.encrypted_flows)
+            .await?;
+
+        verified.encrypted_flows = transaction.encrypted_flows.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        pending_block
+            .encrypted_flows
+            .extend(transaction.encrypted_flows.iter().cloned());
+    }
+}
+
+/// Handles [`FlowDecryption`](penumbra_stake::FlowDecryption)s.
+pub struct FlowDecryptionHandler;
+
+#[async_trait]
+impl ActionHandler for FlowDecryptionHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::FlowDecryption]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let decryption = match action {
+            Action::FlowDecryption(decryption) => decryption,
+            _ => {
+                unreachable!("only flow decryptions are dispatched to the flow decryption handler")
+            }
+        };
+
+        // The shares are checked against the summed flows, which depend on
+        // the chain state.
+        transaction.flow_decryptions.push(decryption);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader
+            .check_flow_decryptions(&transaction.flow_decryptions, &[])
+            .await?;
+
+        verified.flow_decryptions = transaction.flow_decryptions.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        pending_block
+            .flow_decryptions
+            .extend(transaction.flow_decryptions.iter().cloned());
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use metrics::histogram;
+use penumbra_transaction::Action;
+
+use super::{ActionHandler, ActionKind, StatelessContext};
+use crate::{
+    state,
+    verify::{PendingTransaction, VerifiedTransaction},
+    PendingBlock,
+};
+
+/// Handles [`SignedUpgradeProposal`](penumbra_transaction::action::SignedUpgradeProposal)s,
+/// validators' votes to schedule an upgrade plan.
+pub struct UpgradeProposalHandler;
+
+#[async_trait]
+impl ActionHandler for UpgradeProposalHandler {
+    fn kinds(&self) -> &'static [ActionKind] {
+        &[ActionKind::UpgradeProposal]
+    }
+
+    fn check_stateless(
+        &self,
+        _context: &StatelessContext,
+        action: Action,
+        transaction: &mut PendingTransaction,
+    ) -> Result<(), Error> {
+        let proposal = match action {
+            Action::UpgradeProposal(proposal) => proposal,
+            _ => unreachable!(
+                "only upgrade proposals are dispatched to the upgrade proposal handler"
+            ),
+        };
+
+        // Whether the validator may vote depends on the chain state.
+        let start = Instant::now();
+        let signature_result = proposal.verify();
+        histogram!(
+            "node_signature_verification_seconds",
+            start.elapsed().as_secs_f64(),
+            "signature" => "upgrade_proposal"
+        );
+        signature_result?;
+        transaction.upgrade_proposals.push(proposal);
+        Ok(())
+    }
+
+    async fn check_stateful(
+        &self,
+        reader: &state::Reader,
+        transaction: &PendingTransaction,
+        verified: &mut VerifiedTransaction,
+    ) -> Result<(), Error> {
+        reader
+            .check_upgrade_proposals(&transaction.upgrade_proposals, &[])
+            .await?;
+
+        verified.upgrade_proposals = transaction.upgrade_proposals.clone();
+        Ok(())
+    }
+
+    fn execute(&self, transaction: &VerifiedTransaction, pending_block: &mut PendingBlock) {
+        pending_block
+            .upgrade_proposals
+            .extend(transaction.upgrade_proposals.iter().cloned());
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use anyhow::{anyhow, Error};
+use once_cell::sync::OnceCell;
+use tokio::sync::oneshot;
+
+/// The size of the verification pool and how its threads are scheduled.
+#[derive(Debug, Clone)]
+pub struct VerificationPoolConfig {
+    /// The number of threads verifying transactions.
+    pub threads: usize,
+    /// Whether to pin each thread to its own CPU core, wrapping around if
+    /// there are more threads than cores.
+    pub pin_cores: bool,
+}
+
+impl Default for VerificationPoolConfig {
+    fn default() -> Self {
+        // One thread per core keeps every core busy verifying a full block,
+        // without oversubscribing them.
+        Self {
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            pin_cores: false,
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The threads stateless verification runs on, kept apart from Tokio's
+/// blocking pool so that the CPU verification may use can be bounded.
+struct VerificationPool {
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+static POOL: OnceCell<VerificationPool> = OnceCell::new();
+
+impl VerificationPool {
+    fn start(config: VerificationPoolConfig) -> Self {
+        let threads = config.threads.max(1);
+        let cores = if config.pin_cores {
+            match core_affinity::get_core_ids() {
+                Some(cores) if !cores.is_empty() => Some(cores),
+                _ => {
+                    tracing::warn!(
+                        "couldn't list the CPU cores, so verification threads aren't pinned"
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        tracing::info!(
+            threads,
+            pin_cores = cores.is_some(),
+            "starting verification pool"
+        );
+
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for index in 0..threads {
+            let queue = queue.clone();
+            let core = cores.as_ref().map(|cores| cores[index % cores.len()]);
+            thread::Builder::new()
+                .name(format!("pd-verify-{}", index))
+                .spawn(move || {
+                    if let Some(core) = core {
+                        core_affinity::set_for_current(core);
+                    }
+                    loop {
+                        // The lock is released as soon as a job is taken, so
+                        // that the other threads can take the next one.
+                        let job = match queue
+                            .lock()
+                            .expect("no thread panics holding the lock")
+                            .recv()
+                        {
+                            Ok(job) => job,
+                            Err(_) => return,
+                        };
+                        // A panicking job drops its result sender, which the
+                        // caller sees as an error, and the thread carries on.
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                })
+                .expect("can spawn verification threads");
+        }
+
+        Self {
+            jobs: Mutex::new(jobs),
+        }
+    }
+}
+
+/// Starts the verification pool.
+///
+/// This must be called before any transaction is verified, since otherwise
+/// the pool is started with the default configuration.
+pub fn start_verification_pool(config: VerificationPoolConfig) -> Result<(), Error> {
+    POOL.set(VerificationPool::start(config))
+        .map_err(|_| anyhow!("the verification pool is already running"))
+}
+
+/// Runs `f` on the verification pool, returning its result.
+pub async fn on_verification_pool<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, Error> {
+    let pool = POOL.get_or_init(|| VerificationPool::start(Default::default()));
+
+    let (tx, rx) = oneshot::channel();
+    pool.jobs
+        .lock()
+        .expect("no thread panics holding the lock")
+        .send(Box::new(move || {
+            // The caller may have gone away, in which case there's no one to
+            // give the result to.
+            let _ = tx.send(f());
+        }))
+        .map_err(|_| anyhow!("the verification pool has stopped"))?;
+
+    rx.await
+        .map_err(|_| anyhow!("a verification thread panicked"))
+}
@@ -0,0 +1,86 @@
+//! The proofs carried by actions, behind a common interface.
+//!
+//! Handlers verify proofs through the [`Proof`] trait rather than calling a
+//! particular proving system, so that a different proving system can be
+//! introduced for a new version of an action by implementing [`Proof`] for its
+//! proofs, without changing `verify_stateless` or its callers.
+
+use std::time::Instant;
+
+use anyhow::Error;
+use metrics::histogram;
+use penumbra_crypto::{
+    ka, merkle, note,
+    proofs::transparent::{OutputProof, SpendProof},
+    rdsa::{SpendAuth, VerificationKey},
+    value, Nullifier,
+};
+
+/// A proof about an action, verified against public inputs taken from the
+/// action and its transaction.
+pub trait Proof {
+    /// The public inputs the proof is verified against.
+    type PublicInputs;
+
+    /// The kind of proof, as used to label metrics.
+    const KIND: &'static str;
+
+    /// Checks the proof against the public inputs.
+    fn verify_against(&self, public: Self::PublicInputs) -> Result<(), Error>;
+}
+
+/// Verifies `proof` against `public`, recording how long it took.
+pub fn verify_proof<P: Proof>(proof: &P, public: P::PublicInputs) -> Result<(), Error> {
+    let start = Instant::now();
+    let result = proof.verify_against(public);
+    histogram!(
+        "node_proof_verification_seconds",
+        start.elapsed().as_secs_f64(),
+        "proof" => P::KIND
+    );
+    result
+}
+
+/// The public inputs of an [`OutputProof`].
+pub struct OutputPublicInputs {
+    pub value_commitment: value::Commitment,
+    pub note_commitment: note::Commitment,
+    pub ephemeral_key: ka::Public,
+}
+
+impl Proof for OutputProof {
+    type PublicInputs = OutputPublicInputs;
+
+    const KIND: &'static str = "output";
+
+    fn verify_against(&self, public: OutputPublicInputs) -> Result<(), Error> {
+        Ok(self.verify(
+            public.value_commitment,
+            public.note_commitment,
+            public.ephemeral_key,
+        )?)
+    }
+}
+
+/// The public inputs of a [`SpendProof`].
+pub struct SpendPublicInputs {
+    pub anchor: merkle::Root,
+    pub value_commitment: value::Commitment,
+    pub nullifier: Nullifier,
+    pub rk: VerificationKey<SpendAuth>,
+}
+
+impl Proof for SpendProof {
+    type PublicInputs = SpendPublicInputs;
+
+    const KIND: &'static str = "spend";
+
+    fn verify_against(&self, public: SpendPublicInputs) -> Result<(), Error> {
+        Ok(self.verify(
+            public.anchor,
+            public.value_commitment,
+            public.nullifier,
+            public.rk,
+        )?)
+    }
+}
@@ -1,148 +1,811 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    cmp::Ordering,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+};
 
 use anyhow::Error;
-use penumbra_crypto::{note, Nullifier};
-use penumbra_transaction::{Action, Transaction};
+use penumbra_crypto::{merkle, note, Nullifier};
+use penumbra_stake::{
+    Delegate, DkgComplaint, EncryptedFlow, FlowDecryption, IdentityKey, RateData, RateDataById,
+    Redelegate, SignedDkgDealing, Undelegate,
+};
+use penumbra_transaction::{action::SignedUpgradeProposal, Action, Transaction};
 
-use super::{NoteData, PendingTransaction, VerifiedTransaction};
-use crate::state;
+use super::{
+    action::{ActionKind, REGISTRY},
+    NoteData, PendingTransaction, VerifiedTransaction,
+};
+use crate::{flow, state};
+
+/// The maximum share, in basis points, of a validator's delegation tokens that
+/// can be redelegated away from it in a single epoch.
+pub const MAX_REDELEGATED_SHARE_BPS: u64 = 2500;
+
+/// The verdicts on the anchors referenced by a block's transactions.
+///
+/// The valid anchors only change when a block is committed, so each distinct
+/// anchor is checked once per block, and the transactions after the first to
+/// reference it reuse the verdict, including the explanation of an invalid
+/// anchor, which may have needed the database.
+#[derive(Debug, Clone, Default)]
+pub struct AnchorVerdicts(BTreeMap<[u8; 32], Result<(), String>>);
+
+impl AnchorVerdicts {
+    /// Checks that `anchor` is one of the valid anchors, unless it's already
+    /// been checked in this block.
+    pub async fn check(
+        &mut self,
+        reader: &state::Reader,
+        anchor: &merkle::Root,
+    ) -> Result<(), Error> {
+        let verdict = match self.0.entry(anchor.to_bytes()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(reader.check_anchor(anchor).await.map_err(|e| e.to_string()))
+            }
+        };
+        verdict.clone().map_err(|e| anyhow::anyhow!(e))
+    }
+}
 
 impl state::Reader {
+    /// Verifies a transaction against the committed state, as of the block
+    /// after the last committed one.
     pub async fn verify_stateful(
         &self,
         transaction: PendingTransaction,
     ) -> Result<VerifiedTransaction, Error> {
-        let anchor_is_valid = self.valid_anchors_rx().borrow().contains(&transaction.root);
+        self.check_anchor(&transaction.root).await?;
+        let height = self.height().await?.value() + 1;
+        self.verify_stateful_anchored(height, transaction).await
+    }
+
+    /// Verifies a transaction delivered in the block at `height`, whose anchor
+    /// is checked against the block's verdicts rather than on its own.
+    pub async fn verify_stateful_in_block(
+        &self,
+        height: u64,
+        transaction: PendingTransaction,
+        anchor_verdicts: &mut AnchorVerdicts,
+    ) -> Result<VerifiedTransaction, Error> {
+        anchor_verdicts.check(self, &transaction.root).await?;
+        self.verify_stateful_anchored(height, transaction).await
+    }
+
+    /// Checks that `anchor` is one of the valid anchors.
+    async fn check_anchor(&self, anchor: &merkle::Root) -> Result<(), Error> {
+        // The window of valid anchors is kept in memory, so valid transactions
+        // never touch the database for this check.
+        let anchor_is_valid = self.valid_anchors_rx().borrow().contains(anchor);
         if !anchor_is_valid {
-            return Err(anyhow::anyhow!("invalid note commitment tree root"));
+            return Err(self.invalid_anchor_error(anchor).await);
         }
 
-        let existing_nullifiers = self.check_nullifiers(&transaction.spent_nullifiers).await?;
-        if !existing_nullifiers.is_empty() {
-            return Err(anyhow::anyhow!(
-                "nullifiers already spent in state: {:?}",
-                existing_nullifiers
-            ));
+        Ok(())
+    }
+
+    /// Performs the stateful checks other than that of the anchor, for a
+    /// transaction in the block at `height`.
+    async fn verify_stateful_anchored(
+        &self,
+        height: u64,
+        transaction: PendingTransaction,
+    ) -> Result<VerifiedTransaction, Error> {
+        self.check_action_activations(height, &transaction.action_kinds)?;
+
+        let mut verified = VerifiedTransaction {
+            id: transaction.id,
+            new_notes: BTreeMap::new(),
+            spent_nullifiers: BTreeSet::new(),
+            delegation_changes: BTreeMap::new(),
+            redelegations: Vec::new(),
+            dkg_dealings: Vec::new(),
+            encrypted_flows: Vec::new(),
+            flow_decryptions: Vec::new(),
+            dkg_complaints: Vec::new(),
+            denom_metadata: Vec::new(),
+            upgrade_proposals: Vec::new(),
+            fee: transaction.fee,
+            weight: transaction.weight,
+        };
+        for handler in REGISTRY.handlers() {
+            handler
+                .check_stateful(self, &transaction, &mut verified)
+                .await?;
         }
 
-        // TODO: split into methods (after refactoring to have a single db query)
+        Ok(verified)
+    }
 
-        // Tally the delegations and undelegations
-        let mut delegation_changes = BTreeMap::new();
-        for d in &transaction.delegations {
-            let rate_data = self
-                .next_rate_data_rx()
-                .borrow()
-                .get(&d.validator_identity)
-                .ok_or_else(|| {
-                    anyhow::anyhow!("Unknown validator identity {}", d.validator_identity)
-                })?
-                .clone();
+    /// Checks that every kind of action in the transaction has reached its
+    /// activation height, if the chain parameters give it one, as of the
+    /// block at `height`.
+    fn check_action_activations(
+        &self,
+        height: u64,
+        action_kinds: &BTreeSet<ActionKind>,
+    ) -> Result<(), Error> {
+        let chain_params = self.chain_params_rx().borrow();
+        for kind in action_kinds {
+            if let Some(activation_height) = chain_params.action_activations.get(kind.name()) {
+                if height < *activation_height {
+                    return Err(anyhow::anyhow!(
+                        "{} actions are not valid until height {}",
+                        kind,
+                        activation_height
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 
-            // Check whether the epoch is correct first, to give a more helpful
-            // error message if it's wrong.
-            if d.epoch_index != rate_data.epoch_index {
+    /// Checks that every delegation, undelegation, and redelegation in the
+    /// transaction moves at least the chain's `min_delegation_amount` of
+    /// unbonded stake, so that dust can't bloat the per-epoch delegation
+    /// changes.
+    pub(super) fn check_min_delegation_amount(
+        &self,
+        transaction: &PendingTransaction,
+    ) -> Result<(), Error> {
+        let min_delegation_amount = self.chain_params_rx().borrow().min_delegation_amount;
+        let amounts = transaction
+            .delegations
+            .iter()
+            .map(|d| ("Delegation", d.unbonded_amount))
+            .chain(
+                transaction
+                    .undelegations
+                    .iter()
+                    .map(|u| ("Undelegation", u.unbonded_amount)),
+            )
+            .chain(
+                transaction
+                    .redelegations
+                    .iter()
+                    .map(|r| ("Redelegation", r.unbonded_amount)),
+            );
+        for (kind, unbonded_amount) in amounts {
+            if unbonded_amount < min_delegation_amount {
                 return Err(anyhow::anyhow!(
-                    "Delegation was prepared for next epoch {} but the next epoch is {}",
-                    d.epoch_index,
-                    rate_data.epoch_index
+                    "{} of {} unbonded stake is below the minimum of {}",
+                    kind,
+                    unbonded_amount,
+                    min_delegation_amount
                 ));
             }
+        }
+        Ok(())
+    }
 
-            // For delegations, we enforce correct computation (with rounding)
-            // of the *delegation amount based on the unbonded amount*, because
-            // users (should be) starting with the amount of unbonded stake they
-            // wish to delegate, and computing the amount of delegation tokens
-            // they receive.
-            //
-            // The direction of the computation matters because the computation
-            // involves rounding, so while both
-            //
-            // (unbonded amount, rates) -> delegation amount
-            // (delegation amount, rates) -> unbonded amount
-            //
-            // should give approximately the same results, they may not give
-            // exactly the same results.
-            let expected_delegation_amount = rate_data.delegation_amount(d.unbonded_amount);
-
-            if expected_delegation_amount == d.delegation_amount {
-                // The delegation amount is added to the delegation token supply.
-                *delegation_changes
-                    .entry(d.validator_identity.clone())
-                    .or_insert(0) += i64::try_from(d.delegation_amount).unwrap();
-            } else {
+    /// Checks that no validator receiving delegations would hold more than
+    /// the chain's `max_validator_stake_bps` share of the total delegated
+    /// stake once the delegation changes in the current epoch are applied.
+    ///
+    /// Stake is valued at the rates for the next epoch, when the changes take
+    /// effect.  Validators that only lose delegations aren't checked, so stake
+    /// can always be withdrawn from a validator that is over the cap.  The
+    /// changes in `pending`, which were accepted into the block being built,
+    /// are applied along with the committed ones.
+    pub async fn check_delegation_cap(
+        &self,
+        delegation_changes: &BTreeMap<IdentityKey, i64>,
+        pending: &BTreeMap<IdentityKey, i64>,
+    ) -> Result<(), Error> {
+        let max_validator_stake_bps = self.chain_params_rx().borrow().max_validator_stake_bps;
+        if max_validator_stake_bps == 0 || !delegation_changes.values().any(|change| *change > 0) {
+            return Ok(());
+        }
+
+        let next_rate_data = self.next_rate_data_rx().borrow().clone();
+        let current_epoch = match next_rate_data.values().next() {
+            Some(rate_data) => rate_data.epoch_index.saturating_sub(1),
+            None => return Ok(()),
+        };
+        let committed = self.delegation_changes(current_epoch).await?;
+
+        let mut stake = BTreeMap::new();
+        let mut total_stake = 0u128;
+        for (identity_key, rate_data) in &next_rate_data {
+            let supply = self
+                .asset_lookup(identity_key.delegation_token().id())
+                .await?
+                .map(|info| info.total_supply)
+                .unwrap_or(0);
+            let change = [&committed, pending, delegation_changes]
+                .iter()
+                .filter_map(|changes| changes.get(identity_key))
+                .map(|change| *change as i128)
+                .sum::<i128>();
+            let delegation_tokens = (supply as i128 + change).clamp(0, u64::MAX as i128) as u64;
+            let validator_stake = rate_data
+                .checked_unbonded_amount(delegation_tokens)
+                .unwrap_or(u64::MAX) as u128;
+
+            stake.insert(identity_key, validator_stake);
+            total_stake += validator_stake;
+        }
+
+        for (identity_key, _) in delegation_changes.iter().filter(|(_, change)| **change > 0) {
+            let validator_stake = stake.get(identity_key).copied().unwrap_or(0);
+            if validator_stake * 10_000 > total_stake * max_validator_stake_bps as u128 {
                 return Err(anyhow::anyhow!(
-                    "Given {} unbonded stake, expected {} delegation tokens but description produces {}",
-                    d.unbonded_amount,
-                    expected_delegation_amount,
-                    d.delegation_amount
+                    "Validator {} would hold {} of {} delegated stake, exceeding the cap of {} basis points",
+                    identity_key,
+                    validator_stake,
+                    total_stake,
+                    max_validator_stake_bps
                 ));
             }
         }
-        for u in &transaction.undelegations {
-            let rate_data = self
-                .next_rate_data_rx()
-                .borrow()
-                .get(&u.validator_identity)
+
+        Ok(())
+    }
+
+    /// Checks that each DKG dealing is for the round of the current epoch,
+    /// fits that round, proves knowledge of its contribution to the group key,
+    /// and is the first from its dealer.  The dealings in
+    /// `pending`, which were accepted into the block being built, count as
+    /// submitted.
+    pub async fn check_dkg_dealings(
+        &self,
+        dealings: &[SignedDkgDealing],
+        pending: &[SignedDkgDealing],
+    ) -> Result<(), Error> {
+        if dealings.is_empty() {
+            return Ok(());
+        }
+
+        let current_epoch = self
+            .current_epoch()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("the chain has no current epoch"))?
+            .index;
+        let round = self
+            .dkg_round(current_epoch)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("epoch {} has no DKG round", current_epoch))?;
+        let submitted = self.dkg_dealings(current_epoch).await?;
+
+        for signed in dealings {
+            let dealing = &signed.dealing;
+            if dealing.epoch_index != current_epoch {
+                return Err(anyhow::anyhow!(
+                    "DKG dealing is for epoch {}, but the current epoch is {}",
+                    dealing.epoch_index,
+                    current_epoch
+                ));
+            }
+            round.check_dealing(dealing)?;
+            dealing.verify_proof()?;
+            if submitted
+                .iter()
+                .map(|(_, other)| other)
+                .chain(pending)
+                .any(|other| other.dealing.validator_identity == dealing.validator_identity)
+            {
+                return Err(anyhow::anyhow!(
+                    "validator {} has already dealt in the DKG round of epoch {}",
+                    dealing.validator_identity,
+                    current_epoch
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that each DKG complaint is about a dealing submitted to the round
+    /// of the current epoch, is justified, and is the first from its
+    /// participant about that dealer.  The complaints in `pending`, which were
+    /// accepted into the block being built, count as submitted.
+    pub async fn check_dkg_complaints(
+        &self,
+        complaints: &[DkgComplaint],
+        pending: &[DkgComplaint],
+    ) -> Result<(), Error> {
+        if complaints.is_empty() {
+            return Ok(());
+        }
+
+        let current_epoch = self
+            .current_epoch()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("the chain has no current epoch"))?
+            .index;
+        let round = self
+            .dkg_round(current_epoch)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("epoch {} has no DKG round", current_epoch))?;
+        let dealings = self.dkg_dealings(current_epoch).await?;
+        let submitted = self.dkg_complaints(current_epoch).await?;
+
+        for (i, complaint) in complaints.iter().enumerate() {
+            if complaint.epoch_index != current_epoch {
+                return Err(anyhow::anyhow!(
+                    "DKG complaint is for epoch {}, but the current epoch is {}",
+                    complaint.epoch_index,
+                    current_epoch
+                ));
+            }
+            let (_, dealing) = dealings
+                .iter()
+                .find(|(_, dealing)| {
+                    dealing.dealing.validator_identity == complaint.dealer_identity
+                })
                 .ok_or_else(|| {
-                    anyhow::anyhow!("Unknown validator identity {}", u.validator_identity)
-                })?
-                .clone();
+                    anyhow::anyhow!(
+                        "validator {} has not dealt in the DKG round of epoch {}",
+                        complaint.dealer_identity,
+                        current_epoch
+                    )
+                })?;
+            round.check_complaint(complaint, &dealing.dealing)?;
+            // A transaction may carry several complaints, so each is also
+            // checked against the ones before it.
+            let mut earlier = submitted.iter().chain(pending).chain(&complaints[..i]);
+            if earlier.any(|other| {
+                other.validator_identity == complaint.validator_identity
+                    && other.dealer_identity == complaint.dealer_identity
+            }) {
+                return Err(anyhow::anyhow!(
+                    "validator {} has already complained about the DKG dealing from {}",
+                    complaint.validator_identity,
+                    complaint.dealer_identity
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that upgrade proposals are votes for this chain by active
+    /// validators, for plans whose height is after the next block's, and that
+    /// each vote's sequence number is greater than that of the validator's
+    /// previous vote, including any in `pending`, which were accepted into the
+    /// block being built.
+    pub async fn check_upgrade_proposals(
+        &self,
+        proposals: &[SignedUpgradeProposal],
+        pending: &[SignedUpgradeProposal],
+    ) -> Result<(), Error> {
+        if proposals.is_empty() {
+            return Ok(());
+        }
 
-            // Check whether the epoch is correct first, to give a more helpful
-            // error message if it's wrong.
-            if u.epoch_index != rate_data.epoch_index {
+        let chain_id = self.chain_params_rx().borrow().chain_id.clone();
+        let next_height = self.height().await?.value() + 1;
+        let voting_power = self.active_voting_power().await?;
+        let sequence_numbers = self.upgrade_vote_sequence_numbers().await?;
+        for (i, signed) in proposals.iter().enumerate() {
+            let proposal = &signed.proposal;
+            if proposal.chain_id != chain_id {
                 return Err(anyhow::anyhow!(
-                    "Undelegation was prepared for next epoch {} but the next epoch is {}",
-                    u.epoch_index,
-                    rate_data.epoch_index
+                    "upgrade proposal is for chain {:?}, but this is chain {:?}",
+                    proposal.chain_id,
+                    chain_id
                 ));
             }
+            // A transaction may carry several votes, so each is also checked
+            // against the ones before it.
+            let previous = pending
+                .iter()
+                .chain(&proposals[..i])
+                .filter(|other| other.proposal.validator_identity == proposal.validator_identity)
+                .map(|other| other.proposal.sequence_number)
+                .chain(sequence_numbers.get(&proposal.validator_identity).copied())
+                .max();
+            if let Some(previous) = previous {
+                if proposal.sequence_number <= previous {
+                    return Err(anyhow::anyhow!(
+                        "upgrade proposal from validator {} has sequence number {}, but its previous vote had {}",
+                        proposal.validator_identity,
+                        proposal.sequence_number,
+                        previous
+                    ));
+                }
+            }
+            if !voting_power.contains_key(&proposal.validator_identity) {
+                return Err(anyhow::anyhow!(
+                    "validator {} is not active, so it can't vote for upgrades",
+                    proposal.validator_identity
+                ));
+            }
+            if proposal.plan.height <= next_height {
+                return Err(anyhow::anyhow!(
+                    "upgrade {} must be after the next block, at height {}",
+                    proposal.plan,
+                    next_height
+                ));
+            }
+        }
+
+        Ok(())
+    }
 
-            // For undelegations, we enforce correct computation (with rounding)
-            // of the *unbonded amount based on the delegation amount*, because
-            // users (should be) starting with the amount of delegation tokens they
-            // wish to undelegate, and computing the amount of unbonded stake
-            // they receive.
-            //
-            // The direction of the computation matters because the computation
-            // involves rounding, so while both
-            //
-            // (unbonded amount, rates) -> delegation amount
-            // (delegation amount, rates) -> unbonded amount
-            //
-            // should give approximately the same results, they may not give
-            // exactly the same results.
-            let expected_unbonded_amount = rate_data.unbonded_amount(u.delegation_amount);
-
-            if expected_unbonded_amount == u.unbonded_amount {
-                // TODO: in order to have exact tracking of the token supply, we probably
-                // need to change this to record the changes to the unbonded stake and
-                // the delegation token separately
-
-                // The undelegation amount is subtracted from the delegation token supply.
-                *delegation_changes
-                    .entry(u.validator_identity.clone())
-                    .or_insert(0) -= i64::try_from(u.delegation_amount).unwrap();
-            } else {
+    /// Checks the proofs of encrypted flows against the rate data for the
+    /// epoch in which they take effect, like transparent delegations and
+    /// undelegations, and against the threshold key of the current epoch.
+    pub(super) async fn check_encrypted_flows(&self, flows: &[EncryptedFlow]) -> Result<(), Error> {
+        // Take the rate data from a single snapshot, as in `verify_stateful`,
+        // without holding it across the lookups of the flow keys.
+        let rate_data = {
+            let next_rate_data = self.next_rate_data_rx().borrow();
+            flows
+                .iter()
+                .map(|f| {
+                    let rate_data = next_rate_data.get(&f.validator_identity).ok_or_else(|| {
+                        anyhow::anyhow!("Unknown validator identity {}", f.validator_identity)
+                    })?;
+                    check_epoch("Encrypted flow", f.epoch_index, rate_data)?;
+                    Ok(rate_data.clone())
+                })
+                .collect::<Result<Vec<_>, Error>>()?
+        };
+
+        for (f, rate_data) in flows.iter().zip(rate_data.iter()) {
+            // The rates are for the epoch after the one the flow is
+            // performed in, whose key it must be encrypted to.
+            let epoch_index = rate_data.epoch_index.saturating_sub(1);
+            let flow_key = self.flow_key(epoch_index).await?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "epoch {} has no threshold key to encrypt flows to",
+                    epoch_index
+                )
+            })?;
+            f.verify(rate_data, &flow_key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that each flow decryption is for the flows of an ended epoch
+    /// that haven't been decrypted yet, that its shares match the sender's public key share, and that
+    /// the sender hasn't already decrypted them, including in `pending`.
+    pub async fn check_flow_decryptions(
+        &self,
+        decryptions: &[FlowDecryption],
+        pending: &[FlowDecryption],
+    ) -> Result<(), Error> {
+        if decryptions.is_empty() {
+            return Ok(());
+        }
+
+        let current_epoch = self
+            .current_epoch()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("the chain has no current epoch"))?
+            .index;
+        let undecrypted = self.undecrypted_flow_epochs(current_epoch).await?;
+
+        for decryption in decryptions {
+            let epoch_index = decryption.epoch_index;
+            if !undecrypted.contains(&epoch_index) {
+                return Err(anyhow::anyhow!(
+                    "epoch {} hasn't ended or has no undecrypted flows to decrypt",
+                    epoch_index
+                ));
+            }
+            let round = match epoch_index.checked_sub(1) {
+                Some(round_epoch) => self.dkg_round(round_epoch).await?,
+                None => None,
+            }
+            .ok_or_else(|| anyhow::anyhow!("epoch {} has no threshold key", epoch_index))?;
+            let aggregates = self.flow_aggregates(epoch_index).await?;
+            let submitted = self.flow_decryptions(epoch_index).await?;
+
+            flow::check_decryption(&aggregates, &round, decryption)?;
+            if submitted.iter().chain(pending).any(|other| {
+                other.epoch_index == epoch_index
+                    && other.validator_identity == decryption.validator_identity
+            }) {
                 return Err(anyhow::anyhow!(
-                    "Given {} delegation tokens, expected {} unbonded stake but description produces {}",
-                    u.delegation_amount,
-                    expected_unbonded_amount,
-                    u.unbonded_amount,
+                    "validator {} has already decrypted the flows of epoch {}",
+                    decryption.validator_identity,
+                    epoch_index
                 ));
             }
         }
 
-        Ok(VerifiedTransaction {
-            id: transaction.id,
-            new_notes: transaction.new_notes,
-            spent_nullifiers: transaction.spent_nullifiers,
-            delegation_changes,
-        })
+        Ok(())
+    }
+
+    /// Checks the per-epoch limits on redelegations.
+    ///
+    /// Stake redelegated to a validator can't be redelegated away from it
+    /// again in the same epoch, so that redelegations can't be chained to hop
+    /// across validators faster than the epochs advance.  And at most
+    /// [`MAX_REDELEGATED_SHARE_BPS`] of a validator's delegation tokens can be
+    /// redelegated away from it per epoch, so that its voting power can't be
+    /// drained at once.  The redelegations in `pending`, which were accepted
+    /// into the block being built, count towards the limits along with the
+    /// committed ones.
+    pub async fn check_redelegation_limits(
+        &self,
+        redelegations: &[Redelegate],
+        pending: &[Redelegate],
+    ) -> Result<(), Error> {
+        let mut outflows = BTreeMap::<(&IdentityKey, u64), u64>::new();
+        for r in redelegations {
+            let outflow = outflows
+                .entry((&r.from_validator_identity, r.epoch_index))
+                .or_insert(0);
+            *outflow = outflow.saturating_add(r.from_delegation_amount);
+        }
+
+        for ((identity_key, epoch_index), outflow) in outflows {
+            let (mut redelegated_out, mut redelegated_in) =
+                self.redelegation_volume(identity_key, epoch_index).await?;
+            for p in pending.iter().filter(|p| p.epoch_index == epoch_index) {
+                if &p.from_validator_identity == identity_key {
+                    redelegated_out += p.from_delegation_amount;
+                }
+                if &p.to_validator_identity == identity_key {
+                    redelegated_in += p.to_delegation_amount;
+                }
+            }
+
+            if redelegated_in > 0 {
+                return Err(anyhow::anyhow!(
+                    "Stake was redelegated to validator {} for epoch {}, so none can be redelegated away from it until the next epoch",
+                    identity_key,
+                    epoch_index
+                ));
+            }
+
+            let delegation_token_supply = self
+                .asset_lookup(identity_key.delegation_token().id())
+                .await?
+                .map(|info| info.total_supply)
+                .unwrap_or(0);
+            let limit = (delegation_token_supply as u128 * MAX_REDELEGATED_SHARE_BPS as u128
+                / 10_000) as u64;
+            if redelegated_out.saturating_add(outflow) > limit {
+                return Err(anyhow::anyhow!(
+                    "Redelegating {} delegation tokens away from validator {} would exceed its limit of {} for epoch {}, of which {} are already used",
+                    outflow,
+                    identity_key,
+                    limit,
+                    epoch_index,
+                    redelegated_out
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks the delegations, undelegations, and redelegations in a transaction
+/// against the rate data for the epoch in which they take effect, and tallies
+/// the resulting changes to each validator's delegation token supply.
+pub(super) fn delegation_changes(
+    next_rate_data: &RateDataById,
+    delegations: &[Delegate],
+    undelegations: &[Undelegate],
+    redelegations: &[Redelegate],
+) -> Result<BTreeMap<IdentityKey, i64>, Error> {
+    let mut delegation_changes = BTreeMap::new();
+    for d in delegations {
+        let rate_data = next_rate_data.get(&d.validator_identity).ok_or_else(|| {
+            anyhow::anyhow!("Unknown validator identity {}", d.validator_identity)
+        })?;
+
+        // Check whether the epoch is correct first, to give a more helpful
+        // error message if it's wrong.
+        check_epoch("Delegation", d.epoch_index, rate_data)?;
+
+        // For delegations, we enforce correct computation (with rounding)
+        // of the *delegation amount based on the unbonded amount*, because
+        // users (should be) starting with the amount of unbonded stake they
+        // wish to delegate, and computing the amount of delegation tokens
+        // they receive.
+        //
+        // The direction of the computation matters because the computation
+        // involves rounding, so while both
+        //
+        // (unbonded amount, rates) -> delegation amount
+        // (delegation amount, rates) -> unbonded amount
+        //
+        // should give approximately the same results, they may not give
+        // exactly the same results.
+        let expected_delegation_amount = rate_data
+            .checked_delegation_amount(d.unbonded_amount)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Delegation of {} unbonded stake is too large",
+                    d.unbonded_amount
+                )
+            })?;
+
+        if expected_delegation_amount != d.delegation_amount {
+            return Err(anyhow::anyhow!(
+                "Given {} unbonded stake, expected {} delegation tokens but description produces {}",
+                d.unbonded_amount,
+                expected_delegation_amount,
+                d.delegation_amount
+            ));
+        }
+        if d.delegation_amount == 0 {
+            return Err(anyhow::anyhow!(
+                "Delegation of {} unbonded stake produces no delegation tokens",
+                d.unbonded_amount
+            ));
+        }
+
+        // The delegation amount is added to the delegation token supply.
+        add_delegation_change(
+            &mut delegation_changes,
+            &d.validator_identity,
+            i64::try_from(d.delegation_amount).ok(),
+        )?;
+    }
+    for u in undelegations {
+        let rate_data = next_rate_data.get(&u.validator_identity).ok_or_else(|| {
+            anyhow::anyhow!("Unknown validator identity {}", u.validator_identity)
+        })?;
+
+        // Check whether the epoch is correct first, to give a more helpful
+        // error message if it's wrong.
+        check_epoch("Undelegation", u.epoch_index, rate_data)?;
+
+        // For undelegations, we enforce correct computation (with rounding)
+        // of the *unbonded amount based on the delegation amount*, because
+        // users (should be) starting with the amount of delegation tokens they
+        // wish to undelegate, and computing the amount of unbonded stake
+        // they receive.
+        //
+        // The direction of the computation matters because the computation
+        // involves rounding, so while both
+        //
+        // (unbonded amount, rates) -> delegation amount
+        // (delegation amount, rates) -> unbonded amount
+        //
+        // should give approximately the same results, they may not give
+        // exactly the same results.
+        let expected_unbonded_amount = rate_data
+            .checked_unbonded_amount(u.delegation_amount)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Undelegation of {} delegation tokens is too large",
+                    u.delegation_amount
+                )
+            })?;
+
+        if expected_unbonded_amount != u.unbonded_amount {
+            return Err(anyhow::anyhow!(
+                "Given {} delegation tokens, expected {} unbonded stake but description produces {}",
+                u.delegation_amount,
+                expected_unbonded_amount,
+                u.unbonded_amount,
+            ));
+        }
+        if u.delegation_amount == 0 {
+            return Err(anyhow::anyhow!("Undelegation of no delegation tokens"));
+        }
+
+        // TODO: in order to have exact tracking of the token supply, we probably
+        // need to change this to record the changes to the unbonded stake and
+        // the delegation token separately
+
+        // The undelegation amount is subtracted from the delegation token supply.
+        add_delegation_change(
+            &mut delegation_changes,
+            &u.validator_identity,
+            i64::try_from(u.delegation_amount)
+                .ok()
+                .map(|amount| -amount),
+        )?;
+    }
+    for r in redelegations {
+        let from_rate_data = next_rate_data
+            .get(&r.from_validator_identity)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unknown validator identity {}", r.from_validator_identity)
+            })?;
+        let to_rate_data = next_rate_data
+            .get(&r.to_validator_identity)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unknown validator identity {}", r.to_validator_identity)
+            })?;
+
+        // Both validators' rates come from the same snapshot, so they're for
+        // the same epoch.
+        check_epoch("Redelegation", r.epoch_index, from_rate_data)?;
+
+        // A redelegation is an undelegation followed by a delegation, so the
+        // amounts are computed in the same directions as for those actions:
+        //
+        // (source delegation amount, source rates) -> unbonded amount
+        // (unbonded amount, destination rates) -> destination delegation amount
+        let expected_unbonded_amount = from_rate_data
+            .checked_unbonded_amount(r.from_delegation_amount)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Redelegation of {} delegation tokens is too large",
+                    r.from_delegation_amount
+                )
+            })?;
+        if expected_unbonded_amount != r.unbonded_amount {
+            return Err(anyhow::anyhow!(
+                "Given {} delegation tokens, expected {} unbonded stake but redelegation produces {}",
+                r.from_delegation_amount,
+                expected_unbonded_amount,
+                r.unbonded_amount,
+            ));
+        }
+        let expected_to_delegation_amount = to_rate_data
+            .checked_delegation_amount(r.unbonded_amount)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Redelegation of {} unbonded stake is too large",
+                    r.unbonded_amount
+                )
+            })?;
+        if expected_to_delegation_amount != r.to_delegation_amount {
+            return Err(anyhow::anyhow!(
+                "Given {} unbonded stake, expected {} delegation tokens but redelegation produces {}",
+                r.unbonded_amount,
+                expected_to_delegation_amount,
+                r.to_delegation_amount
+            ));
+        }
+        if r.to_delegation_amount == 0 {
+            return Err(anyhow::anyhow!(
+                "Redelegation of {} delegation tokens produces no delegation tokens",
+                r.from_delegation_amount
+            ));
+        }
+
+        add_delegation_change(
+            &mut delegation_changes,
+            &r.from_validator_identity,
+            i64::try_from(r.from_delegation_amount)
+                .ok()
+                .map(|amount| -amount),
+        )?;
+        add_delegation_change(
+            &mut delegation_changes,
+            &r.to_validator_identity,
+            i64::try_from(r.to_delegation_amount).ok(),
+        )?;
+    }
+
+    Ok(delegation_changes)
+}
+
+/// Checks that a delegation change was prepared against the rate data for the
+/// epoch it takes effect in, rather than stale (or not yet known) rates.
+fn check_epoch(kind: &str, epoch_index: u64, rate_data: &RateData) -> Result<(), Error> {
+    match epoch_index.cmp(&rate_data.epoch_index) {
+        Ordering::Equal => Ok(()),
+        Ordering::Less => Err(anyhow::anyhow!(
+            "{} was prepared using stale rates for epoch {}, but the next epoch is {}",
+            kind,
+            epoch_index,
+            rate_data.epoch_index
+        )),
+        Ordering::Greater => Err(anyhow::anyhow!(
+            "{} was prepared for epoch {}, but the next epoch is only {}",
+            kind,
+            epoch_index,
+            rate_data.epoch_index
+        )),
     }
 }
 
+fn add_delegation_change(
+    delegation_changes: &mut BTreeMap<IdentityKey, i64>,
+    identity_key: &IdentityKey,
+    change: Option<i64>,
+) -> Result<(), Error> {
+    let total = delegation_changes.entry(identity_key.clone()).or_insert(0);
+    *total = change
+        .and_then(|change| total.checked_add(change))
+        .ok_or_else(|| anyhow::anyhow!("Delegation changes for {} overflow", identity_key))?;
+    Ok(())
+}
+
 // TODO: replace this with just inserting genesis notes directly
 
 /// One-off function used to mark a genesis transaction as verified.
@@ -156,6 +819,9 @@ pub fn mark_genesis_as_verified(transaction: Transaction) -> VerifiedTransaction
                     NoteData {
                         ephemeral_key: inner.body.ephemeral_key,
                         encrypted_note: inner.body.encrypted_note,
+                        // Genesis outputs carry a placeholder rather than an
+                        // encrypted memo.
+                        encrypted_memo: None,
                         transaction_id: transaction.id(),
                     },
                 );
@@ -171,5 +837,83 @@ pub fn mark_genesis_as_verified(transaction: Transaction) -> VerifiedTransaction
         new_notes,
         spent_nullifiers: BTreeSet::<Nullifier>::new(),
         delegation_changes: BTreeMap::new(),
+        redelegations: Vec::new(),
+        dkg_dealings: Vec::new(),
+        encrypted_flows: Vec::new(),
+        flow_decryptions: Vec::new(),
+        dkg_complaints: Vec::new(),
+        denom_metadata: Vec::new(),
+        upgrade_proposals: Vec::new(),
+        fee: 0,
+        weight: 0,
+    }
+}
+
+impl state::Reader {
+    /// Explains why `anchor` isn't one of the valid anchors, giving the height
+    /// it was last the note commitment tree root at and the heights whose roots
+    /// are currently valid.
+    ///
+    /// Recently expired anchors are explained from the in-memory window, so the
+    /// database is only consulted for anchors it doesn't retain: those that
+    /// expired long ago, were never a root, or became a root in a block that's
+    /// still being committed.
+    async fn invalid_anchor_error(&self, anchor: &merkle::Root) -> Error {
+        let anchor_hex = hex::encode(anchor.to_bytes());
+        let (retained_height, heights, size) = {
+            let valid_anchors = self.valid_anchors_rx().borrow();
+            (
+                valid_anchors.last_height(anchor),
+                valid_anchors.heights(),
+                valid_anchors.size(),
+            )
+        };
+        let latest_height = heights.map(|(_, latest)| latest).unwrap_or(0);
+        let window = match heights {
+            Some((oldest, latest)) => format!(
+                "the valid anchors are the roots at heights {} to {}",
+                oldest, latest
+            ),
+            None => "no blocks have been committed".to_string(),
+        };
+
+        let height = match retained_height {
+            Some(height) => Some(height),
+            None => match self.height_for_anchor(anchor).await {
+                Ok(height) => height.map(|height| height.value()),
+                Err(e) => {
+                    return anyhow::anyhow!(
+                        "invalid anchor {}: failed to look up its history: {}; {}",
+                        anchor_hex,
+                        e,
+                        window
+                    )
+                }
+            },
+        };
+
+        match height {
+            None => anyhow::anyhow!(
+                "invalid anchor {}: it was never a note commitment tree root; {}",
+                anchor_hex,
+                window
+            ),
+            Some(height) if height <= latest_height => anyhow::anyhow!(
+                "invalid anchor {}: it was last the note commitment tree root at height {}, and expired {} blocks ago; {} (anchors are valid for {} blocks)",
+                anchor_hex,
+                height,
+                latest_height + 1 - (height + size),
+                window,
+                size
+            ),
+            // The anchor was a recent root, so the block that made it one
+            // was committed after this verification started.
+            Some(height) => anyhow::anyhow!(
+                "invalid anchor {}: it became the note commitment tree root at height {}, which is still being committed; {}",
+                anchor_hex,
+                height,
+                window
+            ),
+        }
     }
 }
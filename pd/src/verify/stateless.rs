@@ -1,11 +1,68 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Instant,
+};
 
 use anyhow::{Context, Error};
-use penumbra_crypto::{note, Nullifier};
-use penumbra_stake::{Delegate, Undelegate, Validator};
-use penumbra_transaction::{Action, Transaction};
+use metrics::histogram;
+use penumbra_crypto::memo::MEMO_CIPHERTEXT_LEN_BYTES;
+use penumbra_proto::{
+    transaction::{self as pb, action::Action as PbAction},
+    Message, Protobuf,
+};
+use penumbra_transaction::Transaction;
 
-use super::{NoteData, PendingTransaction};
+use super::{
+    action::{ActionKind, StatelessContext, REGISTRY},
+    check_structure, PendingTransaction,
+};
+use crate::fee;
+
+/// Decodes a transaction, rejecting any encoding other than the canonical one.
+///
+/// Protobuf admits many encodings of the same message (unknown fields, fields
+/// out of order, non-minimal varints, ...).  Tendermint identifies transactions
+/// by the hash of their raw bytes, while the transaction ID is the hash of its
+/// canonical encoding, so accepting other encodings would let anyone create
+/// copies of a transaction with the same ID but a different hash.  Requiring
+/// that the bytes round-trip exactly makes the two agree.
+pub fn decode_canonical(tx_bytes: &[u8]) -> Result<Transaction, Error> {
+    let proto = pb::Transaction::decode(tx_bytes)?;
+    check_memo_sizes(&proto)?;
+    let transaction = Transaction::try_from(proto)?;
+    if transaction.encode_to_vec() != tx_bytes {
+        return Err(anyhow::anyhow!("transaction encoding is not canonical"));
+    }
+    Ok(transaction)
+}
+
+/// Checks that every output's encrypted memo is exactly the size of a memo
+/// ciphertext.
+///
+/// Memos are stored and sent to every syncing wallet, so their size is
+/// checked on the encoded transaction, before anything is parsed, and
+/// oversized ones are rejected with a precise reason rather than as a
+/// malformed output.
+fn check_memo_sizes(transaction: &pb::Transaction) -> Result<(), Error> {
+    let actions = transaction
+        .body
+        .iter()
+        .flat_map(|body| body.actions.iter())
+        .filter_map(|action| action.action.as_ref());
+    for (index, action) in actions.enumerate() {
+        if let PbAction::Output(output) = action {
+            if output.encrypted_memo.len() != MEMO_CIPHERTEXT_LEN_BYTES {
+                return Err(anyhow::anyhow!(
+                    "action {} has a {}-byte encrypted memo, but memos must be {} bytes",
+                    index,
+                    output.encrypted_memo.len(),
+                    MEMO_CIPHERTEXT_LEN_BYTES
+                ));
+            }
+        }
+    }
+    Ok(())
+}
 
 /// An extension trait that performs stateless transaction verification
 /// (verifying signatures and proofs, but not checking consistency with the
@@ -18,105 +75,63 @@ pub trait StatelessTransactionExt {
 }
 
 impl StatelessTransactionExt for Transaction {
-    // TODO: use tokio's blocking code when we do work here -- internally to verify_stateless?
     fn verify_stateless(&self) -> Result<PendingTransaction, Error> {
+        // 0. Check the structural rules, before doing any expensive verification.
+        check_structure(self)?;
+
         let id = self.id();
 
         let sighash = self.transaction_body().sighash();
 
         // 1. Check binding signature.
-        self.binding_verification_key()
-            .verify(&sighash, self.binding_sig())
-            .context("binding signature failed to verify")?;
+        let start = Instant::now();
+        let binding_result = self
+            .binding_verification_key()
+            .verify(&sighash, self.binding_sig());
+        histogram!(
+            "node_signature_verification_seconds",
+            start.elapsed().as_secs_f64(),
+            "signature" => "binding"
+        );
+        binding_result.context("binding signature failed to verify")?;
 
-        // 2. Check all spend auth signatures using provided spend auth keys
-        // and check all proofs verify. If any action does not verify, the entire
-        // transaction has failed.
-        let mut spent_nullifiers = BTreeSet::<Nullifier>::new();
-        let mut new_notes = BTreeMap::<note::Commitment, NoteData>::new();
-        let mut delegations = Vec::<Delegate>::new();
-        let mut undelegations = Vec::<Undelegate>::new();
-        let validators = Vec::<Validator>::new();
+        // 2. Check each action with the handler for its kind, such as the spend
+        // auth signatures and the proofs. If any action does not verify, the
+        // entire transaction has failed.
+        let context = StatelessContext {
+            id,
+            sighash,
+            merkle_root: self.transaction_body().merkle_root,
+        };
+        let mut transaction = PendingTransaction {
+            id,
+            root: self.transaction_body().merkle_root,
+            action_kinds: BTreeSet::new(),
+            new_notes: BTreeMap::new(),
+            spent_nullifiers: BTreeSet::new(),
+            delegations: Vec::new(),
+            undelegations: Vec::new(),
+            redelegations: Vec::new(),
+            dkg_dealings: Vec::new(),
+            encrypted_flows: Vec::new(),
+            flow_decryptions: Vec::new(),
+            dkg_complaints: Vec::new(),
+            validators: Vec::new(),
+            denom_metadata: Vec::new(),
+            upgrade_proposals: Vec::new(),
+            fee: self.transaction_body().fee.0,
+            weight: fee::TransactionSkeleton::from(self).weight(),
+        };
 
         for action in self.transaction_body().actions {
-            match action {
-                Action::Output(output) => {
-                    if output
-                        .body
-                        .proof
-                        .verify(
-                            output.body.value_commitment,
-                            output.body.note_commitment,
-                            output.body.ephemeral_key,
-                        )
-                        .is_err()
-                    {
-                        // TODO should the verification error be bubbled up here?
-                        return Err(anyhow::anyhow!("An output proof did not verify"));
-                    }
-
-                    new_notes.insert(
-                        output.body.note_commitment,
-                        NoteData {
-                            ephemeral_key: output.body.ephemeral_key,
-                            encrypted_note: output.body.encrypted_note,
-                            transaction_id: id,
-                        },
-                    );
-                }
-                Action::Spend(spend) => {
-                    spend
-                        .body
-                        .rk
-                        .verify(&sighash, &spend.auth_sig)
-                        .context("spend auth signature failed to verify")?;
-
-                    if spend
-                        .body
-                        .proof
-                        .verify(
-                            self.transaction_body().merkle_root,
-                            spend.body.value_commitment,
-                            spend.body.nullifier.clone(),
-                            spend.body.rk,
-                        )
-                        .is_err()
-                    {
-                        // TODO should the verification error be bubbled up here?
-                        return Err(anyhow::anyhow!("A spend proof did not verify"));
-                    }
-
-                    // Check nullifier has not been revealed already in this transaction.
-                    if spent_nullifiers.contains(&spend.body.nullifier.clone()) {
-                        return Err(anyhow::anyhow!("Double spend"));
-                    }
-
-                    spent_nullifiers.insert(spend.body.nullifier.clone());
-                }
-                Action::Delegate(delegate) => {
-                    // There are currently no stateless verification checks than the ones implied by
-                    // the binding signature.
-                    delegations.push(delegate);
-                }
-                Action::Undelegate(undelegate) => {
-                    // There are currently no stateless verification checks than the ones implied by
-                    // the binding signature.
-                    undelegations.push(undelegate);
-                }
-                _ => {
-                    return Err(anyhow::anyhow!("unsupported action"));
-                }
-            }
+            let kind = ActionKind::from(&action);
+            let handler = REGISTRY
+                .get(kind)
+                .ok_or_else(|| anyhow::anyhow!("unsupported action"))?;
+            handler.check_stateless(&context, action, &mut transaction)?;
+            transaction.action_kinds.insert(kind);
         }
 
-        Ok(PendingTransaction {
-            id,
-            root: self.transaction_body().merkle_root,
-            new_notes,
-            spent_nullifiers,
-            delegations,
-            undelegations,
-            validators,
-        })
+        Ok(transaction)
     }
 }
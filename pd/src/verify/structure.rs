@@ -0,0 +1,206 @@
+use std::collections::BTreeSet;
+
+use penumbra_stake::{FlowDirection, IdentityKey};
+use penumbra_transaction::{Action, Transaction};
+
+/// The maximum number of spends in a single transaction.
+pub const MAX_SPENDS: usize = 64;
+/// The maximum number of outputs in a single transaction.
+pub const MAX_OUTPUTS: usize = 64;
+/// The maximum number of delegations in a single transaction.
+pub const MAX_DELEGATIONS: usize = 16;
+/// The maximum number of undelegations in a single transaction.
+pub const MAX_UNDELEGATIONS: usize = 16;
+/// The maximum number of redelegations in a single transaction.
+pub const MAX_REDELEGATIONS: usize = 16;
+/// The maximum number of DKG dealings in a single transaction.
+pub const MAX_DKG_DEALINGS: usize = 1;
+/// The maximum number of DKG complaints in a single transaction.
+pub const MAX_DKG_COMPLAINTS: usize = 16;
+/// The maximum number of flow decryptions in a single transaction.
+pub const MAX_FLOW_DECRYPTIONS: usize = 1;
+/// The maximum number of validator definitions in a single transaction.
+pub const MAX_VALIDATOR_DEFINITIONS: usize = 1;
+/// The maximum number of denom metadata registrations in a single transaction.
+pub const MAX_DENOM_METADATA: usize = 1;
+/// The maximum number of upgrade proposals in a single transaction.
+pub const MAX_UPGRADE_PROPOSALS: usize = 1;
+
+/// A violation of the structural rules every transaction must follow,
+/// independently of its proofs and signatures.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum StructureError {
+    #[error("transaction has no actions")]
+    NoActions,
+    #[error("transaction has {0} spends, but at most {} are allowed", MAX_SPENDS)]
+    TooManySpends(usize),
+    #[error("transaction has {0} outputs, but at most {} are allowed", MAX_OUTPUTS)]
+    TooManyOutputs(usize),
+    #[error(
+        "transaction has {0} delegations, but at most {} are allowed",
+        MAX_DELEGATIONS
+    )]
+    TooManyDelegations(usize),
+    #[error(
+        "transaction has {0} undelegations, but at most {} are allowed",
+        MAX_UNDELEGATIONS
+    )]
+    TooManyUndelegations(usize),
+    #[error(
+        "transaction has {0} redelegations, but at most {} are allowed",
+        MAX_REDELEGATIONS
+    )]
+    TooManyRedelegations(usize),
+    #[error(
+        "transaction has {0} DKG dealings, but at most {} are allowed",
+        MAX_DKG_DEALINGS
+    )]
+    TooManyDkgDealings(usize),
+    #[error(
+        "transaction has {0} DKG complaints, but at most {} are allowed",
+        MAX_DKG_COMPLAINTS
+    )]
+    TooManyDkgComplaints(usize),
+    #[error(
+        "transaction has {0} flow decryptions, but at most {} are allowed",
+        MAX_FLOW_DECRYPTIONS
+    )]
+    TooManyFlowDecryptions(usize),
+    #[error(
+        "transaction has {0} validator definitions, but at most {} are allowed",
+        MAX_VALIDATOR_DEFINITIONS
+    )]
+    TooManyValidatorDefinitions(usize),
+    #[error(
+        "transaction has {0} denom metadata registrations, but at most {} are allowed",
+        MAX_DENOM_METADATA
+    )]
+    TooManyDenomMetadata(usize),
+    #[error(
+        "transaction has {0} upgrade proposals, but at most {} are allowed",
+        MAX_UPGRADE_PROPOSALS
+    )]
+    TooManyUpgradeProposals(usize),
+    #[error("validator definitions can't be combined with delegation changes")]
+    ValidatorDefinitionWithDelegation,
+    #[error("transaction both delegates to and undelegates from validator {0}")]
+    DelegateAndUndelegate(IdentityKey),
+    #[error("transaction redelegates from validator {0} to itself")]
+    RedelegateToSelf(IdentityKey),
+}
+
+/// Checks the structural rules of a transaction: that it has at least one
+/// action, that it has no more than the allowed number of each kind of action,
+/// and that it doesn't combine actions that conflict with each other.
+///
+/// These checks are cheap, so they're done before any proofs or signatures are
+/// verified.
+pub fn check_structure(transaction: &Transaction) -> Result<(), StructureError> {
+    let actions = &transaction.transaction_body.actions;
+    if actions.is_empty() {
+        return Err(StructureError::NoActions);
+    }
+
+    let (mut spends, mut outputs, mut validator_definitions, mut denom_metadata) = (0, 0, 0, 0);
+    let mut delegated = BTreeSet::<&IdentityKey>::new();
+    let mut undelegated = BTreeSet::<&IdentityKey>::new();
+    let (mut delegations, mut undelegations, mut redelegations) = (0, 0, 0);
+    let (mut dkg_dealings, mut dkg_complaints) = (0, 0);
+    let (mut flow_decryptions, mut upgrade_proposals) = (0, 0);
+    for action in actions {
+        match action {
+            Action::Spend(_) => spends += 1,
+            Action::Output(_) => outputs += 1,
+            Action::Delegate(delegate) => {
+                delegations += 1;
+                delegated.insert(&delegate.validator_identity);
+            }
+            Action::Undelegate(undelegate) => {
+                undelegations += 1;
+                undelegated.insert(&undelegate.validator_identity);
+            }
+            Action::Redelegate(redelegate) => {
+                if redelegate.from_validator_identity == redelegate.to_validator_identity {
+                    return Err(StructureError::RedelegateToSelf(
+                        redelegate.from_validator_identity.clone(),
+                    ));
+                }
+                // A redelegation undelegates from one validator and delegates
+                // to another, so it conflicts with the same actions; this also
+                // prevents chaining redelegations within a transaction.
+                redelegations += 1;
+                undelegated.insert(&redelegate.from_validator_identity);
+                delegated.insert(&redelegate.to_validator_identity);
+            }
+            // Encrypted flows count as the delegations and undelegations
+            // they hide.
+            Action::EncryptedFlow(flow) => match flow.direction {
+                FlowDirection::Delegate => {
+                    delegations += 1;
+                    delegated.insert(&flow.validator_identity);
+                }
+                FlowDirection::Undelegate => {
+                    undelegations += 1;
+                    undelegated.insert(&flow.validator_identity);
+                }
+            },
+            Action::DkgDealing(_) => dkg_dealings += 1,
+            Action::DkgComplaint(_) => dkg_complaints += 1,
+            Action::FlowDecryption(_) => flow_decryptions += 1,
+            Action::ValidatorDefinition(_) => validator_definitions += 1,
+            Action::DenomMetadata(_) => denom_metadata += 1,
+            Action::UpgradeProposal(_) => upgrade_proposals += 1,
+        }
+    }
+
+    if spends > MAX_SPENDS {
+        return Err(StructureError::TooManySpends(spends));
+    }
+    if outputs > MAX_OUTPUTS {
+        return Err(StructureError::TooManyOutputs(outputs));
+    }
+    if delegations > MAX_DELEGATIONS {
+        return Err(StructureError::TooManyDelegations(delegations));
+    }
+    if undelegations > MAX_UNDELEGATIONS {
+        return Err(StructureError::TooManyUndelegations(undelegations));
+    }
+    if redelegations > MAX_REDELEGATIONS {
+        return Err(StructureError::TooManyRedelegations(redelegations));
+    }
+    if dkg_dealings > MAX_DKG_DEALINGS {
+        return Err(StructureError::TooManyDkgDealings(dkg_dealings));
+    }
+    if dkg_complaints > MAX_DKG_COMPLAINTS {
+        return Err(StructureError::TooManyDkgComplaints(dkg_complaints));
+    }
+    if flow_decryptions > MAX_FLOW_DECRYPTIONS {
+        return Err(StructureError::TooManyFlowDecryptions(flow_decryptions));
+    }
+    if validator_definitions > MAX_VALIDATOR_DEFINITIONS {
+        return Err(StructureError::TooManyValidatorDefinitions(
+            validator_definitions,
+        ));
+    }
+    if denom_metadata > MAX_DENOM_METADATA {
+        return Err(StructureError::TooManyDenomMetadata(denom_metadata));
+    }
+    if upgrade_proposals > MAX_UPGRADE_PROPOSALS {
+        return Err(StructureError::TooManyUpgradeProposals(upgrade_proposals));
+    }
+
+    // A validator definition may change the validator's state, so delegation
+    // changes in the same transaction couldn't be checked against it.
+    if validator_definitions > 0 && delegations + undelegations + redelegations > 0 {
+        return Err(StructureError::ValidatorDefinitionWithDelegation);
+    }
+    // Delegating to and undelegating from the same validator at once only
+    // churns the delegation pool; the net change should be made directly.
+    if let Some(identity_key) = delegated.intersection(&undelegated).next() {
+        return Err(StructureError::DelegateAndUndelegate(
+            (*identity_key).clone(),
+        ));
+    }
+
+    Ok(())
+}
@@ -6,6 +6,7 @@ use penumbra_crypto::{
     merkle::{Frontier, NoteCommitmentTree, Tree, TreeExt},
     Fq, Note, Value,
 };
+use penumbra_proto::Protobuf;
 use penumbra_transaction::Transaction;
 use rand_core::OsRng;
 
@@ -66,3 +67,100 @@ fn test_transaction_succeeds_if_values_balance() {
         .verify_stateless()
         .expect("stateless verification should pass");
 }
+
+#[test]
+fn test_transaction_rejected_if_encoding_not_canonical() {
+    let mut rng = OsRng;
+    let sk_sender = SpendKey::generate(&mut rng);
+    let fvk_sender = sk_sender.full_viewing_key();
+    let (send_addr, _) = fvk_sender.incoming().payment_address(0u64.into());
+
+    let value = Value {
+        amount: 20,
+        asset_id: asset::REGISTRY.parse_denom("upenumbra").unwrap().id(),
+    };
+    let note = Note::from_parts(
+        *send_addr.diversifier(),
+        *send_addr.transmission_key(),
+        value,
+        Fq::zero(),
+    )
+    .expect("transmission key is valid");
+
+    let mut nct = NoteCommitmentTree::new(1);
+    nct.append(&note.commit());
+    nct.witness();
+
+    let transaction = Transaction::build_with_root(nct.root2())
+        .set_fee(20)
+        .set_chain_id("penumbra".to_string())
+        .add_spend(&mut rng, &nct, &sk_sender, note)
+        .expect("note is in nct")
+        .finalize(&mut rng)
+        .expect("transaction created ok");
+
+    let mut tx_bytes = transaction.encode_to_vec();
+    decode_canonical(&tx_bytes).expect("canonical encoding is accepted");
+
+    // Appending an unknown field (number 15, varint 1) doesn't change the
+    // decoded transaction, but does change its hash.
+    tx_bytes.extend_from_slice(&[0x78, 0x01]);
+    assert!(Transaction::decode(&tx_bytes[..]).is_ok());
+    assert!(decode_canonical(&tx_bytes).is_err());
+}
+
+#[test]
+fn test_transaction_rejected_if_memo_wrong_size() {
+    let mut rng = OsRng;
+    let sk_sender = SpendKey::generate(&mut rng);
+    let fvk_sender = sk_sender.full_viewing_key();
+    let (dest, _) = fvk_sender.incoming().payment_address(0u64.into());
+
+    let transaction = Transaction::build_with_root(NoteCommitmentTree::new(1).root2())
+        .set_fee(0)
+        .set_chain_id("penumbra".to_string())
+        .add_output(
+            &mut rng,
+            &dest,
+            Value {
+                amount: 0,
+                asset_id: asset::REGISTRY.parse_denom("upenumbra").unwrap().id(),
+            },
+            MemoPlaintext::default(),
+            fvk_sender.outgoing(),
+        )
+        .finalize(&mut rng)
+        .expect("transaction created ok");
+    decode_canonical(&transaction.encode_to_vec()).expect("memo has the right size");
+
+    let mut proto = penumbra_proto::transaction::Transaction::from(transaction);
+    match proto.body.as_mut().unwrap().actions[0].action.as_mut() {
+        Some(penumbra_proto::transaction::action::Action::Output(output)) => {
+            let mut memo = output.encrypted_memo.to_vec();
+            memo.push(0);
+            output.encrypted_memo = memo.into();
+        }
+        _ => unreachable!("the only action is an output"),
+    }
+    let error = decode_canonical(&penumbra_proto::Message::encode_to_vec(&proto))
+        .expect_err("oversized memo is rejected");
+    assert!(error.to_string().contains("encrypted memo"));
+}
+
+#[test]
+fn test_transaction_rejected_if_no_actions() {
+    let mut rng = OsRng;
+    let nct = NoteCommitmentTree::new(1);
+
+    let transaction = Transaction::build_with_root(nct.root2())
+        .set_fee(0)
+        .set_chain_id("penumbra".to_string())
+        .finalize(&mut rng)
+        .expect("transaction created ok");
+
+    assert_eq!(
+        check_structure(&transaction),
+        Err(StructureError::NoActions)
+    );
+    assert!(transaction.verify_stateless().is_err());
+}
@@ -1,18 +1,33 @@
-use std::pin::Pin;
+use std::{
+    collections::BTreeSet,
+    pin::Pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use futures::stream::{StreamExt, TryStreamExt};
+use once_cell::sync::Lazy;
+use penumbra_crypto::{merkle, note, Nullifier};
 use penumbra_proto::{
     self as proto,
     chain::{AssetInfo, ChainParams},
-    crypto::AssetId,
+    crypto::{AssetId, MerkleRoot},
     light_wallet::{
         light_wallet_server::LightWallet, ChainParamsRequest, CompactBlock,
         CompactBlockRangeRequest, ValidatorInfoRequest,
     },
     stake::ValidatorInfo,
     thin_wallet::{
-        thin_wallet_server::ThinWallet, Asset, AssetListRequest, TransactionByNoteRequest,
-        TransactionDetail, ValidatorRateRequest,
+        thin_wallet_server::ThinWallet, AnchorAtHeightRequest, Asset, AssetListRequest,
+        AssetSupply, AuthPath, BlockStats, BlockStatsRequest, CurrentEpoch, CurrentEpochRequest,
+        DelegationChangesRequest, DelegationChangesResponse, DkgRound, DkgRoundRequest, EpochStats,
+        EpochStatsRequest, EpochSummary, FeeEstimate, HeightForAnchorResponse, KeyProof,
+        KeyProofRequest, NotesByTransactionRequest, NotesByTransactionResponse, NullifierStatus,
+        NullifierStatusRequest, NullifierStatusResponse, SignedHeader, SignedHeaderRequest,
+        TransactionByHashRequest, TransactionByHashResponse, TransactionByNoteRequest,
+        TransactionDetail, TransactionSkeleton, ValidatorOverview, ValidatorOverviewRequest,
+        ValidatorRateRequest, ValidatorSet, ValidatorSetProof, ValidatorSetRequest,
+        ValidatorUptimes, ValidatorUptimesRequest, WitnessRequest, WitnessResponse,
     },
 };
 use penumbra_stake::IdentityKey;
@@ -21,7 +36,43 @@ use tokio_stream::wrappers::ReceiverStream;
 use tonic::Status;
 use tracing::{instrument, Instrument, Span};
 
-use crate::state;
+use crate::{fee, state, state::StateError};
+
+/// The number of most recent blocks a validator's uptime is measured over,
+/// unless another window is requested.
+const DEFAULT_UPTIME_WINDOW: u64 = 100;
+/// The largest window a validator's uptime may be measured over, since every
+/// block in it is decoded to count the validator's votes.
+const MAX_UPTIME_WINDOW: u64 = 10_000;
+/// The most nullifiers whose status may be requested at once, so that a
+/// single request's query stays small.
+const MAX_NULLIFIER_STATUSES: usize = 10_000;
+/// The most note commitments that may be witnessed at once.
+const MAX_WITNESS_COMMITMENTS: usize = 1_000;
+/// The most witness requests served each second, across all clients, since
+/// each one extends and hashes the in-memory note commitment tree.
+const WITNESS_REQUESTS_PER_SECOND: u32 = 20;
+
+/// The start of the current second of witness requests, and how many have
+/// been served in it.
+static WITNESS_WINDOW: Lazy<Mutex<(Instant, u32)>> = Lazy::new(|| Mutex::new((Instant::now(), 0)));
+
+/// Returns whether another witness request may be served this second.
+fn take_witness_request() -> bool {
+    let mut window = WITNESS_WINDOW
+        .lock()
+        .expect("no thread panics holding the lock");
+    let (start, served) = &mut *window;
+    if start.elapsed() >= Duration::from_secs(1) {
+        *start = Instant::now();
+        *served = 0;
+    }
+    if *served >= WITNESS_REQUESTS_PER_SECOND {
+        return false;
+    }
+    *served += 1;
+    true
+}
 
 #[tonic::async_trait]
 impl LightWallet for state::Reader {
@@ -36,15 +87,11 @@ impl LightWallet for state::Reader {
         &self,
         _request: tonic::Request<ChainParamsRequest>,
     ) -> Result<tonic::Response<ChainParams>, Status> {
-        let genesis_configuration = self
-            .genesis_configuration()
-            .await
-            .map_err(|_| tonic::Status::unavailable("error retrieving genesis configuration"))?;
+        let genesis_configuration = self.genesis_configuration().await.map_err(Status::from)?;
 
-        Ok(tonic::Response::new(ChainParams {
-            chain_id: genesis_configuration.chain_params.chain_id,
-            epoch_duration: genesis_configuration.chain_params.epoch_duration,
-        }))
+        Ok(tonic::Response::new(
+            genesis_configuration.chain_params.into(),
+        ))
     }
 
     #[instrument(skip(self, request), fields(show_inactive = request.get_ref().show_inactive))]
@@ -55,7 +102,7 @@ impl LightWallet for state::Reader {
         let validator_info = self
             .validator_info(request.into_inner().show_inactive)
             .await
-            .map_err(|_| tonic::Status::unavailable("database error"))?;
+            .map_err(Status::from)?;
 
         Ok(tonic::Response::new(
             futures::stream::iter(validator_info.into_iter().map(|info| Ok(info.into()))).boxed(),
@@ -78,11 +125,9 @@ impl LightWallet for state::Reader {
             end_height,
         } = request.into_inner();
 
-        let current_height = self
-            .height()
-            .await
-            .map_err(|_| tonic::Status::unavailable("database error"))?
-            .value() as u32;
+        // Use the height from the watch channel rather than the database, since
+        // it's only advanced once all of a block's notes have been written.
+        let current_height = self.height_rx().borrow().value() as u32;
 
         // Treat end_height = 0 as end_height = current_height so that if the
         // end_height is unspecified in the proto, it will be treated as a
@@ -103,7 +148,7 @@ impl LightWallet for state::Reader {
 
         let stream = self
             .compact_blocks(start_height.into(), end_height.into())
-            .map_err(|e| tonic::Status::internal(e.to_string()));
+            .map_err(Status::from);
 
         Ok(tonic::Response::new(stream.boxed()))
     }
@@ -123,7 +168,44 @@ impl ThinWallet for state::Reader {
         let transaction = state
             .transaction_by_note(request.into_inner().cm)
             .await
-            .map_err(|_| tonic::Status::not_found("transaction not found"))?;
+            .map_err(|e| match e {
+                StateError::NotFound(_) => tonic::Status::not_found("transaction not found"),
+                e => e.into(),
+            })?;
+        Ok(tonic::Response::new(transaction))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn notes_by_transaction(
+        &self,
+        request: tonic::Request<NotesByTransactionRequest>,
+    ) -> Result<tonic::Response<NotesByTransactionResponse>, Status> {
+        tracing::debug!(id = ?hex::encode(&request.get_ref().id));
+        let fragments = self
+            .notes_by_transaction(request.into_inner().id)
+            .await
+            .map_err(Status::from)?;
+        if fragments.is_empty() {
+            return Err(tonic::Status::not_found("transaction not found"));
+        }
+
+        Ok(tonic::Response::new(NotesByTransactionResponse {
+            fragments,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn transaction_by_hash(
+        &self,
+        request: tonic::Request<TransactionByHashRequest>,
+    ) -> Result<tonic::Response<TransactionByHashResponse>, Status> {
+        tracing::debug!(hash = ?hex::encode(&request.get_ref().hash));
+        let transaction = self
+            .transaction_by_hash(&request.into_inner().hash)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("transaction not found"))?;
+
         Ok(tonic::Response::new(transaction))
     }
 
@@ -139,9 +221,8 @@ impl ThinWallet for state::Reader {
         let asset = state
             .asset_lookup(asset_id)
             .await
-            .map_err(|_| tonic::Status::not_found("asset not found"))?
-            .ok_or(|| anyhow::anyhow!("asset not found"))
-            .map_err(|_| tonic::Status::not_found("asset not found"))?;
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("asset not found"))?;
 
         Ok(tonic::Response::new(asset))
     }
@@ -160,7 +241,7 @@ impl ThinWallet for state::Reader {
                 let assets = state
                     .asset_list()
                     .await
-                    .map_err(|_| tonic::Status::unavailable("database error"))
+                    .map_err(Status::from)
                     .unwrap();
                 for asset in &assets[..] {
 tracing::debug!(asset_id = ?hex::encode(&asset.asset_id), asset_denom = ?asset.asset_denom, "sending asset");
@@ -181,6 +262,47 @@ tracing::debug!(asset_id = ?hex::encode(&asset.asset_id), asset_denom = ?asset.a
         todo!()
     }
 
+    #[instrument(skip(self, request))]
+    async fn validator_overview(
+        &self,
+        request: tonic::Request<ValidatorOverviewRequest>,
+    ) -> Result<tonic::Response<ValidatorOverview>, Status> {
+        let request = request.into_inner();
+        let identity_key = IdentityKey::try_from(
+            request
+                .identity_key
+                .ok_or_else(|| tonic::Status::invalid_argument("missing identity key"))?,
+        )
+        .map_err(|_| tonic::Status::invalid_argument("invalid identity key"))?;
+        let uptime_window = match request.uptime_window {
+            0 => DEFAULT_UPTIME_WINDOW,
+            window => window.min(MAX_UPTIME_WINDOW),
+        };
+
+        let overview = self
+            .validator_overview(&identity_key, uptime_window)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("validator not found"))?;
+
+        Ok(tonic::Response::new(overview))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn validator_uptimes(
+        &self,
+        request: tonic::Request<ValidatorUptimesRequest>,
+    ) -> Result<tonic::Response<ValidatorUptimes>, Status> {
+        let window = match request.into_inner().window {
+            0 => DEFAULT_UPTIME_WINDOW,
+            window => window.min(MAX_UPTIME_WINDOW),
+        };
+
+        let uptimes = self.validator_uptimes(window).await.map_err(Status::from)?;
+
+        Ok(tonic::Response::new(uptimes))
+    }
+
     #[instrument(skip(self, request))]
     async fn validator_rate(
         &self,
@@ -190,7 +312,7 @@ tracing::debug!(asset_id = ?hex::encode(&asset.asset_id), asset_denom = ?asset.a
         let rates = self
             .rate_data(request.epoch_index)
             .await
-            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+            .map_err(Status::from)?;
 
         let identity_key = IdentityKey::try_from(
             request
@@ -206,4 +328,348 @@ tracing::debug!(asset_id = ?hex::encode(&asset.asset_id), asset_denom = ?asset.a
 
         Ok(tonic::Response::new(rate.into()))
     }
+
+    #[instrument(
+        skip(self, request),
+        fields(num_note_commitments = request.get_ref().note_commitments.len()),
+    )]
+    async fn witness(
+        &self,
+        request: tonic::Request<WitnessRequest>,
+    ) -> Result<tonic::Response<WitnessResponse>, Status> {
+        let note_commitments = request.into_inner().note_commitments;
+        if note_commitments.len() > MAX_WITNESS_COMMITMENTS {
+            return Err(tonic::Status::invalid_argument(format!(
+                "at most {} note commitments may be witnessed at once",
+                MAX_WITNESS_COMMITMENTS
+            )));
+        }
+        if !take_witness_request() {
+            return Err(tonic::Status::resource_exhausted(
+                "too many witness requests; try again shortly",
+            ));
+        }
+        let note_commitments = note_commitments
+            .into_iter()
+            .map(note::Commitment::try_from)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map_err(|_| tonic::Status::invalid_argument("invalid note commitment"))?;
+
+        let (height, anchor, paths) = self.witness(note_commitments).await.map_err(Status::from)?;
+
+        Ok(tonic::Response::new(WitnessResponse {
+            height: height.value(),
+            anchor: Some(anchor.into()),
+            auth_paths: paths
+                .into_iter()
+                .map(|(note_commitment, (position, path))| AuthPath {
+                    note_commitment: Some(note_commitment.into()),
+                    position: u64::from(position),
+                    path: path.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(height = request.get_ref().height))]
+    async fn anchor_at_height(
+        &self,
+        request: tonic::Request<AnchorAtHeightRequest>,
+    ) -> Result<tonic::Response<MerkleRoot>, Status> {
+        let anchor = self
+            .anchor_at_height(request.into_inner().height)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("block not found"))?;
+
+        Ok(tonic::Response::new(anchor.into()))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn height_for_anchor(
+        &self,
+        request: tonic::Request<MerkleRoot>,
+    ) -> Result<tonic::Response<HeightForAnchorResponse>, Status> {
+        let anchor = merkle::Root::try_from(request.into_inner())
+            .map_err(|_| tonic::Status::invalid_argument("invalid anchor"))?;
+        tracing::debug!(?anchor);
+
+        let height = self
+            .height_for_anchor(&anchor)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("anchor not found"))?;
+
+        Ok(tonic::Response::new(HeightForAnchorResponse {
+            height: height.value(),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(height = request.get_ref().height))]
+    async fn block_stats(
+        &self,
+        request: tonic::Request<BlockStatsRequest>,
+    ) -> Result<tonic::Response<BlockStats>, Status> {
+        let stats = self
+            .block_stats(request.into_inner().height)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no statistics for block"))?;
+
+        Ok(tonic::Response::new(stats))
+    }
+
+    #[instrument(skip(self, request), fields(epoch_index = request.get_ref().epoch_index))]
+    async fn epoch_stats(
+        &self,
+        request: tonic::Request<EpochStatsRequest>,
+    ) -> Result<tonic::Response<EpochStats>, Status> {
+        let stats = self
+            .epoch_stats(request.into_inner().epoch_index)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no statistics for epoch"))?;
+
+        Ok(tonic::Response::new(stats))
+    }
+
+    #[instrument(skip(self, request), fields(epoch_index = request.get_ref().epoch_index))]
+    async fn epoch_summary(
+        &self,
+        request: tonic::Request<EpochStatsRequest>,
+    ) -> Result<tonic::Response<EpochSummary>, Status> {
+        let summary = self
+            .epoch_summary(request.into_inner().epoch_index)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no summary for epoch"))?;
+
+        Ok(tonic::Response::new(summary))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn delegation_changes(
+        &self,
+        request: tonic::Request<DelegationChangesRequest>,
+    ) -> Result<tonic::Response<DelegationChangesResponse>, Status> {
+        let request = request.into_inner();
+        if request.start_epoch > request.end_epoch {
+            return Err(tonic::Status::invalid_argument(
+                "the start epoch is after the end epoch",
+            ));
+        }
+        let identity_key = request
+            .identity_key
+            .map(IdentityKey::try_from)
+            .transpose()
+            .map_err(|_| tonic::Status::invalid_argument("invalid identity key"))?;
+
+        let changes = self
+            .delegation_change_history(
+                request.start_epoch,
+                request.end_epoch,
+                identity_key.as_ref(),
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(tonic::Response::new(DelegationChangesResponse { changes }))
+    }
+
+    #[instrument(skip(self, request), fields(epoch_index = request.get_ref().epoch_index))]
+    async fn validator_set(
+        &self,
+        request: tonic::Request<ValidatorSetRequest>,
+    ) -> Result<tonic::Response<ValidatorSet>, Status> {
+        let validator_set = self
+            .validator_set(request.into_inner().epoch_index)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no validator set for epoch"))?;
+
+        Ok(tonic::Response::new(validator_set))
+    }
+
+    #[instrument(skip(self, request), fields(epoch_index = request.get_ref().epoch_index))]
+    async fn validator_set_proof(
+        &self,
+        request: tonic::Request<ValidatorSetRequest>,
+    ) -> Result<tonic::Response<ValidatorSetProof>, Status> {
+        let proof = self
+            .validator_set_proof(request.into_inner().epoch_index)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no validator set for epoch"))?;
+
+        Ok(tonic::Response::new(proof))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn key_proof(
+        &self,
+        request: tonic::Request<KeyProofRequest>,
+    ) -> Result<tonic::Response<KeyProof>, Status> {
+        let request = request.into_inner();
+        let key_hash = jmt::hash::HashValue::from_slice(&request.key_hash)
+            .map_err(|_| tonic::Status::invalid_argument("key hash must be 32 bytes"))?;
+
+        let proof = match request.height {
+            0 => self.key_proof(key_hash).await,
+            height => self.key_proof_at(key_hash, height).await,
+        }
+        .map_err(Status::from)?;
+
+        Ok(tonic::Response::new(proof))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn asset_supply(
+        &self,
+        request: tonic::Request<AssetId>,
+    ) -> Result<tonic::Response<AssetSupply>, Status> {
+        let asset_id = penumbra_crypto::asset::Id::try_from(request.into_inner())
+            .map_err(|_| tonic::Status::invalid_argument("invalid asset ID"))?;
+        tracing::debug!(?asset_id);
+
+        let supply = self
+            .asset_supply(asset_id)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("asset not found"))?;
+
+        Ok(tonic::Response::new(supply))
+    }
+
+    #[instrument(skip(self, request), fields(height = request.get_ref().height))]
+    async fn signed_header(
+        &self,
+        request: tonic::Request<SignedHeaderRequest>,
+    ) -> Result<tonic::Response<SignedHeader>, Status> {
+        let height = request.into_inner().height;
+        let signed_header = self
+            .signed_header(height)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no signed header stored for block"))?;
+
+        Ok(tonic::Response::new(SignedHeader {
+            height,
+            signed_header,
+        }))
+    }
+
+    #[instrument(skip(self, _request))]
+    async fn current_epoch(
+        &self,
+        _request: tonic::Request<CurrentEpochRequest>,
+    ) -> Result<tonic::Response<CurrentEpoch>, Status> {
+        let epoch = self
+            .current_epoch()
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no current epoch"))?;
+
+        Ok(tonic::Response::new(epoch))
+    }
+
+    #[instrument(skip(self, request), fields(epoch_index = request.get_ref().epoch_index))]
+    async fn dkg_round(
+        &self,
+        request: tonic::Request<DkgRoundRequest>,
+    ) -> Result<tonic::Response<DkgRound>, Status> {
+        let epoch_index = request.into_inner().epoch_index;
+        let round = self
+            .dkg_round(epoch_index)
+            .await
+            .map_err(Status::from)?
+            .ok_or_else(|| tonic::Status::not_found("no DKG round for epoch"))?;
+        let dealings = self.dkg_dealings(epoch_index).await.map_err(Status::from)?;
+
+        Ok(tonic::Response::new(DkgRound {
+            epoch_index,
+            threshold: round.threshold,
+            participants: round.participants.into_iter().map(Into::into).collect(),
+            dealers: dealings
+                .into_iter()
+                .map(|(_, dealing)| dealing.dealing.validator_identity.into())
+                .collect(),
+            finished: round.finished,
+            group_key: round
+                .group_key
+                .map(|key| key.compress().0.to_vec())
+                .unwrap_or_default(),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn estimate_fee(
+        &self,
+        request: tonic::Request<TransactionSkeleton>,
+    ) -> Result<tonic::Response<FeeEstimate>, Status> {
+        let skeleton = fee::TransactionSkeleton::from(request.into_inner());
+        let recent = self
+            .recent_block_loads(fee::RECENT_BLOCKS)
+            .await
+            .map_err(Status::from)?;
+        let chain_params = self.chain_params_rx().borrow().clone();
+
+        Ok(tonic::Response::new(fee::estimate(
+            &skeleton,
+            &recent,
+            &chain_params,
+        )))
+    }
+
+    #[instrument(skip(self, request), fields(count = request.get_ref().nullifiers.len()))]
+    async fn nullifier_status(
+        &self,
+        request: tonic::Request<NullifierStatusRequest>,
+    ) -> Result<tonic::Response<NullifierStatusResponse>, Status> {
+        let nullifiers = request.into_inner().nullifiers;
+        if nullifiers.len() > MAX_NULLIFIER_STATUSES {
+            return Err(tonic::Status::invalid_argument(format!(
+                "at most {} nullifiers may be checked at once",
+                MAX_NULLIFIER_STATUSES
+            )));
+        }
+        let nullifiers = nullifiers
+            .into_iter()
+            .map(|bytes| {
+                Nullifier::try_from(bytes)
+                    .map_err(|_| tonic::Status::invalid_argument("invalid nullifier"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let heights = self
+            .nullifier_heights(&nullifiers.iter().cloned().collect())
+            .await
+            .map_err(Status::from)?;
+
+        let statuses = nullifiers
+            .into_iter()
+            .map(|nullifier| {
+                let height = heights.get(&nullifier).copied();
+                NullifierStatus {
+                    nullifier: nullifier.to_bytes().to_vec(),
+                    spent: height.is_some(),
+                    height: height.unwrap_or(0),
+                }
+            })
+            .collect();
+
+        Ok(tonic::Response::new(NullifierStatusResponse { statuses }))
+    }
+}
+
+impl From<StateError> for Status {
+    fn from(error: StateError) -> Self {
+        match error {
+            StateError::NotFound(message) => Status::not_found(message),
+            error if error.is_transient() => Status::unavailable("database error"),
+            error => {
+                tracing::error!(?error, "state error while serving request");
+                Status::internal(error.to_string())
+            }
+        }
+    }
 }
@@ -58,6 +58,9 @@ static SERIALIZE: &str = r#"#[derive(::serde::Deserialize, ::serde::Serialize)]"
 /// Serializes newtype structs as if the inner field were serialized on its own.
 static SERDE_TRANSPARENT: &str = r#"#[serde(transparent)]"#;
 
+/// Fills in fields missing from older serialized data with their default value.
+static SERDE_DEFAULT: &str = r#"#[serde(default)]"#;
+
 static AS_HEX: &str = r#"#[serde(with = "crate::serializers::hexstr")]"#;
 static AS_BASE64: &str = r#"#[serde(with = "crate::serializers::base64str")]"#;
 static AS_BECH32_IDENTITY_KEY: &str =
@@ -79,6 +82,7 @@ static TYPE_ATTRIBUTES: &[(&str, &str)] = &[
     (".penumbra.stake.IdentityKey", SERDE_TRANSPARENT),
     (".penumbra.stake.Delegate", SERIALIZE),
     (".penumbra.stake.Undelegate", SERIALIZE),
+    (".penumbra.stake.Redelegate", SERIALIZE),
     (".penumbra.crypto.Address", SERIALIZE),
     (".penumbra.crypto.Address", SERDE_TRANSPARENT),
     (".penumbra.crypto.NoteCommitment", SERIALIZE),
@@ -91,6 +95,7 @@ static TYPE_ATTRIBUTES: &[(&str, &str)] = &[
     (".penumbra.crypto.MerkleRoot", SERIALIZE),
     (".penumbra.crypto.MerkleRoot", SERDE_TRANSPARENT),
     (".penumbra.chain.ChainParams", SERIALIZE),
+    (".penumbra.chain.ActionActivation", SERIALIZE),
     (".penumbra.genesis.GenesisAppState", SERIALIZE),
     (".penumbra.genesis.Allocation", SERIALIZE),
     (".penumbra.genesis.ValidatorPower", SERIALIZE),
@@ -106,4 +111,34 @@ static FIELD_ATTRIBUTES: &[(&str, &str)] = &[
     (".penumbra.crypto.AssetId.inner", AS_BECH32_ASSET_ID),
     (".penumbra.crypto.NoteCommitment.inner", AS_HEX),
     (".penumbra.crypto.MerkleRoot.inner", AS_HEX),
+    // Added after genesis files and wallet state files were already in use.
+    (
+        ".penumbra.chain.ChainParams.num_recent_anchors",
+        SERDE_DEFAULT,
+    ),
+    (
+        ".penumbra.chain.ChainParams.max_block_transactions",
+        SERDE_DEFAULT,
+    ),
+    (".penumbra.chain.ChainParams.max_block_bytes", SERDE_DEFAULT),
+    (
+        ".penumbra.chain.ChainParams.fee_distribution_bps",
+        SERDE_DEFAULT,
+    ),
+    (
+        ".penumbra.chain.ChainParams.epoch_duration_secs",
+        SERDE_DEFAULT,
+    ),
+    (
+        ".penumbra.chain.ChainParams.max_validator_stake_bps",
+        SERDE_DEFAULT,
+    ),
+    (
+        ".penumbra.chain.ChainParams.min_delegation_amount",
+        SERDE_DEFAULT,
+    ),
+    (
+        ".penumbra.chain.ChainParams.action_activations",
+        SERDE_DEFAULT,
+    ),
 ];
@@ -72,7 +72,14 @@ pub mod sighash {
                 Some(TxAction::Output(o)) => Some(SHAction::Output(o)),
                 Some(TxAction::Delegate(d)) => Some(SHAction::Delegate(d)),
                 Some(TxAction::Undelegate(d)) => Some(SHAction::Undelegate(d)),
+                Some(TxAction::Redelegate(r)) => Some(SHAction::Redelegate(r)),
+                Some(TxAction::DkgDealing(d)) => Some(SHAction::DkgDealing(d)),
+                Some(TxAction::EncryptedFlow(f)) => Some(SHAction::EncryptedFlow(f)),
+                Some(TxAction::FlowDecryption(d)) => Some(SHAction::FlowDecryption(d)),
+                Some(TxAction::DkgComplaint(c)) => Some(SHAction::DkgComplaint(c)),
                 Some(TxAction::ValidatorDefinition(d)) => Some(SHAction::ValidatorDefinition(d)),
+                Some(TxAction::DenomMetadata(m)) => Some(SHAction::DenomMetadata(m)),
+                Some(TxAction::UpgradeProposal(p)) => Some(SHAction::UpgradeProposal(p)),
                 // Collapse spends to spend bodies
                 Some(TxAction::Spend(Spend { body: None, .. })) => None,
                 Some(TxAction::Spend(Spend {
@@ -0,0 +1,173 @@
+use penumbra_crypto::{
+    dkg::{Complaint, Dealing},
+    rdsa::{Signature, SpendAuth},
+};
+use penumbra_proto::{stake as pb, Protobuf};
+
+use crate::IdentityKey;
+
+/// A validator's dealing in the distributed key generation run by the active
+/// validators of an epoch, which establishes the threshold decryption key
+/// used in the following epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkgDealing {
+    /// The identity key of the dealing validator.
+    pub validator_identity: IdentityKey,
+    /// The index of the epoch whose key generation this dealing is part of.
+    pub epoch_index: u64,
+    /// The dealing itself, with one encrypted share per participant.
+    pub dealing: Dealing,
+}
+
+impl DkgDealing {
+    /// The context the dealing's proof of knowledge of its constant term is
+    /// bound to, so that it can't be reused by another dealer or round.
+    pub fn proof_context(validator_identity: &IdentityKey, epoch_index: u64) -> Vec<u8> {
+        let mut context = validator_identity.0.to_bytes().to_vec();
+        context.extend_from_slice(&epoch_index.to_le_bytes());
+        context
+    }
+
+    /// Checks the dealing's proof of knowledge of its constant term.
+    pub fn verify_proof(&self) -> anyhow::Result<()> {
+        self.dealing
+            .verify_proof(&Self::proof_context(
+                &self.validator_identity,
+                self.epoch_index,
+            ))
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "invalid DKG dealing from {}: {}",
+                    self.validator_identity,
+                    e
+                )
+            })
+    }
+}
+
+/// A [`DkgDealing`], authorized by the dealer's identity key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedDkgDealing {
+    pub dealing: DkgDealing,
+    pub auth_sig: Signature<SpendAuth>,
+}
+
+impl SignedDkgDealing {
+    /// Checks that the dealing was signed by the dealer's identity key.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.dealing
+            .validator_identity
+            .0
+            .verify(&self.dealing.encode_to_vec(), &self.auth_sig)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "DKG dealing is not signed by validator {}",
+                    self.dealing.validator_identity
+                )
+            })
+    }
+}
+
+/// A DKG participant's complaint that the share another participant's dealing
+/// dealt it doesn't match the dealer's commitments.
+///
+/// A justified complaint leaves the dealer out of the round.  Complaints need
+/// no signature, since only the participant the share was dealt to can make
+/// one that checks out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkgComplaint {
+    /// The identity key of the complaining validator.
+    pub validator_identity: IdentityKey,
+    /// The index of the epoch whose key generation the dealing is part of.
+    pub epoch_index: u64,
+    /// The identity key of the dealer complained about.
+    pub dealer_identity: IdentityKey,
+    pub complaint: Complaint,
+}
+
+impl Protobuf<pb::DkgDealing> for DkgDealing {}
+
+impl From<DkgDealing> for pb::DkgDealing {
+    fn from(d: DkgDealing) -> Self {
+        pb::DkgDealing {
+            validator_identity: Some(d.validator_identity.into()),
+            epoch_index: d.epoch_index,
+            dealing: Some(d.dealing.into()),
+        }
+    }
+}
+
+impl TryFrom<pb::DkgDealing> for DkgDealing {
+    type Error = anyhow::Error;
+    fn try_from(d: pb::DkgDealing) -> Result<Self, Self::Error> {
+        Ok(DkgDealing {
+            validator_identity: d
+                .validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing validator identity"))?
+                .try_into()?,
+            epoch_index: d.epoch_index,
+            dealing: d
+                .dealing
+                .ok_or_else(|| anyhow::anyhow!("missing dealing"))?
+                .try_into()?,
+        })
+    }
+}
+
+impl Protobuf<pb::SignedDkgDealing> for SignedDkgDealing {}
+
+impl From<SignedDkgDealing> for pb::SignedDkgDealing {
+    fn from(d: SignedDkgDealing) -> Self {
+        pb::SignedDkgDealing {
+            dealing: Some(d.dealing.into()),
+            auth_sig: d.auth_sig.to_bytes().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::SignedDkgDealing> for SignedDkgDealing {
+    type Error = anyhow::Error;
+    fn try_from(d: pb::SignedDkgDealing) -> Result<Self, Self::Error> {
+        Ok(SignedDkgDealing {
+            dealing: d
+                .dealing
+                .ok_or_else(|| anyhow::anyhow!("missing dealing"))?
+                .try_into()?,
+            auth_sig: d.auth_sig.as_slice().try_into()?,
+        })
+    }
+}
+
+impl Protobuf<pb::DkgComplaint> for DkgComplaint {}
+
+impl From<DkgComplaint> for pb::DkgComplaint {
+    fn from(c: DkgComplaint) -> Self {
+        pb::DkgComplaint {
+            validator_identity: Some(c.validator_identity.into()),
+            epoch_index: c.epoch_index,
+            dealer_identity: Some(c.dealer_identity.into()),
+            complaint: Some(c.complaint.into()),
+        }
+    }
+}
+
+impl TryFrom<pb::DkgComplaint> for DkgComplaint {
+    type Error = anyhow::Error;
+    fn try_from(c: pb::DkgComplaint) -> Result<Self, Self::Error> {
+        Ok(DkgComplaint {
+            validator_identity: c
+                .validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing validator identity"))?
+                .try_into()?,
+            epoch_index: c.epoch_index,
+            dealer_identity: c
+                .dealer_identity
+                .ok_or_else(|| anyhow::anyhow!("missing dealer identity"))?
+                .try_into()?,
+            complaint: c
+                .complaint
+                .ok_or_else(|| anyhow::anyhow!("missing complaint"))?
+                .try_into()?,
+        })
+    }
+}
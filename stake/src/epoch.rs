@@ -5,6 +5,8 @@ use tendermint::block;
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Epoch {
     pub index: u64,
+    /// The number of blocks in each epoch, or zero for time-based epochs,
+    /// whose heights aren't known in advance.
     pub duration: u64,
 }
 
@@ -17,12 +19,14 @@ impl Epoch {
         }
     }
 
-    /// Indicates the starting block height for this epoch (inclusive)
+    /// Indicates the starting block height for this epoch (inclusive).
+    /// Only meaningful for block-count epochs.
     pub fn start_height(&self) -> block::Height {
         block::Height::try_from(self.index * self.duration).expect("able to parse block height")
     }
 
-    /// Indicates the ending block height for this epoch (inclusive)
+    /// Indicates the ending block height for this epoch (inclusive).
+    /// Only meaningful for block-count epochs.
     pub fn end_height(&self) -> block::Height {
         block::Height::try_from((self.index + 1) * self.duration - 1)
             .expect("able to parse block height")
@@ -0,0 +1,166 @@
+use penumbra_crypto::{
+    flow::{Ciphertext, DecryptionShare},
+    proofs::transparent::FlowProof,
+    value,
+};
+use penumbra_proto::{stake as pb, Protobuf};
+
+use crate::{DelegationToken, IdentityKey, RateData, STAKING_TOKEN_ASSET_ID};
+
+/// Whether an [`EncryptedFlow`] adds stake to a validator's delegation pool
+/// or withdraws it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FlowDirection {
+    Delegate,
+    Undelegate,
+}
+
+/// A transaction action delegating to or undelegating from a validator,
+/// like [`Delegate`](crate::Delegate) or [`Undelegate`](crate::Undelegate),
+/// but with the amount hidden.
+///
+/// The amount of delegation tokens produced or consumed is encrypted to the
+/// epoch's threshold key.  The encrypted amounts for each validator are summed
+/// over the epoch, and only the sums are decrypted, by the holders of the key
+/// shares during the next epoch.
+///
+/// The amounts are not actually hidden yet: the [`FlowProof`] is a transparent
+/// proof, which carries them in the clear.
+#[derive(Debug, Clone)]
+pub struct EncryptedFlow {
+    /// The identity key of the validator to delegate to or undelegate from.
+    pub validator_identity: IdentityKey,
+    /// The index of the epoch in which this flow was performed.
+    pub epoch_index: u64,
+    pub direction: FlowDirection,
+    /// A commitment to the value contributed to the transaction by this flow.
+    pub value_commitment: value::Commitment,
+    /// The amount of delegation tokens produced or consumed, encrypted to the
+    /// epoch's threshold key.
+    pub amount_ciphertext: Ciphertext,
+    /// A proof that the value commitment and the encrypted amount are
+    /// consistent with each other and with the validator's exchange rate.
+    pub proof: FlowProof,
+}
+
+impl EncryptedFlow {
+    /// Checks the flow's proof against the validator's rate data for the epoch
+    /// in which it was performed, and the key its amount is encrypted to.
+    pub fn verify(&self, rate_data: &RateData, flow_key: &decaf377::Element) -> anyhow::Result<()> {
+        self.proof
+            .verify(
+                self.value_commitment,
+                &self.amount_ciphertext,
+                flow_key,
+                *STAKING_TOKEN_ASSET_ID,
+                DelegationToken::new(self.validator_identity.clone()).id(),
+                rate_data.validator_exchange_rate,
+                self.direction == FlowDirection::Undelegate,
+            )
+            .map_err(|e| anyhow::anyhow!("invalid encrypted flow proof: {}", e))
+    }
+}
+
+/// A transaction action publishing a key share holder's decryption shares of
+/// the summed [`EncryptedFlow`]s of an epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowDecryption {
+    /// The identity key of the validator holding the key share.
+    pub validator_identity: IdentityKey,
+    /// The index of the epoch whose flows are decrypted.
+    pub epoch_index: u64,
+    /// The decryption shares of the epoch's summed flows, ordered by
+    /// validator identity key, then with delegations before undelegations.
+    pub shares: Vec<DecryptionShare>,
+}
+
+impl From<FlowDirection> for pb::FlowDirection {
+    fn from(direction: FlowDirection) -> Self {
+        match direction {
+            FlowDirection::Delegate => pb::FlowDirection::Delegate,
+            FlowDirection::Undelegate => pb::FlowDirection::Undelegate,
+        }
+    }
+}
+
+impl From<pb::FlowDirection> for FlowDirection {
+    fn from(direction: pb::FlowDirection) -> Self {
+        match direction {
+            pb::FlowDirection::Delegate => FlowDirection::Delegate,
+            pb::FlowDirection::Undelegate => FlowDirection::Undelegate,
+        }
+    }
+}
+
+impl Protobuf<pb::EncryptedFlow> for EncryptedFlow {}
+
+impl From<EncryptedFlow> for pb::EncryptedFlow {
+    fn from(f: EncryptedFlow) -> Self {
+        let cv_bytes: [u8; 32] = f.value_commitment.into();
+        let proof: Vec<u8> = f.proof.into();
+        pb::EncryptedFlow {
+            validator_identity: Some(f.validator_identity.into()),
+            epoch_index: f.epoch_index,
+            direction: pb::FlowDirection::from(f.direction) as i32,
+            value_commitment: cv_bytes.to_vec(),
+            amount_ciphertext: Some(f.amount_ciphertext.into()),
+            proof,
+        }
+    }
+}
+
+impl TryFrom<pb::EncryptedFlow> for EncryptedFlow {
+    type Error = anyhow::Error;
+    fn try_from(f: pb::EncryptedFlow) -> Result<Self, Self::Error> {
+        Ok(EncryptedFlow {
+            validator_identity: f
+                .validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing validator identity"))?
+                .try_into()?,
+            epoch_index: f.epoch_index,
+            direction: pb::FlowDirection::from_i32(f.direction)
+                .ok_or_else(|| anyhow::anyhow!("invalid flow direction"))?
+                .into(),
+            value_commitment: (f.value_commitment[..])
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("invalid value commitment"))?,
+            amount_ciphertext: f
+                .amount_ciphertext
+                .ok_or_else(|| anyhow::anyhow!("missing amount ciphertext"))?
+                .try_into()?,
+            proof: (f.proof[..])
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("invalid encrypted flow proof"))?,
+        })
+    }
+}
+
+impl Protobuf<pb::FlowDecryption> for FlowDecryption {}
+
+impl From<FlowDecryption> for pb::FlowDecryption {
+    fn from(d: FlowDecryption) -> Self {
+        pb::FlowDecryption {
+            validator_identity: Some(d.validator_identity.into()),
+            epoch_index: d.epoch_index,
+            shares: d.shares.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::FlowDecryption> for FlowDecryption {
+    type Error = anyhow::Error;
+    fn try_from(d: pb::FlowDecryption) -> Result<Self, Self::Error> {
+        Ok(FlowDecryption {
+            validator_identity: d
+                .validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing validator identity"))?
+                .try_into()?,
+            epoch_index: d.epoch_index,
+            shares: d
+                .shares
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
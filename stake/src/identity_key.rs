@@ -13,6 +13,13 @@ impl IdentityKey {
     pub fn delegation_token(&self) -> DelegationToken {
         DelegationToken::new(self.clone())
     }
+
+    /// The identity key as a group element, the key DKG shares are encrypted to.
+    pub fn dkg_key(&self) -> decaf377::Element {
+        decaf377::Encoding(self.0.to_bytes())
+            .decompress()
+            .expect("verification keys are valid group elements")
+    }
 }
 
 impl std::str::FromStr for IdentityKey {
@@ -3,22 +3,28 @@ use once_cell::sync::Lazy;
 use penumbra_crypto::asset;
 
 mod delegate;
+mod dkg;
 mod epoch;
+mod flow;
 mod funding_stream;
 mod identity_key;
 mod info;
 mod rate;
+mod redelegate;
 mod status;
 mod token;
 mod undelegate;
 mod validator;
 
 pub use delegate::Delegate;
+pub use dkg::{DkgComplaint, DkgDealing, SignedDkgDealing};
 pub use epoch::Epoch;
+pub use flow::{EncryptedFlow, FlowDecryption, FlowDirection};
 pub use funding_stream::FundingStream;
 pub use identity_key::IdentityKey;
 pub use info::ValidatorInfo;
 pub use rate::{BaseRateData, RateData, RateDataById};
+pub use redelegate::Redelegate;
 pub use status::{ValidatorState, ValidatorStateName, ValidatorStatus};
 pub use token::DelegationToken;
 pub use undelegate::Undelegate;
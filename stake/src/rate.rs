@@ -75,11 +75,18 @@ impl RateData {
     /// ```
     /// but in general *not both*, because the computation involves rounding.
     pub fn delegation_amount(&self, unbonded_amount: u64) -> u64 {
+        self.checked_delegation_amount(unbonded_amount)
+            .expect("delegation amount fits in 64 bits")
+    }
+
+    /// Like [`RateData::delegation_amount`], but returns `None` rather than
+    /// panicking if the result doesn't fit in 64 bits.
+    pub fn checked_delegation_amount(&self, unbonded_amount: u64) -> Option<u64> {
         // validator_exchange_rate fits in 32 bits, but unbonded_amount is 64-bit;
-        // upconvert to u128 intermediates and panic if the result is too large (unlikely)
+        // upconvert to u128 intermediates and fail if the result is too large (unlikely)
         ((unbonded_amount as u128 * 1_0000_0000) / self.validator_exchange_rate as u128)
             .try_into()
-            .unwrap()
+            .ok()
     }
 
     /// Computes the amount of unbonded stake corresponding to the given amount of delegation tokens.
@@ -96,11 +103,18 @@ impl RateData {
     /// ```
     /// but in general *not both*, because the computation involves rounding.
     pub fn unbonded_amount(&self, delegation_amount: u64) -> u64 {
+        self.checked_unbonded_amount(delegation_amount)
+            .expect("unbonded amount fits in 64 bits")
+    }
+
+    /// Like [`RateData::unbonded_amount`], but returns `None` rather than
+    /// panicking if the result doesn't fit in 64 bits.
+    pub fn checked_unbonded_amount(&self, delegation_amount: u64) -> Option<u64> {
         // validator_exchange_rate fits in 32 bits, but unbonded_amount is 64-bit;
-        // upconvert to u128 intermediates and panic if the result is too large (unlikely)
+        // upconvert to u128 intermediates and fail if the result is too large (unlikely)
         ((delegation_amount as u128 * self.validator_exchange_rate as u128) / 1_0000_0000)
             .try_into()
-            .unwrap()
+            .ok()
     }
 
     /// Computes the validator's voting power at this epoch given the total supply of the
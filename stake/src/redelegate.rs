@@ -0,0 +1,88 @@
+use penumbra_crypto::{value, Fr, Value, Zero};
+use penumbra_proto::{stake as pb, Protobuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{DelegationToken, IdentityKey};
+
+/// A transaction action moving stake from one validator's delegation pool to
+/// another's, without going through the unbonding period.
+///
+/// The delegation tokens of the source validator are converted to unbonded
+/// stake at its exchange rate, which is then converted to delegation tokens of
+/// the destination validator at its exchange rate.  No staking tokens are
+/// produced or consumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "pb::Redelegate", into = "pb::Redelegate")]
+pub struct Redelegate {
+    /// The identity key of the validator to withdraw delegation from.
+    pub from_validator_identity: IdentityKey,
+    /// The identity key of the validator to delegate to.
+    pub to_validator_identity: IdentityKey,
+    /// The index of the epoch in which this redelegation was performed.
+    /// The redelegation takes effect in the next epoch.
+    pub epoch_index: u64,
+    /// The amount of the source validator's delegation tokens consumed by this action.
+    pub from_delegation_amount: u64,
+    /// The amount being moved, in units of unbonded stake.
+    ///
+    /// This and `to_delegation_amount` are implied by the validators' exchange
+    /// rates in the specified epoch (and should be checked in transaction
+    /// validation!).
+    pub unbonded_amount: u64,
+    /// The amount of the destination validator's delegation tokens produced by this action.
+    pub to_delegation_amount: u64,
+}
+
+impl Redelegate {
+    /// Compute a commitment to the value contributed to a transaction by this redelegation.
+    pub fn value_commitment(&self) -> value::Commitment {
+        let from = Value {
+            amount: self.from_delegation_amount,
+            asset_id: DelegationToken::new(self.from_validator_identity.clone()).id(),
+        }
+        .commit(Fr::zero());
+        let to = Value {
+            amount: self.to_delegation_amount,
+            asset_id: DelegationToken::new(self.to_validator_identity.clone()).id(),
+        }
+        .commit(Fr::zero());
+
+        // We consume the source delegation tokens and produce the destination ones.
+        to - from
+    }
+}
+
+impl Protobuf<pb::Redelegate> for Redelegate {}
+
+impl From<Redelegate> for pb::Redelegate {
+    fn from(r: Redelegate) -> Self {
+        pb::Redelegate {
+            from_validator_identity: Some(r.from_validator_identity.into()),
+            to_validator_identity: Some(r.to_validator_identity.into()),
+            epoch_index: r.epoch_index,
+            from_delegation_amount: r.from_delegation_amount,
+            unbonded_amount: r.unbonded_amount,
+            to_delegation_amount: r.to_delegation_amount,
+        }
+    }
+}
+
+impl TryFrom<pb::Redelegate> for Redelegate {
+    type Error = anyhow::Error;
+    fn try_from(r: pb::Redelegate) -> Result<Self, Self::Error> {
+        Ok(Self {
+            from_validator_identity: r
+                .from_validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing source validator identity"))?
+                .try_into()?,
+            to_validator_identity: r
+                .to_validator_identity
+                .ok_or_else(|| anyhow::anyhow!("missing destination validator identity"))?
+                .try_into()?,
+            epoch_index: r.epoch_index,
+            from_delegation_amount: r.from_delegation_amount,
+            unbonded_amount: r.unbonded_amount,
+            to_delegation_amount: r.to_delegation_amount,
+        })
+    }
+}
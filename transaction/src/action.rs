@@ -7,11 +7,15 @@ use penumbra_stake as stake;
 // TODO: remove & replace w/ anyhow
 pub mod error;
 
+pub mod denom_metadata;
 pub mod output;
 pub mod spend;
+pub mod upgrade;
 
+pub use denom_metadata::DenomMetadata;
 pub use output::Output;
 pub use spend::Spend;
+pub use upgrade::{SignedUpgradeProposal, UpgradePlan, UpgradeProposal};
 
 /// Supported actions in a Penumbra transaction.
 #[derive(Clone, Debug)]
@@ -20,7 +24,14 @@ pub enum Action {
     Spend(spend::Spend),
     Delegate(stake::Delegate),
     Undelegate(stake::Undelegate),
+    Redelegate(stake::Redelegate),
+    DkgDealing(stake::SignedDkgDealing),
+    EncryptedFlow(stake::EncryptedFlow),
+    FlowDecryption(stake::FlowDecryption),
+    DkgComplaint(stake::DkgComplaint),
     ValidatorDefinition(stake::ValidatorDefinition),
+    DenomMetadata(denom_metadata::DenomMetadata),
+    UpgradeProposal(upgrade::SignedUpgradeProposal),
 }
 
 impl Action {
@@ -32,7 +43,14 @@ impl Action {
             Action::Spend(spend) => spend.body.value_commitment,
             Action::Delegate(delegate) => delegate.value_commitment(),
             Action::Undelegate(undelegate) => undelegate.value_commitment(),
+            Action::Redelegate(redelegate) => redelegate.value_commitment(),
+            Action::DkgDealing(_) => value::Commitment::default(),
+            Action::EncryptedFlow(flow) => flow.value_commitment,
+            Action::FlowDecryption(_) => value::Commitment::default(),
+            Action::DkgComplaint(_) => value::Commitment::default(),
             Action::ValidatorDefinition(_) => value::Commitment::default(),
+            Action::DenomMetadata(_) => value::Commitment::default(),
+            Action::UpgradeProposal(_) => value::Commitment::default(),
         }
     }
 }
@@ -54,9 +72,30 @@ impl From<Action> for pb::Action {
             Action::Undelegate(inner) => pb::Action {
                 action: Some(pb::action::Action::Undelegate(inner.into())),
             },
+            Action::Redelegate(inner) => pb::Action {
+                action: Some(pb::action::Action::Redelegate(inner.into())),
+            },
+            Action::DkgDealing(inner) => pb::Action {
+                action: Some(pb::action::Action::DkgDealing(inner.into())),
+            },
+            Action::EncryptedFlow(inner) => pb::Action {
+                action: Some(pb::action::Action::EncryptedFlow(inner.into())),
+            },
+            Action::FlowDecryption(inner) => pb::Action {
+                action: Some(pb::action::Action::FlowDecryption(inner.into())),
+            },
+            Action::DkgComplaint(inner) => pb::Action {
+                action: Some(pb::action::Action::DkgComplaint(inner.into())),
+            },
             Action::ValidatorDefinition(inner) => pb::Action {
                 action: Some(pb::action::Action::ValidatorDefinition(inner.into())),
             },
+            Action::DenomMetadata(inner) => pb::Action {
+                action: Some(pb::action::Action::DenomMetadata(inner.into())),
+            },
+            Action::UpgradeProposal(inner) => pb::Action {
+                action: Some(pb::action::Action::UpgradeProposal(inner.into())),
+            },
         }
     }
 }
@@ -75,9 +114,24 @@ impl TryFrom<pb::Action> for Action {
             pb::action::Action::Spend(inner) => Ok(Action::Spend(inner.try_into()?)),
             pb::action::Action::Delegate(inner) => Ok(Action::Delegate(inner.try_into()?)),
             pb::action::Action::Undelegate(inner) => Ok(Action::Undelegate(inner.try_into()?)),
+            pb::action::Action::Redelegate(inner) => Ok(Action::Redelegate(inner.try_into()?)),
+            pb::action::Action::DkgDealing(inner) => Ok(Action::DkgDealing(inner.try_into()?)),
+            pb::action::Action::EncryptedFlow(inner) => {
+                Ok(Action::EncryptedFlow(inner.try_into()?))
+            }
+            pb::action::Action::FlowDecryption(inner) => {
+                Ok(Action::FlowDecryption(inner.try_into()?))
+            }
+            pb::action::Action::DkgComplaint(inner) => Ok(Action::DkgComplaint(inner.try_into()?)),
             pb::action::Action::ValidatorDefinition(inner) => {
                 Ok(Action::ValidatorDefinition(inner.try_into()?))
             }
+            pb::action::Action::DenomMetadata(inner) => {
+                Ok(Action::DenomMetadata(inner.try_into()?))
+            }
+            pb::action::Action::UpgradeProposal(inner) => {
+                Ok(Action::UpgradeProposal(inner.try_into()?))
+            }
         }
     }
 }
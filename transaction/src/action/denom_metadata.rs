@@ -0,0 +1,82 @@
+use std::convert::{TryFrom, TryInto};
+
+use anyhow::anyhow;
+use penumbra_crypto::asset;
+use penumbra_proto::{transaction as pb, Protobuf};
+
+/// The maximum length, in bytes, of a registered symbol.
+pub const MAX_SYMBOL_LEN: usize = 16;
+/// The maximum length, in bytes, of a registered description.
+pub const MAX_DESCRIPTION_LEN: usize = 256;
+/// The largest display exponent that still leaves a `u64` amount able to
+/// express more than one display unit.
+pub const MAX_DISPLAY_EXPONENT: u8 = 18;
+
+/// A transaction action registering display metadata for an asset, so that
+/// wallets can render amounts of assets they don't already know about.
+///
+/// Metadata can only be registered once per asset, and each symbol can only be
+/// used by one asset.
+#[derive(Clone, Debug)]
+pub struct DenomMetadata {
+    /// The base denomination of the asset the metadata describes.
+    pub denom: asset::Denom,
+    pub description: String,
+    /// The power of ten relating the asset's base unit to its display unit.
+    pub display_exponent: u8,
+    /// A short, alphanumeric ticker symbol for the asset.
+    pub symbol: String,
+}
+
+impl Protobuf<pb::DenomMetadata> for DenomMetadata {}
+
+impl From<DenomMetadata> for pb::DenomMetadata {
+    fn from(metadata: DenomMetadata) -> Self {
+        pb::DenomMetadata {
+            denom: Some(metadata.denom.into()),
+            description: metadata.description,
+            display_exponent: metadata.display_exponent as u32,
+            symbol: metadata.symbol,
+        }
+    }
+}
+
+impl TryFrom<pb::DenomMetadata> for DenomMetadata {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: pb::DenomMetadata) -> Result<Self, Self::Error> {
+        let denom = msg
+            .denom
+            .ok_or_else(|| anyhow!("missing denom"))?
+            .try_into()?;
+
+        if msg.symbol.is_empty()
+            || msg.symbol.len() > MAX_SYMBOL_LEN
+            || !msg.symbol.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(anyhow!(
+                "symbol must be 1 to {} ASCII letters and digits",
+                MAX_SYMBOL_LEN
+            ));
+        }
+        if msg.description.len() > MAX_DESCRIPTION_LEN {
+            return Err(anyhow!(
+                "description must be at most {} bytes",
+                MAX_DESCRIPTION_LEN
+            ));
+        }
+        if msg.display_exponent > MAX_DISPLAY_EXPONENT as u32 {
+            return Err(anyhow!(
+                "display exponent must be at most {}",
+                MAX_DISPLAY_EXPONENT
+            ));
+        }
+
+        Ok(DenomMetadata {
+            denom,
+            description: msg.description,
+            display_exponent: msg.display_exponent as u8,
+            symbol: msg.symbol,
+        })
+    }
+}
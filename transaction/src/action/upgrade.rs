@@ -0,0 +1,179 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use penumbra_crypto::rdsa::{Signature, SpendAuth};
+use penumbra_proto::{transaction as pb, Protobuf};
+use penumbra_stake::IdentityKey;
+
+/// The maximum length, in bytes, of an upgrade name.
+pub const MAX_UPGRADE_NAME_LEN: usize = 64;
+
+/// A plan to upgrade the chain: nodes halt at `height`, and only carry on once
+/// they're restarted with a release that supports the upgrade named `name`.
+///
+/// An emergency halt stops every node at `height`, even those on a release
+/// that supports an upgrade of the same name.  Only a release that declares it
+/// has resolved the halt named `name` carries on.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UpgradePlan {
+    /// The name of the upgrade, as declared by the releases that support it.
+    pub name: String,
+    /// The height of the first block the upgraded release processes.
+    pub height: u64,
+    /// Whether the plan halts every release, rather than only those that
+    /// don't support the upgrade.
+    pub emergency_halt: bool,
+}
+
+impl fmt::Display for UpgradePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.emergency_halt {
+            write!(f, "emergency halt ")?;
+        }
+        write!(f, "{} at height {}", self.name, self.height)
+    }
+}
+
+impl Protobuf<pb::UpgradePlan> for UpgradePlan {}
+
+impl From<UpgradePlan> for pb::UpgradePlan {
+    fn from(plan: UpgradePlan) -> Self {
+        pb::UpgradePlan {
+            name: plan.name,
+            height: plan.height,
+            emergency_halt: plan.emergency_halt,
+        }
+    }
+}
+
+impl TryFrom<pb::UpgradePlan> for UpgradePlan {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: pb::UpgradePlan) -> Result<Self, Self::Error> {
+        if msg.name.is_empty()
+            || msg.name.len() > MAX_UPGRADE_NAME_LEN
+            || !msg
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-._".contains(c))
+        {
+            return Err(anyhow!(
+                "upgrade name must be 1 to {} lowercase ASCII letters, digits, '-', '.' or '_'",
+                MAX_UPGRADE_NAME_LEN
+            ));
+        }
+
+        Ok(UpgradePlan {
+            name: msg.name,
+            height: msg.height,
+            emergency_halt: msg.emergency_halt,
+        })
+    }
+}
+
+/// A validator's vote to schedule an [`UpgradePlan`].
+///
+/// A plan is scheduled once validators with more than two thirds of the voting
+/// power have voted for it.  Each validator has one vote, and voting again
+/// replaces its previous vote.  The chain ID and sequence number are signed
+/// along with the plan, so that a vote can't be replayed on another chain or
+/// to undo a later vote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpgradeProposal {
+    /// The identity key of the voting validator.
+    pub validator_identity: IdentityKey,
+    pub plan: UpgradePlan,
+    /// The ID of the chain the vote is for.
+    pub chain_id: String,
+    /// Must be greater than the sequence number of the validator's previous
+    /// vote.
+    pub sequence_number: u64,
+}
+
+impl Protobuf<pb::UpgradeProposal> for UpgradeProposal {}
+
+impl From<UpgradeProposal> for pb::UpgradeProposal {
+    fn from(proposal: UpgradeProposal) -> Self {
+        pb::UpgradeProposal {
+            validator_identity: Some(proposal.validator_identity.into()),
+            plan: Some(proposal.plan.into()),
+            chain_id: proposal.chain_id,
+            sequence_number: proposal.sequence_number,
+        }
+    }
+}
+
+impl TryFrom<pb::UpgradeProposal> for UpgradeProposal {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: pb::UpgradeProposal) -> Result<Self, Self::Error> {
+        Ok(UpgradeProposal {
+            validator_identity: msg
+                .validator_identity
+                .ok_or_else(|| anyhow!("missing validator identity"))?
+                .try_into()?,
+            plan: msg
+                .plan
+                .ok_or_else(|| anyhow!("missing upgrade plan"))?
+                .try_into()?,
+            chain_id: msg.chain_id,
+            sequence_number: msg.sequence_number,
+        })
+    }
+}
+
+/// An [`UpgradeProposal`], authorized by the validator's identity key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedUpgradeProposal {
+    pub proposal: UpgradeProposal,
+    pub auth_sig: Signature<SpendAuth>,
+}
+
+impl SignedUpgradeProposal {
+    /// Checks that the proposal was signed by the voting validator's identity key.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.proposal
+            .validator_identity
+            .0
+            .verify(&self.proposal.encode_to_vec(), &self.auth_sig)
+            .map_err(|_| {
+                anyhow!(
+                    "upgrade proposal is not signed by validator {}",
+                    self.proposal.validator_identity
+                )
+            })
+    }
+}
+
+impl Protobuf<pb::SignedUpgradeProposal> for SignedUpgradeProposal {}
+
+impl From<SignedUpgradeProposal> for pb::SignedUpgradeProposal {
+    fn from(signed: SignedUpgradeProposal) -> Self {
+        pb::SignedUpgradeProposal {
+            proposal: Some(signed.proposal.into()),
+            auth_sig: Bytes::copy_from_slice(&signed.auth_sig.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<pb::SignedUpgradeProposal> for SignedUpgradeProposal {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: pb::SignedUpgradeProposal) -> Result<Self, Self::Error> {
+        let sig_bytes: [u8; 64] = msg.auth_sig[..]
+            .try_into()
+            .map_err(|_| anyhow!("upgrade proposal signature must be 64 bytes"))?;
+
+        Ok(SignedUpgradeProposal {
+            proposal: msg
+                .proposal
+                .ok_or_else(|| anyhow!("missing upgrade proposal"))?
+                .try_into()?,
+            auth_sig: sig_bytes.into(),
+        })
+    }
+}
@@ -62,6 +62,11 @@ impl Transaction {
             outputs: Vec::new(),
             delegations: Vec::new(),
             undelegations: Vec::new(),
+            redelegations: Vec::new(),
+            dkg_dealings: Vec::new(),
+            encrypted_flows: Vec::new(),
+            flow_decryptions: Vec::new(),
+            dkg_complaints: Vec::new(),
             fee: None,
             synthetic_blinding_factor: Fr::zero(),
             value_balance: decaf377::Element::default(),
@@ -3,14 +3,18 @@ use std::ops::Deref;
 use ark_ff::{UniformRand, Zero};
 use incrementalmerkletree::Tree;
 use penumbra_crypto::{
-    ka,
+    flow, ka,
     keys::{OutgoingViewingKey, SpendKey},
     memo::MemoPlaintext,
     merkle::{self, NoteCommitmentTree},
+    proofs::transparent::FlowProof,
     rdsa::{Binding, Signature, SigningKey, SpendAuth},
     value, Address, Fr, Note, Value,
 };
-use penumbra_stake::{Delegate, RateData, Undelegate, STAKING_TOKEN_ASSET_ID};
+use penumbra_stake::{
+    Delegate, DelegationToken, DkgComplaint, EncryptedFlow, FlowDecryption, FlowDirection,
+    RateData, Redelegate, SignedDkgDealing, Undelegate, STAKING_TOKEN_ASSET_ID,
+};
 use rand::seq::SliceRandom;
 use rand_core::{CryptoRng, RngCore};
 
@@ -30,6 +34,16 @@ pub struct Builder {
     pub delegations: Vec<Delegate>,
     /// List of undelegations in the transaction.
     pub undelegations: Vec<Undelegate>,
+    /// List of redelegations in the transaction.
+    pub redelegations: Vec<Redelegate>,
+    /// List of DKG dealings in the transaction.
+    pub dkg_dealings: Vec<SignedDkgDealing>,
+    /// List of encrypted delegations and undelegations in the transaction.
+    pub encrypted_flows: Vec<EncryptedFlow>,
+    /// List of flow decryptions in the transaction.
+    pub flow_decryptions: Vec<FlowDecryption>,
+    /// List of DKG complaints in the transaction.
+    pub dkg_complaints: Vec<DkgComplaint>,
     /// Transaction fee. None if unset.
     pub fee: Option<Fee>,
     /// Sum of blinding factors for each value commitment.
@@ -119,6 +133,22 @@ impl Builder {
         ovk: &OutgoingViewingKey,
     ) -> Note {
         let note = Note::generate(rng, dest, value_to_send);
+        self.add_output_for_note(rng, dest, note.clone(), memo, ovk);
+        note
+    }
+
+    /// Add an output for an existing note, encrypting the provided
+    /// [`MemoPlaintext`] with a fresh ephemeral secret key.
+    ///
+    /// The note must be addressed to `dest`.
+    pub fn add_output_for_note<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        dest: &Address,
+        note: Note,
+        memo: MemoPlaintext,
+        ovk: &OutgoingViewingKey,
+    ) -> &mut Self {
         let diversified_generator = note.diversified_generator();
         let transmission_key = note.transmission_key();
         let value_to_send = note.value();
@@ -150,7 +180,7 @@ impl Builder {
             ovk_wrapped_key,
         });
 
-        note
+        self
     }
 
     /// Create a new `Delegate` description for the transaction.
@@ -193,6 +223,158 @@ impl Builder {
         self
     }
 
+    /// Create a new `Redelegate` description for the transaction, moving
+    /// `delegation_amount` delegation tokens of the validator described by
+    /// `from_rate_data` to the one described by `to_rate_data`.
+    pub fn add_redelegation(
+        &mut self,
+        from_rate_data: &RateData,
+        to_rate_data: &RateData,
+        delegation_amount: u64,
+    ) -> &mut Self {
+        let unbonded_amount = from_rate_data.unbonded_amount(delegation_amount);
+        let redelegate = Redelegate {
+            from_validator_identity: from_rate_data.identity_key.clone(),
+            to_validator_identity: to_rate_data.identity_key.clone(),
+            epoch_index: from_rate_data.epoch_index,
+            from_delegation_amount: delegation_amount,
+            unbonded_amount,
+            to_delegation_amount: to_rate_data.delegation_amount(unbonded_amount),
+        };
+
+        let value_commitment = redelegate.value_commitment();
+        // The value commitment has 0 blinding factor, so we skip
+        // accumulating a blinding term into the synthetic blinding factor.
+        self.value_balance += value_commitment.0;
+        self.value_commitments += value_commitment.0;
+
+        self.redelegations.push(redelegate);
+
+        self
+    }
+
+    /// Create a new `EncryptedFlow` description delegating `unbonded_amount`
+    /// of stake, with the amount encrypted to `flow_key`, the threshold key
+    /// of the epoch.
+    ///
+    /// The flow's proof is still transparent and reveals the amount.
+    pub fn add_encrypted_delegation<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        rate_data: &RateData,
+        unbonded_amount: u64,
+        flow_key: &decaf377::Element,
+    ) -> &mut Self {
+        let delegation_amount = rate_data.delegation_amount(unbonded_amount);
+        self.add_encrypted_flow(
+            rng,
+            rate_data,
+            FlowDirection::Delegate,
+            unbonded_amount,
+            delegation_amount,
+            flow_key,
+        )
+    }
+
+    /// Create a new `EncryptedFlow` description undelegating
+    /// `delegation_amount` delegation tokens, with the amount encrypted to
+    /// `flow_key`, the threshold key of the epoch.
+    ///
+    /// The flow's proof is still transparent and reveals the amount.
+    pub fn add_encrypted_undelegation<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        rate_data: &RateData,
+        delegation_amount: u64,
+        flow_key: &decaf377::Element,
+    ) -> &mut Self {
+        let unbonded_amount = rate_data.unbonded_amount(delegation_amount);
+        self.add_encrypted_flow(
+            rng,
+            rate_data,
+            FlowDirection::Undelegate,
+            unbonded_amount,
+            delegation_amount,
+            flow_key,
+        )
+    }
+
+    fn add_encrypted_flow<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        rate_data: &RateData,
+        direction: FlowDirection,
+        unbonded_amount: u64,
+        delegation_amount: u64,
+        flow_key: &decaf377::Element,
+    ) -> &mut Self {
+        let stake = Value {
+            amount: unbonded_amount,
+            asset_id: *STAKING_TOKEN_ASSET_ID,
+        };
+        let delegation = Value {
+            amount: delegation_amount,
+            asset_id: DelegationToken::new(rate_data.identity_key.clone()).id(),
+        };
+        // Delegations produce delegation tokens and consume staking tokens,
+        // and undelegations do the reverse.
+        let (produced, consumed) = match direction {
+            FlowDirection::Delegate => (delegation, stake),
+            FlowDirection::Undelegate => (stake, delegation),
+        };
+
+        let v_blinding = Fr::rand(rng);
+        let value_commitment = produced.commit(v_blinding) - consumed.commit(Fr::zero());
+
+        self.synthetic_blinding_factor += v_blinding;
+        self.value_balance += (produced.commit(Fr::zero()) - consumed.commit(Fr::zero())).0;
+        self.value_commitments += value_commitment.0;
+
+        let flow_blindings = flow::Ciphertext::random_blindings(rng);
+        self.encrypted_flows.push(EncryptedFlow {
+            validator_identity: rate_data.identity_key.clone(),
+            epoch_index: rate_data.epoch_index,
+            direction,
+            value_commitment,
+            amount_ciphertext: flow::Ciphertext::encrypt(
+                delegation_amount,
+                flow_key,
+                &flow_blindings,
+            ),
+            proof: FlowProof {
+                unbonded_amount,
+                delegation_amount,
+                v_blinding,
+                flow_blindings,
+            },
+        });
+
+        self
+    }
+
+    /// Add a validator's decryption shares of an epoch's encrypted flows to
+    /// the transaction.
+    pub fn add_flow_decryption(&mut self, decryption: FlowDecryption) -> &mut Self {
+        // Decryptions don't move any value.
+        self.flow_decryptions.push(decryption);
+        self
+    }
+
+    /// Add a validator's signed DKG dealing to the transaction.
+    pub fn add_dkg_dealing(&mut self, dealing: SignedDkgDealing) -> &mut Self {
+        // Dealings don't move any value.
+        self.dkg_dealings.push(dealing);
+        self
+    }
+
+    /// Add a validator's complaint about another's DKG dealing to the
+    /// transaction.
+    pub fn add_dkg_complaint(&mut self, complaint: DkgComplaint) -> &mut Self {
+        // Complaints don't move any value.
+        self.dkg_complaints.push(complaint);
+        self
+    }
+
     /// Set the transaction fee in PEN.
     ///
     /// Note that we're using the lower case `pen` in the code.
@@ -271,6 +453,8 @@ impl Builder {
         self.outputs.shuffle(rng);
         self.delegations.shuffle(rng);
         self.undelegations.shuffle(rng);
+        self.redelegations.shuffle(rng);
+        self.encrypted_flows.shuffle(rng);
 
         // Fill in the spends using blank signatures, so we can build the sighash tx
         for (_, body) in &self.spends {
@@ -288,6 +472,21 @@ impl Builder {
         for undelegation in self.undelegations.drain(..) {
             actions.push(Action::Undelegate(undelegation));
         }
+        for redelegation in self.redelegations.drain(..) {
+            actions.push(Action::Redelegate(redelegation));
+        }
+        for dealing in self.dkg_dealings.drain(..) {
+            actions.push(Action::DkgDealing(dealing));
+        }
+        for flow in self.encrypted_flows.drain(..) {
+            actions.push(Action::EncryptedFlow(flow));
+        }
+        for decryption in self.flow_decryptions.drain(..) {
+            actions.push(Action::FlowDecryption(decryption));
+        }
+        for complaint in self.dkg_complaints.drain(..) {
+            actions.push(Action::DkgComplaint(complaint));
+        }
 
         let mut transaction_body = TransactionBody {
             actions,
@@ -393,6 +393,82 @@ impl ClientState {
         tx_builder.finalize(rng).map_err(Into::into)
     }
 
+    /// Generate a new transaction redelegating stake from one validator to another.
+    #[instrument(skip(self, rng))]
+    pub fn build_redelegate<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        from_rate_data: RateData,
+        to_rate_data: RateData,
+        delegation_amount: u64,
+        fee: u64,
+        source_address: Option<u64>,
+    ) -> Result<Transaction, anyhow::Error> {
+        // If the source address is set, send the delegation tokens to the same
+        // address; otherwise, send them to the default address.
+        let (_label, self_address) = self
+            .wallet()
+            .address_by_index(source_address.unwrap_or(0) as usize)?;
+
+        let mut tx_builder = Transaction::build_with_root(self.note_commitment_tree.root2());
+
+        tx_builder
+            .set_fee(fee)
+            .set_chain_id(self.chain_id()?)
+            .add_redelegation(&from_rate_data, &to_rate_data, delegation_amount);
+
+        // The redelegation produces no staking tokens, so the fee is paid
+        // separately, and each denomination gets its own change output.
+        let from_denom = from_rate_data.identity_key.delegation_token().denom();
+        let mut spends = vec![(from_denom, delegation_amount)];
+        if fee > 0 {
+            spends.push((STAKING_TOKEN_DENOM.clone(), fee));
+        }
+        for (denom, amount) in spends {
+            let mut spent_amount = 0;
+            for note in self.notes_to_spend(rng, amount, &denom, source_address)? {
+                spent_amount += note.amount();
+                tx_builder.add_spend(
+                    rng,
+                    &self.note_commitment_tree,
+                    self.wallet.spend_key(),
+                    note,
+                )?;
+            }
+
+            let change_amount = spent_amount - amount;
+            // TODO: support dummy notes, and produce a change output unconditionally.
+            if change_amount > 0 {
+                let change_note = tx_builder.add_output_producing_note(
+                    rng,
+                    &self_address,
+                    Value {
+                        amount: change_amount,
+                        asset_id: denom.id(),
+                    },
+                    memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
+                    self.wallet.outgoing_viewing_key(),
+                );
+                self.register_change(change_note);
+            }
+        }
+
+        let unbonded_amount = from_rate_data.unbonded_amount(delegation_amount);
+        let delegation_note = tx_builder.add_output_producing_note(
+            rng,
+            &self_address,
+            Value {
+                amount: to_rate_data.delegation_amount(unbonded_amount),
+                asset_id: to_rate_data.identity_key.delegation_token().id(),
+            },
+            memo::MemoPlaintext([0u8; memo::MEMO_LEN_BYTES]),
+            self.wallet.outgoing_viewing_key(),
+        );
+        self.register_change(delegation_note);
+
+        tx_builder.finalize(rng).map_err(Into::into)
+    }
+
     /// Generate a new transaction sending value to `dest_address`.
     #[instrument(skip(self, rng))]
     pub fn build_send<R: RngCore + CryptoRng>(
@@ -658,6 +734,7 @@ impl ClientState {
             note_commitment,
             ephemeral_key,
             encrypted_note,
+            ..
         } in fragments.into_iter()
         {
             // Unconditionally insert the note commitment into the merkle tree